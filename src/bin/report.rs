@@ -0,0 +1,7 @@
+use clap::Parser;
+use profiler::cli::report::{run, ReportArgs};
+
+// The standalone name of `profiler report`, for existing scripts.
+fn main() {
+    run(ReportArgs::parse());
+}
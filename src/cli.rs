@@ -0,0 +1,49 @@
+use std::{sync::LazyLock, time::Duration};
+
+use crate::schema;
+
+pub mod android;
+pub mod collect;
+pub mod profile;
+pub mod report;
+pub mod ssh;
+pub mod sync;
+
+/// `--version`, with the log schema the binaries write and read.
+pub static VERSION: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        "{} (log schema {})",
+        env!("CARGO_PKG_VERSION"),
+        schema::VERSION
+    )
+});
+
+/// A duration with an optional `ms`, `s`, `m` or `h` suffix, seconds when
+/// bare.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (value, unit) = text.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("\"{}\" is not a duration, expected e.g. 30s", text))?;
+    let millis = match unit {
+        "ms" => 1.0,
+        "" | "s" => 1000.0,
+        "m" => 60.0 * 1000.0,
+        "h" => 60.0 * 60.0 * 1000.0,
+        _ => return Err(format!("unknown unit \"{}\", expected ms, s, m or h", unit)),
+    };
+    Ok(Duration::from_millis((value * millis).round() as u64))
+}
+
+/// A list of CPUs such as `0-3,6`, one value to clap rather than many.
+#[derive(Clone)]
+pub struct CpuList(pub Vec<u64>);
+
+pub fn parse_cpu_list(text: &str) -> Result<CpuList, String> {
+    crate::report::parse_cpu_list(text)
+        .map(CpuList)
+        .ok_or_else(|| format!("\"{}\" is not a list of CPUs such as 0-3,6", text))
+}
@@ -0,0 +1,301 @@
+use std::{
+    fs::metadata,
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{exit, Command, Stdio},
+    thread::{self, sleep},
+    time::Duration,
+};
+
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::Signals,
+};
+use uuid::Uuid;
+
+use super::{parse_duration, profile::quote_command};
+
+/// Where the binary, its log and its pid go on the device.
+const DEVICE_DIR: &str = "/data/local/tmp";
+const PULL_ATTEMPTS: u32 = 5;
+const PULL_RETRY: Duration = Duration::from_secs(2);
+
+/// Captures on an Android device from the host: pushes the android build of
+/// the profiler, runs `profile` on the device, as root if it can, pulls the
+/// log back and removes what it put on the device.
+///
+/// Exits as the profiler on the device did. Ctrl-C stops the capture on the
+/// device, which still closes its log.
+#[derive(Parser)]
+#[command(name = "android")]
+pub struct AndroidArgs {
+    /// Serial of the device as `adb devices` lists it; adb picks the device
+    /// when omitted
+    #[arg(short, long)]
+    serial: Option<String>,
+
+    /// The android profiler to push; `profiler-android` next to this binary
+    /// when omitted
+    #[arg(long, value_name = "PATH")]
+    binary: Option<PathBuf>,
+
+    /// File on the host to pull the log to
+    #[arg(short, long)]
+    output: String,
+
+    /// Overwrite the output file if it exists
+    #[arg(long)]
+    force: bool,
+
+    /// How long to capture for, as with `profile`; `0` runs until interrupted
+    #[arg(short, long, value_parser = parse_duration)]
+    duration: Duration,
+
+    /// Time between samples, as with `profile`
+    #[arg(short, long, value_parser = parse_duration)]
+    interval: Duration,
+
+    /// Further options of `profile` on the device, after `--`, e.g.
+    /// `-- --thermal --battery`
+    #[arg(last = true, value_name = "OPTIONS")]
+    options: Vec<String>,
+}
+
+/// How commands get root on the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Root {
+    /// adbd runs as root, or there is no root to be had.
+    Shell,
+    /// Magisk and SuperSU's `su -c COMMAND`.
+    SuCommand,
+    /// The `su UID COMMAND` of userdebug builds.
+    SuUid,
+}
+
+impl Root {
+    fn wrap(self, command: &str) -> String {
+        let quoted = quote_command(&[command.to_string()]);
+        match self {
+            Root::Shell => command.to_string(),
+            Root::SuCommand => format!("su -c {}", quoted),
+            Root::SuUid => format!("su 0 sh -c {}", quoted),
+        }
+    }
+}
+
+fn adb(serial: Option<&str>) -> Command {
+    let mut adb = Command::new("adb");
+    if let Some(serial) = serial {
+        adb.args(["-s", serial]);
+    }
+    adb
+}
+
+/// Runs `command` in `adb shell`, returning its output if it succeeded.
+fn shell_output(serial: Option<&str>, command: &str) -> Option<String> {
+    let output = adb(serial)
+        .args(["shell", command])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn detect_root(serial: Option<&str>) -> Root {
+    let is_root = |root: Root| shell_output(serial, &root.wrap("id -u")).as_deref() == Some("0");
+    [Root::Shell, Root::SuCommand, Root::SuUid]
+        .into_iter()
+        .find(|root| is_root(*root))
+        .unwrap_or_else(|| {
+            eprintln!("No root on the device, perf may not be allowed to count");
+            Root::Shell
+        })
+}
+
+/// The profiler pushed to a device, along with where its log and pid go
+/// there.
+#[derive(Clone)]
+pub struct Device {
+    serial: Option<String>,
+    root: Root,
+    binary: String,
+    log: String,
+    pid_file: String,
+}
+
+impl Device {
+    /// Pushes `binary` and finds out how to get root. Named by a run of its
+    /// own, so that captures running side by side don't clash.
+    pub fn push(serial: Option<String>, binary: &Path) -> Result<Self, String> {
+        let name = format!("{}/profiler-{}", DEVICE_DIR, Uuid::new_v4());
+        let pushed = adb(serial.as_deref())
+            .arg("push")
+            .arg(binary)
+            .arg(&name)
+            .status()
+            .is_ok_and(|status| status.success());
+        if !pushed || shell_output(serial.as_deref(), &format!("chmod 755 {}", name)).is_none() {
+            return Err(format!("cannot push {} to the device", binary.display()));
+        }
+        let root = detect_root(serial.as_deref());
+        Ok(Device {
+            log: format!("{}.xml", name),
+            pid_file: format!("{}.pid", name),
+            binary: name,
+            serial,
+            root,
+        })
+    }
+
+    /// `command` run in `adb shell` on this device.
+    pub fn shell(&self, command: &str) -> Command {
+        let mut adb = adb(self.serial.as_deref());
+        adb.args(["shell", command]);
+        adb
+    }
+
+    /// `adb shell` running `profile` with `options` into the log on the
+    /// device. Without a terminal and in a process group of its own, so
+    /// that a Ctrl-C reaches the device only as `forward_signals` passes it
+    /// on.
+    pub fn profile(&self, options: &[String]) -> Command {
+        let mut profile = vec![
+            self.binary.clone(),
+            "profile".to_string(),
+            "--output".to_string(),
+            self.log.clone(),
+        ];
+        profile.extend(options.iter().cloned());
+        // exec keeps the pid the signals are forwarded to.
+        let script = format!(
+            "echo $$ > {} && exec {}",
+            self.pid_file,
+            quote_command(&profile)
+        );
+        let mut adb = adb(self.serial.as_deref());
+        adb.args(["shell", "-T", &self.root.wrap(&script)])
+            .stdin(Stdio::null())
+            .process_group(0);
+        adb
+    }
+
+    /// Sends the profiler on the device `signal`, e.g. `INT`.
+    pub fn kill(&self, signal: &str) {
+        let kill = format!("kill -{} $(cat {})", signal, self.pid_file);
+        let _ = self.shell(&self.root.wrap(&kill)).status();
+    }
+
+    /// Passes the first SIGINT or SIGTERM on to the profiler on the device,
+    /// so that it closes its log, and the next as SIGKILL.
+    pub fn forward_signals(&self) {
+        let mut signals = Signals::new([SIGINT, SIGTERM]).unwrap();
+        let device = self.clone();
+        thread::spawn(move || {
+            for (count, _) in signals.forever().enumerate() {
+                device.kill(if count == 0 { "INT" } else { "KILL" });
+                if count == 0 {
+                    eprintln!(
+                        "\nInterrupted, closing the log on the device (Ctrl-C again to force)"
+                    );
+                }
+            }
+        });
+    }
+
+    /// Pulls the log to `output`, waiting for the device to come back
+    /// between attempts, and removes what was put on the device; the log is
+    /// left there if it can't be pulled.
+    pub fn collect(self, output: &str) -> bool {
+        let serial = self.serial.as_deref();
+        let _ = adb(serial).arg("wait-for-device").status();
+        let mut leftovers = vec![self.binary, self.pid_file];
+        let pulled = if shell_output(serial, &format!("test -e {}", self.log)).is_none() {
+            eprintln!("The profiler on the device left no log");
+            false
+        } else if pull(serial, &self.log, output) {
+            leftovers.push(self.log);
+            true
+        } else {
+            eprintln!(
+                "cannot pull the log, it is left on the device in {}",
+                self.log
+            );
+            false
+        };
+        let _ = shell_output(
+            serial,
+            &self.root.wrap(&format!("rm -f {}", leftovers.join(" "))),
+        );
+        pulled
+    }
+}
+
+fn pull(serial: Option<&str>, log: &str, output: &str) -> bool {
+    for attempt in 1..=PULL_ATTEMPTS {
+        let _ = adb(serial).arg("wait-for-device").status();
+        let pulled = adb(serial)
+            .args(["pull", log, output])
+            .status()
+            .is_ok_and(|status| status.success());
+        if pulled {
+            return true;
+        }
+        if attempt < PULL_ATTEMPTS {
+            eprintln!("Pulling {} failed, retrying", log);
+            sleep(PULL_RETRY);
+        }
+    }
+    false
+}
+
+/// `profiler-android` next to this binary, where a release puts it.
+pub fn default_binary() -> PathBuf {
+    std::env::current_exe()
+        .unwrap()
+        .with_file_name("profiler-android")
+}
+
+pub fn run(args: AndroidArgs) {
+    let mut command = AndroidArgs::command();
+    let binary = args.binary.unwrap_or_else(default_binary);
+    if !binary.is_file() {
+        command
+            .error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "no android profiler at {}, pass one with --binary",
+                    binary.display()
+                ),
+            )
+            .exit();
+    }
+    if !args.force && metadata(&args.output).is_ok() {
+        command
+            .error(
+                ErrorKind::ValueValidation,
+                format!("{} exists, pass --force to overwrite it", args.output),
+            )
+            .exit();
+    }
+
+    let device = Device::push(args.serial, &binary).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        exit(1);
+    });
+    let mut options = vec![
+        "--duration".to_string(),
+        format!("{}ms", args.duration.as_millis()),
+        "--interval".to_string(),
+        format!("{}ms", args.interval.as_millis()),
+    ];
+    options.extend(args.options);
+    device.forward_signals();
+    let status = device.profile(&options).status();
+    let code = status.map_or(1, |status| status.code().unwrap_or(1));
+    let pulled = device.collect(&args.output);
+    exit(if pulled { code } else { code.max(1) });
+}
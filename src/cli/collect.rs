@@ -0,0 +1,98 @@
+use std::{
+    fs::{create_dir_all, OpenOptions},
+    io::{self, BufRead, BufReader, Error, ErrorKind, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::exit,
+    sync::Arc,
+    thread,
+};
+
+use clap::Parser;
+use uuid::Uuid;
+
+use crate::log::REMOTE_HELLO;
+
+/// Accepts logs streamed with `profile --remote` and writes each to
+/// `DIR/ID.xml`, named by the id of its capture, which `report` reads like
+/// any other log.
+///
+/// A logger that reconnects after the link dropped goes on where its file
+/// ends.
+#[derive(Parser)]
+#[command(name = "collect")]
+pub struct CollectArgs {
+    /// Address to accept loggers on, e.g. `:9000` for every interface
+    #[arg(long, value_name = "ADDR", default_value = ":9000")]
+    listen: String,
+
+    /// Directory to write the logs to, created if missing
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    dir: PathBuf,
+}
+
+/// Reads the logger's hello, answers with the length of the log so far and
+/// appends the rest of the stream to it, returning its id and new length.
+fn receive(stream: TcpStream, dir: &Path) -> io::Result<(Uuid, u64)> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let id = line
+        .trim_end()
+        .strip_prefix(REMOTE_HELLO)
+        .and_then(|id| Uuid::parse_str(id.trim()).ok())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "not a profiler logger"))?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(format!("{}.xml", id)))?;
+    let offset = file.metadata()?.len();
+    writeln!(&stream, "{}", offset)?;
+    let received = io::copy(&mut reader, &mut file)?;
+    file.sync_data()?;
+    Ok((id, offset + received))
+}
+
+pub fn run(args: CollectArgs) {
+    if let Err(error) = create_dir_all(&args.dir) {
+        eprintln!("cannot create {}: {}", args.dir.display(), error);
+        exit(1);
+    }
+    // `:9000` as `nc -l` takes it.
+    let address = if args.listen.starts_with(':') {
+        format!("0.0.0.0{}", args.listen)
+    } else {
+        args.listen
+    };
+    let listener = TcpListener::bind(&address).unwrap_or_else(|error| {
+        eprintln!("cannot listen on {}: {}", address, error);
+        exit(1);
+    });
+    eprintln!(
+        "Collecting on {} into {}",
+        listener.local_addr().unwrap(),
+        args.dir.display()
+    );
+    let dir = Arc::new(args.dir);
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let dir = dir.clone();
+        thread::spawn(move || {
+            let peer = stream
+                .peer_addr()
+                .map_or("?".to_string(), |peer| peer.to_string());
+            match receive(stream, &dir) {
+                Ok((id, length)) => eprintln!(
+                    "{}: {} bytes of {} in {}",
+                    peer,
+                    length,
+                    id,
+                    dir.join(format!("{}.xml", id)).display()
+                ),
+                Err(error) => eprintln!("{}: {}", peer, error),
+            }
+        });
+    }
+}
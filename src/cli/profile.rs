@@ -0,0 +1,1175 @@
+use clap::{
+    error::ErrorKind, parser::ValueSource, Arg, ArgAction, ArgMatches, CommandFactory, Parser,
+};
+use lazy_regex::regex_captures;
+use regex::Regex;
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::Signals,
+};
+use std::{
+    ffi::{CString, OsString},
+    fs::{metadata, read_to_string, remove_file},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::FileTypeExt,
+        process::{CommandExt, ExitStatusExt},
+    },
+    path::Path,
+    process::{exit, Child, Command, ExitStatus},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+use crate::{
+    log::*,
+    platform::{on_path, PerfGranularity, Platform},
+    prometheus::Exporter,
+    report::format_cpu_list,
+};
+
+use super::{parse_cpu_list, parse_duration, CpuList};
+
+/// Samples /proc/stat and per-core perf counters into an XML log.
+///
+/// Durations accept an optional unit suffix: `ms`, `s`, `m` or `h`.
+/// Bare numbers are seconds, so `30`, `30s` and `30000ms` are equivalent.
+/// The log records the capture's duration in whole seconds, so `--duration`
+/// must be one, e.g. `2000ms` but not `500ms`; sub-second values are for
+/// `--interval` and the like.
+#[derive(Parser)]
+#[command(name = "profile")]
+pub struct ProfileArgs {
+    /// Output file (legacy positional form of --output)
+    #[arg(value_name = "OUTPUT")]
+    output_pos: Option<String>,
+
+    /// Test duration (legacy positional form of --duration)
+    #[arg(value_name = "DURATION", value_parser = parse_duration)]
+    duration_pos: Option<Duration>,
+
+    /// Sampling interval (legacy positional form of --interval)
+    #[arg(value_name = "INTERVAL", value_parser = parse_duration)]
+    interval_pos: Option<Duration>,
+
+    /// TOML file of options to capture with, keyed by their long names, e.g.
+    /// `duration = "5m"` or `thermal = true`; options given here win
+    #[arg(long, value_name = "FILE", env = "PROFILER_CONFIG")]
+    config: Option<String>,
+
+    /// Also apply the `[profile.NAME]` table of the config file over its
+    /// top-level options
+    #[arg(
+        id = "capture_profile",
+        long = "profile",
+        value_name = "NAME",
+        env = "PROFILER_PROFILE",
+        requires = "config"
+    )]
+    capture_profile: Option<String>,
+
+    /// File the XML log is written to, gzip compressed if it ends in `.gz`,
+    /// or `-` for stdout; `{date}`, `{time}`, `{host}` and `{id}` are
+    /// filled in, e.g. `run-{date}-{time}-{host}-{id}.xml`
+    #[arg(short, long, conflicts_with = "output_pos")]
+    output: Option<String>,
+
+    /// Stream the log to `profiler collect` at HOST:PORT instead of a file,
+    /// spooling it locally for as long as the link is down
+    #[arg(
+        long,
+        value_name = "HOST:PORT",
+        conflicts_with_all = ["output", "output_pos", "rotate_size", "rotate_entries"]
+    )]
+    remote: Option<String>,
+
+    /// Serve the latest interval's per-CPU load, cycles and context switches
+    /// as Prometheus gauges at http://ADDR/metrics, e.g. `:9184` for every
+    /// interface
+    #[arg(long, value_name = "ADDR")]
+    prometheus: Option<String>,
+
+    /// Overwrite the output file if it exists
+    #[arg(long)]
+    force: bool,
+
+    /// Go on in `NAME.part2.xml`, `NAME.part3.xml` and so on once the log is
+    /// SIZE long, e.g. `100M`, for captures too long to pull off in one file;
+    /// `report --merge` stitches the parts back together
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    rotate_size: Option<u64>,
+
+    /// Go on in a new part, as with `--rotate-size`, after N entries
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    rotate_entries: Option<u32>,
+
+    /// How long to capture for in whole seconds, e.g. `60`, `90s`, `5m`, `1h`; `0` runs until
+    /// interrupted
+    #[arg(short, long, value_parser = parse_duration, conflicts_with = "duration_pos")]
+    duration: Option<Duration>,
+
+    /// Which perf tool and output format to expect; detected when omitted
+    #[arg(long, value_enum)]
+    platform: Option<Platform>,
+
+    /// Only record this network interface from /proc/net/dev; repeatable
+    #[arg(long = "iface", value_name = "IFACE")]
+    ifaces: Vec<String>,
+
+    /// Only record this block device from /proc/diskstats, e.g. `sda` or
+    /// `mmcblk0`; repeatable
+    #[arg(long = "disk", value_name = "NAME")]
+    disks: Vec<String>,
+
+    /// Also record /proc/<PID>/stat and count perf events for this process
+    /// only instead of system-wide; repeatable
+    #[arg(long = "pid", value_name = "PID")]
+    pids: Vec<u32>,
+
+    /// Also record the cpu.stat of this cgroup v2 directory, e.g.
+    /// `uid_10123` or `/sys/fs/cgroup/system.slice`, relative to
+    /// /sys/fs/cgroup; repeatable
+    #[arg(long = "cgroup", value_name = "PATH")]
+    cgroups: Vec<String>,
+
+    /// Comma separated perf events to count, e.g.
+    /// `cycles,instructions,cache-misses`; defaults to cycles and context
+    /// switches
+    #[arg(long, value_name = "EVENTS", value_parser = parse_events)]
+    events: Option<String>,
+
+    /// Only sample /proc without running perf, for devices and containers
+    /// where perf counters are unavailable
+    #[arg(long, conflicts_with = "events")]
+    no_perf: bool,
+
+    /// Count perf events on these CPUs only, e.g. `4-7` for a workload
+    /// isolated there, and have the report keep and sum only them
+    #[arg(long, value_name = "CPUS", value_parser = parse_cpu_list)]
+    cpu_list: Option<CpuList>,
+
+    /// Split perf's system-wide counts per core, per socket or not at all;
+    /// fewer rows multiplex less on large machines. Android only has core
+    /// and system
+    #[arg(long, value_enum, value_name = "GRANULARITY", default_value = "core")]
+    perf_granularity: PerfGranularity,
+
+    /// Also sample the temperature of every thermal zone
+    #[arg(long)]
+    thermal: bool,
+
+    /// Also sample the battery's current, voltage and charge, for the power
+    /// the device draws; Android and laptops
+    #[arg(long)]
+    battery: bool,
+
+    /// Also sample how busy the GPU is, from Adreno's gpubusy or the devfreq
+    /// load of the GPU
+    #[arg(long)]
+    gpu: bool,
+
+    /// Sample the GPU's load from this file instead, in either format;
+    /// implies `--gpu`
+    #[arg(long, value_name = "PATH")]
+    gpu_path: Option<String>,
+
+    /// Also record the network type and signal strength at the end of each
+    /// interval, from `dumpsys telephony.registry` on Android and from
+    /// /proc/net/wireless for Wi-Fi. dumpsys takes tens of milliseconds of
+    /// CPU itself
+    #[arg(long)]
+    radio: bool,
+
+    /// Also sample cpufreq's time_in_state, how long each CPU spent at each
+    /// of its frequencies
+    #[arg(long)]
+    time_in_state: bool,
+
+    /// Also sample /proc/schedstat, to tell how long runnable tasks waited
+    /// for each CPU
+    #[arg(long)]
+    schedstat: bool,
+
+    /// Also sample how long each CPU spent in each of its cpuidle states, to
+    /// tell shallow idle from deep sleep
+    #[arg(long)]
+    cpuidle: bool,
+
+    /// Also sample /proc/softirqs, to tell NET_RX from NET_TX and TIMER
+    /// softirqs per CPU
+    #[arg(long)]
+    softirqs: bool,
+
+    /// Also record the wall-clock time of every entry; the start time is
+    /// always recorded
+    #[arg(long)]
+    timestamps: bool,
+
+    /// Write the buffered log out every N entries, e.g. `10`, or once per
+    /// DURATION, e.g. `30s` or `5m`; a unit makes it a duration
+    #[arg(long, value_name = "N|DURATION", default_value = "1", value_parser = parse_flush)]
+    flush_every: Flush,
+
+    /// Also fsync the log whenever it is written out, for devices that may
+    /// lose power
+    #[arg(long)]
+    sync: bool,
+
+    /// Run the logger and perf on this CPU only, so they disturb the others
+    /// less; the report can leave it out with `--exclude-profiler-cpu`
+    #[arg(long, value_name = "N")]
+    pin_cpu: Option<u32>,
+
+    /// Sample for this long before the capture starts without logging it, so
+    /// that perf and the caches settle, e.g. `30s`; rounded up to whole
+    /// intervals
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    warmup: Option<Duration>,
+
+    /// Count down for this long on stderr before starting perf and the
+    /// capture, e.g. `15s`, to put the device down first
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    delay: Option<Duration>,
+
+    /// Wait for Enter on stdin before the `--delay` countdown or, without
+    /// one, before the capture
+    #[arg(long)]
+    wait_for_enter: bool,
+
+    /// Start the capture, warmup included, once this machine's clock reads
+    /// UNIX_MS milliseconds since the epoch, as `profiler sync` asks
+    #[arg(long, value_name = "UNIX_MS", conflicts_with_all = ["delay", "wait_for_enter"])]
+    start_at: Option<u64>,
+
+    /// Record that this machine's clock is MS milliseconds ahead of the
+    /// coordinator's, for the report to align captures by
+    #[arg(long, value_name = "MS", allow_negative_numbers = true)]
+    clock_offset_ms: Option<i64>,
+
+    /// Read markers for the report to split the capture by from the FIFO at
+    /// PATH, created if missing, a label a line, e.g. `echo phase=lte-start >
+    /// PATH`; a SIGUSR1 always marks `mark-1`, `mark-2` and so on
+    #[arg(long, value_name = "PATH")]
+    markers: Option<String>,
+
+    /// Only record the rows of /proc/interrupts matching REGEX, e.g.
+    /// `wlan|msm_dwc3`, as the whole table is long on phones
+    #[arg(long, value_name = "REGEX", value_parser = Regex::new)]
+    irq_filter: Option<Regex>,
+
+    /// Also record `ss -tin FILTER` at the end of every interval, for the
+    /// RTT, congestion window and retransmits of the TCP connections FILTER
+    /// matches, e.g. `'dport = :5201'`
+    #[arg(long, value_name = "FILTER")]
+    sockets: Option<String>,
+
+    /// Capture until SIGINT/SIGTERM instead of for a fixed duration
+    #[arg(long, conflicts_with_all = ["duration", "duration_pos"])]
+    forever: bool,
+
+    /// Time between samples, e.g. `1`, `0.5`, `200ms`; at least 10ms
+    #[arg(short, long, value_parser = parse_duration, conflicts_with = "interval_pos")]
+    interval: Option<Duration>,
+}
+
+/// Runs COMMAND and captures from its start until it exits, recording its
+/// command line and how it ended.
+///
+/// Takes the options of `profile` except for the duration. SIGINT and SIGTERM
+/// are passed on to COMMAND, which runs in a process group of its own, and
+/// the logger exits with its exit code.
+#[derive(Parser)]
+#[command(name = "run")]
+pub struct RunArgs {
+    #[command(flatten)]
+    profile: ProfileArgs,
+
+    /// The command to run and its arguments, after `--`
+    #[arg(last = true, required = true, value_name = "COMMAND")]
+    command: Vec<String>,
+}
+
+/// Samples perf and /proc/stat once and reports whether a capture would
+/// work, exiting nonzero if not.
+#[derive(Parser)]
+#[command(name = "check")]
+pub struct CheckArgs {
+    /// Which perf tool and output format to expect; detected when omitted
+    #[arg(long, value_enum)]
+    platform: Option<Platform>,
+
+    /// Comma separated perf events to check; defaults to those a capture
+    /// counts
+    #[arg(long, value_name = "EVENTS", value_parser = parse_events)]
+    events: Option<String>,
+}
+
+fn parse_flush(text: &str) -> Result<Flush, String> {
+    match text.parse::<u32>() {
+        Ok(0) => Err("flushing every 0 entries would never write the log".to_string()),
+        Ok(entries) => Ok(Flush::Entries(entries)),
+        Err(_) => Ok(Flush::Every(parse_duration(text)?)),
+    }
+}
+
+/// Bytes, or kibi-, mebi- or gibibytes with a `K`, `M` or `G` suffix.
+fn parse_size(text: &str) -> Result<u64, String> {
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(split) => text.split_at(split),
+        None => (text, ""),
+    };
+    let unit = match unit {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return Err(format!("unknown unit \"{}\", expected K, M or G", unit)),
+    };
+    match number.parse::<u64>() {
+        Ok(number) if number > 0 => Ok(number * unit),
+        _ => Err(format!("\"{}\" is not a size such as 100M", text)),
+    }
+}
+
+fn parse_events(text: &str) -> Result<String, String> {
+    if text.split(',').any(|event| event.trim().is_empty()) {
+        return Err(format!(
+            "\"{}\" is not a comma separated list of events",
+            text
+        ));
+    }
+    Ok(text.to_string())
+}
+
+impl ProfileArgs {
+    /// `child` is the command line of the command `profiler run` lasts for.
+    fn into_config(self, child: Option<String>) -> Config {
+        let mut command = if child.is_some() {
+            RunArgs::command()
+        } else {
+            ProfileArgs::command()
+        };
+        let duration = if child.is_some() {
+            if self.forever || self.duration.or(self.duration_pos).is_some() {
+                command
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        "the capture lasts as long as COMMAND, so takes no duration",
+                    )
+                    .exit();
+            }
+            Some(Duration::ZERO)
+        } else if self.forever {
+            Some(Duration::ZERO)
+        } else {
+            self.duration.or(self.duration_pos)
+        };
+        let platform = self.platform.unwrap_or_else(Platform::detect);
+        let id = Uuid::new_v4();
+        // The collector names the file, so a remote log is only spooled here.
+        let spool = self.remote.as_ref().map(|_| {
+            std::env::temp_dir()
+                .join(format!("profiler-{}.xml", id))
+                .to_string_lossy()
+                .into_owned()
+        });
+        let (Some(output), Some(duration), Some(interval)) = (
+            spool.clone().or(self.output).or(self.output_pos),
+            duration,
+            self.interval.or(self.interval_pos),
+        ) else {
+            command
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "an output, a duration and an interval are required",
+                )
+                .exit();
+        };
+
+        let template = output;
+        let output = if spool.is_some() {
+            template.clone()
+        } else {
+            expand_output(&template, id, SystemTime::now(), &device_id(platform))
+                .unwrap_or_else(|error| command.error(ErrorKind::ValueValidation, error).exit())
+        };
+        if output == "-" && (self.rotate_size.is_some() || self.rotate_entries.is_some()) {
+            command
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "stdout can't be split into parts, log to a file to rotate",
+                )
+                .exit();
+        }
+        if output != "-" && spool.is_none() && !self.force && metadata(&output).is_ok() {
+            command
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!("{} exists, pass --force to overwrite it", output),
+                )
+                .exit();
+        }
+
+        if interval < MIN_INTERVAL {
+            command
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!(
+                        "the interval must be at least {}ms, the finest perf supports",
+                        MIN_INTERVAL.as_millis()
+                    ),
+                )
+                .exit();
+        }
+        if duration.subsec_nanos() != 0 {
+            command
+                .error(
+                    ErrorKind::ValueValidation,
+                    "the duration must be a whole number of seconds",
+                )
+                .exit();
+        }
+        let duration = (!duration.is_zero()).then_some(duration);
+        if let Some(duration) = duration.filter(|&duration| interval > duration) {
+            command
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!(
+                        "the interval ({:?}) is longer than the duration ({:?})",
+                        interval, duration
+                    ),
+                )
+                .exit();
+        }
+
+        if let Some(pid) = self.pids.iter().find(|&&pid| read_pid_stat(pid).is_none()) {
+            command
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!("no process with pid {} is running", pid),
+                )
+                .exit();
+        }
+
+        let cgroups: Vec<_> = self
+            .cgroups
+            .iter()
+            .map(|path| {
+                let path = path.trim_end_matches('/');
+                if path.starts_with('/') {
+                    path.to_string()
+                } else {
+                    format!("/sys/fs/cgroup/{}", path)
+                }
+            })
+            .collect();
+        if let Some(path) = cgroups
+            .iter()
+            .find(|path| !read_cgroup_stat(path).is_some_and(|stat| stat.contains("usage_usec")))
+        {
+            command
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!(
+                        "{} has no cpu.stat with usage_usec; --cgroup needs a cgroup v2 \
+                         directory, such as one under /sys/fs/cgroup/unified on hybrid systems",
+                        path
+                    ),
+                )
+                .exit();
+        }
+
+        let battery = self.battery.then(read_battery);
+        if battery.is_some_and(|battery| {
+            !battery.contains("current_now") || !battery.contains("voltage_now")
+        }) {
+            command
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!("--battery needs current_now and voltage_now in {}", BATTERY),
+                )
+                .exit();
+        }
+
+        if self.cpuidle && read_cpuidle().is_empty() {
+            command
+                .error(
+                    ErrorKind::ValueValidation,
+                    "--cpuidle found no /sys/devices/system/cpu/cpu*/cpuidle states",
+                )
+                .exit();
+        }
+
+        if self.schedstat && read_schedstat().is_none() {
+            command
+                .error(
+                    ErrorKind::ValueValidation,
+                    "--schedstat cannot read /proc/schedstat, which needs CONFIG_SCHEDSTATS",
+                )
+                .exit();
+        }
+
+        if self.time_in_state && read_time_in_state().is_empty() {
+            command
+                .error(
+                    ErrorKind::ValueValidation,
+                    "--time-in-state found no cpufreq stats, which need CONFIG_CPU_FREQ_STAT",
+                )
+                .exit();
+        }
+
+        if self.sockets.is_some() && !on_path("ss") {
+            command
+                .error(
+                    ErrorKind::ValueValidation,
+                    "--sockets needs ss from iproute2 on PATH",
+                )
+                .exit();
+        }
+
+        if self.radio && !on_path("dumpsys") && metadata("/proc/net/wireless").is_err() {
+            command
+                .error(
+                    ErrorKind::ValueValidation,
+                    "--radio needs dumpsys from Android or /proc/net/wireless",
+                )
+                .exit();
+        }
+
+        if let Some(cpu) = self.pin_cpu {
+            let online = online_cpus();
+            if !online.contains(&cpu) {
+                let online: Vec<_> = online.iter().map(|cpu| cpu.to_string()).collect();
+                command
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!(
+                            "CPU {} is not online, pick one of {}",
+                            cpu,
+                            online.join(", ")
+                        ),
+                    )
+                    .exit();
+            }
+        }
+
+        if let Some(CpuList(cpus)) = &self.cpu_list {
+            let online = online_cpus();
+            if let Some(cpu) = cpus.iter().find(|&&cpu| !online.contains(&(cpu as u32))) {
+                let online: Vec<_> = online.iter().map(|&cpu| cpu as u64).collect();
+                command
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!(
+                            "CPU {} of --cpu-list is not online, this machine has {}",
+                            cpu,
+                            format_cpu_list(&online)
+                        ),
+                    )
+                    .exit();
+            }
+        }
+
+        // Without a GPU to sample the capture is still worth having.
+        let gpu = match self.gpu_path {
+            Some(path) if read_to_string(&path).is_err() => {
+                eprintln!("Cannot read {}, logging without the GPU", path);
+                None
+            }
+            Some(path) => Some(path),
+            None if self.gpu => {
+                let gpu = find_gpu();
+                if gpu.is_none() {
+                    eprintln!("Found no GPU load to sample, logging without the GPU");
+                }
+                gpu
+            }
+            None => None,
+        };
+
+        if platform == Platform::Android && self.perf_granularity == PerfGranularity::Socket {
+            command
+                .error(
+                    ErrorKind::ValueValidation,
+                    "simpleperf has no per-socket counts, use core or system",
+                )
+                .exit();
+        }
+        let events = self
+            .events
+            .unwrap_or_else(|| platform.default_events().to_string());
+
+        if output != "-" && spool.is_none() {
+            if let Err(error) = check_creatable(&output) {
+                command
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!("cannot create {}: {}", output, error),
+                    )
+                    .exit();
+            }
+        }
+        if output != template {
+            eprintln!("Logging to {}", output);
+        }
+        Config {
+            id,
+            output,
+            remote: self.remote,
+            prometheus: self.prometheus,
+            platform,
+            duration,
+            interval,
+            ifaces: self.ifaces,
+            disks: self.disks,
+            pids: self.pids,
+            cgroups,
+            thermal: self.thermal,
+            battery: self.battery,
+            gpu,
+            radio: self.radio,
+            softirqs: self.softirqs,
+            cpuidle: self.cpuidle,
+            schedstat: self.schedstat,
+            time_in_state: self.time_in_state,
+            events,
+            perf: !self.no_perf,
+            perf_granularity: self.perf_granularity,
+            options: RESOLVED_CONFIG.get().cloned(),
+            cpu_list: self.cpu_list.map(|CpuList(cpus)| format_cpu_list(&cpus)),
+            clock_offset: self.clock_offset_ms,
+            rotate_size: self.rotate_size,
+            rotate_entries: self.rotate_entries,
+            timestamps: self.timestamps,
+            flush: self.flush_every,
+            sync: self.sync,
+            pin_cpu: self.pin_cpu,
+            warmup: interval
+                * self
+                    .warmup
+                    .unwrap_or_default()
+                    .div_duration_f64(interval)
+                    .ceil() as u32,
+            command: child,
+            markers: self.markers,
+            irq_filter: self.irq_filter,
+            sockets: self.sockets,
+        }
+    }
+}
+
+/// Interval the check samples perf at, short enough to answer quickly.
+const CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The value of a counter row of perf's output.
+fn perf_counter_value(platform: Platform, line: &str) -> Option<&str> {
+    match platform {
+        Platform::Ubuntu => {
+            regex_captures!(r#"^\s*\d+\.\d+\s+(?:\S+-\S+-\S+\s+\d+\s+)?(\S+)\s"#, line)
+                .map(|(_, value)| value)
+        }
+        Platform::Android => regex_captures!(r#"^\d+,(\d+|<not counted>|<not supported>),"#, line)
+            .map(|(_, value)| value),
+    }
+}
+
+/// Prints the outcome of one check, with a hint on how to fix a failure.
+fn report_check(name: &str, result: Result<String, (String, String)>) -> bool {
+    match result {
+        Ok(detail) => {
+            println!("{:20} ok, {}", name, detail);
+            true
+        }
+        Err((problem, hint)) => {
+            println!("{:20} FAILED, {}", name, problem);
+            println!("{:20} {}", "", hint);
+            false
+        }
+    }
+}
+
+fn check_perf(platform: Platform, events: &str) -> Result<String, (String, String)> {
+    let tool = match platform {
+        Platform::Ubuntu => "perf",
+        Platform::Android => "simpleperf",
+    };
+    if !on_path(tool) {
+        return Err((
+            format!("{} is not on PATH", tool),
+            match platform {
+                Platform::Ubuntu => "install the linux-tools package for the running kernel",
+                Platform::Android => {
+                    "push simpleperf from the NDK to the device and add it to PATH"
+                }
+            }
+            .to_string(),
+        ));
+    }
+
+    let config = Config {
+        events: events.to_string(),
+        perf: true,
+        ..Config::bare(platform, CHECK_INTERVAL)
+    };
+    let slot = PerfSlot::default();
+    let guard = PerfGuard(slot.clone());
+    let entry = start_perf(&config, &slot).recv_timeout(Duration::from_secs(3));
+    drop(guard);
+
+    let Ok(entry) = entry else {
+        let paranoid = read_to_string("/proc/sys/kernel/perf_event_paranoid").unwrap_or_default();
+        let hint = match paranoid.trim().parse::<i32>() {
+            Ok(paranoid) if paranoid > 1 => format!(
+                "perf_event_paranoid is {}, set kernel.perf_event_paranoid=1",
+                paranoid
+            ),
+            _ => "make sure sudo doesn't ask for a password and that perf runs by hand".to_string(),
+        };
+        return Err((format!("{} printed no samples", tool), hint));
+    };
+
+    let event_count = events.split(',').count();
+    let cores = platform.perf_rows(false);
+    let expected = cores * event_count;
+    let values: Vec<_> = entry
+        .lines()
+        .filter_map(|line| perf_counter_value(platform, line))
+        .collect();
+    if values.len() != expected {
+        return Err((
+            format!(
+                "perf doesn't print {} counter lines ({} cores, {} events) per interval",
+                expected, cores, event_count
+            ),
+            format!("check that `{} stat --per-core` reports every core", tool),
+        ));
+    }
+    if values.iter().any(|value| value.starts_with('<')) {
+        return Err((
+            "some events were not counted".to_string(),
+            "check the names with `perf list`, or pass --events with supported ones".to_string(),
+        ));
+    }
+    Ok(format!(
+        "{} counts {} events on {} cores",
+        tool, event_count, cores
+    ))
+}
+
+/// The report reads every `cpu` line of /proc/stat with this layout.
+fn check_proc_stat() -> Result<String, (String, String)> {
+    let hint = "the report can't read this kernel's /proc/stat".to_string();
+    let stat = read_to_string("/proc/stat").map_err(|error| (error.to_string(), hint.clone()))?;
+    let cpus: Vec<_> = stat
+        .lines()
+        .filter(|line| line.starts_with("cpu"))
+        .collect();
+    if let Some(line) = cpus
+        .iter()
+        .find(|line| regex_captures!(r#"^cpu(\d+| ) (\d+ ){7}\d+ \d+ \d+"#, line).is_none())
+    {
+        return Err((format!("unexpected line \"{}\"", line), hint));
+    }
+    if !cpus.iter().any(|line| line.starts_with("cpu ")) {
+        return Err(("no total cpu line".to_string(), hint));
+    }
+    Ok(format!("{} cpu lines", cpus.len()))
+}
+
+pub fn check(args: CheckArgs) {
+    let platform = args.platform.unwrap_or_else(Platform::detect);
+    let events = args
+        .events
+        .unwrap_or_else(|| platform.default_events().to_string());
+    println!("{:20} {}", "platform", platform.name());
+
+    let results = [
+        report_check("perf", check_perf(platform, &events)),
+        report_check("/proc/stat", check_proc_stat()),
+    ];
+    let failed = results.iter().filter(|&&passed| !passed).count();
+    if failed > 0 {
+        println!("{} of {} checks failed", failed, results.len());
+        exit(1);
+    }
+    println!("all checks passed");
+}
+
+/// Holds the capture back as `--wait-for-enter` and `--delay` ask.
+fn wait_to_start(delay: Duration, wait_for_enter: bool) {
+    if wait_for_enter {
+        eprint!("Press Enter to start");
+        std::io::stdin().read_line(&mut String::new()).unwrap();
+    }
+    let mut left = delay;
+    while !left.is_zero() {
+        eprint!("\rStarting in {}s ", left.as_secs_f64().ceil());
+        let step = left.min(Duration::from_secs(1));
+        thread::sleep(step);
+        left -= step;
+    }
+    if !delay.is_zero() {
+        eprintln!("\rStarting        ");
+    }
+}
+
+/// Sleeps until the wall clock reads `start`, warning if it already has.
+fn wait_until(start: SystemTime) {
+    match start.duration_since(SystemTime::now()) {
+        Ok(left) => {
+            eprintln!("Starting in {:.1}s", left.as_secs_f64());
+            thread::sleep(left);
+        }
+        Err(late) => eprintln!(
+            "--start-at was {}ms ago, starting now",
+            late.duration().as_millis()
+        ),
+    }
+}
+
+/// Sets the returned flag on the first SIGINT/SIGTERM so the capture loop can
+/// finish the current entry and close the log, passing the signal on to
+/// `child`; a second signal kills `child` and exits immediately.
+fn install_signal_handler(perf: PerfSlot, child: Option<u32>) -> Arc<AtomicBool> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut signals = Signals::new([SIGINT, SIGTERM]).unwrap();
+    let flag = stop.clone();
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            let forced = flag.swap(true, Ordering::SeqCst);
+            if let Some(child) = child {
+                let signal = if forced { libc::SIGKILL } else { signal };
+                unsafe {
+                    libc::kill(child as libc::pid_t, signal);
+                }
+            }
+            if forced {
+                stop_perf_slot(&perf);
+                exit(130);
+            }
+            eprintln!("\nInterrupted, closing the log (Ctrl-C again to force)");
+        }
+    });
+    stop
+}
+
+/// Whether `path` could be created or overwritten, asked of it and its
+/// directory rather than by creating it, so that the capture's waits, if
+/// interrupted, leave no file behind.
+fn check_creatable(path: &str) -> std::io::Result<()> {
+    let directory = Path::new(path)
+        .parent()
+        .filter(|directory| !directory.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let writable = |path: &Path, mode| {
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        if unsafe { libc::access(c_path.as_ptr(), mode) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    };
+    writable(directory, libc::W_OK | libc::X_OK)?;
+    if metadata(path).is_ok() {
+        writable(Path::new(path), libc::W_OK)?;
+    }
+    Ok(())
+}
+
+/// Creates the `--markers` FIFO unless there is one, returning whether it did
+/// so that it can be removed afterwards.
+fn create_fifo(path: &str) -> bool {
+    match metadata(path) {
+        Ok(metadata) if metadata.file_type().is_fifo() => false,
+        Ok(_) => {
+            eprintln!("{} exists and is not a FIFO", path);
+            exit(1);
+        }
+        Err(_) => {
+            let c_path = CString::new(path).unwrap();
+            if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+                eprintln!(
+                    "cannot create {}: {}",
+                    path,
+                    std::io::Error::last_os_error()
+                );
+                exit(1);
+            }
+            true
+        }
+    }
+}
+
+/// `command` as it would be typed into a shell, quoting the arguments that
+/// need it.
+pub fn quote_command(command: &[String]) -> String {
+    let quoted: Vec<_> = command
+        .iter()
+        .map(|arg| {
+            let plain = !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+            if plain {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        })
+        .collect();
+    quoted.join(" ")
+}
+
+/// Starts `command` in a process group of its own, so that a Ctrl-C reaches
+/// it only through the logger, and on every CPU even if the logger is
+/// pinned. Gives up on the capture, log included, if it can't be started.
+fn spawn_child(command: &[String], config: &Config) -> Child {
+    let child = match Command::new(&command[0])
+        .args(&command[1..])
+        .process_group(0)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(error) => {
+            eprintln!("cannot run {}: {}", command[0], error);
+            if config.output != "-" {
+                let _ = remove_file(&config.output);
+            }
+            exit(1);
+        }
+    };
+    if config.pin_cpu.is_some() {
+        if let Err(error) = set_cpus(child.id(), &online_cpus()) {
+            eprintln!("cannot unpin {}: {}", command[0], error);
+        }
+    }
+    child
+}
+
+/// The options of every capture, as `apply_config` resolved them, for the
+/// log's metadata.
+static RESOLVED_CONFIG: OnceLock<String> = OnceLock::new();
+
+/// The `profile` or `run` command `args` invoke under `command`, the
+/// profiler's, or its root for the bare form, with their matches and where
+/// their own arguments start.
+fn capture_matches(
+    command: &clap::Command,
+    args: &[OsString],
+) -> Option<(clap::Command, ArgMatches, usize)> {
+    let matches = command.clone().get_matches_from(args);
+    match matches.subcommand() {
+        Some((name @ ("profile" | "run"), matches)) => Some((
+            command.find_subcommand(name).unwrap().clone(),
+            matches.clone(),
+            2,
+        )),
+        Some(_) => None,
+        None => Some((command.clone(), matches, 1)),
+    }
+}
+
+/// The options of a config file, with those of `[profile.NAME]` over the
+/// top-level ones.
+fn read_config_file(command: &mut clap::Command, path: &str, profile: Option<&str>) -> toml::Table {
+    let text = read_to_string(path).unwrap_or_else(|error| {
+        command
+            .error(ErrorKind::Io, format!("cannot read {}: {}", path, error))
+            .exit()
+    });
+    let mut table: toml::Table = text.parse().unwrap_or_else(|error| {
+        command
+            .error(ErrorKind::InvalidValue, format!("{}: {}", path, error))
+            .exit()
+    });
+    let profiles = table.remove("profile");
+    if let Some(name) = profile {
+        let Some(toml::Value::Table(options)) =
+            profiles.as_ref().and_then(|profiles| profiles.get(name))
+        else {
+            command
+                .error(
+                    ErrorKind::InvalidValue,
+                    format!("{} has no [profile.{}]", path, name),
+                )
+                .exit();
+        };
+        table.extend(options.clone());
+    }
+    table
+}
+
+/// Adds the options of `--config` to `args` that they don't already give,
+/// directly or by a conflicting option, and records the options the capture
+/// then runs with. Every argument but those of `profile` and `run` is passed
+/// through as is.
+pub fn apply_config(command: clap::Command, mut args: Vec<OsString>) -> Vec<OsString> {
+    let Some((mut capture, matches, start)) = capture_matches(&command, &args) else {
+        return args;
+    };
+    if let Some(path) = matches.get_one::<String>("config") {
+        let profile = matches.get_one::<String>("capture_profile");
+        let table = read_config_file(&mut capture, path, profile.map(String::as_str));
+        let given = |arg: &Arg| {
+            matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+        };
+        let mut options = Vec::new();
+        for (key, value) in table {
+            let Some(arg) = capture
+                .get_arguments()
+                .find(|arg| arg.get_long() == Some(key.as_str()))
+            else {
+                capture
+                    .error(
+                        ErrorKind::UnknownArgument,
+                        format!("{} sets \"{}\", which is no option", path, key),
+                    )
+                    .exit();
+            };
+            if given(arg) || capture.get_arg_conflicts_with(arg).into_iter().any(given) {
+                continue;
+            }
+            let values = match value {
+                toml::Value::Array(values) => values,
+                value => vec![value],
+            };
+            for value in values {
+                let value = match value {
+                    toml::Value::Boolean(true) => None,
+                    toml::Value::Boolean(false) => continue,
+                    toml::Value::String(value) => Some(value),
+                    toml::Value::Integer(value) => Some(value.to_string()),
+                    toml::Value::Float(value) => Some(value.to_string()),
+                    value => capture
+                        .error(
+                            ErrorKind::InvalidValue,
+                            format!(
+                                "{} sets {} to {}, not a string, number or boolean",
+                                path, key, value
+                            ),
+                        )
+                        .exit(),
+                };
+                options.push(OsString::from(format!("--{}", key)));
+                options.extend(value.map(OsString::from));
+            }
+        }
+        args.splice(start..start, options);
+    }
+
+    let (capture, matches, _) = capture_matches(&command, &args).unwrap();
+    let mut resolved = toml::Table::new();
+    for arg in capture.get_arguments() {
+        let id = arg.get_id().as_str();
+        // Positional forms under the names of their options, but not the
+        // command of `profiler run`, which the metadata has anyway.
+        let Some(key) = arg.get_long().or(id.strip_suffix("_pos")) else {
+            continue;
+        };
+        if ["config", "profile", "help", "version"].contains(&key)
+            || matches.value_source(id).is_none()
+        {
+            continue;
+        }
+        let values: Vec<_> = matches
+            .get_raw(id)
+            .into_iter()
+            .flatten()
+            .map(|value| value.to_string_lossy().into_owned())
+            .collect();
+        let value = match arg.get_action() {
+            ArgAction::SetTrue if values == ["true"] => toml::Value::Boolean(true),
+            ArgAction::SetTrue => continue,
+            ArgAction::Append => {
+                toml::Value::Array(values.into_iter().map(toml::Value::String).collect())
+            }
+            _ => toml::Value::String(values.join(",")),
+        };
+        resolved.insert(key.to_string(), value);
+    }
+    RESOLVED_CONFIG.set(resolved.to_string()).unwrap();
+    args
+}
+
+pub fn run(args: ProfileArgs) {
+    capture(args, None);
+}
+
+/// `profiler run`, which exits as the command did.
+pub fn run_command(args: RunArgs) {
+    let status = capture(args.profile, Some(args.command)).unwrap();
+    exit(
+        status
+            .code()
+            .or(status.signal().map(|signal| 128 + signal))
+            .unwrap_or(1),
+    );
+}
+
+/// Captures for the configured duration or, given a `command`, for as long
+/// as it runs, returning how it ended.
+fn capture(args: ProfileArgs, command: Option<Vec<String>>) -> Option<ExitStatus> {
+    let (delay, wait_for_enter) = (args.delay.unwrap_or_default(), args.wait_for_enter);
+    let start_at = args.start_at;
+    let config = args.into_config(command.as_deref().map(quote_command));
+    // Before the log is created and SIGINT handled, so that interrupting the
+    // wait leaves no file behind; `into_config` only checks that it could be
+    // created.
+    wait_to_start(delay, wait_for_enter);
+    if let Some(start_at) = start_at {
+        wait_until(UNIX_EPOCH + Duration::from_millis(start_at));
+    }
+    // Before any thread or perf is started, so that they are pinned too.
+    if let Some(cpu) = config.pin_cpu {
+        if let Err(error) = pin_to_cpu(cpu) {
+            eprintln!("cannot pin to CPU {}: {}", cpu, error);
+            exit(1);
+        }
+    }
+    let fifo = config.markers.as_deref().filter(|&path| create_fifo(path));
+    let exporter = config.prometheus.as_deref().map(|address| {
+        Exporter::serve(address).unwrap_or_else(|error| {
+            eprintln!("cannot serve Prometheus metrics on {}: {}", address, error);
+            exit(1);
+        })
+    });
+    let output = match &config.remote {
+        Some(address) => Output::remote(address, config.id, &config.output),
+        None => Output::create(&config.output),
+    };
+    let mut output = output.unwrap_or_else(|error| {
+        eprintln!("cannot create {}: {}", config.output, error);
+        exit(1);
+    });
+
+    let rotates = config.rotate_size.is_some() || config.rotate_entries.is_some();
+    write_log_start(&mut output, &config, rotates.then_some(1));
+
+    let child = command.map(|command| spawn_child(&command, &config));
+    let perf = PerfSlot::default();
+    let stop = install_signal_handler(perf.clone(), child.as_ref().map(Child::id));
+    // Ends the capture once the command exits, as a signal would.
+    let waiter = child.map(|mut child| {
+        let stop = stop.clone();
+        thread::spawn(move || {
+            let status = child.wait().unwrap();
+            stop.store(true, Ordering::SeqCst);
+            status
+        })
+    });
+    let guard = PerfGuard(perf.clone());
+    let elapsed = process(&mut output, &config, &perf, &stop, exporter.as_ref());
+    drop(guard);
+
+    let status = waiter.map(|waiter| waiter.join().unwrap());
+    write_log_end(&mut output, elapsed, status, None);
+    output.finish(config.sync);
+    if let Some(fifo) = fifo {
+        let _ = remove_file(fifo);
+    }
+    status
+}
@@ -0,0 +1,3591 @@
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs::{read_dir, File},
+    io::{self, stdout, BufReader, BufWriter, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::exit,
+    sync::OnceLock,
+    thread,
+    time::Duration,
+};
+
+use clap::{error::ErrorKind, ColorChoice, CommandFactory, Parser, ValueEnum};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    platform::{PerfGranularity, Platform},
+    report::{
+        cache::{load_cached, Cache},
+        stats::{RunSpread, Stats},
+        *,
+    },
+    timestamp,
+};
+
+use super::{parse_cpu_list, parse_duration, CpuList};
+
+mod html;
+mod influx;
+mod parquet;
+mod plot;
+mod sqlite;
+mod tui;
+mod watch;
+
+/// Tells on stderr what `load_report` had to leave out of a log and what the
+/// capture itself is missing, returning whether the log is complete.
+fn print_warnings(filename: &str, report: &Report) -> bool {
+    if report.truncated {
+        let recovered = report.entries.len() + report.skipped.len();
+        if report.duration.is_zero() {
+            eprintln!(
+                "{}: log is truncated, recovered {} entries",
+                filename, recovered
+            );
+        } else {
+            eprintln!(
+                "{}: log is truncated, recovered {} of {} expected entries",
+                filename,
+                recovered,
+                report.duration.as_millis() / report.interval.as_millis()
+            );
+        }
+    }
+    for error in report.skipped.iter() {
+        eprintln!("{}: skipped {}", filename, error);
+    }
+    let anomalous: Vec<_> = report
+        .entries
+        .iter()
+        .filter(|entry| !entry.proc_anomalies.is_empty())
+        .collect();
+    for entry in anomalous.iter().take(5) {
+        let mut cpus: Vec<_> = entry.proc_anomalies.iter().collect();
+        cpus.sort_by(|(a, _), (b, _)| compare_cpus(a, b));
+        let phrases: Vec<_> = [
+            (ProcAnomaly::Backwards, "went backwards"),
+            (ProcAnomaly::Stalled, "didn't advance"),
+        ]
+        .into_iter()
+        .filter_map(|(kind, phrase)| {
+            let of: Vec<_> = cpus
+                .iter()
+                .filter(|(_, anomaly)| **anomaly == kind)
+                .map(|(cpu, _)| cpu.as_str())
+                .collect();
+            (!of.is_empty()).then(|| format!("of {} {}", of.join(", "), phrase))
+        })
+        .collect();
+        eprintln!(
+            "{}: left out the load at {:.1}s, where the /proc/stat counters {}",
+            filename,
+            entry.time.as_secs_f64(),
+            phrases.join(" and ")
+        );
+    }
+    if anomalous.len() > 5 {
+        eprintln!(
+            "{}: {} more intervals with /proc/stat counters left out",
+            filename,
+            anomalous.len() - 5
+        );
+    }
+    let completeness = get_completeness(report);
+    match completeness.expected {
+        Some(expected) if completeness.actual < expected && !report.truncated => eprintln!(
+            "{}: {} of {} expected entries",
+            filename, completeness.actual, expected
+        ),
+        _ => {}
+    }
+    if !completeness.gaps.is_empty() {
+        // The first few place the trouble, any more only add up.
+        let mut gaps: Vec<_> = completeness
+            .gaps
+            .iter()
+            .take(5)
+            .map(|(time, gap)| format!("{:.1}s ({:.3}s)", time.as_secs_f64(), gap.as_secs_f64()))
+            .collect();
+        if completeness.gaps.len() > 5 {
+            gaps.push(format!("{} more", completeness.gaps.len() - 5));
+        }
+        eprintln!(
+            "{}: {} {} over 1.5 intervals, before the entries at {}",
+            filename,
+            completeness.gaps.len(),
+            if completeness.gaps.len() == 1 {
+                "gap"
+            } else {
+                "gaps"
+            },
+            gaps.join(", ")
+        );
+    }
+    !report.truncated && completeness.is_complete()
+}
+
+/// The `--json` document for one input file.
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    file: &'a str,
+    #[serde(flatten)]
+    report: &'a Report,
+    averages: Vec<CpuAverages>,
+}
+
+fn format_khz(khz: f64) -> String {
+    format!("{:.2} GHz", khz / 1_000_000.0)
+}
+
+/// How `format_number` prints, from `--precision` and `--raw-numbers`.
+#[derive(Clone, Copy)]
+struct NumberFormat {
+    precision: usize,
+    raw: bool,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        NumberFormat {
+            precision: 2,
+            raw: false,
+        }
+    }
+}
+
+static NUMBER_FORMAT: OnceLock<NumberFormat> = OnceLock::new();
+
+// SI prefixes; the blank keeps unscaled numbers aligned with scaled ones.
+const UNIT_NAMES: &[&str] = &[" ", "K", "M", "G", "T", "P", "E"];
+
+impl NumberFormat {
+    fn format(self, number: f64) -> String {
+        let precision = self.precision;
+        if self.raw {
+            return format!("{:.precision$}", number);
+        }
+        // Scale by what the number rounds to, so 999.999 is 1.00K rather
+        // than 1000.00.
+        let rounded = |number: f64| format!("{:.precision$}", number).parse::<f64>().unwrap();
+        let mut number = number;
+        let mut unit = 0;
+        while rounded(number).abs() >= 1000.0 && unit + 1 < UNIT_NAMES.len() {
+            number /= 1000.0;
+            unit += 1;
+        }
+        format!("{:.precision$}{}", number, UNIT_NAMES[unit])
+    }
+}
+
+fn format_number(number: f64) -> String {
+    NUMBER_FORMAT
+        .get()
+        .copied()
+        .unwrap_or_default()
+        .format(number)
+}
+
+fn format_duration(report: &Report) -> String {
+    match report.duration_actual {
+        Some(actual) if report.exit.is_some() => {
+            format!("{:.1} (until the command exited)", actual.as_secs_f64())
+        }
+        Some(actual) if report.duration.is_zero() => {
+            format!("{:.1} (until interrupted)", actual.as_secs_f64())
+        }
+        None if report.duration.is_zero() => "until interrupted".to_string(),
+        Some(actual) if report.part.is_some() => format!(
+            "{} ({:.1} of it in this part)",
+            if report.duration.is_zero() {
+                "until interrupted".to_string()
+            } else {
+                report.duration.as_secs().to_string()
+            },
+            actual.as_secs_f64()
+        ),
+        Some(actual) if actual < report.duration => format!(
+            "{} (stopped early after {:.1})",
+            report.duration.as_secs(),
+            actual.as_secs_f64()
+        ),
+        _ => report.duration.as_secs().to_string(),
+    }
+}
+
+fn format_exit(exit: Exit) -> String {
+    match exit {
+        Exit::Code(code) => format!("exited with {}", code),
+        Exit::Signal(signal) => format!("killed by signal {}", signal),
+    }
+}
+
+/// One row per CPU of the spread of a metric's per-interval samples,
+/// leaving out intervals without a sample.
+fn print_stats_table(
+    title: &str,
+    cpus: &[String],
+    samples: impl Fn(&str) -> Vec<f64>,
+    format: impl Fn(f64) -> String,
+) {
+    println!("{}", title.bold().yellow());
+    let max_cpu_len = cpus.iter().map(|s| s.len()).max().unwrap_or(0).max(3);
+    println!(
+        "{:max_cpu_len$} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+        "cpu", "mean", "min", "max", "stddev", "p50", "p95", "p99"
+    );
+    for cpu in cpus {
+        let Some(stats) = Stats::from_samples(&samples(cpu)) else {
+            continue;
+        };
+        println!(
+            "{:max_cpu_len$} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+            cpu.as_str().bold().yellow(),
+            format(stats.mean),
+            format(stats.min),
+            format(stats.max),
+            format(stats.stddev),
+            format(stats.p50),
+            format(stats.p95),
+            format(stats.p99)
+        );
+    }
+}
+
+fn print_stats(report: &Report) {
+    print_stats_table(
+        "Per CPU load statistics (%)",
+        &report.proc_cpus,
+        |cpu| {
+            report
+                .entries
+                .iter()
+                .filter_map(|entry| Some(entry.proc.get(cpu)?.load))
+                .collect()
+        },
+        |load| {
+            let precision = NUMBER_FORMAT.get().copied().unwrap_or_default().precision;
+            format!("{:.precision$}", load)
+        },
+    );
+    for event in report.perf_events.iter() {
+        print_stats_table(
+            &format!("Per CPU {} statistics", event_title(event)),
+            &report.perf_cpus,
+            |cpu| {
+                report
+                    .entries
+                    .iter()
+                    .filter_map(|entry| Some(entry.perf.get(cpu)?.get(event)? as f64))
+                    .collect()
+            },
+            format_number,
+        );
+    }
+}
+
+/// Length of `text` on screen, without its color escape codes.
+fn visible_len(text: &str) -> usize {
+    lazy_regex::regex!(r#"\x1b\[[0-9;]*m"#)
+        .replace_all(text, "")
+        .chars()
+        .count()
+}
+
+/// Where and how wide `print_string_collection` lays out its cells, from
+/// `--width` and `--separator`.
+struct Layout {
+    width: usize,
+    separator: String,
+}
+
+static LAYOUT: OnceLock<Layout> = OnceLock::new();
+
+/// `--width`, else the terminal's, else `$COLUMNS`, else 80 columns.
+fn output_width(width: Option<usize>) -> usize {
+    width
+        .or_else(|| {
+            stdout()
+                .is_terminal()
+                .then(termion::terminal_size)
+                .and_then(Result::ok)
+                .map(|(width, _)| width as usize)
+        })
+        .or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+        .unwrap_or(80)
+}
+
+fn pad(text: &str, width: usize) -> String {
+    format!(
+        "{}{}",
+        text,
+        " ".repeat(width.saturating_sub(visible_len(text)))
+    )
+}
+
+/// Lays `label value` cells out on one line when they fit, and otherwise as
+/// a grid, with a line of labels heading every line of values.
+fn layout_cells(cells: &[(String, String)], layout: &Layout) -> Vec<String> {
+    if cells.is_empty() {
+        return Vec::new();
+    }
+    let Layout { width, separator } = layout;
+    let label_len = cells
+        .iter()
+        .map(|(label, _)| visible_len(label))
+        .max()
+        .unwrap();
+    let value_len = cells
+        .iter()
+        .map(|(_, value)| visible_len(value))
+        .max()
+        .unwrap();
+
+    let cell_len = label_len + 1 + value_len;
+    let line_len = cells.len() * (cell_len + separator.len()) - separator.len();
+    if line_len <= *width {
+        let line: Vec<_> = cells
+            .iter()
+            .map(|(label, value)| format!("{} {}", pad(label, label_len), pad(value, value_len)))
+            .collect();
+        return vec![line.join(separator)];
+    }
+
+    let column_len = label_len.max(value_len);
+    let columns = ((width + separator.len()) / (column_len + separator.len())).max(1);
+    let mut lines = Vec::new();
+    for row in cells.chunks(columns) {
+        let mut line = |text: fn(&(String, String)) -> &String| {
+            let cells: Vec<_> = row.iter().map(|cell| pad(text(cell), column_len)).collect();
+            lines.push(cells.join(separator).trim_end().to_string());
+        };
+        line(|(label, _)| label);
+        line(|(_, value)| value);
+    }
+    lines
+}
+
+/// Interrupt sources the summary lists, as few make up most interrupts.
+const TOP_IRQS: usize = 10;
+
+fn print_string_collection(cells: &[(String, String)]) {
+    let default_layout = Layout {
+        width: 80,
+        separator: " | ".to_string(),
+    };
+    for line in layout_cells(cells, LAYOUT.get().unwrap_or(&default_layout)) {
+        println!("{}", line);
+    }
+}
+
+const CSV_HEADER: &str = "file,report_id,time_ms,cpu,load,user,nice,system,idle,iowait,irq,softirq,total,cycles,context_switches,hostname,uname,cpu_model,logical_cpus,physical_cpus,device_model,psi_cpu_some_us,psi_cpu_full_us,psi_memory_some_us,psi_memory_full_us,psi_io_some_us,psi_io_full_us";
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Writes one row per entry per CPU. /proc/stat and perf name CPUs
+/// differently on ubuntu, so a row only fills the columns its source knows;
+/// the PSI stall times are machine-wide and repeated on every row.
+fn write_csv(output: &mut impl Write, filename: &str, report: &Report) {
+    let metadata = &report.metadata;
+    let text = |field: &Option<String>| csv_field(field.as_deref().unwrap_or_default());
+    let number = |field: Option<u32>| field.map(|value| value.to_string()).unwrap_or_default();
+    let metadata = format!(
+        "{},{},{},{},{},{}",
+        text(&metadata.hostname),
+        text(&metadata.uname),
+        text(&metadata.cpu_model),
+        number(metadata.logical_cpus),
+        number(metadata.physical_cpus),
+        text(&metadata.device_model)
+    );
+    for entry in report.entries.iter() {
+        let pressure: Vec<_> = PRESSURE_RESOURCES
+            .iter()
+            .flat_map(|resource| {
+                ["some", "full"].map(|kind| {
+                    entry
+                        .pressure
+                        .get(&format!("{} {}", resource, kind))
+                        .map(|pressure| pressure.stall_us.to_string())
+                        .unwrap_or_default()
+                })
+            })
+            .collect();
+        let mut cpus: Vec<_> = entry
+            .proc
+            .keys()
+            .chain(
+                entry
+                    .perf
+                    .keys()
+                    .filter(|cpu| !entry.proc.contains_key(*cpu)),
+            )
+            .collect();
+        cpus.sort_by(|a, b| compare_cpus(a, b));
+        for cpu in cpus {
+            let proc = match entry.proc.get(cpu) {
+                Some(proc) => format!(
+                    "{},{},{},{},{},{},{},{},{}",
+                    proc.load,
+                    proc.user,
+                    proc.nice,
+                    proc.system,
+                    proc.idle,
+                    proc.iowait,
+                    proc.irq,
+                    proc.softirq,
+                    proc.total
+                ),
+                None => ",,,,,,,,".to_string(),
+            };
+            let perf = match entry.perf.get(cpu) {
+                Some(perf) => format!(
+                    "{},{}",
+                    perf.get(CYCLES)
+                        .map(|value| value.to_string())
+                        .unwrap_or_default(),
+                    perf.get(CONTEXT_SWITCHES)
+                        .map(|value| value.to_string())
+                        .unwrap_or_default()
+                ),
+                None => ",".to_string(),
+            };
+            writeln!(
+                output,
+                "{},{},{},{},{},{},{},{}",
+                csv_field(filename),
+                report.id,
+                entry.time.as_millis(),
+                csv_field(cpu),
+                proc,
+                perf,
+                metadata,
+                pressure.join(",")
+            )
+            .unwrap();
+        }
+    }
+}
+
+/// A CPU whose run average went over a `--max-avg-*` limit, or that had no
+/// data for the limited metric at all.
+struct Violation {
+    cpu: String,
+    metric: &'static str,
+    average: Option<f64>,
+    limit: f64,
+}
+
+impl Violation {
+    fn describe(&self) -> String {
+        let metric = match self.metric {
+            LOAD => "load".to_string(),
+            event => event_title(event),
+        };
+        match self.average {
+            Some(average) => format!(
+                "CPU {} average {} {:.2} exceeds {} by {:.2}",
+                self.cpu,
+                metric,
+                average,
+                self.limit,
+                average - self.limit
+            ),
+            None => format!("CPU {} has no {} data", self.cpu, metric),
+        }
+    }
+}
+
+const LOAD: &str = "load";
+
+fn check_thresholds(report: &Report, args: &ReportArgs) -> Vec<Violation> {
+    let limits = [
+        (LOAD, args.max_avg_load),
+        (CYCLES, args.max_avg_cycles),
+        (CONTEXT_SWITCHES, args.max_avg_context_switches),
+    ];
+    let mut violations = Vec::new();
+    for cpu in args.threshold_cpus.iter() {
+        for (metric, limit) in limits {
+            let Some(limit) = limit else {
+                continue;
+            };
+            let average = if metric == LOAD {
+                report
+                    .proc_cpus
+                    .contains(cpu)
+                    .then(|| get_average_proc_load(report, cpu))
+            } else {
+                report
+                    .perf_cpus
+                    .contains(cpu)
+                    .then(|| get_average_perf(report, cpu, metric))
+                    .filter(|average| !average.is_nan())
+            };
+            if average.is_none_or(|average| average > limit) {
+                violations.push(Violation {
+                    cpu: cpu.clone(),
+                    metric,
+                    average,
+                    limit,
+                });
+            }
+        }
+    }
+    violations
+}
+
+fn print_metadata(metadata: &Metadata) {
+    let fields = [
+        ("Host ", metadata.hostname.clone()),
+        ("Device ", metadata.device_model.clone()),
+        ("System ", metadata.uname.clone()),
+        ("Command ", metadata.command.clone()),
+        (
+            "Options ",
+            metadata
+                .options
+                .as_ref()
+                .map(|options| options.lines().collect::<Vec<_>>().join(", ")),
+        ),
+        (
+            "Profiler CPU ",
+            metadata.pinned_cpu.map(|cpu| format!("{} (pinned)", cpu)),
+        ),
+        ("CPU ", metadata.cpu_model.clone()),
+        (
+            "Cores ",
+            match (metadata.logical_cpus, metadata.physical_cpus) {
+                (Some(logical), Some(physical)) => {
+                    Some(format!("{} logical, {} physical", logical, physical))
+                }
+                (Some(logical), None) => Some(format!("{} logical", logical)),
+                (None, Some(physical)) => Some(format!("{} physical", physical)),
+                (None, None) => None,
+            },
+        ),
+        ("Clusters ", {
+            let clusters: Vec<_> = detect_clusters(&metadata.topology)
+                .iter()
+                .map(|group| format!("{} {}", group.name, format_cpu_list(&group.cpus)))
+                .collect();
+            (!clusters.is_empty()).then(|| clusters.join(", "))
+        }),
+    ];
+    for (label, value) in fields {
+        if let Some(value) = value {
+            println!("{:20} {}", label.bold().yellow(), value);
+        }
+    }
+}
+
+/// Escapes the cell separator, which would otherwise split a value such as
+/// a uname in two.
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+/// The metadata and per CPU averages as GitHub-flavored markdown tables
+/// under a heading naming the file.
+fn print_markdown(filename: &str, report: &Report) {
+    let metadata = &report.metadata;
+    println!("## {}", markdown_cell(filename));
+    println!();
+    println!("| | |");
+    println!("|:--|:--|");
+    let fields = [
+        ("Report ID", Some(report.id.to_string())),
+        ("Host", metadata.hostname.clone()),
+        ("Device", metadata.device_model.clone()),
+        ("System", metadata.uname.clone()),
+        ("CPU", metadata.cpu_model.clone()),
+        (
+            "Logical CPUs",
+            metadata.logical_cpus.map(|cpus| cpus.to_string()),
+        ),
+        (
+            "Physical CPUs",
+            metadata.physical_cpus.map(|cpus| cpus.to_string()),
+        ),
+        (
+            "Profiler CPU",
+            metadata.pinned_cpu.map(|cpu| cpu.to_string()),
+        ),
+        ("Start", report.start.map(timestamp::format)),
+        ("Platform", Some(report.platform.name().to_string())),
+        ("Command", metadata.command.clone()),
+        ("Test Duration", Some(format_duration(report))),
+        ("Exit Status", report.exit.map(format_exit)),
+        ("Test Interval", Some(format!("{:?}", report.interval))),
+    ];
+    for (label, value) in fields {
+        if let Some(value) = value {
+            println!("| **{}** | {} |", label, markdown_cell(&value));
+        }
+    }
+    println!();
+    println!("| CPU | Load | Cycles | Context switches |");
+    println!("|:--|--:|--:|--:|");
+    let number = |value: Option<f64>, unit: &str| {
+        value.map_or("-".to_string(), |value| {
+            format!("{}{}", format_number(value).trim_end(), unit)
+        })
+    };
+    for averages in get_averages(report) {
+        println!(
+            "| {} | {} | {} | {} |",
+            markdown_cell(&averages.cpu),
+            number(averages.load, "%"),
+            number(averages.cycles, ""),
+            number(averages.context_switches, "")
+        );
+    }
+    println!();
+}
+
+/// `--quiet`: the file, its run and the average load, cycles and context
+/// switches of all CPUs on one line.
+fn print_summary_line(filename: &str, report: &Report) {
+    let averages = get_averages(report);
+    let all = averages.iter().find(|average| average.cpu == "all");
+    let mut line = format!(
+        "{}: {} entries over {:.1}s",
+        filename,
+        report.entries.len(),
+        report
+            .duration_actual
+            .unwrap_or(report.interval * report.entries.len() as u32)
+            .as_secs_f64()
+    );
+    if let Some(load) = all.and_then(|all| all.load) {
+        write!(line, ", load {}%", format_number(load).trim_end()).unwrap();
+    }
+    let recorded = |event| report.perf_events.iter().any(|recorded| recorded == event);
+    if let Some(cycles) = all.and_then(|all| all.cycles).filter(|_| recorded(CYCLES)) {
+        write!(line, ", cycles {}", format_number(cycles).trim_end()).unwrap();
+    }
+    if let Some(switches) = all
+        .and_then(|all| all.context_switches)
+        .filter(|_| recorded(CONTEXT_SWITCHES))
+    {
+        write!(
+            line,
+            ", context switches {}",
+            format_number(switches).trim_end()
+        )
+        .unwrap();
+    }
+    println!("{}", line);
+}
+
+fn print_report(
+    filename: &str,
+    report: &Report,
+    args: &ReportArgs,
+    violations: &[Violation],
+    phases: Option<&[Phase]>,
+    groups: Option<&[NetTypeGroup]>,
+) {
+    let violated = |cpu: &str, metric: &str| {
+        violations
+            .iter()
+            .any(|violation| violation.cpu == cpu && violation.metric == metric)
+    };
+    let highlight = |text: String, cpu: &str, metric: &str| {
+        if violated(cpu, metric) {
+            text.red().to_string()
+        } else {
+            text
+        }
+    };
+
+    println!("{:20} {}", "Report ID ".bold().yellow(), report.id);
+    println!("{:20} {}", "File ".bold().yellow(), filename);
+    print_metadata(&report.metadata);
+    if let Some(start) = report.start {
+        println!(
+            "{:20} {}",
+            "Start ".bold().yellow(),
+            timestamp::format(start)
+        );
+    }
+    if let Some(offset) = report.clock_offset_ms {
+        println!(
+            "{:20} {:+}ms against the profiler sync coordinator",
+            "Clock Offset ".bold().yellow(),
+            offset
+        );
+    }
+    if let Some(warmup) = report.warmup {
+        println!(
+            "{:20} {:.1}s (excluded)",
+            "Warmup ".bold().yellow(),
+            warmup.as_secs_f64()
+        );
+    }
+    if args.from.is_some() || args.to.is_some() {
+        let first = report.entries.first().unwrap().time;
+        let last = report.entries.last().unwrap().time + report.interval;
+        println!(
+            "{:20} {:.1}s to {:.1}s ({} entries)",
+            "Window ".bold().yellow(),
+            first.as_secs_f64(),
+            last.as_secs_f64(),
+            report.entries.len()
+        );
+    }
+    println!(
+        "{:20} {}",
+        "Platform ".bold().yellow(),
+        report.platform.name()
+    );
+    match report.perf_granularity {
+        PerfGranularity::Core => {}
+        PerfGranularity::Socket => {
+            println!("{:20} per socket", "Perf Counts ".bold().yellow())
+        }
+        PerfGranularity::System => {
+            println!("{:20} system-wide", "Perf Counts ".bold().yellow())
+        }
+    }
+    if let Some(part) = report.part {
+        println!(
+            "{:20} {}{}",
+            "Part ".bold().yellow(),
+            part,
+            match &report.next_part {
+                Some(next) => format!(", continues in {}; --merge the parts", next),
+                None => ", the last".to_string(),
+            }
+        );
+    }
+    if !report.cpu_list.is_empty() {
+        println!(
+            "{:20} {}, the only CPUs profiled",
+            "CPU List ".bold().yellow(),
+            format_cpu_list(&report.cpu_list)
+        );
+    }
+    println!(
+        "{:20} {}",
+        "Test Duration ".bold().yellow(),
+        format_duration(report)
+    );
+    if let Some(exit) = report.exit {
+        println!(
+            "{:20} {}",
+            "Exit Status ".bold().yellow(),
+            format_exit(exit)
+        );
+    }
+    println!(
+        "{:20} {:?}",
+        "Test Interval ".bold().yellow(),
+        report.interval
+    );
+    if let Some((average, peak)) = get_profiler_share(report) {
+        println!(
+            "{:20} average {:.2}%, peak {:.2}% of all CPUs{}",
+            "Profiler Overhead ".bold().yellow(),
+            average,
+            peak,
+            if args.subtract_self {
+                ", taken out of all"
+            } else {
+                ""
+            }
+        );
+    }
+    let skipped: u32 = report.entries.iter().map(|entry| entry.skipped).sum();
+    if skipped > 0 {
+        println!(
+            "{:20} {}, as the logger fell behind",
+            "Skipped Intervals ".bold().yellow(),
+            skipped
+        );
+    }
+    if let Some(jitter) = get_jitter(report) {
+        let stats = jitter.stats;
+        println!(
+            "{:20} mean {:+.1}ms, min {:+.1}ms, max {:+.1}ms, p99 {:+.1}ms",
+            "Interval Jitter ".bold().yellow(),
+            stats.mean,
+            stats.min,
+            stats.max,
+            stats.p99
+        );
+        if !jitter.late.is_empty() {
+            println!(
+                "{}",
+                "Late entries, over 1.5 intervals after the previous"
+                    .bold()
+                    .yellow()
+            );
+            let late = jitter
+                .late
+                .iter()
+                .map(|(time, gap)| {
+                    (
+                        format!("{:>7.1}s", time.as_secs_f64()),
+                        format!("after {:.3}s", gap.as_secs_f64()),
+                    )
+                })
+                .collect::<Vec<_>>();
+            print_string_collection(&late);
+        }
+    }
+    if !report.markers.is_empty() {
+        println!("{}", "Markers".bold().yellow());
+        let markers = report
+            .markers
+            .iter()
+            .map(|marker| {
+                (
+                    format!("{:>7.1}s", marker.time.as_secs_f64()),
+                    marker.label.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&markers);
+    }
+
+    println!("{}", "Per CPU average load".bold().yellow());
+    let proc_average_load = report
+        .proc_cpus
+        .iter()
+        .map(|cpu| {
+            let load = format!(
+                "{:>6.20}%",
+                format_number(get_average_proc_load(report, cpu))
+            );
+            (
+                cpu.as_str().bold().yellow().to_string(),
+                highlight(load, cpu, LOAD),
+            )
+        })
+        .collect::<Vec<_>>();
+    print_string_collection(&proc_average_load);
+    if let Some(loadavg) = get_loadavg_stats(report) {
+        println!(
+            "{:20} 1 min average {:.2}, peak {:.2}{}",
+            "Load Average ".bold().yellow(),
+            loadavg.average,
+            loadavg.peak,
+            loadavg.busy_cpus.map_or(String::new(), |busy| format!(
+                ", against {:.2} CPUs busy by /proc/stat",
+                busy
+            ))
+        );
+        println!(
+            "{:20} peak {} of {} tasks",
+            "Runnable Tasks ".bold().yellow(),
+            loadavg.peak_runnable,
+            loadavg.tasks
+        );
+    }
+    let run_delay = get_run_delay_stats(report);
+    if !run_delay.is_empty() {
+        println!("{}", "Per CPU run delay".bold().yellow());
+        let run_delay = run_delay
+            .iter()
+            .map(|cpu| {
+                (
+                    cpu.cpu.as_str().bold().yellow().to_string(),
+                    format!(
+                        "average {:.2} ms/s, peak {:.2} ms/s{}",
+                        cpu.average,
+                        cpu.peak,
+                        cpu.per_timeslice_us.map_or(String::new(), |wait| format!(
+                            ", {:.1} µs per timeslice",
+                            wait
+                        ))
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&run_delay);
+    }
+    if let Some((average, peak)) = get_gpu_load(report) {
+        println!(
+            "{:20} average {:.2}%, peak {:.2}%",
+            "GPU Load ".bold().yellow(),
+            average,
+            peak
+        );
+    }
+    if args.breakdown {
+        println!("{}", "Per CPU average time breakdown".bold().yellow());
+        let breakdowns = report
+            .proc_cpus
+            .iter()
+            .map(|cpu| {
+                let categories = PROC_CATEGORIES
+                    .iter()
+                    .zip(get_average_breakdown(report, cpu))
+                    .map(|(category, percent)| format!("{} {:>5.2} %", category, percent))
+                    .collect::<Vec<_>>();
+                (
+                    cpu.as_str().bold().yellow().to_string(),
+                    categories.join(" "),
+                )
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&breakdowns);
+    }
+    let hotplugged = report
+        .proc_cpus
+        .iter()
+        .any(|cpu| get_online_percent(report, cpu) < 100.0);
+    if hotplugged {
+        println!("{}", "Per CPU online intervals".bold().yellow());
+        let online = report
+            .proc_cpus
+            .iter()
+            .map(|cpu| {
+                (
+                    cpu.as_str().bold().yellow().to_string(),
+                    format!("{:>6.2} %", get_online_percent(report, cpu)),
+                )
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&online);
+    }
+    let has_ipc = report.perf_events.iter().any(|event| event == INSTRUCTIONS)
+        && report.perf_events.iter().any(|event| event == CYCLES);
+    for event in report.perf_events.iter() {
+        let dropped = get_dropped_samples(report, event);
+        let title = if dropped > 0 {
+            format!(
+                "Per CPU average {} ({} samples not counted)",
+                event_title(event),
+                dropped
+            )
+        } else {
+            format!("Per CPU average {}", event_title(event))
+        };
+        println!("{}", title.bold().yellow());
+        let perf_average = report
+            .perf_cpus
+            .iter()
+            .map(|cpu| {
+                let average = get_average_perf(report, cpu, event);
+                let formatted = highlight(format_number(average), cpu, event);
+                let value = if event == CONTEXT_SWITCHES {
+                    format!(
+                        "{} ({}/s)",
+                        formatted,
+                        format_number(average / report.interval.as_secs_f64())
+                    )
+                } else if event == CYCLES && has_ipc {
+                    format!("{} (IPC {:.2})", formatted, get_average_ipc(report, cpu))
+                } else {
+                    formatted
+                };
+                (cpu.as_str().bold().yellow().to_string(), value)
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&perf_average);
+    }
+    if args.stats {
+        print_stats(report);
+    }
+    if !report.freq_cpus.is_empty() {
+        println!("{}", "Per CPU average/minimum frequency".bold().yellow());
+        let freq_stats = report
+            .freq_cpus
+            .iter()
+            .map(|cpu| {
+                let (average, min) = get_freq_stats(report, cpu);
+                (
+                    cpu.as_str().bold().yellow().to_string(),
+                    format!("{} / {}", format_khz(average), format_khz(min as f64)),
+                )
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&freq_stats);
+
+        let drops = get_freq_drops(report, args.freq_drop);
+        if !drops.is_empty() {
+            println!(
+                "{}",
+                format!("Frequency drops over {}%", args.freq_drop)
+                    .bold()
+                    .yellow()
+            );
+            let drops = drops
+                .iter()
+                .map(|drop| {
+                    (
+                        format!("{:>7.1}s", drop.time.as_secs_f64()),
+                        format!(
+                            "cpu{} {} -> {}",
+                            drop.cpu,
+                            format_khz(drop.from as f64),
+                            format_khz(drop.to as f64)
+                        ),
+                    )
+                })
+                .collect::<Vec<_>>();
+            print_string_collection(&drops);
+        }
+    }
+    let residency = get_freq_residency(report, args.freq_buckets);
+    if !residency.is_empty() {
+        println!("{}", "Per CPU frequency residency".bold().yellow());
+        let residency = residency
+            .iter()
+            .map(|cpu| {
+                let mut cells: Vec<_> = cpu
+                    .buckets
+                    .iter()
+                    .map(|bucket| {
+                        let range = if bucket.min_khz == bucket.max_khz {
+                            format_khz(bucket.min_khz as f64)
+                        } else {
+                            format!(
+                                "{:.2}-{}",
+                                bucket.min_khz as f64 / 1_000_000.0,
+                                format_khz(bucket.max_khz as f64)
+                            )
+                        };
+                        format!("{} {:.1}%", range, bucket.share)
+                    })
+                    .collect();
+                if let Some(khz) = cpu.effective_khz {
+                    cells.push(format!("effective {}", format_khz(khz)));
+                }
+                if let Some(load) = cpu.invariant_load {
+                    cells.push(format!("invariant load {:.2}%", load));
+                }
+                (
+                    cpu.cpu.as_str().bold().yellow().to_string(),
+                    cells.join(", "),
+                )
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&residency);
+    }
+    if !report.thermal_zones.is_empty() {
+        println!("{}", "Per zone average/peak temperature".bold().yellow());
+        let thermal_stats = report
+            .thermal_zones
+            .iter()
+            .map(|zone| {
+                let kind = report
+                    .entries
+                    .iter()
+                    .find_map(|entry| entry.thermal.get(zone))
+                    .map_or("", |thermal| thermal.kind.as_str());
+                let stats = match get_thermal_stats(report, zone) {
+                    Some((average, peak)) => {
+                        format!("{:.1}°C / {:.1}°C", average / 1000.0, peak as f64 / 1000.0)
+                    }
+                    None => "unreadable".to_string(),
+                };
+                (
+                    zone.as_str().bold().yellow().to_string(),
+                    format!("{} {}", kind, stats),
+                )
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&thermal_stats);
+
+        let suspects = get_throttle_suspects(report, args.thermal_threshold);
+        if !suspects.is_empty() {
+            println!(
+                "{}",
+                format!("Possible throttling above {}°C", args.thermal_threshold)
+                    .bold()
+                    .yellow()
+            );
+        }
+        for suspect in suspects {
+            let mut drops = Vec::new();
+            if let Some((from, to)) = suspect.load {
+                drops.push(format!("load {:.1}% -> {:.1}%", from, to));
+            }
+            if let Some((from, to)) = suspect.cycles {
+                drops.push(format!(
+                    "cycles {} -> {}",
+                    format_number(from as f64),
+                    format_number(to as f64)
+                ));
+            }
+            println!(
+                "{:>7.1}s {}, {} at {:.1}°C",
+                suspect.time.as_secs_f64(),
+                drops.join(", "),
+                suspect.zone,
+                suspect.temp as f64 / 1000.0
+            );
+        }
+    }
+    if !report.interfaces.is_empty() {
+        println!("{}", "Per interface average throughput".bold().yellow());
+        let net_average_throughput = report
+            .interfaces
+            .iter()
+            .map(|iface| {
+                (
+                    iface.as_str().bold().yellow().to_string(),
+                    format!(
+                        "rx {}B/s {}pkt/s tx {}B/s {}pkt/s",
+                        format_number(get_average_net_rate(report, iface, |net| net.rx_bytes)),
+                        format_number(get_average_net_rate(report, iface, |net| net.rx_packets)),
+                        format_number(get_average_net_rate(report, iface, |net| net.tx_bytes)),
+                        format_number(get_average_net_rate(report, iface, |net| net.tx_packets)),
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&net_average_throughput);
+    }
+    // Loop and zram devices are many and mostly idle.
+    let disks: Vec<_> = report
+        .disks
+        .iter()
+        .filter(|disk| {
+            report.entries.iter().any(|entry| {
+                entry
+                    .disk
+                    .get(*disk)
+                    .is_some_and(|disk| disk.reads > 0 || disk.writes > 0)
+            })
+        })
+        .collect();
+    if !disks.is_empty() {
+        println!(
+            "{:20} {:.2}% of all CPUs",
+            "IO Wait ".bold().yellow(),
+            get_average_breakdown(report, "all")[4]
+        );
+        println!("{}", "Per disk average throughput".bold().yellow());
+        let disks = disks
+            .iter()
+            .map(|disk| {
+                let stats = get_disk_stats(report, disk);
+                (
+                    disk.as_str().bold().yellow().to_string(),
+                    format!(
+                        "read {}B/s (peak {}B/s) write {}B/s (peak {}B/s) busy {:.1}% (peak {:.1}%)",
+                        format_number(stats.read_rate),
+                        format_number(stats.peak_read_rate),
+                        format_number(stats.write_rate),
+                        format_number(stats.peak_write_rate),
+                        stats.busy,
+                        stats.peak_busy
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&disks);
+    }
+    if let Some(tcp) = get_tcp_rates(report) {
+        println!(
+            "{:20} in {}/s, out {}/s",
+            "TCP Segments ".bold().yellow(),
+            format_number(tcp.in_segs).trim_end(),
+            format_number(tcp.out_segs).trim_end()
+        );
+        let ratio = match (tcp.retrans_ratio, tcp.peak_ratio) {
+            (Some(ratio), Some((time, peak))) if peak > 0.0 => format!(
+                ", {:.2}% of segments sent (peak {:.2}% at {:.1}s)",
+                ratio,
+                peak,
+                time.as_secs_f64()
+            ),
+            (Some(ratio), _) => format!(", {:.2}% of segments sent", ratio),
+            _ => String::new(),
+        };
+        println!(
+            "{:20} {}/s{}",
+            "TCP Retransmits ".bold().yellow(),
+            format_number(tcp.retrans_segs).trim_end(),
+            ratio
+        );
+        println!(
+            "{:20} fast retransmits {}/s, lost retransmits {}/s, timeouts {}/s",
+            "TCP Loss Recovery ".bold().yellow(),
+            format_number(tcp.fast_retrans).trim_end(),
+            format_number(tcp.lost_retransmit).trim_end(),
+            format_number(tcp.timeouts).trim_end()
+        );
+        println!(
+            "{:20} {} segments in error, {} resets sent, {} listen drops",
+            "TCP Errors ".bold().yellow(),
+            tcp.in_errs,
+            tcp.out_rsts,
+            tcp.listen_drops
+        );
+    }
+    let (softnet, pressure) = get_softnet_stats(report);
+    if !softnet.is_empty() {
+        println!("{}", "Per CPU softnet".bold().yellow());
+        let softnet = softnet
+            .iter()
+            .map(|stats| {
+                (
+                    stats.cpu.as_str().bold().yellow().to_string(),
+                    format!(
+                        "{}pkt/s, {} squeezes, {} drops, softirq {:.2}%",
+                        format_number(stats.processed_rate),
+                        stats.time_squeeze,
+                        stats.dropped,
+                        stats.softirq
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&softnet);
+    }
+    if !pressure.is_empty() {
+        println!(
+            "{}",
+            "Intervals NAPI ran out of budget or dropped"
+                .bold()
+                .yellow()
+        );
+        let pressure = pressure
+            .iter()
+            .map(|pressure| {
+                (
+                    format!("{:.1}s CPU {}", pressure.time.as_secs_f64(), pressure.cpu)
+                        .bold()
+                        .yellow()
+                        .to_string(),
+                    format!(
+                        "{} squeezes, {} drops, softirq {}",
+                        pressure.time_squeeze,
+                        pressure.dropped,
+                        pressure
+                            .softirq
+                            .map_or("-".to_string(), |softirq| format!("{:.2}%", softirq))
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&pressure);
+    }
+    let softirqs = get_softirq_rates(report);
+    if !softirqs.is_empty() {
+        println!("{}", "Per CPU softirqs".bold().yellow());
+        let softirqs = softirqs
+            .iter()
+            .map(|softirqs| {
+                let rates: Vec<_> = SOFTIRQ_TYPES
+                    .iter()
+                    .zip(softirqs.rates)
+                    .map(|(kind, rate)| format!("{} {}/s", kind, format_number(rate).trim_end()))
+                    .collect();
+                (
+                    softirqs.cpu.as_str().bold().yellow().to_string(),
+                    format!(
+                        "{}{}",
+                        rates.join(", "),
+                        softirqs
+                            .net_rx_share
+                            .map_or(String::new(), |share| format!(", {:.1}% of NET_RX", share))
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&softirqs);
+    }
+    let cpuidle = get_cpuidle_stats(report);
+    if !cpuidle.is_empty() {
+        println!("{}", "Per CPU idle states".bold().yellow());
+        let cpuidle = cpuidle
+            .iter()
+            .map(|cpu| {
+                let states: Vec<_> = cpu
+                    .states
+                    .iter()
+                    .map(|(name, residency)| format!("{} {:.1}%", name, residency))
+                    .collect();
+                let total: f64 = cpu.states.iter().map(|(_, residency)| residency).sum();
+                (
+                    cpu.cpu.as_str().bold().yellow().to_string(),
+                    format!(
+                        "{}, {:.1}% in all states{}",
+                        states.join(", "),
+                        total,
+                        cpu.proc_idle.map_or(String::new(), |idle| format!(
+                            ", /proc/stat idle {:.1}%",
+                            idle
+                        ))
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&cpuidle);
+    }
+    let sockets = get_socket_stats(report);
+    if !sockets.is_empty() {
+        println!("{}", "Per connection TCP".bold().yellow());
+    }
+    for socket in sockets.iter() {
+        let range = |range: Option<MinAvgMax>, precision: usize| {
+            range.map_or("-".to_string(), |range| {
+                format!(
+                    "{:.*}/{:.*}/{:.*}",
+                    precision, range.min, precision, range.avg, precision, range.max
+                )
+            })
+        };
+        println!(
+            "{} {:.1}s-{:.1}s",
+            socket.connection.as_str().bold().yellow(),
+            socket.first.as_secs_f64(),
+            (socket.last + report.interval).as_secs_f64()
+        );
+        println!(
+            "  rtt min/avg/max {} ms, cwnd {} segments, {} retransmits, delivery {}b/s",
+            range(socket.rtt, 2),
+            range(socket.cwnd, 0),
+            socket.retrans,
+            socket
+                .delivery_rate
+                .map_or("-".to_string(), |rate| format_number(rate)
+                    .trim_end()
+                    .to_string())
+        );
+    }
+    let net_types = get_net_types(report);
+    if !net_types.is_empty() {
+        let total: usize = net_types.iter().map(|(_, count)| count).sum();
+        let net_types: Vec<_> = net_types
+            .iter()
+            .map(|(net_type, count)| {
+                format!("{} {:.1}%", net_type, *count as f64 / total as f64 * 100.0)
+            })
+            .collect();
+        println!(
+            "{:20} {}",
+            "Network Types ".bold().yellow(),
+            net_types.join(", ")
+        );
+        let signal = get_signal_averages(report);
+        let cell = format_cell_signal(&signal);
+        if !cell.is_empty() {
+            println!("{:20} {}", "Cell Signal ".bold().yellow(), cell);
+        }
+        if let Some(rssi) = signal.wifi_rssi {
+            println!(
+                "{:20} RSSI {:.1} dBm",
+                "Wi-Fi Signal ".bold().yellow(),
+                rssi
+            );
+        }
+    }
+    let irqs = get_irq_stats(report);
+    if irqs.iter().any(|irq| irq.rate > 0.0) {
+        println!("{}", "Top interrupt sources".bold().yellow());
+        let irqs = irqs
+            .iter()
+            .filter(|irq| irq.rate > 0.0)
+            .take(TOP_IRQS)
+            .map(|irq| {
+                let cpus: Vec<_> = irq
+                    .cpus
+                    .iter()
+                    .map(|(cpu, share)| format!("CPU {} {:.0}%", cpu, share))
+                    .collect();
+                (
+                    format!("IRQ {}", irq.irq).bold().yellow().to_string(),
+                    format!(
+                        "{} {}/s, {}",
+                        irq.name,
+                        format_number(irq.rate).trim_end(),
+                        cpus.join(", ")
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&irqs);
+    }
+    if !report.pids.is_empty() {
+        println!("{}", "Per process share of total CPU".bold().yellow());
+    }
+    for pid in report.pids.iter() {
+        let shares: Vec<_> = report
+            .entries
+            .iter()
+            .map(|entry| {
+                let process = entry
+                    .processes
+                    .get(pid)
+                    .and_then(|process| process.as_ref());
+                (entry.time, process.map(|process| process.share))
+            })
+            .collect();
+        let running: Vec<_> = shares.iter().filter_map(|(_, share)| *share).collect();
+        let summary = if running.is_empty() {
+            "never seen running".to_string()
+        } else {
+            format!(
+                "average {:.2}%, min {:.2}%, max {:.2}%, running in {}/{} intervals",
+                running.iter().sum::<f64>() / running.len() as f64,
+                running.iter().copied().fold(f64::INFINITY, f64::min),
+                running.iter().copied().fold(0.0, f64::max),
+                running.len(),
+                shares.len()
+            )
+        };
+        println!("{:20} {}", format!("pid {} ", pid).bold().yellow(), summary);
+        let per_interval = shares
+            .iter()
+            .map(|(time, share)| {
+                let share = match share {
+                    Some(share) => format!("{:>6.2}%", share),
+                    None => format!("{:>7}", "-"),
+                };
+                (format!("{:>7.1}s", time.as_secs_f64()), share)
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&per_interval);
+    }
+    if !report.cgroups.is_empty() {
+        println!("{}", "Per cgroup CPU".bold().yellow());
+    }
+    for path in report.cgroups.iter() {
+        let usage: Vec<_> = report
+            .entries
+            .iter()
+            .map(|entry| {
+                let cgroup = entry.cgroups.get(path).and_then(|cgroup| cgroup.as_ref());
+                (entry.time, cgroup)
+            })
+            .collect();
+        let seen: Vec<_> = usage.iter().filter_map(|(_, cgroup)| *cgroup).collect();
+        let summary = if seen.is_empty() {
+            "gone for the whole run".to_string()
+        } else {
+            let seconds = |cgroup: &&CgroupReport| cgroup.usage_us as f64 / 1e6;
+            format!(
+                "{:.3}s CPU per interval, average {:.2}% of total CPU (user {:.3}s, system {:.3}s in all), throttled {} times for {:.3}s",
+                seen.iter().map(seconds).sum::<f64>() / seen.len() as f64,
+                seen.iter().map(|cgroup| cgroup.share).sum::<f64>() / seen.len() as f64,
+                seen.iter().map(|cgroup| cgroup.user_us).sum::<u64>() as f64 / 1e6,
+                seen.iter().map(|cgroup| cgroup.system_us).sum::<u64>() as f64 / 1e6,
+                seen.iter().map(|cgroup| cgroup.nr_throttled).sum::<u64>(),
+                seen.iter().map(|cgroup| cgroup.throttled_us).sum::<u64>() as f64 / 1e6
+            )
+        };
+        println!("{} {}", path.as_str().bold().yellow(), summary);
+        let per_interval = usage
+            .iter()
+            .map(|(time, cgroup)| {
+                let usage = match cgroup {
+                    Some(cgroup) => format!(
+                        "{:>6.3}s {:>6.2}%",
+                        cgroup.usage_us as f64 / 1e6,
+                        cgroup.share
+                    ),
+                    None => format!("{:>15}", "-"),
+                };
+                (format!("{:>7.1}s", time.as_secs_f64()), usage)
+            })
+            .collect::<Vec<_>>();
+        print_string_collection(&per_interval);
+    }
+    if let Some((average, min)) = get_available_memory(report) {
+        let total = report
+            .entries
+            .iter()
+            .filter_map(|entry| entry.mem.as_ref())
+            .map(|mem| mem.total)
+            .max()
+            .unwrap_or(0);
+        println!(
+            "{:20} average {:.0} MB, minimum {} MB of {} MB",
+            "Available Memory ".bold().yellow(),
+            average / 1024.0,
+            min / 1024,
+            total / 1024
+        );
+    }
+    if let Some(battery) = get_battery_stats(report) {
+        println!(
+            "{:20} average {:.0} mW, peak {:.0} mW, {:.2} mWh in all{}",
+            "Battery Power ".bold().yellow(),
+            battery.power,
+            battery.peak_power,
+            battery.energy_mwh,
+            battery
+                .energy_per_gigacycle
+                .map_or(String::new(), |energy| format!(
+                    ", {:.1} mJ per billion cycles",
+                    energy
+                ))
+        );
+        if let Some((first, last)) = battery.capacity {
+            println!(
+                "{:20} {}% to {}%{}",
+                "Battery Charge ".bold().yellow(),
+                first,
+                last,
+                if battery.charging > 0 {
+                    format!(
+                        ", charging in {} intervals left out of the power",
+                        battery.charging
+                    )
+                } else {
+                    String::new()
+                }
+            );
+        }
+    }
+    let run = report
+        .duration_actual
+        .unwrap_or(report.duration)
+        .as_secs_f64();
+    for resource in PRESSURE_RESOURCES {
+        let lines: Vec<_> = ["some", "full"]
+            .iter()
+            .filter_map(|kind| {
+                let (avg10, stall) = get_pressure_stats(report, &format!("{} {}", resource, kind))?;
+                Some(format!(
+                    "{} avg10 {:.2}%, stalled {:.3}s ({:.1}% of run)",
+                    kind,
+                    avg10,
+                    stall.as_secs_f64(),
+                    stall.as_secs_f64() / run.max(f64::EPSILON) * 100.0
+                ))
+            })
+            .collect();
+        if !lines.is_empty() {
+            println!(
+                "{:20} {}",
+                format!(
+                    "{} Pressure ",
+                    match resource {
+                        "memory" => "Memory".to_string(),
+                        _ => resource.to_uppercase(),
+                    }
+                )
+                .bold()
+                .yellow(),
+                lines.join("; ")
+            );
+        }
+    }
+    if let Some(phases) = phases {
+        print_phases(phases, args.min_phase_entries);
+    }
+    if let Some(groups) = groups {
+        print_net_type_groups(groups);
+    }
+    if let Some(count) = args.top {
+        for metric in args.top_by.iter() {
+            print_top(report, count, *metric);
+        }
+    }
+    if let Some(rows) = args.sparkline {
+        print_sparklines(report, rows);
+    }
+    if args.heatmap {
+        print_heatmap(report);
+    }
+    if args.dump {
+        print_dump(report);
+    }
+    println!();
+}
+
+/// Which CPUs `--sparkline` draws.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SparklineRows {
+    Cpus,
+    All,
+}
+
+/// The CPUs a load chart has a row for, how wide its labels are and how many
+/// buckets fit in the rest of the line.
+fn chart_rows(report: &Report, all_only: bool) -> (Vec<&String>, usize, usize) {
+    let cpus: Vec<_> = report
+        .proc_cpus
+        .iter()
+        .filter(|cpu| !all_only || *cpu == "all")
+        .collect();
+    let label_len = cpus.iter().map(|cpu| cpu.len()).max().unwrap_or(0);
+    let width = LAYOUT.get().map_or(80, |layout| layout.width);
+    (cpus, label_len, width.saturating_sub(label_len + 1).max(1))
+}
+
+/// How many seconds of the run each bucket of a chart averages.
+fn print_chart_scale(report: &Report, buckets: usize) {
+    let run = report.entries.len() as f64 * report.interval.as_secs_f64();
+    println!("each column averages {:.1}s", run / buckets as f64);
+}
+
+fn print_sparklines(report: &Report, rows: SparklineRows) {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    println!("{}", "Per CPU load over time".bold().yellow());
+    let (cpus, label_len, buckets) = chart_rows(report, rows == SparklineRows::All);
+    let mut columns = 0;
+    for cpu in cpus {
+        let loads = get_load_buckets(report, cpu, buckets);
+        columns = loads.len();
+        let line: String = loads
+            .iter()
+            .map(|load| match load {
+                Some(load) => BLOCKS[((load / 100.0 * 8.0) as usize).min(7)],
+                None => ' ',
+            })
+            .collect();
+        println!("{:label_len$} {}", cpu.as_str().bold().yellow(), line);
+    }
+    print_chart_scale(report, columns);
+}
+
+/// A time by CPU grid, shaded and, with colors enabled, colored from blue at
+/// idle to red at full load.
+fn print_heatmap(report: &Report) {
+    println!("{}", "Per CPU load heatmap".bold().yellow());
+    let (cpus, label_len, buckets) = chart_rows(report, false);
+    let mut columns = 0;
+    for cpu in cpus {
+        let loads = get_load_buckets(report, cpu, buckets);
+        columns = loads.len();
+        let line: String = loads
+            .iter()
+            .map(|load| match load {
+                Some(load) if *load < 20.0 => "░".blue().to_string(),
+                Some(load) if *load < 40.0 => "░".cyan().to_string(),
+                Some(load) if *load < 60.0 => "▒".green().to_string(),
+                Some(load) if *load < 80.0 => "▓".yellow().to_string(),
+                Some(_) => "█".red().to_string(),
+                None => " ".to_string(),
+            })
+            .collect();
+        println!("{:label_len$} {}", cpu.as_str().bold().yellow(), line);
+    }
+    println!("░ <40% ▒ <60% ▓ <80% █ above");
+    print_chart_scale(report, columns);
+}
+
+/// What `--top` ranks intervals by.
+#[derive(Clone, Copy, ValueEnum)]
+enum TopMetric {
+    Load,
+    Cycles,
+    ContextSwitches,
+}
+
+impl TopMetric {
+    fn value(self, entry: &ReportEntry, cpu: &str) -> Option<f64> {
+        match self {
+            TopMetric::Load => entry.proc.get(cpu).map(|proc| proc.load),
+            TopMetric::Cycles => Some(entry.perf.get(cpu)?.get(CYCLES)? as f64),
+            TopMetric::ContextSwitches => Some(entry.perf.get(cpu)?.get(CONTEXT_SWITCHES)? as f64),
+        }
+    }
+
+    fn format(self, value: f64) -> String {
+        match self {
+            TopMetric::Load => format!("{}%", format_number(value)),
+            _ => format_number(value),
+        }
+    }
+}
+
+/// The `count` intervals with the highest "all" value of `metric`, when they
+/// started and how their CPUs contributed.
+fn print_top(report: &Report, count: usize, metric: TopMetric) {
+    let (title, cpus) = match metric {
+        TopMetric::Load => ("load", &report.proc_cpus),
+        TopMetric::Cycles => ("CPU cycles", &report.perf_cpus),
+        TopMetric::ContextSwitches => ("context switches", &report.perf_cpus),
+    };
+    let busiest = get_busiest_entries(report, count, |entry| metric.value(entry, "all"));
+    if busiest.is_empty() {
+        return;
+    }
+    println!(
+        "{}",
+        format!("Top {} intervals by {}", busiest.len(), title)
+            .bold()
+            .yellow()
+    );
+    for (entry, value) in busiest {
+        let wall_time = get_wall_time(report, entry)
+            .map(|time| format!(" {}", timestamp::format(time)))
+            .unwrap_or_default();
+        println!(
+            "{:>8.1}s{} {} {}",
+            entry.time.as_secs_f64(),
+            wall_time,
+            "all".bold().yellow(),
+            metric.format(value)
+        );
+        let cells: Vec<_> = cpus
+            .iter()
+            .filter(|cpu| !report.is_aggregate(cpu))
+            .filter_map(|cpu| {
+                let value = metric.value(entry, cpu)?;
+                Some((
+                    cpu.as_str().bold().yellow().to_string(),
+                    metric.format(value),
+                ))
+            })
+            .collect();
+        print_string_collection(&cells);
+    }
+}
+
+/// One row per entry of the load, cycles and context switches of each CPU,
+/// under a single header; `--cpus` keeps it narrow on many-core logs.
+type AverageSelector = fn(&CpuAverages) -> Option<f64>;
+
+/// `--split-by-markers` and `--split-at`: every phase's per CPU averages side
+/// by side, each followed by its change from the phase before, flagging the
+/// phases too short to go by.
+fn print_phases(phases: &[Phase], min_entries: usize) {
+    if phases.len() < 2 {
+        println!(
+            "{:20} a single one, as no split falls among the entries",
+            "Phases ".bold().yellow()
+        );
+        return;
+    }
+    let weak = |phase: &Phase| phase.entries < min_entries;
+    println!("{}", "Phases".bold().yellow());
+    for (index, phase) in phases.iter().enumerate() {
+        println!(
+            "{:>3} {} ({:.1}s to {:.1}s, {} entries){}",
+            index + 1,
+            phase.label,
+            phase.from.as_secs_f64(),
+            phase.to.as_secs_f64(),
+            phase.entries,
+            if weak(phase) {
+                format!(", too few to go by (under {})", min_entries)
+                    .red()
+                    .to_string()
+            } else {
+                String::new()
+            }
+        );
+    }
+
+    let mut cpus: Vec<&str> = Vec::new();
+    for averages in phases.iter().flat_map(|phase| phase.averages.iter()) {
+        if !cpus.contains(&averages.cpu.as_str()) {
+            cpus.push(&averages.cpu);
+        }
+    }
+    let cpu_width = cpus.iter().map(|cpu| cpu.len()).max().unwrap_or(0);
+    let metrics: [(&str, AverageSelector); 3] = [
+        ("Per phase average load", |averages| averages.load),
+        ("Per phase average CPU cycles", |averages| averages.cycles),
+        ("Per phase average context switches", |averages| {
+            averages.context_switches
+        }),
+    ];
+    for (title, metric) in metrics {
+        let value = |phase: &Phase, cpu: &str| {
+            phase
+                .averages
+                .iter()
+                .find(|averages| averages.cpu == cpu)
+                .and_then(metric)
+                .filter(|value| !value.is_nan())
+        };
+        if !cpus
+            .iter()
+            .any(|cpu| phases.iter().any(|phase| value(phase, cpu).is_some()))
+        {
+            continue;
+        }
+        println!("{}", title.bold().yellow());
+        // Weak phases are starred, and `2-1` is the change from 1 to 2.
+        let mut header = " ".repeat(cpu_width);
+        for (index, phase) in phases.iter().enumerate() {
+            let star = if weak(phase) { "*" } else { "" };
+            header.push_str(&format!(" {:>10}", format!("{}{}", index + 1, star)));
+            if index > 0 {
+                header.push_str(&format!(" {:>18}", format!("{}-{}", index + 1, index)));
+            }
+        }
+        println!("{}", header);
+        for cpu in cpus.iter() {
+            let mut row = format!("{:cpu_width$}", cpu.bold().yellow());
+            for (index, phase) in phases.iter().enumerate() {
+                let new = value(phase, cpu);
+                row.push_str(&format!(
+                    " {:>10}",
+                    new.map_or("-".to_string(), |new| format_number(new)
+                        .trim_end()
+                        .to_string())
+                ));
+                if index == 0 {
+                    continue;
+                }
+                let Some((base, new)) = value(&phases[index - 1], cpu).zip(new) else {
+                    row.push_str(&format!(" {:>18}", ""));
+                    continue;
+                };
+                let delta = new - base;
+                let change = format!(
+                    " {:>10} {:>7}",
+                    format_signed_number(delta).trim_end(),
+                    if base == 0.0 {
+                        String::new()
+                    } else {
+                        format!("{:+.1}%", delta / base * 100.0)
+                    }
+                );
+                // As in `--compare`, everything measured is a cost.
+                row.push_str(&if delta > 0.0 {
+                    change.red().to_string()
+                } else if delta < 0.0 {
+                    change.green().to_string()
+                } else {
+                    change
+                });
+            }
+            println!("{}", row);
+        }
+    }
+}
+
+/// The averaged cell signal, e.g. `RSRP -97.0 dBm, RSRQ -11.0 dB`, leaving
+/// out what the modem didn't report.
+fn format_cell_signal(signal: &SignalAverages) -> String {
+    [
+        ("RSRP", signal.rsrp, "dBm"),
+        ("RSRQ", signal.rsrq, "dB"),
+        ("RSSI", signal.rssi, "dBm"),
+    ]
+    .iter()
+    .filter_map(|(name, value, unit)| Some(format!("{} {:.1} {}", name, (*value)?, unit)))
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+/// What `--group-by` averages the intervals by.
+#[derive(Clone, Copy, ValueEnum)]
+enum GroupBy {
+    NetType,
+}
+
+/// `--group-by net-type`: the per CPU averages of each network type side by
+/// side, with the signal on it.
+fn print_net_type_groups(groups: &[NetTypeGroup]) {
+    if groups.is_empty() {
+        println!(
+            "{:20} none, as the log was captured without --radio",
+            "Network Types ".bold().yellow()
+        );
+        return;
+    }
+    let total: usize = groups.iter().map(|group| group.entries).sum();
+    println!("{}", "Per network type".bold().yellow());
+    for group in groups.iter() {
+        let cell = format_cell_signal(&group.signal);
+        println!(
+            "{} {} entries ({:.1}%){}{}",
+            group.net_type.as_str().bold().yellow(),
+            group.entries,
+            group.entries as f64 / total as f64 * 100.0,
+            if cell.is_empty() {
+                String::new()
+            } else {
+                format!(", {}", cell)
+            },
+            group.signal.wifi_rssi.map_or(String::new(), |rssi| format!(
+                ", Wi-Fi RSSI {:.1} dBm",
+                rssi
+            ))
+        );
+    }
+
+    let mut cpus: Vec<&str> = Vec::new();
+    for averages in groups.iter().flat_map(|group| group.averages.iter()) {
+        if !cpus.contains(&averages.cpu.as_str()) {
+            cpus.push(&averages.cpu);
+        }
+    }
+    let cpu_width = cpus.iter().map(|cpu| cpu.len()).max().unwrap_or(0);
+    let metrics: [(&str, AverageSelector); 3] = [
+        ("Per network type average load", |averages| averages.load),
+        ("Per network type average CPU cycles", |averages| {
+            averages.cycles
+        }),
+        ("Per network type average context switches", |averages| {
+            averages.context_switches
+        }),
+    ];
+    for (title, metric) in metrics {
+        let value = |group: &NetTypeGroup, cpu: &str| {
+            group
+                .averages
+                .iter()
+                .find(|averages| averages.cpu == cpu)
+                .and_then(metric)
+                .filter(|value| !value.is_nan())
+        };
+        if !cpus
+            .iter()
+            .any(|cpu| groups.iter().any(|group| value(group, cpu).is_some()))
+        {
+            continue;
+        }
+        println!("{}", title.bold().yellow());
+        let mut header = " ".repeat(cpu_width);
+        for group in groups.iter() {
+            header.push_str(&format!(" {:>10}", group.net_type));
+        }
+        println!("{}", header);
+        for cpu in cpus.iter() {
+            let mut row = format!("{:cpu_width$}", cpu.bold().yellow());
+            for group in groups.iter() {
+                row.push_str(&format!(
+                    " {:>10}",
+                    value(group, cpu).map_or("-".to_string(), |value| format_number(value)
+                        .trim_end()
+                        .to_string())
+                ));
+            }
+            println!("{}", row);
+        }
+    }
+}
+
+fn print_dump(report: &Report) {
+    let perf_only = report
+        .perf_cpus
+        .iter()
+        .filter(|cpu| !report.proc_cpus.contains(cpu));
+    let mut cpus: Vec<_> = report.proc_cpus.iter().chain(perf_only).collect();
+    cpus.sort_by(|a, b| compare_cpus(a, b));
+
+    println!("{}", "Per interval values".bold().yellow());
+    let mut labels = format!("{:9}", "");
+    let mut columns = format!("{:>9}", "time");
+    for cpu in cpus.iter() {
+        labels.push_str(&format!(" | {:^29}", cpu.as_str().bold().yellow()));
+        columns.push_str(&format!(" | {:>9} {:>9} {:>9}", "load", "cycles", "ctx sw"));
+    }
+    println!("{}", labels);
+    println!("{}", columns);
+    let precision = NUMBER_FORMAT.get().copied().unwrap_or_default().precision;
+    let missing = || "-".to_string();
+    // Each between the entries it fell between, leaving out those outside
+    // the `--from`/`--to` window.
+    let window =
+        report.entries.first().unwrap().time..report.entries.last().unwrap().time + report.interval;
+    let mut markers: Vec<_> = report
+        .markers
+        .iter()
+        .filter(|marker| window.contains(&marker.time))
+        .collect();
+    markers.sort_by_key(|marker| marker.time);
+    let mut markers = markers.into_iter().peekable();
+    let print_marker = |marker: &Marker| {
+        println!(
+            "{:>8.1}s {} {}",
+            marker.time.as_secs_f64(),
+            ">".bold().yellow(),
+            marker.label.as_str().bold().yellow()
+        )
+    };
+    for entry in report.entries.iter() {
+        while let Some(marker) = markers.next_if(|marker| marker.time <= entry.time) {
+            print_marker(marker);
+        }
+        let mut row = format!("{:>8.1}s", entry.time.as_secs_f64());
+        for cpu in cpus.iter() {
+            let load = entry
+                .proc
+                .get(*cpu)
+                .map(|proc| format!("{:.precision$}%", proc.load));
+            let perf = |event| {
+                let value = entry.perf.get(*cpu)?.get(event)?;
+                Some(format_number(value as f64))
+            };
+            row.push_str(&format!(
+                " | {:>9} {:>9} {:>9}",
+                load.unwrap_or_else(missing),
+                perf(CYCLES).unwrap_or_else(missing),
+                perf(CONTEXT_SWITCHES).unwrap_or_else(missing)
+            ));
+        }
+        println!("{}", row);
+    }
+    markers.for_each(print_marker);
+
+    if report.entries.iter().any(|entry| entry.loadavg.is_some()) {
+        println!("{}", "Per interval loadavg".bold().yellow());
+        println!(
+            "{:>9} | {:>8} {:>8} {:>8} | {:>9}",
+            "time", "1 min", "5 min", "15 min", "runnable"
+        );
+        for entry in report.entries.iter() {
+            let Some(loadavg) = entry.loadavg.as_ref() else {
+                continue;
+            };
+            println!(
+                "{:>8.1}s | {:>8.2} {:>8.2} {:>8.2} | {:>9}",
+                entry.time.as_secs_f64(),
+                loadavg.one,
+                loadavg.five,
+                loadavg.fifteen,
+                format!("{}/{}", loadavg.runnable, loadavg.tasks)
+            );
+        }
+    }
+
+    let delay_cpus: Vec<_> = cpus
+        .iter()
+        .filter(|cpu| {
+            report
+                .entries
+                .iter()
+                .any(|entry| entry.schedstat.contains_key(**cpu))
+        })
+        .collect();
+    if !delay_cpus.is_empty() {
+        println!("{}", "Per interval run delay".bold().yellow());
+        let mut header = format!("{:>9}", "time");
+        for cpu in delay_cpus.iter() {
+            header.push_str(&format!(" | {:>9}", cpu.as_str().bold().yellow()));
+        }
+        println!("{}", header);
+        for entry in report.entries.iter() {
+            let mut row = format!("{:>8.1}s", entry.time.as_secs_f64());
+            for cpu in delay_cpus.iter() {
+                row.push_str(&format!(
+                    " | {:>9}",
+                    entry
+                        .schedstat
+                        .get(**cpu)
+                        .map_or_else(missing, |schedstat| {
+                            format!("{:.precision$} ms", schedstat.run_delay_ns as f64 / 1e6)
+                        })
+                ));
+            }
+            println!("{}", row);
+        }
+    }
+
+    let residency_cpus: Vec<_> = cpus
+        .iter()
+        .filter(|cpu| {
+            report
+                .entries
+                .iter()
+                .any(|entry| entry.time_in_state.contains_key(**cpu))
+        })
+        .collect();
+    if !residency_cpus.is_empty() {
+        println!("{}", "Per interval effective frequency".bold().yellow());
+        let mut header = format!("{:>9}", "time");
+        for cpu in residency_cpus.iter() {
+            header.push_str(&format!(" | {:>9}", cpu.as_str().bold().yellow()));
+        }
+        println!("{}", header);
+        for entry in report.entries.iter() {
+            let mut row = format!("{:>8.1}s", entry.time.as_secs_f64());
+            for cpu in residency_cpus.iter() {
+                let khz = entry
+                    .time_in_state
+                    .get(**cpu)
+                    .and_then(|states| effective_khz(states));
+                row.push_str(&format!(
+                    " | {:>9}",
+                    khz.map_or_else(missing, |khz| format!("{:.0} MHz", khz / 1000.0))
+                ));
+            }
+            println!("{}", row);
+        }
+    }
+
+    if report.entries.iter().any(|entry| entry.tcp.is_some()) {
+        println!("{}", "Per interval TCP".bold().yellow());
+        println!(
+            "{:>9} | {:>10} {:>10} {:>10} {:>10}",
+            "time", "in seg/s", "out seg/s", "retrans/s", "retrans %"
+        );
+        let seconds = report.interval.as_secs_f64();
+        for entry in report.entries.iter() {
+            let Some(tcp) = entry.tcp.as_ref() else {
+                continue;
+            };
+            let rate = |count: u64| format_number(count as f64 / seconds);
+            println!(
+                "{:>8.1}s | {:>10} {:>10} {:>10} {:>10}",
+                entry.time.as_secs_f64(),
+                rate(tcp.in_segs),
+                rate(tcp.out_segs),
+                rate(tcp.retrans_segs),
+                retrans_ratio(tcp).map_or_else(missing, |ratio| format!("{:.precision$}%", ratio))
+            );
+        }
+    }
+
+    if report.entries.iter().any(|entry| !entry.sockets.is_empty()) {
+        println!("{}", "Per interval sockets".bold().yellow());
+        println!(
+            "{:>9} | {:>10} {:>8} {:>8} {:>12} | connection",
+            "time", "rtt ms", "cwnd", "retrans", "delivery b/s"
+        );
+        let mut previous: HashMap<&String, u64> = HashMap::new();
+        for entry in report.entries.iter() {
+            let mut connections: Vec<_> = entry.sockets.iter().collect();
+            connections.sort_by_key(|(connection, _)| *connection);
+            for (connection, socket) in connections {
+                // Retransmits since the connection's previous entry.
+                let retrans = previous
+                    .insert(connection, socket.retrans)
+                    .map_or_else(missing, |retrans| {
+                        socket.retrans.saturating_sub(retrans).to_string()
+                    });
+                println!(
+                    "{:>8.1}s | {:>10} {:>8} {:>8} {:>12} | {}",
+                    entry.time.as_secs_f64(),
+                    socket
+                        .rtt
+                        .map_or_else(missing, |rtt| format!("{:.precision$}", rtt)),
+                    socket.cwnd.map_or_else(missing, |cwnd| cwnd.to_string()),
+                    retrans,
+                    socket.delivery_rate.map_or_else(missing, format_number),
+                    connection
+                );
+            }
+        }
+    }
+}
+
+/// A metric averaged over the base run and the new run.
+#[derive(Serialize)]
+struct MetricDelta {
+    base: f64,
+    new: f64,
+    delta: f64,
+    /// Null when the base average is zero.
+    percent: Option<f64>,
+}
+
+impl MetricDelta {
+    fn new(base: Option<f64>, new: Option<f64>) -> Option<Self> {
+        let (base, new) = (base?, new?);
+        Some(MetricDelta {
+            base,
+            new,
+            delta: new - base,
+            percent: (base != 0.0).then(|| 100.0 * (new - base) / base),
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct CpuComparison {
+    cpu: String,
+    load: Option<MetricDelta>,
+    cycles: Option<MetricDelta>,
+    context_switches: Option<MetricDelta>,
+}
+
+/// What comparisons need to know about a run; the `--save-baseline` file.
+#[derive(Serialize, Deserialize)]
+struct Baseline {
+    file: String,
+    id: Uuid,
+    platform: Platform,
+    interval_ms: u64,
+    /// Measured duration, or the requested one for logs that don't record it.
+    duration_ms: u64,
+    cpus: Vec<String>,
+    averages: Vec<CpuAverages>,
+}
+
+impl Baseline {
+    fn from_report(file: &str, report: &Report) -> Self {
+        let averages = get_averages(report);
+        Baseline {
+            file: file.to_string(),
+            id: report.id,
+            platform: report.platform,
+            interval_ms: report.interval.as_millis() as u64,
+            duration_ms: report
+                .duration_actual
+                .unwrap_or(report.duration)
+                .as_millis() as u64,
+            cpus: averages.iter().map(|average| average.cpu.clone()).collect(),
+            averages,
+        }
+    }
+}
+
+/// The `--compare` and `--baseline` result, also their `--json` document.
+#[derive(Serialize)]
+struct Comparison {
+    base: String,
+    new: String,
+    warnings: Vec<String>,
+    /// The wall-clock window both runs were cut down to with `--align`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aligned: Option<String>,
+    /// CPUs present in both runs.
+    cpus: Vec<CpuComparison>,
+    /// Metrics that grew by more than `--tolerance`.
+    regressions: Vec<String>,
+}
+
+fn compare_reports(base: &Baseline, new: &Baseline, tolerance: Option<f64>) -> Comparison {
+    let (base_file, new_file) = (base.file.as_str(), new.file.as_str());
+    let mut warnings = Vec::new();
+    if base.interval_ms != new.interval_ms {
+        warnings.push(format!(
+            "intervals differ: {:?} vs {:?}",
+            Duration::from_millis(base.interval_ms),
+            Duration::from_millis(new.interval_ms)
+        ));
+    }
+    if base.duration_ms != new.duration_ms {
+        warnings.push(format!(
+            "durations differ: {:?} vs {:?}",
+            Duration::from_millis(base.duration_ms),
+            Duration::from_millis(new.duration_ms)
+        ));
+    }
+    if base.platform != new.platform {
+        warnings.push(format!(
+            "platforms differ: {} vs {}",
+            base.platform.name(),
+            new.platform.name()
+        ));
+    }
+    if base.cpus != new.cpus {
+        warnings.push("CPU lists differ, the runs may come from different devices".to_string());
+    }
+
+    let base_averages = &base.averages;
+    let new_averages = &new.averages;
+    for (averages, file, other) in [
+        (base_averages, base_file, new_averages),
+        (new_averages, new_file, base_averages),
+    ] {
+        let missing: Vec<_> = averages
+            .iter()
+            .filter(|a| !other.iter().any(|b| b.cpu == a.cpu))
+            .map(|a| a.cpu.as_str())
+            .collect();
+        if !missing.is_empty() {
+            warnings.push(format!("CPUs only in {}: {}", file, missing.join(", ")));
+        }
+    }
+
+    let cpus: Vec<_> = base_averages
+        .iter()
+        .filter_map(|base| {
+            let new = new_averages.iter().find(|new| new.cpu == base.cpu)?;
+            Some(CpuComparison {
+                cpu: base.cpu.clone(),
+                load: MetricDelta::new(base.load, new.load),
+                cycles: MetricDelta::new(base.cycles, new.cycles),
+                context_switches: MetricDelta::new(base.context_switches, new.context_switches),
+            })
+        })
+        .collect();
+
+    let mut regressions = Vec::new();
+    if let Some(tolerance) = tolerance {
+        for cpu in cpus.iter() {
+            for (metric, delta) in [
+                ("load", &cpu.load),
+                ("CPU cycles", &cpu.cycles),
+                ("context switches", &cpu.context_switches),
+            ] {
+                let Some(delta) = delta else {
+                    continue;
+                };
+                let regressed = match delta.percent {
+                    Some(percent) => percent > tolerance,
+                    // A metric that was zero regresses by any increase.
+                    None => delta.delta > 0.0,
+                };
+                if regressed {
+                    regressions.push(format!(
+                        "CPU {} {} {} ({}), over the {}% tolerance",
+                        cpu.cpu,
+                        metric,
+                        format_signed_number(delta.delta).trim_end(),
+                        delta
+                            .percent
+                            .map(|percent| format!("{:+.1}%", percent))
+                            .unwrap_or_else(|| "from zero".to_string()),
+                        tolerance
+                    ));
+                }
+            }
+        }
+    }
+
+    Comparison {
+        base: base_file.to_string(),
+        new: new_file.to_string(),
+        warnings,
+        aligned: None,
+        cpus,
+        regressions,
+    }
+}
+
+type DeltaSelector = fn(&CpuComparison) -> &Option<MetricDelta>;
+
+fn format_signed_number(number: f64) -> String {
+    if number < 0.0 {
+        format!("-{}", format_number(-number))
+    } else {
+        format!("+{}", format_number(number))
+    }
+}
+
+fn print_comparison(comparison: &Comparison) {
+    println!("{:20} {}", "Base ".bold().yellow(), comparison.base);
+    println!("{:20} {}", "New ".bold().yellow(), comparison.new);
+    if let Some(aligned) = &comparison.aligned {
+        println!("{:20} {}", "Aligned ".bold().yellow(), aligned);
+    }
+    for warning in comparison.warnings.iter() {
+        println!("{} {}", "warning:".bold().red(), warning);
+    }
+
+    let max_cpu_len = comparison
+        .cpus
+        .iter()
+        .map(|cpu| cpu.cpu.len())
+        .max()
+        .unwrap_or(0);
+    let metrics: [(&str, DeltaSelector); 3] = [
+        ("Per CPU average load", |cpu| &cpu.load),
+        ("Per CPU average CPU cycles", |cpu| &cpu.cycles),
+        ("Per CPU average context switches", |cpu| {
+            &cpu.context_switches
+        }),
+    ];
+    for (title, metric) in metrics {
+        println!("{}", title.bold().yellow());
+        for cpu in comparison.cpus.iter() {
+            let Some(delta) = metric(cpu) else {
+                continue;
+            };
+            let change = format!(
+                "{:>10} {:>8}",
+                format_signed_number(delta.delta),
+                delta
+                    .percent
+                    .map(|percent| format!("{:+.1}%", percent))
+                    .unwrap_or_default()
+            );
+            // Everything measured here is a cost, so going up is bad.
+            let change = if delta.delta > 0.0 {
+                change.red()
+            } else if delta.delta < 0.0 {
+                change.green()
+            } else {
+                change.normal()
+            };
+            println!(
+                "{:max_cpu_len$} {:>10} -> {:>10} {}",
+                cpu.cpu.as_str().bold().yellow(),
+                format_number(delta.base),
+                format_number(delta.new),
+                change
+            );
+        }
+    }
+    for regression in comparison.regressions.iter() {
+        println!("{} {}", "regression:".bold().red(), regression);
+    }
+}
+
+/// Summarises logs written by the profiler.
+#[derive(Parser)]
+#[command(name = "report", version = super::VERSION.as_str())]
+pub struct ReportArgs {
+    /// Log files to report on, gzip compressed if they end in `.gz`, or `-`
+    /// for stdin
+    #[arg(required_unless_present = "compare")]
+    files: Vec<String>,
+
+    /// Also show how each CPU's time split between user, nice, system, idle,
+    /// iowait, irq and softirq
+    #[arg(long)]
+    breakdown: bool,
+
+    /// Also list the N intervals with the highest "all" values of each
+    /// `--top-by` metric, with their CPUs at the time
+    #[arg(long, value_name = "N")]
+    top: Option<usize>,
+
+    /// Metric `--top` ranks intervals by; repeatable
+    #[arg(long, value_enum, value_name = "METRIC", default_values = ["load", "cycles"])]
+    top_by: Vec<TopMetric>,
+
+    /// Also draw the load of every CPU, or of `all` only, over time as a
+    /// sparkline fitted to `--width`
+    #[arg(long, value_enum, value_name = "ROWS", num_args = 0..=1, default_missing_value = "cpus")]
+    sparkline: Option<SparklineRows>,
+
+    /// Also draw the load of every CPU over time as a shaded grid, fitted to
+    /// `--width`
+    #[arg(long)]
+    heatmap: bool,
+
+    /// Also chart the load and cycles of every file over time as an SVG at
+    /// PATH, overlaid in different colors, or as a PNG if PATH ends in .png
+    /// and the png feature is on
+    #[arg(long, value_name = "PATH", conflicts_with = "compare")]
+    plot: Option<String>,
+
+    /// CPU `--plot` and `--html` chart, a panel each; repeatable
+    #[arg(long, value_name = "CPU", default_value = "all")]
+    plot_cpu: Vec<String>,
+
+    /// Also write a self-contained HTML page at PATH with the metadata, per
+    /// CPU averages and charts of every file
+    #[arg(long, value_name = "PATH", conflicts_with = "compare")]
+    html: Option<String>,
+
+    /// Also print the load, cycles and context switches of every interval,
+    /// e.g. with `--from`, `--to` and `--cpus` around a spike
+    #[arg(long)]
+    dump: bool,
+
+    /// Also average every stretch between consecutive markers, as set off by
+    /// the logger's `--markers` or SIGUSR1, side by side with the change from
+    /// each to the next
+    #[arg(long)]
+    split_by_markers: bool,
+
+    /// Also split at these offsets, e.g. `60s,120s`, as `--split-by-markers`
+    /// does at the markers
+    #[arg(long, value_name = "TIMES", value_delimiter = ',', value_parser = parse_duration)]
+    split_at: Vec<Duration>,
+
+    /// Also average the intervals of each network type a `--radio` log was
+    /// on separately
+    #[arg(long, value_enum, value_name = "KEY")]
+    group_by: Option<GroupBy>,
+
+    /// Flag the phases with fewer entries than this as too short to go by
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    min_phase_entries: usize,
+
+    /// Also show the mean, min, max, standard deviation and p50/p95/p99 of
+    /// each CPU's per-interval load and perf counts
+    #[arg(long)]
+    stats: bool,
+
+    /// Exit with an error if the average load of a `--cpu` exceeds PERCENT
+    #[arg(long, value_name = "PERCENT")]
+    max_avg_load: Option<f64>,
+
+    /// Exit with an error if the average cycles per interval of a `--cpu`
+    /// exceed N
+    #[arg(long, value_name = "N")]
+    max_avg_cycles: Option<f64>,
+
+    /// Exit with an error if the average context switches per interval of a
+    /// `--cpu` exceed N
+    #[arg(long, value_name = "N")]
+    max_avg_context_switches: Option<f64>,
+
+    /// CPU the `--max-avg-*` limits apply to; repeatable
+    #[arg(long = "cpu", value_name = "CPU", default_value = "all")]
+    threshold_cpus: Vec<String>,
+
+    /// Only report these CPUs, e.g. `4,5,6,7` or `4-7`; "all" then sums only
+    /// them. perf's per-core rows on ubuntu go by core number
+    #[arg(long, value_name = "CPUS", value_parser = parse_cpu_list)]
+    cpus: Option<CpuList>,
+
+    /// Leave out the CPU the profiler was pinned to with `--pin-cpu`, so that
+    /// "all" sums only the CPUs it didn't run on
+    #[arg(long)]
+    exclude_profiler_cpu: bool,
+
+    /// Take the profiler's own CPU time out of the load of "all"
+    #[arg(long)]
+    subtract_self: bool,
+
+    /// Also report the CPUs of NAME summed into a row of their own, e.g.
+    /// `big=4-7`, in place of the clusters the log's topology suggests;
+    /// repeatable
+    #[arg(long = "group", value_name = "NAME=CPUS", value_parser = parse_cpu_group)]
+    groups: Vec<CpuGroup>,
+
+    /// Also take the logs in the subdirectories of directories given as
+    /// FILES
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// Print one summary line per file instead of the full report, e.g. for
+    /// big batches
+    #[arg(short, long, conflicts_with_all = ["compare", "aggregate", "markdown", "tui", "watch"])]
+    quiet: bool,
+
+    /// Only report this network interface; repeatable
+    #[arg(long = "iface", value_name = "IFACE")]
+    ifaces: Vec<String>,
+
+    /// Flag intervals where a core's frequency fell by more than this many
+    /// percent since the previous interval
+    #[arg(long, value_name = "PERCENT", default_value_t = 20.0)]
+    freq_drop: f64,
+
+    /// Runs of neighbouring frequencies the residency of a `--time-in-state`
+    /// log is grouped into, per CPU
+    #[arg(long, value_name = "N", default_value_t = 4)]
+    freq_buckets: usize,
+
+    /// Temperature in °C above which falling load or cycles are flagged as
+    /// possible thermal throttling
+    #[arg(long, value_name = "CELSIUS", default_value_t = 70.0)]
+    thermal_threshold: f64,
+
+    /// Follow the one log file while it is being written, refreshing its
+    /// running per-CPU averages and last interval's values, until the log is
+    /// closed or on Ctrl-C
+    #[arg(
+        long,
+        conflicts_with_all = ["compare", "baseline", "save_baseline", "aggregate", "merge"]
+    )]
+    watch: bool,
+
+    /// Browse the one log file in a full-screen viewer: step through its
+    /// intervals, toggle CPUs, switch between load, cycles and context
+    /// switches and narrow the window the statistics cover. Prints the
+    /// report as usual when stdout is not a terminal
+    #[arg(
+        long,
+        conflicts_with_all = ["compare", "baseline", "aggregate", "merge", "watch", "markdown"]
+    )]
+    tui: bool,
+
+    /// How often `--watch` refreshes
+    #[arg(long, value_name = "DURATION", default_value = "2s", value_parser = parse_duration, requires = "watch")]
+    refresh: Duration,
+
+    /// Compare the averages of two runs, e.g. before and after a change
+    #[arg(long, num_args = 2, value_names = ["BASE", "NEW"], conflicts_with_all = ["files", "csv"])]
+    compare: Option<Vec<String>>,
+
+    /// Save the per-CPU averages of the one log file as a baseline for
+    /// `--baseline`
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["compare", "baseline"])]
+    save_baseline: Option<String>,
+
+    /// Compare the one log file against a baseline saved with
+    /// `--save-baseline` and fail if a metric regressed beyond `--tolerance`
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["compare", "csv"])]
+    baseline: Option<String>,
+
+    /// Treat the files as repeated runs of one experiment and print the mean,
+    /// standard deviation and 95% confidence interval of each CPU's average
+    /// load and cycles across them; `--json` writes these instead
+    #[arg(long, conflicts_with_all = ["compare", "baseline", "save_baseline", "csv", "markdown"])]
+    aggregate: bool,
+
+    /// Treat the files as consecutive logs of one run, e.g. of a restarted
+    /// logger, and report them as one
+    #[arg(long, conflicts_with_all = ["compare", "baseline", "aggregate"])]
+    merge: bool,
+
+    /// Exit with an error when a log has fewer entries than its duration
+    /// and interval call for or gaps over 1.5 intervals, and fail
+    /// `--aggregate` when the runs have different numbers of entries instead
+    /// of cutting them down to the shortest
+    #[arg(long)]
+    strict: bool,
+
+    /// Cut the two runs of `--compare` down to the wall-clock time both
+    /// cover, with the clock offsets `profiler sync` recorded taken off, to
+    /// compare captures of different machines over the same moments
+    #[arg(long, requires = "compare")]
+    align: bool,
+
+    /// Percentage a metric may grow by before `--baseline` or `--compare`
+    /// fail, e.g. `5%`; `--baseline` defaults to 5%
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percent)]
+    tolerance: Option<f64>,
+
+    /// Also write every entry of every file to this CSV file, one row per CPU
+    #[arg(long, value_name = "PATH")]
+    csv: Option<String>,
+
+    /// Write the analyzed reports as a JSON array to PATH, or to stdout
+    /// instead of the summary when no PATH is given. Durations are integer
+    /// milliseconds (`*_ms` fields); each report carries the per-CPU
+    /// `averages` shown in the summary.
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "-")]
+    json: Option<String>,
+
+    /// Write every entry of every file to PATH in InfluxDB line protocol, one
+    /// point per CPU tagged with the run id, CPU and hostname and timestamped
+    /// from the recorded start time
+    #[arg(long, value_name = "PATH", conflicts_with = "compare")]
+    influx: Option<String>,
+
+    /// Also push the points to InfluxDB 2 at this http:// URL, e.g.
+    /// `http://localhost:8086`, in gzipped batches
+    #[arg(
+        long,
+        value_name = "URL",
+        requires = "bucket",
+        conflicts_with = "compare"
+    )]
+    influx_url: Option<String>,
+
+    /// Bucket `--influx-url` writes to
+    #[arg(long, value_name = "NAME", requires = "influx_url")]
+    bucket: Option<String>,
+
+    /// Organization owning the bucket
+    #[arg(long, value_name = "NAME", env = "INFLUX_ORG", requires = "influx_url")]
+    org: Option<String>,
+
+    /// API token to write with
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        env = "INFLUX_TOKEN",
+        hide_env_values = true
+    )]
+    influx_token: Option<String>,
+
+    /// Add every file's run, entries and per-CPU proc and perf samples to
+    /// the SQLite database at PATH, created if missing; a run already in it
+    /// is replaced
+    #[arg(long, value_name = "PATH", conflicts_with = "compare")]
+    sqlite: Option<String>,
+
+    /// Write every entry of every file to PATH as one Parquet table, a row
+    /// per CPU with the run id, time, load, /proc/stat times, cycles and
+    /// context switches, and a row group per file
+    #[arg(long, value_name = "PATH", conflicts_with = "compare")]
+    parquet: Option<String>,
+
+    /// Only report the entries starting at or after TIME: an offset into the
+    /// run, e.g. `90s`, or a wall-clock time, e.g. `2024-03-01T12:30:00Z`,
+    /// which is UTC unless it ends in an offset such as `+02:00`
+    #[arg(long, value_name = "TIME", value_parser = parse_time_bound)]
+    from: Option<TimeBound>,
+
+    /// Only report the entries starting at or before TIME, given like `--from`
+    #[arg(long, value_name = "TIME", value_parser = parse_time_bound)]
+    to: Option<TimeBound>,
+
+    /// Whether to color the output; `auto` colors it for terminals unless
+    /// NO_COLOR is set
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Same as `--color never`
+    #[arg(long, conflicts_with = "color")]
+    no_color: bool,
+
+    /// Columns to wrap the per-CPU lists at; defaults to the terminal's
+    /// width, or $COLUMNS or 80 when not writing to a terminal
+    #[arg(long, value_name = "N")]
+    width: Option<usize>,
+
+    /// Text between the cells of the per-CPU lists
+    #[arg(long, value_name = "TEXT", default_value = " | ")]
+    separator: String,
+
+    /// Digits printed after the decimal point
+    #[arg(long, value_name = "DIGITS", default_value_t = 2)]
+    precision: usize,
+
+    /// Print plain numbers such as `12345678.00` instead of `12.35M`, e.g. for
+    /// pasting into spreadsheets
+    #[arg(long)]
+    raw_numbers: bool,
+
+    /// Print the metadata and per-CPU averages as markdown tables instead of
+    /// the summary, e.g. for pasting into issues
+    #[arg(long, conflicts_with = "compare")]
+    markdown: bool,
+
+    /// Files to parse at once; defaults to the number of CPUs
+    #[arg(short, long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    jobs: Option<u32>,
+
+    /// Always parse the logs, neither reading nor writing the FILE.cache
+    /// each parsed log is otherwise kept in for the next report
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Parse the logs even if their caches are valid, and write them anew
+    #[arg(long, conflicts_with = "no_cache")]
+    refresh_cache: bool,
+
+    /// Skip entries that fail to parse instead of giving up on the file
+    #[arg(long)]
+    lenient: bool,
+
+    /// Unit of the entry times in logs written before they were recorded as
+    /// `time-ms`; guessed from the log duration when not given
+    #[arg(long, value_enum, value_name = "UNIT")]
+    time_unit: Option<TimeUnit>,
+}
+
+/// Whether `name` matches the shell pattern `pattern` of `*`, `?` and
+/// `[...]` classes such as `[0-9]` or `[!a]`.
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| glob_match(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && glob_match(&pattern[1..], &name[1..]),
+        Some('[') if pattern.contains(&']') => {
+            let Some(&c) = name.first() else {
+                return false;
+            };
+            let negated = matches!(pattern.get(1), Some('!' | '^'));
+            let start = if negated { 2 } else { 1 };
+            // A `]` right after the `[` is part of the class.
+            let end = start
+                + 1
+                + pattern[start + 1..]
+                    .iter()
+                    .position(|&c| c == ']')
+                    .unwrap_or(pattern.len() - start - 1);
+            if end >= pattern.len() {
+                return pattern[0] == c && glob_match(&pattern[1..], &name[1..]);
+            }
+            let class = &pattern[start..end];
+            let mut matched = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    matched |= (class[i]..=class[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    matched |= class[i] == c;
+                    i += 1;
+                }
+            }
+            matched != negated && glob_match(&pattern[end + 1..], &name[1..])
+        }
+        Some(&c) => name.first() == Some(&c) && glob_match(&pattern[1..], &name[1..]),
+    }
+}
+
+fn is_log(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    name.ends_with(".xml") || name.ends_with(".xml.gz")
+}
+
+/// The logs in `dir` sorted by name, and with `recursive` those of its
+/// subdirectories in place of them.
+fn dir_logs(dir: &Path, recursive: bool, logs: &mut Vec<PathBuf>) -> Result<(), String> {
+    let mut paths: Vec<_> = read_dir(dir)
+        .map_err(|error| format!("{}: {}", dir.display(), error))?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            if recursive {
+                dir_logs(&path, recursive, logs)?;
+            }
+        } else if is_log(&path) {
+            logs.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The paths matching `pattern`, sorted by name, each of its components
+/// matched against the directory the previous ones led to.
+fn glob_paths(pattern: &str) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::new()];
+    for component in Path::new(pattern).components() {
+        let part = component.as_os_str().to_string_lossy();
+        if !part.contains(['*', '?', '[']) {
+            paths = paths.into_iter().map(|path| path.join(&*part)).collect();
+            continue;
+        }
+        let part: Vec<char> = part.chars().collect();
+        let mut matched = Vec::new();
+        for path in paths {
+            let dir = if path.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                &path
+            };
+            let Ok(entries) = read_dir(dir) else {
+                continue;
+            };
+            let mut names: Vec<_> = entries
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                // Like the shell, `*` doesn't match hidden files.
+                .filter(|name| !name.starts_with('.') || part[0] == '.')
+                .filter(|name| glob_match(&part, &name.chars().collect::<Vec<_>>()))
+                .collect();
+            names.sort();
+            matched.extend(names.into_iter().map(|name| path.join(name)));
+        }
+        paths = matched;
+    }
+    paths.retain(|path| path.exists());
+    paths
+}
+
+/// Expands each of `inputs` that names a directory or is a glob pattern into
+/// the `.xml` and `.xml.gz` logs in it or matching it, sorted by name; other
+/// inputs are kept as given.
+fn expand_inputs(inputs: &[String], recursive: bool) -> Result<Vec<String>, String> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let path = Path::new(input);
+        let paths = if path.is_dir() {
+            vec![path.to_path_buf()]
+        } else if input != "-" && !path.exists() && input.contains(['*', '?', '[']) {
+            glob_paths(input)
+        } else {
+            files.push(input.clone());
+            continue;
+        };
+        let mut logs = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                dir_logs(&path, recursive, &mut logs)?;
+            } else if is_log(&path) {
+                logs.push(path);
+            }
+        }
+        if logs.is_empty() {
+            return Err(format!("no .xml or .xml.gz logs in or matching {}", input));
+        }
+        files.extend(logs.iter().map(|log| log.to_string_lossy().into_owned()));
+    }
+    Ok(files)
+}
+
+fn parse_percent(text: &str) -> Result<f64, String> {
+    text.trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("\"{}\" is not a percentage", text))
+}
+
+fn parse_cpu_group(text: &str) -> Result<CpuGroup, String> {
+    let (name, cpus) = text
+        .split_once('=')
+        .ok_or_else(|| format!("\"{}\" is not a group such as big=4-7", text))?;
+    if name.is_empty() || name == "all" || name.contains(|c: char| c.is_ascii_digit()) {
+        return Err(format!(
+            "\"{}\" can't name a group, it must not be empty, \"all\" or contain digits",
+            name
+        ));
+    }
+    Ok(CpuGroup {
+        name: name.to_string(),
+        cpus: parse_cpu_list(cpus)?.0,
+    })
+}
+
+fn parse_time_bound(text: &str) -> Result<TimeBound, String> {
+    if let Some(time) = timestamp::parse(text) {
+        return Ok(TimeBound::At(time));
+    }
+    parse_duration(text).map(TimeBound::Offset).map_err(|_| {
+        format!(
+            "\"{}\" is neither an offset such as 90s nor a time such as 2024-03-01T12:30:00Z",
+            text
+        )
+    })
+}
+
+/// Narrows a report down to the `--from`/`--to` window and the `--cpus`, and
+/// adds the `--group` rows.
+fn select(filename: &str, report: &mut Report, args: &ReportArgs) -> Result<(), ParseError> {
+    filter_time(report, args.from, args.to)?;
+    if let Some(CpuList(cpus)) = &args.cpus {
+        filter_cpus(report, cpus);
+    }
+    if args.exclude_profiler_cpu {
+        match report.metadata.pinned_cpu {
+            Some(cpu) => exclude_cpu(report, cpu.into()),
+            None => eprintln!(
+                "{}: the profiler was not pinned to a CPU, so none is left out",
+                filename
+            ),
+        }
+    }
+    // After "all" is summed over the selected CPUs, which the profiler's
+    // time is taken out of.
+    if args.subtract_self {
+        subtract_profiler(report);
+    }
+    // Clusters from the topology, unless `--group` names groups itself.
+    let groups = if args.groups.is_empty() {
+        topology_groups(report)
+    } else {
+        args.groups.clone()
+    };
+    for warning in add_cpu_groups(report, &groups) {
+        eprintln!("{}: {}", filename, warning);
+    }
+    Ok(())
+}
+
+fn print_aggregate(aggregate: &Aggregate) {
+    println!(
+        "{:20} {} of {} entries at {:?}",
+        "Runs ".bold().yellow(),
+        aggregate.files.len(),
+        aggregate.entries,
+        aggregate.interval
+    );
+    let print_metric = |title: &str, unit: &str, metric: fn(&CpuAggregate) -> Option<RunSpread>| {
+        let cells: Vec<_> = aggregate
+            .cpus
+            .iter()
+            .filter_map(|cpu| {
+                let spread = metric(cpu)?;
+                Some((
+                    cpu.cpu.as_str().bold().yellow().to_string(),
+                    format!(
+                        "{}{} ± {} (95% CI {} to {})",
+                        format_number(spread.mean).trim_end(),
+                        unit,
+                        format_number(spread.stddev).trim_end(),
+                        format_number(spread.ci95_low).trim_end(),
+                        format_number(spread.ci95_high).trim_end()
+                    ),
+                ))
+            })
+            .collect();
+        if !cells.is_empty() {
+            println!("{}", title.bold().yellow());
+            print_string_collection(&cells);
+        }
+    };
+    print_metric("Per CPU average load across runs", "%", |cpu| cpu.load);
+    print_metric("Per CPU average cycles across runs", "", |cpu| cpu.cycles);
+}
+
+fn jobs(args: &ReportArgs) -> usize {
+    args.jobs.map_or_else(
+        || thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+        |jobs| jobs as usize,
+    )
+}
+
+fn cache(args: &ReportArgs) -> Cache {
+    if args.no_cache {
+        Cache::Off
+    } else if args.refresh_cache {
+        Cache::Refresh
+    } else {
+        Cache::Use
+    }
+}
+
+/// `--merge`: the files are consecutive logs of one run.
+fn merge(args: &ReportArgs) -> Report {
+    let logs = args
+        .files
+        .iter()
+        .zip(load_reports(
+            &args.files,
+            jobs(args),
+            args.lenient,
+            args.time_unit,
+            cache(args),
+        ))
+        .map(|(filename, report)| {
+            let report = report.unwrap_or_else(|error| {
+                eprintln!("{}", error);
+                exit(1);
+            });
+            if !print_warnings(filename, &report) && args.strict {
+                exit(1);
+            }
+            (filename.clone(), report)
+        })
+        .collect();
+    let (report, warnings) = merge_reports(logs).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        exit(1);
+    });
+    for warning in warnings {
+        eprintln!("{}", warning);
+    }
+    report
+}
+
+/// `--aggregate`: every file is a run of the same experiment.
+fn aggregate(args: &ReportArgs) {
+    let mut runs = Vec::new();
+    let loaded = load_reports(
+        &args.files,
+        jobs(args),
+        args.lenient,
+        args.time_unit,
+        cache(args),
+    );
+    for (filename, report) in args.files.iter().zip(loaded) {
+        // A bad run among many is left out rather than failing the lot.
+        let mut report = match report {
+            Ok(report) => report,
+            Err(error) => {
+                eprintln!("{}, leaving it out", error);
+                continue;
+            }
+        };
+        if !print_warnings(filename, &report) && args.strict {
+            exit(1);
+        }
+        if let Err(error) = select(filename, &mut report, args) {
+            eprintln!("{}: {}, leaving it out", filename, error);
+            continue;
+        }
+        runs.push((filename.as_str(), report));
+    }
+    if runs.len() < 2 {
+        eprintln!("--aggregate needs at least two runs that could be read");
+        exit(1);
+    }
+    let (aggregate, warnings) = aggregate_runs(&mut runs, args.strict).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        exit(1);
+    });
+    for warning in warnings {
+        eprintln!("{}", warning);
+    }
+    match args.json.as_deref() {
+        Some("-") => {
+            serde_json::to_writer_pretty(std::io::stdout().lock(), &aggregate).unwrap();
+            println!();
+        }
+        Some(path) => {
+            print_aggregate(&aggregate);
+            if let Err(error) = write_json(path, &aggregate) {
+                eprintln!("{}: {}", path, error);
+                exit(1);
+            }
+        }
+        None => print_aggregate(&aggregate),
+    }
+}
+
+fn write_json(path: &str, value: &impl Serialize) -> io::Result<()> {
+    let mut output = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut output, value)?;
+    output.flush()
+}
+
+fn read_baseline(path: &str) -> Result<Baseline, String> {
+    let file = File::open(path).map_err(|error| error.to_string())?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|error| error.to_string())
+}
+
+pub fn run(mut args: ReportArgs) {
+    let expanded = expand_inputs(&args.files, args.recursive).and_then(|files| {
+        let compare = match args.compare.as_ref() {
+            Some(compare) => Some(
+                compare
+                    .iter()
+                    .map(|input| {
+                        match expand_inputs(std::slice::from_ref(input), args.recursive)?[..] {
+                            [ref file] => Ok(file.clone()),
+                            ref files => Err(format!(
+                                "{} is {} logs, --compare takes one on each side",
+                                input,
+                                files.len()
+                            )),
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            None => None,
+        };
+        Ok((files, compare))
+    });
+    match expanded {
+        Ok((files, compare)) => {
+            args.files = files;
+            args.compare = compare;
+        }
+        Err(error) => ReportArgs::command()
+            .error(ErrorKind::ValueValidation, error)
+            .exit(),
+    }
+    let color = match (args.no_color, args.color) {
+        (true, _) | (false, ColorChoice::Never) => false,
+        (false, ColorChoice::Always) => true,
+        (false, ColorChoice::Auto) => {
+            std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                && stdout().is_terminal()
+        }
+    };
+    colored::control::set_override(color);
+    NUMBER_FORMAT
+        .set(NumberFormat {
+            precision: args.precision,
+            raw: args.raw_numbers,
+        })
+        .ok();
+    LAYOUT
+        .set(Layout {
+            width: output_width(args.width),
+            separator: args.separator.clone(),
+        })
+        .ok();
+    let inverted = match (args.from, args.to) {
+        (Some(TimeBound::Offset(from)), Some(TimeBound::Offset(to))) => from > to,
+        (Some(TimeBound::At(from)), Some(TimeBound::At(to))) => from > to,
+        _ => false,
+    };
+    if inverted {
+        ReportArgs::command()
+            .error(ErrorKind::ArgumentConflict, "--from is after --to")
+            .exit();
+    }
+    if let Some(path) = args.plot.as_ref() {
+        if path.ends_with(".png") && !cfg!(feature = "png") {
+            ReportArgs::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    "--plot writes PNG files only when built with the png feature",
+                )
+                .exit();
+        }
+        if !path.ends_with(".png") && !path.ends_with(".svg") {
+            ReportArgs::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    "--plot only writes SVG or PNG files",
+                )
+                .exit();
+        }
+    }
+    if args.markdown && args.json.as_deref() == Some("-") {
+        ReportArgs::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--markdown and --json without a PATH both print to stdout",
+            )
+            .exit();
+    }
+    for (set, flag) in [(args.aggregate, "--aggregate"), (args.merge, "--merge")] {
+        if set && args.files.len() < 2 {
+            ReportArgs::command()
+                .error(
+                    ErrorKind::WrongNumberOfValues,
+                    format!("{} takes at least two log files", flag),
+                )
+                .exit();
+        }
+    }
+    if args.watch {
+        if args.files.len() != 1 {
+            ReportArgs::command()
+                .error(ErrorKind::WrongNumberOfValues, "--watch takes one log file")
+                .exit();
+        }
+        watch::watch(&args.files[0], args.refresh, args.lenient);
+        return;
+    }
+    if args.tui && args.files.len() != 1 {
+        ReportArgs::command()
+            .error(ErrorKind::WrongNumberOfValues, "--tui takes one log file")
+            .exit();
+    }
+    let tui = args.tui && stdout().is_terminal();
+    if (args.save_baseline.is_some() || args.baseline.is_some()) && args.files.len() != 1 {
+        ReportArgs::command()
+            .error(
+                ErrorKind::WrongNumberOfValues,
+                "--save-baseline and --baseline take exactly one log file",
+            )
+            .exit();
+    }
+
+    let load = |filename: &str| {
+        let mut report = load_cached(filename, args.lenient, args.time_unit, cache(&args))
+            .unwrap_or_else(|error| {
+                eprintln!("{}", error);
+                exit(1);
+            });
+        if !print_warnings(filename, &report) && args.strict {
+            exit(1);
+        }
+        if let Err(error) = select(filename, &mut report, &args) {
+            eprintln!("{}: {}", filename, error);
+            exit(1);
+        }
+        report
+    };
+    let comparison = match (&args.compare, &args.baseline) {
+        (Some(files), _) => {
+            let (mut base, mut new) = (load(&files[0]), load(&files[1]));
+            let aligned = args.align.then(|| {
+                align_by_time(&mut [&mut base, &mut new]).unwrap_or_else(|error| {
+                    eprintln!("cannot align {} and {}: {}", files[0], files[1], error);
+                    exit(1);
+                })
+            });
+            let mut comparison = compare_reports(
+                &Baseline::from_report(&files[0], &base),
+                &Baseline::from_report(&files[1], &new),
+                args.tolerance,
+            );
+            comparison.aligned = aligned.map(|(from, to)| {
+                format!("{} to {}", timestamp::format(from), timestamp::format(to))
+            });
+            Some(comparison)
+        }
+        (None, Some(path)) => {
+            let base = read_baseline(path).unwrap_or_else(|error| {
+                eprintln!("{}: {}", path, error);
+                exit(1);
+            });
+            Some(compare_reports(
+                &base,
+                &Baseline::from_report(&args.files[0], &load(&args.files[0])),
+                Some(args.tolerance.unwrap_or(5.0)),
+            ))
+        }
+        (None, None) => None,
+    };
+    if let Some(comparison) = comparison {
+        match args.json.as_deref() {
+            Some("-") => {
+                serde_json::to_writer_pretty(std::io::stdout().lock(), &comparison).unwrap();
+                println!();
+            }
+            Some(path) => {
+                print_comparison(&comparison);
+                if let Err(error) = write_json(path, &comparison) {
+                    eprintln!("{}: {}", path, error);
+                    exit(1);
+                }
+            }
+            None => print_comparison(&comparison),
+        }
+        if !comparison.regressions.is_empty() {
+            exit(1);
+        }
+        return;
+    }
+
+    if args.aggregate {
+        aggregate(&args);
+        return;
+    }
+
+    let mut csv = args.csv.as_ref().map(|path| {
+        let mut csv = BufWriter::new(File::create(path).unwrap_or_else(|error| {
+            eprintln!("{}: {}", path, error);
+            exit(1);
+        }));
+        writeln!(csv, "{}", CSV_HEADER).unwrap();
+        csv
+    });
+
+    let json_to_stdout = args.json.as_deref() == Some("-");
+    let mut reports = Vec::new();
+    let mut failed = false;
+    let mut violated = false;
+
+    let mut merged = args.merge.then(|| merge(&args));
+    let mut loaded = merged.is_none().then(|| {
+        load_reports(
+            &args.files,
+            jobs(&args),
+            args.lenient,
+            args.time_unit,
+            cache(&args),
+        )
+    });
+    let merged_name = args.files.join(" + ");
+    let filenames: Vec<_> = if merged.is_some() {
+        vec![&merged_name]
+    } else {
+        args.files.iter().collect()
+    };
+
+    for filename in filenames {
+        let mut report = match merged.take() {
+            Some(report) => report,
+            None => match loaded.as_mut().unwrap().next().unwrap() {
+                Ok(report) => {
+                    failed |= !print_warnings(filename, &report) && args.strict;
+                    report
+                }
+                Err(error) => {
+                    eprintln!("{}", error);
+                    failed = true;
+                    continue;
+                }
+            },
+        };
+        if let Err(error) = select(filename, &mut report, &args) {
+            eprintln!("{}: {}", filename, error);
+            failed = true;
+            continue;
+        }
+        filter_interfaces(&mut report, &args.ifaces);
+        let violations = check_thresholds(&report, &args);
+        if let Some(path) = args.save_baseline.as_ref() {
+            if let Err(error) = write_json(path, &Baseline::from_report(filename, &report)) {
+                eprintln!("{}: {}", path, error);
+                failed = true;
+            }
+        }
+        if tui {
+            if let Err(error) = tui::run(&report) {
+                eprintln!("{}: {}", filename, error);
+                failed = true;
+            }
+        } else if args.quiet {
+            print_summary_line(filename, &report);
+        } else if args.markdown {
+            print_markdown(filename, &report);
+        } else if !json_to_stdout {
+            let phases = (args.split_by_markers || !args.split_at.is_empty()).then(|| {
+                let mut splits: Vec<_> = args
+                    .split_at
+                    .iter()
+                    .map(|time| (format!("{}s", time.as_secs_f64()), *time))
+                    .collect();
+                if args.split_by_markers {
+                    splits.extend(
+                        report
+                            .markers
+                            .iter()
+                            .map(|marker| (marker.label.clone(), marker.time)),
+                    );
+                }
+                split_phases(&mut report, splits)
+            });
+            let groups = args
+                .group_by
+                .map(|GroupBy::NetType| group_by_net_type(&mut report));
+            print_report(
+                filename,
+                &report,
+                &args,
+                &violations,
+                phases.as_deref(),
+                groups.as_deref(),
+            );
+        }
+        for violation in violations.iter() {
+            eprintln!("{}: {}", filename, violation.describe());
+        }
+        violated |= !violations.is_empty();
+
+        if let Some(csv) = csv.as_mut() {
+            write_csv(csv, filename, &report);
+        }
+        if args.json.is_some()
+            || args.plot.is_some()
+            || args.html.is_some()
+            || args.influx.is_some()
+            || args.influx_url.is_some()
+            || args.sqlite.is_some()
+            || args.parquet.is_some()
+        {
+            reports.push((filename, report));
+        }
+    }
+
+    if let Some(csv) = csv.as_mut() {
+        csv.flush().unwrap();
+    }
+
+    let plotted: Vec<_> = reports
+        .iter()
+        .map(|(filename, report)| (filename.as_str(), report))
+        .collect();
+    if let Some(path) = args.plot.as_ref() {
+        if let Err(error) = plot::write_plot(path, &plotted, &args.plot_cpu) {
+            eprintln!("{}: {}", path, error);
+            failed = true;
+        }
+    }
+    if let Some(path) = args.html.as_ref() {
+        if let Err(error) = html::write_html(path, &plotted, &args.plot_cpu) {
+            eprintln!("{}: {}", path, error);
+            failed = true;
+        }
+    }
+
+    if let Some(path) = args.parquet.as_ref() {
+        if let Err(error) = parquet::write_parquet(path, &plotted) {
+            eprintln!("{}: {}", path, error);
+            failed = true;
+        }
+    }
+    if let Some(path) = args.sqlite.as_ref() {
+        if let Err(error) = sqlite::write_sqlite(path, &plotted) {
+            eprintln!("{}: {}", path, error);
+            failed = true;
+        }
+    }
+    if args.influx.is_some() || args.influx_url.is_some() {
+        match influx::render_points(&plotted) {
+            Ok(points) => {
+                if let Some(path) = args.influx.as_ref() {
+                    if let Err(error) = influx::write_points(path, &points) {
+                        eprintln!("{}: {}", path, error);
+                        failed = true;
+                    }
+                }
+                if let (Some(url), Some(bucket)) = (args.influx_url.as_ref(), args.bucket.as_ref())
+                {
+                    let destination = influx::Destination {
+                        url,
+                        bucket,
+                        org: args.org.as_deref(),
+                        token: args.influx_token.as_deref(),
+                    };
+                    match influx::push_points(&destination, &points) {
+                        Ok(batches) => eprintln!(
+                            "Wrote {} points to {} in {} batches",
+                            points.lines().count(),
+                            bucket,
+                            batches
+                        ),
+                        Err(error) => {
+                            eprintln!("{}: {}", url, error);
+                            failed = true;
+                        }
+                    }
+                }
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                failed = true;
+            }
+        }
+    }
+
+    if let Some(path) = args.json {
+        let json: Vec<_> = reports
+            .iter()
+            .map(|(filename, report)| JsonReport {
+                file: filename,
+                report,
+                averages: get_averages(report),
+            })
+            .collect();
+        if json_to_stdout {
+            serde_json::to_writer_pretty(std::io::stdout().lock(), &json).unwrap();
+            println!();
+        } else if let Err(error) = write_json(&path, &json) {
+            eprintln!("{}: {}", path, error);
+            failed = true;
+        }
+    }
+
+    if failed || violated {
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(number: f64) -> String {
+        NumberFormat::default().format(number)
+    }
+
+    #[test]
+    fn matches_shell_patterns() {
+        let matches = |pattern: &str, name: &str| {
+            glob_match(
+                &pattern.chars().collect::<Vec<_>>(),
+                &name.chars().collect::<Vec<_>>(),
+            )
+        };
+        assert!(matches("2024-06-*.xml", "2024-06-01.xml"));
+        assert!(!matches("2024-06-*.xml", "2024-06-01.xml.gz"));
+        assert!(matches("run-?[0-9].xml", "run-a7.xml"));
+        assert!(!matches("run-[!0-9]*", "run-1.xml"));
+        assert!(matches("[]x]", "]"));
+        assert!(matches("*", ""));
+    }
+
+    #[test]
+    fn formats_numbers_with_si_suffixes() {
+        assert_eq!(format(0.0), "0.00 ");
+        assert_eq!(format(999.94), "999.94 ");
+        assert_eq!(format(999.95), "999.95 ");
+        assert_eq!(format(999.995), "1.00K");
+        assert_eq!(format(12_345.0), "12.35K");
+        assert_eq!(format(1_234_567.0), "1.23M");
+        assert_eq!(format(999_999_999.0), "1.00G");
+        assert_eq!(format(2.5e12), "2.50T");
+        assert_eq!(format(3.0e15), "3.00P");
+        assert_eq!(format(4.0e18), "4.00E");
+        assert_eq!(format(5.0e21), "5000.00E");
+        assert_eq!(format(-12_345.0), "-12.35K");
+    }
+
+    #[test]
+    fn wraps_cells_into_a_grid_under_their_labels() {
+        let cells: Vec<_> = ["0", "1", "2", "all"]
+            .iter()
+            .map(|cpu| (cpu.to_string(), format!("{}0.00 %", cpu.len())))
+            .collect();
+        let layout = |width| Layout {
+            width,
+            separator: " | ".to_string(),
+        };
+        assert_eq!(
+            layout_cells(&cells, &layout(80)),
+            ["0   10.00 % | 1   10.00 % | 2   10.00 % | all 30.00 %"]
+        );
+        assert_eq!(
+            layout_cells(&cells, &layout(20)),
+            [
+                "0       | 1",
+                "10.00 % | 10.00 %",
+                "2       | all",
+                "10.00 % | 30.00 %"
+            ]
+        );
+        assert_eq!(layout_cells(&cells, &layout(1)).len(), 8);
+    }
+
+    #[test]
+    fn formats_numbers_raw_and_at_other_precisions() {
+        let raw = NumberFormat {
+            precision: 2,
+            raw: true,
+        };
+        assert_eq!(raw.format(12_345_678.0), "12345678.00");
+        assert_eq!(raw.format(1e16), "10000000000000000.00");
+        let precise = NumberFormat {
+            precision: 0,
+            raw: false,
+        };
+        assert_eq!(precise.format(999.5), "1K");
+        assert_eq!(precise.format(1_500.0), "2K");
+        assert_eq!(precise.format(42.4), "42 ");
+        let tenths = NumberFormat {
+            precision: 1,
+            raw: false,
+        };
+        assert_eq!(tenths.format(999.95), "1.0K");
+        assert_eq!(tenths.format(999.94), "999.9 ");
+    }
+}
@@ -0,0 +1,134 @@
+use std::{fmt::Write as _, fs, io};
+
+use crate::{
+    log::escape_xml,
+    report::{get_averages, Report},
+    timestamp,
+};
+
+use super::{format_exit, plot};
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ccc; padding: 4px 10px; }
+td.number { text-align: right; font-family: monospace; }
+th { background: #f4f4f4; text-align: left; }";
+
+/// Numbers as Rust prints them, with a `.` and no grouping whatever the
+/// reader's locale, or `-` when the CPU has no such value.
+fn number(value: Option<f64>, precision: usize) -> String {
+    value.map_or("-".to_string(), |value| format!("{:.*}", precision, value))
+}
+
+fn metadata_table(html: &mut String, filename: &str, report: &Report) {
+    let metadata = &report.metadata;
+    let duration = report.duration_actual.unwrap_or(report.duration);
+    let rows = [
+        ("File", Some(filename.to_string())),
+        ("Report ID", Some(report.id.to_string())),
+        ("Platform", Some(report.platform.name().to_string())),
+        ("Host", metadata.hostname.clone()),
+        ("Device", metadata.device_model.clone()),
+        ("System", metadata.uname.clone()),
+        ("CPU", metadata.cpu_model.clone()),
+        (
+            "Logical CPUs",
+            metadata.logical_cpus.map(|cpus| cpus.to_string()),
+        ),
+        (
+            "Physical CPUs",
+            metadata.physical_cpus.map(|cpus| cpus.to_string()),
+        ),
+        (
+            "Profiler CPU",
+            metadata.pinned_cpu.map(|cpu| cpu.to_string()),
+        ),
+        ("Command", metadata.command.clone()),
+        ("Start", report.start.map(timestamp::format)),
+        (
+            "Duration (s)",
+            Some(format!("{:.3}", duration.as_secs_f64())),
+        ),
+        ("Exit Status", report.exit.map(format_exit)),
+        (
+            "Interval (ms)",
+            Some(report.interval.as_millis().to_string()),
+        ),
+        ("Entries", Some(report.entries.len().to_string())),
+    ];
+    writeln!(html, "<table>").unwrap();
+    for (label, value) in rows {
+        if let Some(value) = value {
+            writeln!(
+                html,
+                "<tr><th>{}</th><td>{}</td></tr>",
+                label,
+                escape_xml(&value)
+            )
+            .unwrap();
+        }
+    }
+    writeln!(html, "</table>").unwrap();
+}
+
+fn averages_table(html: &mut String, report: &Report) {
+    writeln!(
+        html,
+        "<table>\n<tr><th>CPU</th><th>Load (%)</th><th>Online (%)</th><th>Cycles</th><th>Context switches</th></tr>"
+    )
+    .unwrap();
+    for averages in get_averages(report) {
+        writeln!(
+            html,
+            r#"<tr><th>{}</th><td class="number">{}</td><td class="number">{}</td><td class="number">{}</td><td class="number">{}</td></tr>"#,
+            escape_xml(&averages.cpu),
+            number(averages.load, 2),
+            number(averages.online, 2),
+            number(averages.cycles, 0),
+            number(averages.context_switches, 1)
+        )
+        .unwrap();
+    }
+    writeln!(html, "</table>").unwrap();
+}
+
+/// A page needing nothing but itself: a section per report with its
+/// metadata, per CPU averages and the `--plot` chart of `cpus` inline.
+pub fn render_html(reports: &[(&str, &Report)], cpus: &[String]) -> String {
+    let mut html = String::new();
+    writeln!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Profiler report</title>\n<style>\n{}\n</style>\n</head>\n<body>",
+        STYLE
+    )
+    .unwrap();
+    for (filename, report) in reports.iter() {
+        writeln!(html, "<section>\n<h2>{}</h2>", escape_xml(filename)).unwrap();
+        metadata_table(&mut html, filename, report);
+        writeln!(html, "<h3>Per CPU averages</h3>").unwrap();
+        averages_table(&mut html, report);
+        if !report.entries.is_empty() {
+            writeln!(html, "<h3>Load and cycles over time</h3>").unwrap();
+            html.push_str(&plot::render_svg(&[(filename, report)], cpus));
+        }
+        writeln!(html, "</section>").unwrap();
+    }
+    writeln!(html, "</body>\n</html>").unwrap();
+    html
+}
+
+pub fn write_html(path: &str, reports: &[(&str, &Report)], cpus: &[String]) -> io::Result<()> {
+    fs::write(path, render_html(reports, cpus))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_numbers_without_locale() {
+        assert_eq!(number(Some(1234567.891), 2), "1234567.89");
+        assert_eq!(number(Some(2.5), 0), "2");
+        assert_eq!(number(None, 2), "-");
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, Error, ErrorKind, Read, Write},
+    net::TcpStream,
+    time::UNIX_EPOCH,
+};
+
+use flate2::{write::GzEncoder, Compression};
+
+use crate::report::{compare_cpus, get_wall_time, Report, CONTEXT_SWITCHES, CYCLES};
+
+const MEASUREMENT: &str = "profiler";
+/// InfluxDB suggests writes of about 5000 lines.
+const BATCH_LINES: usize = 5000;
+
+/// Tag values and keys may not hold unescaped commas, equals signs or
+/// spaces.
+fn escape_tag(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+/// One point per entry per CPU of every report, in line protocol, tagged
+/// with the report's id, the CPU and the host, and timestamped in
+/// nanoseconds at the entry's wall-clock time: `--timestamps`' if logged,
+/// else the capture's start plus the entry's offset.
+pub fn render_points(reports: &[(&str, &Report)]) -> io::Result<String> {
+    let mut text = String::new();
+    for (filename, report) in reports {
+        let mut tags = format!("{},run_id={}", MEASUREMENT, report.id);
+        if let Some(hostname) = report.metadata.hostname.as_deref() {
+            write!(tags, ",hostname={}", escape_tag(hostname)).unwrap();
+        }
+        for entry in report.entries.iter() {
+            let time = get_wall_time(report, entry)
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("{} has no start time to timestamp its points by", filename),
+                    )
+                })?
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos();
+            let mut cpus: Vec<_> = entry
+                .proc
+                .keys()
+                .chain(
+                    entry
+                        .perf
+                        .keys()
+                        .filter(|cpu| !entry.proc.contains_key(*cpu)),
+                )
+                .collect();
+            cpus.sort_by(|a, b| compare_cpus(a, b));
+            for cpu in cpus {
+                let mut fields = Vec::new();
+                if let Some(proc) = entry.proc.get(cpu).filter(|proc| proc.load.is_finite()) {
+                    fields.push(format!("load={}", proc.load));
+                }
+                if let Some(perf) = entry.perf.get(cpu) {
+                    for event in [CYCLES, CONTEXT_SWITCHES] {
+                        if let Some(value) = perf.get(event) {
+                            fields.push(format!("{}={}i", event, value));
+                        }
+                    }
+                }
+                if fields.is_empty() {
+                    continue;
+                }
+                writeln!(
+                    text,
+                    "{},cpu={} {} {}",
+                    tags,
+                    escape_tag(cpu),
+                    fields.join(","),
+                    time
+                )
+                .unwrap();
+            }
+        }
+    }
+    Ok(text)
+}
+
+pub fn write_points(path: &str, points: &str) -> io::Result<()> {
+    fs::write(path, points)
+}
+
+fn encode_query(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Where to write points to with InfluxDB 2's `/api/v2/write`.
+pub struct Destination<'a> {
+    /// `http://HOST:PORT`, HTTPS isn't supported.
+    pub url: &'a str,
+    pub bucket: &'a str,
+    pub org: Option<&'a str>,
+    pub token: Option<&'a str>,
+}
+
+/// Posts `points` to InfluxDB in gzipped batches of `BATCH_LINES` lines and
+/// returns how many batches were written; the batches before a failed one
+/// stay written.
+pub fn push_points(destination: &Destination, points: &str) -> io::Result<usize> {
+    let invalid = |message: String| Error::new(ErrorKind::InvalidInput, message);
+    let rest = destination.url.strip_prefix("http://").ok_or_else(|| {
+        invalid(format!(
+            "{} is not an http:// URL, HTTPS is not supported",
+            destination.url
+        ))
+    })?;
+    let (authority, prefix) = match rest.split_once('/') {
+        Some((authority, prefix)) => (authority, format!("/{}", prefix.trim_end_matches('/'))),
+        None => (rest, String::new()),
+    };
+    let address = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+    let mut path = format!(
+        "{}/api/v2/write?bucket={}&precision=ns",
+        prefix,
+        encode_query(destination.bucket)
+    );
+    if let Some(org) = destination.org {
+        write!(path, "&org={}", encode_query(org)).unwrap();
+    }
+
+    let lines: Vec<_> = points.lines().collect();
+    for (batch, lines) in lines.chunks(BATCH_LINES).enumerate() {
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        for line in lines {
+            writeln!(gzip, "{}", line)?;
+        }
+        let body = gzip.finish()?;
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\nConnection: close\r\n",
+            path,
+            authority,
+            body.len()
+        );
+        if let Some(token) = destination.token {
+            write!(request, "Authorization: Token {}\r\n", token).unwrap();
+        }
+        request.push_str("\r\n");
+
+        let mut stream = TcpStream::connect(&address)?;
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(&body)?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        let response = String::from_utf8_lossy(&response);
+        let status = response.split_whitespace().nth(1).unwrap_or_default();
+        if !status.starts_with('2') {
+            let reason = response
+                .split_once("\r\n\r\n")
+                .map_or(response.as_ref(), |(_, body)| body)
+                .trim();
+            return Err(Error::other(format!(
+                "InfluxDB refused batch {} with {}: {}",
+                batch + 1,
+                status,
+                reason
+            )));
+        }
+    }
+    Ok(lines.len().div_ceil(BATCH_LINES))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::report::{get_report, PerfReport};
+
+    #[test]
+    fn renders_a_point_per_entry_and_cpu() {
+        let log = format!(
+            "<log id=\"{}\" platform=\"ubuntu\" duration=\"1\" interval-ms=\"1000\" perf=\"off\">\n<log-entry time-ms=\"0\">\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n</log-entry>\n</log>\n",
+            Uuid::nil(),
+            include_str!("../../../tests/fixtures/ubuntu/proc-stat-start.txt"),
+            include_str!("../../../tests/fixtures/ubuntu/proc-stat-end.txt")
+        );
+        let mut report = get_report(log.as_bytes(), false, None).unwrap();
+        report.metadata.hostname = Some("lab host".to_string());
+        let entry = &mut report.entries[0];
+        entry.proc.retain(|cpu, _| cpu == "0");
+        entry.proc.get_mut("0").unwrap().load = 12.5;
+        let mut perf = PerfReport::default();
+        perf.events.insert(CYCLES.to_string(), 1000);
+        entry.perf.insert("0".to_string(), perf);
+
+        assert!(render_points(&[("run.xml", &report)]).is_err());
+        report.start = Some(UNIX_EPOCH + Duration::from_secs(100));
+        let line = format!(
+            "profiler,run_id={},hostname=lab\\ host,cpu=0 load=12.5,cycles=1000i",
+            Uuid::nil()
+        );
+        assert_eq!(
+            render_points(&[("run.xml", &report)]).unwrap(),
+            format!("{} 100000000000\n", line)
+        );
+        // A logged wall-clock time wins over the start plus the offset.
+        report.entries[0].wall_time = Some(UNIX_EPOCH + Duration::from_millis(200_500));
+        assert_eq!(
+            render_points(&[("run.xml", &report)]).unwrap(),
+            format!("{} 200500000000\n", line)
+        );
+    }
+
+    #[test]
+    fn escapes_tags_and_queries() {
+        assert_eq!(escape_tag("my host,a=b"), "my\\ host\\,a\\=b");
+        assert_eq!(encode_query("perf runs/1"), "perf%20runs%2F1");
+    }
+}
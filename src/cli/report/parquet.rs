@@ -0,0 +1,207 @@
+use std::{fs::File, sync::Arc};
+
+use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, GzipLevel},
+    file::properties::WriterProperties,
+};
+
+use crate::report::{compare_cpus, Report, CONTEXT_SWITCHES, CYCLES};
+
+/// The per-entry per-CPU time series of `report`, the rows in the order
+/// `--csv` writes them.
+fn record_batch(schema: &SchemaRef, report: &Report) -> Result<RecordBatch, ArrowError> {
+    let mut run_id = Vec::new();
+    let mut time = Vec::new();
+    let mut cpus = Vec::new();
+    let mut load = Vec::new();
+    let mut counters: [Vec<Option<i64>>; 10] = Default::default();
+    for entry in report.entries.iter() {
+        let mut keys: Vec<_> = entry
+            .proc
+            .keys()
+            .chain(
+                entry
+                    .perf
+                    .keys()
+                    .filter(|cpu| !entry.proc.contains_key(*cpu)),
+            )
+            .collect();
+        keys.sort_by(|a, b| compare_cpus(a, b));
+        for cpu in keys {
+            run_id.push(report.id.to_string());
+            time.push(entry.time.as_millis() as i64);
+            cpus.push(cpu.as_str());
+            let proc = entry.proc.get(cpu);
+            load.push(proc.map(|proc| proc.load).filter(|load| load.is_finite()));
+            let proc = proc.map(|proc| {
+                [
+                    proc.user,
+                    proc.nice,
+                    proc.system,
+                    proc.idle,
+                    proc.iowait,
+                    proc.irq,
+                    proc.softirq,
+                    proc.total,
+                ]
+            });
+            let perf = entry.perf.get(cpu);
+            for (index, counter) in counters.iter_mut().enumerate() {
+                let value = match index {
+                    0..=7 => proc.map(|proc| proc[index]),
+                    8 => perf.and_then(|perf| perf.get(CYCLES)),
+                    _ => perf.and_then(|perf| perf.get(CONTEXT_SWITCHES)),
+                };
+                counter.push(value.map(|value| value as i64));
+            }
+        }
+    }
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from(run_id)),
+        Arc::new(Int64Array::from(time)),
+        Arc::new(StringArray::from(cpus)),
+        Arc::new(Float64Array::from(load)),
+    ];
+    for counter in counters {
+        columns.push(Arc::new(Int64Array::from(counter)));
+    }
+    RecordBatch::try_new(schema.clone(), columns)
+}
+
+/// The columns of the table, the run id, time and CPU being the only ones
+/// never null.
+fn schema() -> SchemaRef {
+    let mut fields = vec![
+        Field::new("run_id", DataType::Utf8, false),
+        Field::new("time_ms", DataType::Int64, false),
+        Field::new("cpu", DataType::Utf8, false),
+        Field::new("load", DataType::Float64, true),
+    ];
+    for name in [
+        "user",
+        "nice",
+        "system",
+        "idle",
+        "iowait",
+        "irq",
+        "softirq",
+        "total",
+        CYCLES,
+        CONTEXT_SWITCHES,
+    ] {
+        fields.push(Field::new(name, DataType::Int64, true));
+    }
+    Arc::new(Schema::new(fields))
+}
+
+/// Writes the reports as one gzipped table, a row group each.
+pub fn write_parquet(path: &str, reports: &[(&str, &Report)]) -> parquet::errors::Result<()> {
+    let schema = schema();
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::GZIP(GzipLevel::default()))
+        .set_max_row_group_row_count(None)
+        .set_created_by(format!("profiler version {}", env!("CARGO_PKG_VERSION")))
+        .build();
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), Some(properties))?;
+    for (_, report) in reports {
+        writer.write(&record_batch(&schema, report)?)?;
+        // Closes the row group, so that the next run starts its own.
+        writer.flush()?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::report::{get_report, PerfReport};
+
+    fn report(id: Uuid) -> Report {
+        let entry = format!(
+            "<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n</log-entry>\n",
+            include_str!("../../../tests/fixtures/ubuntu/proc-stat-start.txt"),
+            include_str!("../../../tests/fixtures/ubuntu/proc-stat-end.txt")
+        );
+        let log = format!(
+            "<log id=\"{}\" platform=\"ubuntu\" duration=\"2\" interval-ms=\"1000\" perf=\"off\">\n<log-entry time-ms=\"0\">\n{}<log-entry time-ms=\"1000\">\n{}</log>\n",
+            id, entry, entry
+        );
+        let mut report = get_report(log.as_bytes(), false, None).unwrap();
+        for entry in report.entries.iter_mut() {
+            entry.proc.retain(|cpu, _| cpu == "0");
+            // A CPU perf counted that /proc/stat has no row for.
+            let mut perf = PerfReport::default();
+            perf.events.insert(CYCLES.to_string(), 1000);
+            entry.perf.insert("S0".to_string(), perf);
+        }
+        report
+    }
+
+    #[test]
+    fn writes_a_row_group_per_run_that_readers_can_read() {
+        let path = std::env::temp_dir().join(format!("profiler-{}.parquet", Uuid::new_v4()));
+        let (first, second) = (report(Uuid::new_v4()), report(Uuid::new_v4()));
+        write_parquet(
+            path.to_str().unwrap(),
+            &[("first.xml", &first), ("second.xml", &second)],
+        )
+        .unwrap();
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap();
+        let groups = builder.metadata().row_groups();
+        assert_eq!(groups.len(), 2);
+        assert!(groups.iter().all(|group| group.num_rows() == 4));
+        assert_eq!(builder.schema().fields(), schema().fields());
+        let batches: Vec<_> = builder.build().unwrap().collect::<Result<_, _>>().unwrap();
+        let column = |name: &str| {
+            batches
+                .iter()
+                .map(|batch| batch.column_by_name(name).unwrap().clone())
+                .collect::<Vec<_>>()
+        };
+        let strings = |name| {
+            column(name)
+                .iter()
+                .flat_map(|column| {
+                    let column = column.as_any().downcast_ref::<StringArray>().unwrap();
+                    column
+                        .iter()
+                        .map(|value| value.unwrap().to_string())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let ints = |name| {
+            column(name)
+                .iter()
+                .flat_map(|column| {
+                    let column = column.as_any().downcast_ref::<Int64Array>().unwrap();
+                    column.iter().collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let ids = [first.id, second.id].map(|id| vec![id.to_string(); 4]);
+        assert_eq!(strings("run_id"), ids.concat());
+        assert_eq!(strings("cpu"), ["0", "S0", "0", "S0", "0", "S0", "0", "S0"]);
+        assert_eq!(
+            ints("time_ms")[..4],
+            [Some(0), Some(0), Some(1000), Some(1000)]
+        );
+        let user = first.entries[0].proc["0"].user as i64;
+        assert_eq!(ints("user")[..2], [Some(user), None]);
+        assert_eq!(ints(CYCLES)[..2], [None, Some(1000)]);
+        let load = &column("load")[0];
+        let load = load.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(load.value(0), first.entries[0].proc["0"].load);
+        assert!(load.is_null(1));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
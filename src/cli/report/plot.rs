@@ -0,0 +1,302 @@
+use std::{error::Error, fs};
+
+use plotters::{coord::Shift, element::DashedPathElement, prelude::*};
+
+use crate::report::{Report, ReportEntry, CYCLES};
+
+const WIDTH: u32 = 960;
+const PANEL_HEIGHT: u32 = 320;
+const TITLE_HEIGHT: u32 = 40;
+/// Colors of the overlaid files, reused from the start past the tenth.
+const COLORS: [RGBColor; 10] = [
+    RGBColor(0x1f, 0x77, 0xb4),
+    RGBColor(0xd6, 0x27, 0x28),
+    RGBColor(0x2c, 0xa0, 0x2c),
+    RGBColor(0xff, 0x7f, 0x0e),
+    RGBColor(0x94, 0x67, 0xbd),
+    RGBColor(0x8c, 0x56, 0x4b),
+    RGBColor(0xe3, 0x77, 0xc2),
+    RGBColor(0x7f, 0x7f, 0x7f),
+    RGBColor(0xbc, 0xbd, 0x22),
+    RGBColor(0x17, 0xbe, 0xcf),
+];
+
+/// Roughly five round steps from zero to at least `max`.
+fn ticks(max: f64) -> Vec<f64> {
+    let max = if max > 0.0 { max } else { 1.0 };
+    let rough = max / 5.0;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 2.5, 5.0, 10.0]
+        .iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| *step >= rough)
+        .unwrap();
+    let count = (max / step).ceil() as usize;
+    (0..=count).map(|tick| tick as f64 * step).collect()
+}
+
+/// Trims the zeros a tick step leaves, e.g. `2.50` to `2.5`.
+fn tick_label(value: f64) -> String {
+    let text = format!("{:.2}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+type DrawResult<DB> = Result<(), DrawingAreaErrorKind<<DB as DrawingBackend>::ErrorType>>;
+
+/// One panel of load on the left axis and cycles per second on the right
+/// for `cpu`, each file in its own color.
+fn panel<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    reports: &[(&str, &Report)],
+    cpu: &str,
+    run: f64,
+) -> DrawResult<DB> {
+    // Cycles per second, in billions, as perf counts per interval.
+    let rate = |report: &Report, cycles: u64| cycles as f64 / report.interval.as_secs_f64() / 1e9;
+    let max_rate = reports
+        .iter()
+        .flat_map(|(_, report)| {
+            report
+                .entries
+                .iter()
+                .filter_map(move |entry| Some(rate(report, entry.perf.get(cpu)?.get(CYCLES)?)))
+        })
+        .fold(0.0, f64::max);
+    let time_ticks = ticks(run);
+    let rate_ticks = ticks(max_rate);
+    let end = *time_ticks.last().unwrap();
+
+    let mut chart = ChartBuilder::on(area)
+        .caption(
+            format!("CPU {}", cpu),
+            ("sans-serif", 16).into_font().style(FontStyle::Bold),
+        )
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .right_y_label_area_size(70)
+        .build_cartesian_2d(0.0..end, 0.0..100.0)?
+        .set_secondary_coord(0.0..end, 0.0..*rate_ticks.last().unwrap());
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .light_line_style(WHITE)
+        .label_style(("sans-serif", 14))
+        .axis_desc_style(("sans-serif", 14))
+        .x_labels(time_ticks.len())
+        .x_label_formatter(&|seconds| format!("{}s", tick_label(*seconds)))
+        .y_labels(6)
+        .y_label_formatter(&|load| format!("{}%", tick_label(*load)))
+        .y_desc("load")
+        .draw()?;
+    chart
+        .configure_secondary_axes()
+        .label_style(("sans-serif", 14))
+        .axis_desc_style(("sans-serif", 14))
+        .y_labels(rate_ticks.len())
+        .y_label_formatter(&|rate| tick_label(*rate))
+        .y_desc("billion cycles/s")
+        .draw()?;
+
+    for (index, (filename, report)) in reports.iter().enumerate() {
+        let color = COLORS[index % COLORS.len()];
+        // Each point sits in the middle of its interval.
+        let middle = |entry: &ReportEntry| (entry.time + report.interval / 2).as_secs_f64();
+        let loads: Vec<_> = report
+            .entries
+            .iter()
+            .filter_map(|entry| Some((middle(entry), entry.proc.get(cpu)?.load)))
+            .collect();
+        let rates: Vec<_> = report
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                let cycles = entry.perf.get(cpu)?.get(CYCLES)?;
+                Some((middle(entry), rate(report, cycles)))
+            })
+            .collect();
+        chart
+            .draw_series(LineSeries::new(loads, color.stroke_width(2)))?
+            .label(format!("{}: load", filename))
+            .legend(move |(x, y)| PathElement::new([(x, y), (x + 20, y)], color.stroke_width(2)));
+        chart
+            .draw_secondary_series(DashedLineSeries::new(rates, 6, 3, color.stroke_width(2)))?
+            .label(format!("{}: cycles", filename))
+            .legend(move |(x, y)| {
+                DashedPathElement::new([(x, y), (x + 20, y)], 6, 3, color.stroke_width(2))
+            });
+        // A dotted line down the panel with its label alongside.
+        for marker in report.markers.iter() {
+            let seconds = marker.time.as_secs_f64();
+            if seconds > end {
+                continue;
+            }
+            chart.draw_series(DashedLineSeries::new(
+                [(seconds, 0.0), (seconds, 100.0)],
+                2,
+                2,
+                color.into(),
+            ))?;
+            chart.draw_series([Text::new(
+                marker.label.clone(),
+                (seconds, 97.0),
+                ("sans-serif", 10).into_font().color(&color),
+            )])?;
+        }
+    }
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperRight)
+        .label_font(("sans-serif", 14))
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK.mix(0.4))
+        .draw()
+}
+
+/// The whole chart: a title naming the reports and a panel per CPU.
+fn draw<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    reports: &[(&str, &Report)],
+    cpus: &[String],
+) -> DrawResult<DB> {
+    root.fill(&WHITE)?;
+    let titles: Vec<_> = reports
+        .iter()
+        .map(|(_, report)| {
+            let duration = report.duration_actual.unwrap_or(report.duration);
+            format!("{} ({:.1}s)", report.id, duration.as_secs_f64())
+        })
+        .collect();
+    let (title, panels) = root.split_vertically(TITLE_HEIGHT);
+    title.draw_text(
+        &titles.join(" vs "),
+        &("sans-serif", 20).into_font().style(FontStyle::Bold).into(),
+        (10, 12),
+    )?;
+    let run = reports
+        .iter()
+        .filter_map(|(_, report)| {
+            Some((report.entries.last()?.time + report.interval).as_secs_f64())
+        })
+        .fold(0.0, f64::max);
+    for (cpu, area) in cpus.iter().zip(panels.split_evenly((cpus.len().max(1), 1))) {
+        panel(&area, reports, cpu, run)?;
+    }
+    root.present()
+}
+
+fn size(cpus: &[String]) -> (u32, u32) {
+    (WIDTH, TITLE_HEIGHT + PANEL_HEIGHT * cpus.len() as u32)
+}
+
+/// An SVG chart of the load and cycles of each of `cpus` over time, a panel
+/// per CPU with every report overlaid in its own color.
+pub fn render_svg(reports: &[(&str, &Report)], cpus: &[String]) -> String {
+    let mut svg = String::new();
+    draw(
+        &SVGBackend::with_string(&mut svg, size(cpus)).into_drawing_area(),
+        reports,
+        cpus,
+    )
+    .unwrap();
+    svg
+}
+
+/// Writes the chart as a PNG if `path` ends in `.png` and the `png` feature
+/// is on, as an SVG otherwise.
+pub fn write_plot(
+    path: &str,
+    reports: &[(&str, &Report)],
+    cpus: &[String],
+) -> Result<(), Box<dyn Error>> {
+    #[cfg(feature = "png")]
+    if path.ends_with(".png") {
+        let root = BitMapBackend::new(path, size(cpus)).into_drawing_area();
+        return Ok(draw(&root, reports, cpus)?);
+    }
+    fs::write(path, render_svg(reports, cpus))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::report::{get_report, PerfReport};
+
+    fn report(id: Uuid, entries: u64, cycles: u64) -> Report {
+        let entry = format!(
+            "<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n</log-entry>\n",
+            include_str!("../../../tests/fixtures/ubuntu/proc-stat-start.txt"),
+            include_str!("../../../tests/fixtures/ubuntu/proc-stat-end.txt")
+        );
+        let entries: String = (0..entries)
+            .map(|index| format!("<log-entry time-ms=\"{}\">\n{}", index * 1000, entry))
+            .collect();
+        let log = format!(
+            "<log id=\"{}\" platform=\"ubuntu\" duration=\"4\" interval-ms=\"1000\" perf=\"off\">\n{}</log>\n",
+            id, entries
+        );
+        let mut report = get_report(log.as_bytes(), false, None).unwrap();
+        for entry in report.entries.iter_mut() {
+            let mut perf = PerfReport::default();
+            perf.events.insert(CYCLES.to_string(), cycles);
+            entry.perf.insert("all".to_string(), perf);
+        }
+        report
+    }
+
+    /// The text of every `<text>` element, in order.
+    fn texts(svg: &str) -> Vec<&str> {
+        svg.split("<text")
+            .skip(1)
+            .map(|text| {
+                let text = &text[text.find('>').unwrap() + 1..];
+                text[..text.find("</text>").unwrap()].trim()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn overlays_reports_with_a_title_legend_and_both_axes() {
+        let (first, second) = (
+            report(Uuid::new_v4(), 4, 2_000_000_000),
+            report(Uuid::new_v4(), 2, 1_000_000_000),
+        );
+        let cpus = ["all".to_string()];
+        let svg = render_svg(&[("first.xml", &first), ("second.xml", &second)], &cpus);
+        let texts = texts(&svg);
+
+        let title = format!("{} (4.0s) vs {} (4.0s)", first.id, second.id);
+        assert_eq!(texts[0], title);
+        assert!(texts.contains(&"CPU all"));
+        for label in [
+            "first.xml: load",
+            "first.xml: cycles",
+            "second.xml: load",
+            "second.xml: cycles",
+        ] {
+            assert!(texts.contains(&label), "no {} in {:?}", label, texts);
+        }
+        // Time below, load on the left and billions of cycles on the right,
+        // both ending at a round tick above the highest value.
+        for label in ["load", "billion cycles/s", "0s", "4s", "0%", "100%", "2"] {
+            assert!(texts.contains(&label), "no {} in {:?}", label, texts);
+        }
+        assert!(!texts.contains(&"5s"));
+        // Each file in its own color.
+        for color in &COLORS[..2] {
+            let stroke = format!("stroke=\"#{:02X}{:02X}{:02X}\"", color.0, color.1, color.2);
+            assert!(svg.contains(&stroke), "no {} in {}", stroke, svg);
+        }
+    }
+
+    #[test]
+    fn picks_round_ticks() {
+        let labels = |max| ticks(max).into_iter().map(tick_label).collect::<Vec<_>>();
+        assert_eq!(labels(100.0), ["0", "20", "40", "60", "80", "100"]);
+        assert_eq!(labels(2.3), ["0", "0.5", "1", "1.5", "2", "2.5"]);
+        assert_eq!(labels(0.0), ["0", "0.2", "0.4", "0.6", "0.8", "1"]);
+    }
+}
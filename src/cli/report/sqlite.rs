@@ -0,0 +1,237 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, Transaction};
+
+use crate::report::{compare_cpus, Report};
+
+/// Created if missing, so that the database can be appended to run after
+/// run. Every table but `runs` is keyed by the run's id and the entry's
+/// index within it.
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS runs (
+    id TEXT PRIMARY KEY,
+    file TEXT NOT NULL,
+    platform TEXT NOT NULL,
+    hostname TEXT,
+    uname TEXT,
+    cpu_model TEXT,
+    device_model TEXT,
+    logical_cpus INTEGER,
+    physical_cpus INTEGER,
+    start_ms INTEGER,
+    interval_ms INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    duration_actual_ms INTEGER
+);
+CREATE TABLE IF NOT EXISTS entries (
+    run_id TEXT NOT NULL REFERENCES runs (id),
+    entry INTEGER NOT NULL,
+    time_ms INTEGER NOT NULL,
+    wall_time_ms INTEGER,
+    PRIMARY KEY (run_id, entry)
+);
+CREATE TABLE IF NOT EXISTS proc_samples (
+    run_id TEXT NOT NULL REFERENCES runs (id),
+    entry INTEGER NOT NULL,
+    cpu TEXT NOT NULL,
+    load REAL,
+    user INTEGER NOT NULL,
+    nice INTEGER NOT NULL,
+    system INTEGER NOT NULL,
+    idle INTEGER NOT NULL,
+    iowait INTEGER NOT NULL,
+    irq INTEGER NOT NULL,
+    softirq INTEGER NOT NULL,
+    total INTEGER NOT NULL,
+    PRIMARY KEY (run_id, entry, cpu)
+);
+CREATE TABLE IF NOT EXISTS perf_samples (
+    run_id TEXT NOT NULL REFERENCES runs (id),
+    entry INTEGER NOT NULL,
+    cpu TEXT NOT NULL,
+    event TEXT NOT NULL,
+    value INTEGER NOT NULL,
+    PRIMARY KEY (run_id, entry, cpu, event)
+);
+";
+
+const TABLES: [&str; 4] = ["perf_samples", "proc_samples", "entries", "runs"];
+
+fn millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+/// Replaces the rows of `report`'s run with its own.
+fn insert_run(transaction: &Transaction, filename: &str, report: &Report) -> rusqlite::Result<()> {
+    let id = report.id.to_string();
+    for table in TABLES {
+        let key = if table == "runs" { "id" } else { "run_id" };
+        transaction.execute(&format!("DELETE FROM {} WHERE {} = ?1", table, key), [&id])?;
+    }
+    let metadata = &report.metadata;
+    transaction.execute(
+        "INSERT INTO runs VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        params![
+            id,
+            filename,
+            report.platform.name(),
+            metadata.hostname,
+            metadata.uname,
+            metadata.cpu_model,
+            metadata.device_model,
+            metadata.logical_cpus,
+            metadata.physical_cpus,
+            report.start.map(millis),
+            report.interval.as_millis() as i64,
+            report.duration.as_millis() as i64,
+            report
+                .duration_actual
+                .map(|duration| duration.as_millis() as i64),
+        ],
+    )?;
+    let mut entries = transaction.prepare_cached("INSERT INTO entries VALUES (?1, ?2, ?3, ?4)")?;
+    let mut proc_samples = transaction.prepare_cached(
+        "INSERT INTO proc_samples VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+    )?;
+    let mut perf_samples =
+        transaction.prepare_cached("INSERT INTO perf_samples VALUES (?1, ?2, ?3, ?4, ?5)")?;
+    for (index, entry) in report.entries.iter().enumerate() {
+        entries.execute(params![
+            id,
+            index,
+            entry.time.as_millis() as i64,
+            entry.wall_time.map(millis)
+        ])?;
+        let mut cpus: Vec<_> = entry.proc.keys().collect();
+        cpus.sort_by(|a, b| compare_cpus(a, b));
+        for cpu in cpus {
+            let proc = &entry.proc[cpu];
+            proc_samples.execute(params![
+                id,
+                index,
+                cpu,
+                proc.load.is_finite().then_some(proc.load),
+                proc.user,
+                proc.nice,
+                proc.system,
+                proc.idle,
+                proc.iowait,
+                proc.irq,
+                proc.softirq,
+                proc.total,
+            ])?;
+        }
+        let mut cpus: Vec<_> = entry.perf.keys().collect();
+        cpus.sort_by(|a, b| compare_cpus(a, b));
+        for cpu in cpus {
+            let mut events: Vec<_> = entry.perf[cpu].events.iter().collect();
+            events.sort();
+            for (event, value) in events {
+                perf_samples.execute(params![id, index, cpu, event, value])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Creates the database at `path` if missing and adds the runs to it in one
+/// transaction, replacing, with a warning, those it already holds.
+pub fn write_sqlite(path: &str, reports: &[(&str, &Report)]) -> rusqlite::Result<()> {
+    let mut connection = Connection::open(path)?;
+    connection.execute_batch(SCHEMA)?;
+    let transaction = connection.transaction()?;
+    for (filename, report) in reports {
+        let existing: bool = transaction.query_row(
+            "SELECT EXISTS (SELECT 1 FROM runs WHERE id = ?1)",
+            [report.id.to_string()],
+            |row| row.get(0),
+        )?;
+        if existing {
+            eprintln!(
+                "{}: run {} is already in {}, replacing it",
+                filename, report.id, path
+            );
+        }
+        insert_run(&transaction, filename, report)?;
+    }
+    transaction.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::report::{get_report, PerfReport, CYCLES};
+
+    fn report(id: Uuid) -> Report {
+        let entry = format!(
+            "<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n</log-entry>\n",
+            include_str!("../../../tests/fixtures/ubuntu/proc-stat-start.txt"),
+            include_str!("../../../tests/fixtures/ubuntu/proc-stat-end.txt")
+        );
+        let log = format!(
+            "<log id=\"{}\" platform=\"ubuntu\" duration=\"2\" interval-ms=\"1000\" perf=\"off\">\n<log-entry time-ms=\"0\">\n{}<log-entry time-ms=\"1000\">\n{}</log>\n",
+            id, entry, entry
+        );
+        let mut report = get_report(log.as_bytes(), false, None).unwrap();
+        for entry in report.entries.iter_mut() {
+            let mut perf = PerfReport::default();
+            perf.events.insert(CYCLES.to_string(), 1000);
+            entry.perf.insert("0".to_string(), perf);
+        }
+        report
+    }
+
+    fn counts(path: &str) -> Vec<i64> {
+        let connection = Connection::open(path).unwrap();
+        TABLES
+            .iter()
+            .map(|table| {
+                connection
+                    .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                        row.get(0)
+                    })
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn imports_the_same_runs_again_in_place() {
+        let path = env::temp_dir().join(format!("profiler-{}.db", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        let (first, second) = (report(Uuid::new_v4()), report(Uuid::new_v4()));
+        let cpus = first.entries[0].proc.len() as i64;
+        let runs = [("first.xml", &first), ("second.xml", &second)];
+
+        write_sqlite(path, &runs).unwrap();
+        // Perf, proc, entries and runs, as in `TABLES`.
+        assert_eq!(counts(path), [2 * 2, 2 * 2 * cpus, 2 * 2, 2]);
+        write_sqlite(path, &runs).unwrap();
+        write_sqlite(path, &runs[..1]).unwrap();
+        assert_eq!(counts(path), [2 * 2, 2 * 2 * cpus, 2 * 2, 2]);
+
+        let connection = Connection::open(path).unwrap();
+        let (file, interval): (String, i64) = connection
+            .query_row(
+                "SELECT file, interval_ms FROM runs WHERE id = ?1",
+                [first.id.to_string()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((file.as_str(), interval), ("first.xml", 1000));
+        let cycles: i64 = connection
+            .query_row(
+                "SELECT SUM(value) FROM perf_samples WHERE run_id = ?1 AND event = ?2",
+                params![second.id.to_string(), CYCLES],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(cycles, 2000);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,428 @@
+use std::{
+    collections::HashSet,
+    io::{self, stdin, stdout},
+};
+
+use ratatui::{
+    backend::TermionBackend,
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    symbols::Marker,
+    text::Line,
+    widgets::{Axis, Chart, Dataset, GraphType, Paragraph, Row, Table},
+    Frame, Terminal,
+};
+use termion::{event::Key, input::TermRead, raw::IntoRawMode, screen::IntoAlternateScreen};
+
+use crate::{
+    report::{get_wall_time, stats::Stats, Report},
+    timestamp,
+};
+
+use super::TopMetric;
+
+const HELP: &str = "←/→ interval  PgUp/PgDn ×10  Home/End  ↑/↓ CPU  space toggle  a all  l/c/s load/cycles/switches  m max  [ ] window  r reset  q quit";
+/// Colors of the charted CPUs, reused from the start past the last.
+const COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Yellow,
+    Color::Green,
+    Color::Magenta,
+    Color::Blue,
+    Color::Red,
+];
+
+/// What the viewer shows: the interval under the cursor, the window of
+/// intervals the statistics and chart cover and the CPUs toggled off.
+struct View<'a> {
+    report: &'a Report,
+    metric: TopMetric,
+    cursor: usize,
+    window: (usize, usize),
+    selected: usize,
+    hidden: HashSet<String>,
+}
+
+impl<'a> View<'a> {
+    /// The whole of `report` by load, `None` if it has no intervals.
+    fn new(report: &'a Report) -> Option<Self> {
+        let last = report.entries.len().checked_sub(1)?;
+        Some(View {
+            report,
+            metric: TopMetric::Load,
+            cursor: 0,
+            window: (0, last),
+            selected: 0,
+            hidden: HashSet::new(),
+        })
+    }
+
+    fn cpus(&self) -> &'a [String] {
+        match self.metric {
+            TopMetric::Load => &self.report.proc_cpus,
+            _ => &self.report.perf_cpus,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self.metric {
+            TopMetric::Load => "load",
+            TopMetric::Cycles => "CPU cycles",
+            TopMetric::ContextSwitches => "context switches",
+        }
+    }
+
+    /// The values of `cpu` over the window, `None` where it has none.
+    fn samples(&self, cpu: &str) -> Vec<Option<f64>> {
+        self.report.entries[self.window.0..=self.window.1]
+            .iter()
+            .map(|entry| {
+                self.metric
+                    .value(entry, cpu)
+                    .filter(|value| value.is_finite())
+            })
+            .collect()
+    }
+
+    fn move_cursor(&mut self, by: isize) {
+        self.cursor = self
+            .cursor
+            .saturating_add_signed(by)
+            .clamp(self.window.0, self.window.1);
+    }
+
+    /// The interval of the window where "all" peaks.
+    fn jump_to_max(&mut self) {
+        let peak = self
+            .samples("all")
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, value)| Some((index, value?)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+        if let Some((index, _)) = peak {
+            self.cursor = self.window.0 + index;
+        }
+    }
+
+    /// Handles `key`, returning false to quit.
+    fn handle(&mut self, key: Key) -> bool {
+        let last = self.report.entries.len() - 1;
+        match key {
+            Key::Char('q') | Key::Esc | Key::Ctrl('c') => return false,
+            Key::Left => self.move_cursor(-1),
+            Key::Right => self.move_cursor(1),
+            Key::PageUp => self.move_cursor(-10),
+            Key::PageDown => self.move_cursor(10),
+            Key::Home => self.cursor = self.window.0,
+            Key::End => self.cursor = self.window.1,
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => {
+                self.selected = (self.selected + 1).min(self.cpus().len().saturating_sub(1))
+            }
+            Key::Char(' ') => {
+                if let Some(cpu) = self.cpus().get(self.selected).cloned() {
+                    if !self.hidden.remove(&cpu) {
+                        self.hidden.insert(cpu);
+                    }
+                }
+            }
+            Key::Char('a') => self.hidden.clear(),
+            Key::Char('l') => self.metric = TopMetric::Load,
+            Key::Char('c') => self.metric = TopMetric::Cycles,
+            Key::Char('s') => self.metric = TopMetric::ContextSwitches,
+            Key::Char('m') => self.jump_to_max(),
+            Key::Char('[') => self.window = (self.cursor, self.window.1),
+            Key::Char(']') => self.window = (self.window.0, self.cursor),
+            Key::Char('r') => self.window = (0, last),
+            _ => {}
+        }
+        self.selected = self.selected.min(self.cpus().len().saturating_sub(1));
+        true
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let report = self.report;
+        let entry = &report.entries[self.cursor];
+        let cpus = self.cpus();
+        // The table gives way to the chart past half the screen.
+        let rows = (cpus.len() as u16 + 2).min(frame.area().height / 2);
+        let [header, table, chart, help] = Layout::vertical([
+            Constraint::Length(4),
+            Constraint::Length(rows),
+            Constraint::Min(8),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let wall_time = get_wall_time(report, entry)
+            .map(|time| format!(", {}", timestamp::format(time)))
+            .unwrap_or_default();
+        let bold = Style::new().add_modifier(Modifier::BOLD);
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::styled(format!("Report {} — {}", report.id, self.title()), bold),
+                Line::raw(format!(
+                    "Interval {} of {} at {:.1}s{}",
+                    self.cursor + 1,
+                    report.entries.len(),
+                    entry.time.as_secs_f64(),
+                    wall_time
+                )),
+                Line::raw(format!(
+                    "Window {:.1}s to {:.1}s, {} intervals",
+                    report.entries[self.window.0].time.as_secs_f64(),
+                    (report.entries[self.window.1].time + report.interval).as_secs_f64(),
+                    self.window.1 - self.window.0 + 1
+                )),
+            ]),
+            header,
+        );
+
+        let number =
+            |value: Option<f64>| value.map_or("-".to_string(), |value| self.metric.format(value));
+        let rows = cpus.iter().enumerate().map(|(index, cpu)| {
+            let samples: Vec<_> = self.samples(cpu).into_iter().flatten().collect();
+            let stats = Stats::from_samples(&samples);
+            let shown = if self.hidden.contains(cpu) {
+                "[ ]"
+            } else {
+                "[x]"
+            };
+            let row = Row::new([
+                shown.to_string(),
+                cpu.clone(),
+                number(self.metric.value(entry, cpu)),
+                number(stats.map(|stats| stats.mean)),
+                number(stats.map(|stats| stats.min)),
+                number(stats.map(|stats| stats.max)),
+                number(stats.map(|stats| stats.p95)),
+            ]);
+            if index == self.selected {
+                row.style(Style::new().add_modifier(Modifier::REVERSED))
+            } else {
+                row
+            }
+        });
+        let label = cpus.iter().map(|cpu| cpu.len()).max().unwrap_or(3).max(3) as u16;
+        let mut widths = vec![Constraint::Length(3), Constraint::Length(label)];
+        widths.extend([Constraint::Length(10); 5]);
+        frame.render_widget(
+            Table::new(rows, widths)
+                .header(Row::new(["", "CPU", "now", "mean", "min", "max", "p95"]).style(bold)),
+            table,
+        );
+
+        // A line per CPU shown over the window and one up the cursor. Only
+        // load has a fixed scale, the counters are scaled to the window's
+        // peak.
+        let shown: Vec<_> = cpus
+            .iter()
+            .enumerate()
+            .filter(|(_, cpu)| !self.hidden.contains(*cpu))
+            .map(|(index, cpu)| {
+                let points: Vec<_> = report.entries[self.window.0..=self.window.1]
+                    .iter()
+                    .zip(self.samples(cpu))
+                    .filter_map(|(entry, value)| Some((entry.time.as_secs_f64(), value?)))
+                    .collect();
+                (cpu, COLORS[index % COLORS.len()], points)
+            })
+            .collect();
+        let top = match self.metric {
+            TopMetric::Load => 100.0,
+            _ => shown
+                .iter()
+                .flat_map(|(_, _, points)| points.iter().map(|(_, value)| *value))
+                .fold(0.0, f64::max)
+                .max(1.0),
+        };
+        let at = entry.time.as_secs_f64();
+        let cursor = [(at, 0.0), (at, top)];
+        let mut datasets: Vec<_> = shown
+            .iter()
+            .map(|(cpu, color, points)| {
+                Dataset::default()
+                    .name(cpu.as_str())
+                    .marker(Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(Style::new().fg(*color))
+                    .data(points)
+            })
+            .collect();
+        datasets.push(
+            Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::new().fg(Color::DarkGray))
+                .data(&cursor),
+        );
+        let (start, end) = (
+            report.entries[self.window.0].time.as_secs_f64(),
+            (report.entries[self.window.1].time + report.interval).as_secs_f64(),
+        );
+        frame.render_widget(
+            Chart::new(datasets)
+                .x_axis(
+                    Axis::default()
+                        .bounds([start, end])
+                        .labels([format!("{:.1}s", start), format!("{:.1}s", end)]),
+                )
+                .y_axis(
+                    Axis::default()
+                        .title(self.title())
+                        .bounds([0.0, top])
+                        .labels([self.metric.format(0.0), self.metric.format(top)]),
+                ),
+            chart,
+        );
+
+        frame.render_widget(
+            Paragraph::new(HELP).style(Style::new().add_modifier(Modifier::DIM)),
+            help,
+        );
+    }
+}
+
+/// Shows `report` full screen until `q`, for browsing its intervals by key.
+pub fn run(report: &Report) -> io::Result<()> {
+    let Some(mut view) = View::new(report) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no intervals to browse",
+        ));
+    };
+    let screen = stdout().into_raw_mode()?.into_alternate_screen()?;
+    let mut terminal = Terminal::new(TermionBackend::new(screen))?;
+    terminal.hide_cursor()?;
+    terminal.draw(|frame| view.draw(frame))?;
+    for key in stdin().keys() {
+        if !view.handle(key?) {
+            break;
+        }
+        terminal.draw(|frame| view.draw(frame))?;
+    }
+    terminal.show_cursor()
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::backend::TestBackend;
+
+    use super::*;
+    use crate::report::{get_report, PerfReport, CYCLES};
+
+    /// Four intervals with "all" at 10, 40, 30 and 20 billion cycles.
+    fn report() -> Report {
+        let entry = format!(
+            "<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n</log-entry>\n",
+            include_str!("../../../tests/fixtures/ubuntu/proc-stat-start.txt"),
+            include_str!("../../../tests/fixtures/ubuntu/proc-stat-end.txt")
+        );
+        let entries: String = (0..4)
+            .map(|index| format!("<log-entry time-ms=\"{}\">\n{}", index * 1000, entry))
+            .collect();
+        let log = format!(
+            "<log id=\"{}\" platform=\"ubuntu\" duration=\"4\" interval-ms=\"1000\" perf=\"off\">\n{}</log>\n",
+            uuid::Uuid::new_v4(),
+            entries
+        );
+        let mut report = get_report(log.as_bytes(), false, None).unwrap();
+        for (entry, cycles) in report.entries.iter_mut().zip([10, 40, 30, 20]) {
+            for cpu in ["all", "0"] {
+                let mut perf = PerfReport::default();
+                perf.events
+                    .insert(CYCLES.to_string(), cycles * 1_000_000_000);
+                entry.perf.insert(cpu.to_string(), perf);
+            }
+        }
+        report.perf_cpus = vec!["all".to_string(), "0".to_string()];
+        report
+    }
+
+    #[test]
+    fn refuses_a_report_without_intervals() {
+        let mut report = report();
+        report.entries.clear();
+        assert!(View::new(&report).is_none());
+        assert_eq!(run(&report).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn narrows_and_resets_the_window() {
+        let report = report();
+        let mut view = View::new(&report).unwrap();
+        assert_eq!(view.window, (0, 3));
+        view.handle(Key::Right);
+        assert!(view.handle(Key::Char('[')));
+        assert_eq!(view.window, (1, 3));
+        view.handle(Key::Right);
+        view.handle(Key::Char(']'));
+        assert_eq!(view.window, (1, 2));
+        // The cursor stays inside the window.
+        view.handle(Key::PageDown);
+        assert_eq!(view.cursor, 2);
+        view.handle(Key::Home);
+        assert_eq!(view.cursor, 1);
+        view.handle(Key::Char('r'));
+        assert_eq!(view.window, (0, 3));
+        assert!(!view.handle(Key::Char('q')));
+    }
+
+    #[test]
+    fn jumps_to_the_peak_of_the_window() {
+        let report = report();
+        let mut view = View::new(&report).unwrap();
+        view.handle(Key::Char('c'));
+        assert_eq!(view.title(), "CPU cycles");
+        view.handle(Key::Char('m'));
+        assert_eq!(view.cursor, 1);
+        // In a window past the peak, the highest interval of the window.
+        view.handle(Key::End);
+        view.handle(Key::Left);
+        view.handle(Key::Char('['));
+        view.handle(Key::Char('m'));
+        assert_eq!((view.window, view.cursor), ((2, 3), 2));
+    }
+
+    #[test]
+    fn toggles_the_selected_cpu() {
+        let report = report();
+        let mut view = View::new(&report).unwrap();
+        view.handle(Key::Char('c'));
+        view.handle(Key::Down);
+        view.handle(Key::Down);
+        assert_eq!(view.selected, 1);
+        view.handle(Key::Char(' '));
+        assert_eq!(view.hidden, HashSet::from(["0".to_string()]));
+        view.handle(Key::Up);
+        view.handle(Key::Char(' '));
+        assert_eq!(view.hidden.len(), 2);
+        view.handle(Key::Char(' '));
+        assert_eq!(view.hidden, HashSet::from(["0".to_string()]));
+        view.handle(Key::Char('a'));
+        assert!(view.hidden.is_empty());
+    }
+
+    #[test]
+    fn draws_the_header_table_and_chart() {
+        let report = report();
+        let mut view = View::new(&report).unwrap();
+        view.handle(Key::Char('c'));
+        let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+        terminal.draw(|frame| view.draw(frame)).unwrap();
+        let screen: Vec<String> = terminal
+            .backend()
+            .buffer()
+            .content
+            .chunks(100)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect())
+            .collect();
+        assert!(screen[0].starts_with(&format!("Report {} — CPU cycles", report.id)));
+        assert!(screen[1].starts_with("Interval 1 of 4 at 0.0s"));
+        assert!(screen[2].starts_with("Window 0.0s to 4.0s, 4 intervals"));
+        assert!(screen[4].contains("CPU") && screen[4].contains("p95"));
+        assert!(screen[5].starts_with("[x] all"));
+        assert!(screen.iter().any(|row| row.contains("4.0s")));
+        assert!(screen[29].starts_with("←/→ interval"));
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, stdout, BufReader, IsTerminal, Read},
+    process::exit,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use colored::Colorize;
+use signal_hook::consts::{SIGINT, SIGTERM};
+
+use crate::report::{compare_cpus, read_report, ParseError, ReportEntry, CONTEXT_SWITCHES, CYCLES};
+
+use super::{format_number, print_string_collection};
+
+/// How often to look for more of the log once its end is reached.
+const POLL: Duration = Duration::from_millis(200);
+
+/// A file read as it grows, like `tail -f`: reads at its end wait for more
+/// to be written, until `stop` is set.
+struct Follow {
+    file: File,
+    stop: Arc<AtomicBool>,
+}
+
+impl Read for Follow {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.file.read(buf)?;
+            if read > 0 || self.stop.load(Ordering::SeqCst) {
+                return Ok(read);
+            }
+            sleep(POLL);
+        }
+    }
+}
+
+/// The running average of a metric of a CPU and its value in the last
+/// interval.
+#[derive(Default)]
+struct Running {
+    sum: f64,
+    count: usize,
+    last: Option<f64>,
+}
+
+#[derive(Default)]
+struct Summary {
+    entries: usize,
+    /// When the last entry started, from the start of the run.
+    last: Duration,
+    load: HashMap<String, Running>,
+    cycles: HashMap<String, Running>,
+    context_switches: HashMap<String, Running>,
+}
+
+fn add(metric: &mut HashMap<String, Running>, values: Vec<(&String, f64)>) {
+    for running in metric.values_mut() {
+        running.last = None;
+    }
+    for (cpu, value) in values {
+        let running = metric.entry(cpu.clone()).or_default();
+        running.sum += value;
+        running.count += 1;
+        running.last = Some(value);
+    }
+}
+
+impl Summary {
+    fn add(&mut self, entry: &ReportEntry) {
+        self.entries += 1;
+        self.last = entry.time;
+        add(
+            &mut self.load,
+            entry
+                .proc
+                .iter()
+                .map(|(cpu, proc)| (cpu, proc.load))
+                .filter(|(_, load)| load.is_finite())
+                .collect(),
+        );
+        for (metric, event) in [
+            (&mut self.cycles, CYCLES),
+            (&mut self.context_switches, CONTEXT_SWITCHES),
+        ] {
+            add(
+                metric,
+                entry
+                    .perf
+                    .iter()
+                    .filter_map(|(cpu, perf)| Some((cpu, perf.get(event)? as f64)))
+                    .collect(),
+            );
+        }
+    }
+}
+
+fn print_metric(title: &str, metric: &HashMap<String, Running>, unit: &str) {
+    if metric.is_empty() {
+        return;
+    }
+    println!("{}", title.bold().yellow());
+    let mut cpus: Vec<_> = metric.keys().collect();
+    cpus.sort_by(|a, b| compare_cpus(a, b));
+    let cells: Vec<_> = cpus
+        .into_iter()
+        .map(|cpu| {
+            let running = &metric[cpu];
+            let last = running.last.map_or("-".to_string(), |last| {
+                format!("{}{}", format_number(last), unit)
+            });
+            (
+                cpu.as_str().bold().yellow().to_string(),
+                format!(
+                    "{}{} / {}",
+                    format_number(running.sum / running.count as f64),
+                    unit,
+                    last
+                ),
+            )
+        })
+        .collect();
+    print_string_collection(&cells);
+}
+
+fn print_summary(filename: &str, summary: &Summary, status: &str) {
+    if stdout().is_terminal() {
+        print!("{}{}", termion::clear::All, termion::cursor::Goto(1, 1));
+    } else {
+        println!();
+    }
+    println!("{:20} {}", "File ".bold().yellow(), filename);
+    println!("{:20} {}", "Status ".bold().yellow(), status);
+    println!(
+        "{:20} {}, the last at {:.1}s",
+        "Entries ".bold().yellow(),
+        summary.entries,
+        summary.last.as_secs_f64()
+    );
+    print_metric("Per CPU load, average / last interval", &summary.load, "%");
+    print_metric(
+        "Per CPU cycles, average / last interval",
+        &summary.cycles,
+        "",
+    );
+    print_metric(
+        "Per CPU context switches, average / last interval",
+        &summary.context_switches,
+        "",
+    );
+}
+
+/// Follows the log being written to `filename`, refreshing a summary of its
+/// entries so far every `refresh`, until the log is closed or on Ctrl-C.
+pub fn watch(filename: &str, refresh: Duration, lenient: bool) {
+    if filename.ends_with(".gz") {
+        eprintln!("{}: --watch can't follow a gzipped log", filename);
+        exit(1);
+    }
+    let file = File::open(filename).unwrap_or_else(|error| {
+        eprintln!("{}: {}", filename, error);
+        exit(1);
+    });
+    let stop = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM] {
+        signal_hook::flag::register(signal, stop.clone()).unwrap();
+    }
+    let input = BufReader::new(Follow {
+        file,
+        stop: stop.clone(),
+    });
+
+    let mut summary = Summary::default();
+    let mut printed: Option<Instant> = None;
+    let result = read_report(input, lenient, None, |entry| {
+        summary.add(entry);
+        if printed.is_none_or(|printed| printed.elapsed() >= refresh) {
+            print_summary(filename, &summary, "capturing");
+            printed = Some(Instant::now());
+        }
+    });
+    match result {
+        Ok(report) => {
+            let status = if !report.truncated {
+                "log closed"
+            } else if stop.load(Ordering::SeqCst) {
+                "interrupted, the log is still open"
+            } else {
+                "log cut off"
+            };
+            print_summary(filename, &summary, status);
+        }
+        Err(ParseError::NoEntries) if stop.load(Ordering::SeqCst) => {
+            eprintln!("{}: interrupted before the first entry", filename);
+        }
+        Err(error) => {
+            eprintln!("{}: {}", filename, error);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use std::{
+    collections::HashMap,
+    fs::{metadata, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    os::unix::process::CommandExt,
+    process::{exit, Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, sleep},
+    time::{Duration, Instant},
+};
+
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::Signals,
+};
+use uuid::Uuid;
+
+use crate::{
+    log::{write_log_end, write_log_header, write_proc_entry, Config},
+    platform::Platform,
+    schema::Element,
+};
+
+use super::{parse_duration, profile::quote_command};
+
+const FETCH_ATTEMPTS: u32 = 5;
+const FETCH_RETRY: Duration = Duration::from_secs(2);
+
+/// ssh to `host` as the user's agent and config allow, never prompting for
+/// a password.
+fn ssh(host: &str) -> Command {
+    let mut ssh = Command::new("ssh");
+    ssh.args(["-o", "BatchMode=yes", host]);
+    ssh
+}
+
+/// A capture on an ssh host by a copy of the profiler there, along with
+/// where its log and pid go on the host.
+#[derive(Clone)]
+pub struct Host {
+    host: String,
+    binary: String,
+    log: String,
+    pid_file: String,
+}
+
+impl Host {
+    /// Named by a run of its own, so that captures running side by side
+    /// don't clash.
+    pub fn new(host: &str, binary: &str) -> Self {
+        let name = format!("/tmp/profiler-{}", Uuid::new_v4());
+        Host {
+            host: host.to_string(),
+            binary: binary.to_string(),
+            log: format!("{}.xml", name),
+            pid_file: format!("{}.pid", name),
+        }
+    }
+
+    /// `command` run by the shell on the host.
+    pub fn shell(&self, command: &str) -> Command {
+        let mut ssh = ssh(&self.host);
+        ssh.arg(command);
+        ssh
+    }
+
+    /// ssh running `profile` with `options` into the log on the host, in a
+    /// process group of its own so that a Ctrl-C reaches the host only as
+    /// `kill` passes it on.
+    pub fn profile(&self, options: &[String]) -> Command {
+        self.profile_to(&self.log, options)
+    }
+
+    /// `profile` logging to stdout, for the log to arrive as it is written.
+    pub fn stream(&self, options: &[String]) -> Command {
+        self.profile_to("-", options)
+    }
+
+    fn profile_to(&self, output: &str, options: &[String]) -> Command {
+        let mut profile = vec![
+            self.binary.clone(),
+            "profile".to_string(),
+            "--output".to_string(),
+            output.to_string(),
+        ];
+        profile.extend(options.iter().cloned());
+        // exec keeps the pid the signals are passed on to.
+        let script = format!(
+            "echo $$ > {} && exec {}",
+            self.pid_file,
+            quote_command(&profile)
+        );
+        let mut ssh = self.shell(&script);
+        ssh.stdin(Stdio::null()).process_group(0);
+        ssh
+    }
+
+    /// Sends the profiler on the host `signal`, e.g. `INT`.
+    pub fn kill(&self, signal: &str) {
+        let kill = format!("kill -{} $(cat {})", signal, self.pid_file);
+        let _ = self.shell(&kill).status();
+    }
+
+    /// Copies the log to `output` with scp, retrying while the host is out
+    /// of reach, and removes what the capture left on the host; the log is
+    /// left there if it can't be copied.
+    pub fn collect(self, output: &str) -> bool {
+        let source = format!("{}:{}", self.host, self.log);
+        let mut fetched = false;
+        for attempt in 1..=FETCH_ATTEMPTS {
+            fetched = Command::new("scp")
+                .args(["-q", "-o", "BatchMode=yes", &source, output])
+                .status()
+                .is_ok_and(|status| status.success());
+            if fetched || attempt == FETCH_ATTEMPTS {
+                break;
+            }
+            eprintln!("Copying {} failed, retrying", source);
+            sleep(FETCH_RETRY);
+        }
+        let mut leftovers = vec![self.pid_file.clone()];
+        if fetched {
+            leftovers.push(self.log.clone());
+        } else {
+            eprintln!("cannot copy the log, it is left in {}", source);
+        }
+        let _ = self
+            .shell(&format!("rm -f {}", leftovers.join(" ")))
+            .stdin(Stdio::null())
+            .status();
+        fetched
+    }
+}
+
+/// Captures on a remote machine over ssh, as the user's agent and ssh config
+/// allow, and writes the log here as it arrives.
+///
+/// Runs the profiler on the host if it has one; otherwise the host streams
+/// its /proc files, assembled here into a log without perf. If the link
+/// drops, the entries received so far are kept as a truncated log.
+#[derive(Parser)]
+#[command(name = "ssh")]
+pub struct SshArgs {
+    /// Host to capture on, e.g. `user@host` or a `Host` of the ssh config
+    host: String,
+
+    /// File the log is written to
+    #[arg(short, long)]
+    output: String,
+
+    /// Overwrite the output file if it exists
+    #[arg(long)]
+    force: bool,
+
+    /// How long to capture for, as with `profile`; `0` runs until interrupted
+    #[arg(short, long, value_parser = parse_duration)]
+    duration: Duration,
+
+    /// Time between samples, as with `profile`
+    #[arg(short, long, value_parser = parse_duration)]
+    interval: Duration,
+
+    /// The profiler on the host
+    #[arg(long, value_name = "PATH", default_value = "profiler")]
+    binary: String,
+
+    /// Assemble the log from the host's /proc files even if it has the
+    /// profiler
+    #[arg(long)]
+    raw: bool,
+
+    /// Further options of `profile` on the host, after `--`; not with the
+    /// /proc files alone
+    #[arg(last = true, value_name = "OPTIONS")]
+    options: Vec<String>,
+}
+
+/// Sends what the metadata says of the host, then snapshots of /proc every
+/// interval until killed, each file under a `@@ NAME` line.
+fn raw_script(interval: Duration) -> String {
+    format!(
+        "echo '@@ hostname'; cat /proc/sys/kernel/hostname; echo '@@ uname'; uname -a; \
+         echo '@@ cpuinfo'; cat /proc/cpuinfo; echo '@@ nproc'; nproc; echo '@@ snapshot'; \
+         while :; do echo '@@ stat'; cat /proc/stat; echo '@@ meminfo'; cat /proc/meminfo; \
+         echo '@@ loadavg'; cat /proc/loadavg; echo '@@ snapshot'; sleep {}; done",
+        interval.as_secs_f64()
+    )
+}
+
+/// What the host sends of `raw_script` up to the next `@@ snapshot`, the
+/// description or one snapshot, by file name; none once the link is gone.
+fn read_sections(lines: &mut impl BufRead) -> Option<HashMap<String, String>> {
+    let mut sections = HashMap::new();
+    let mut name: Option<String> = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if lines.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        match line.trim_end().strip_prefix("@@ ") {
+            Some("snapshot") => return Some(sections),
+            Some(section) => {
+                sections.insert(section.to_string(), String::new());
+                name = Some(section.to_string());
+            }
+            None => {
+                if let Some(name) = &name {
+                    sections.get_mut(name).unwrap().push_str(&line);
+                }
+            }
+        }
+    }
+}
+
+/// The metadata of a log from the description `raw_script` sends.
+fn raw_metadata(sections: &HashMap<String, String>) -> Vec<(Element, String)> {
+    let mut metadata = Vec::new();
+    for (name, element) in [
+        ("hostname", Element::Hostname),
+        ("uname", Element::Uname),
+        ("nproc", Element::LogicalCpus),
+    ] {
+        if let Some(value) = sections.get(name) {
+            metadata.push((element, value.trim().to_string()));
+        }
+    }
+    let mut models: Vec<&str> = Vec::new();
+    let cpuinfo = sections.get("cpuinfo").map_or("", String::as_str);
+    for (key, value) in cpuinfo.lines().filter_map(|line| line.split_once(':')) {
+        if matches!(key.trim(), "model name" | "Hardware") && !models.contains(&value.trim()) {
+            models.push(value.trim());
+        }
+    }
+    if !models.is_empty() {
+        metadata.push((Element::CpuModel, models.join("; ")));
+    }
+    metadata
+}
+
+/// Assembles the log from the snapshots the host sends, entries spanning
+/// consecutive ones, until `duration` is covered or the link is gone, for
+/// good when `stop` says so. Returns whether the log is complete.
+fn assemble(child: &mut Child, output: &mut File, config: &Config, stop: &AtomicBool) -> bool {
+    let mut lines = BufReader::new(child.stdout.take().unwrap());
+    let Some(description) = read_sections(&mut lines) else {
+        return false;
+    };
+    let mut output = BufWriter::new(output);
+    write_log_header(&mut output, config, None, raw_metadata(&description));
+    let mut previous = read_sections(&mut lines).map(|snapshot| (Instant::now(), snapshot));
+    let start = previous
+        .as_ref()
+        .map_or(Instant::now(), |(started, _)| *started);
+    let wanted = config
+        .duration
+        .filter(|duration| !duration.is_zero())
+        .map(|duration| (duration.as_nanos() / config.interval.as_nanos()) as usize);
+    let section =
+        |snapshot: &HashMap<String, String>, name| snapshot.get(name).cloned().unwrap_or_default();
+    let mut entries = 0;
+    let mut last = start;
+    while let Some((started, start_snapshot)) = previous.take() {
+        last = started;
+        if wanted.is_some_and(|wanted| entries >= wanted) {
+            break;
+        }
+        let Some(end_snapshot) = read_sections(&mut lines) else {
+            break;
+        };
+        write_proc_entry(
+            &mut output,
+            started - start,
+            &section(&start_snapshot, "stat"),
+            &section(&end_snapshot, "stat"),
+            &section(&end_snapshot, "meminfo"),
+            &section(&end_snapshot, "loadavg"),
+        );
+        output.flush().unwrap();
+        entries += 1;
+        eprint!("\rLogged {} times", entries);
+        previous = Some((Instant::now(), end_snapshot));
+    }
+    let complete = wanted.is_some_and(|wanted| entries >= wanted) || stop.load(Ordering::SeqCst);
+    if complete {
+        write_log_end(&mut output, last - start, None, None);
+    }
+    output.flush().unwrap();
+    complete
+}
+
+/// Cuts a log that broke off down to its last whole entry, returning how
+/// many entries it keeps.
+fn salvage(path: &str) -> usize {
+    let mut log = std::fs::read(path).unwrap_or_default();
+    let end = format!("</{}>\n", Element::LogEntry.name());
+    let entries = log
+        .windows(end.len())
+        .filter(|window| *window == end.as_bytes())
+        .count();
+    if let Some(last) = log
+        .windows(end.len())
+        .rposition(|window| window == end.as_bytes())
+    {
+        log.truncate(last + end.len());
+        std::fs::write(path, log).unwrap();
+    }
+    entries
+}
+
+pub fn run(args: SshArgs) {
+    let mut command = SshArgs::command();
+    if !args.force && metadata(&args.output).is_ok() {
+        command
+            .error(
+                ErrorKind::ValueValidation,
+                format!("{} exists, pass --force to overwrite it", args.output),
+            )
+            .exit();
+    }
+    let host = Host::new(&args.host, &args.binary);
+    let check = format!(
+        "command -v {} >/dev/null && echo yes || echo no",
+        quote_command(std::slice::from_ref(&args.binary))
+    );
+    let found = match host.shell(&check).stdin(Stdio::null()).output() {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).trim() == "yes"
+        }
+        _ => {
+            eprintln!("cannot reach {} over ssh", args.host);
+            exit(1);
+        }
+    };
+    let raw = args.raw || !found;
+    if raw && !args.options.is_empty() {
+        command
+            .error(
+                ErrorKind::ArgumentConflict,
+                "options of profile need the profiler on the host",
+            )
+            .exit();
+    }
+    if !args.raw && !found {
+        eprintln!(
+            "No {} on {}, assembling the log from its /proc files, without perf",
+            args.binary, args.host
+        );
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut child = if raw {
+        host.shell(&raw_script(args.interval))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .process_group(0)
+            .spawn()
+    } else {
+        let mut options = vec![
+            "--duration".to_string(),
+            format!("{}ms", args.duration.as_millis()),
+            "--interval".to_string(),
+            format!("{}ms", args.interval.as_millis()),
+        ];
+        options.extend(args.options);
+        host.stream(&options).stdout(Stdio::piped()).spawn()
+    }
+    .unwrap_or_else(|error| {
+        eprintln!("cannot run ssh: {}", error);
+        exit(1);
+    });
+    // The host closes its log on the first signal; the raw stream is simply
+    // cut off, closing the log here.
+    let mut signals = Signals::new([SIGINT, SIGTERM]).unwrap();
+    {
+        let (host, stop, pid) = (host.clone(), stop.clone(), child.id());
+        thread::spawn(move || {
+            for (count, _) in signals.forever().enumerate() {
+                stop.store(true, Ordering::SeqCst);
+                if raw {
+                    unsafe {
+                        libc::kill(pid as libc::pid_t, libc::SIGTERM);
+                    }
+                } else {
+                    host.kill(if count == 0 { "INT" } else { "KILL" });
+                }
+                if count == 0 {
+                    eprintln!("\nInterrupted, closing the log (Ctrl-C again to force)");
+                }
+            }
+        });
+    }
+
+    let mut output = File::create(&args.output).unwrap();
+    let (code, complete) = if raw {
+        let mut config = Config::bare(Platform::Ubuntu, args.interval);
+        config.id = Uuid::new_v4();
+        config.duration = Some(args.duration);
+        let complete = assemble(&mut child, &mut output, &config, &stop);
+        let _ = child.kill();
+        let _ = child.wait();
+        eprintln!();
+        (0, complete)
+    } else {
+        let copied = std::io::copy(child.stdout.as_mut().unwrap(), &mut output);
+        let status = child.wait();
+        let _ = host
+            .shell(&format!("rm -f {}", host.pid_file))
+            .stdin(Stdio::null())
+            .status();
+        let log = std::fs::read(&args.output).unwrap_or_default();
+        let complete =
+            copied.is_ok() && log.ends_with(format!("</{}>\n", Element::Log.name()).as_bytes());
+        (
+            status.map_or(255, |status| status.code().unwrap_or(255)),
+            complete,
+        )
+    };
+    // ssh exits with 255 when the connection fails, as good as being killed.
+    if !complete && (raw || code == 255) {
+        let entries = salvage(&args.output);
+        eprintln!(
+            "Lost {}, kept the {} entries received in {} as a truncated log",
+            args.host, entries, args.output
+        );
+        exit(1);
+    }
+    exit(code);
+}
@@ -0,0 +1,390 @@
+use std::{
+    fs::{create_dir_all, metadata},
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::process::CommandExt,
+    path::PathBuf,
+    process::{exit, ChildStderr, Command, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::Signals,
+};
+
+use super::{
+    android::{self, Device},
+    parse_duration,
+    ssh::Host,
+};
+
+/// Round trips to a target's clock, the quickest of which gives its offset.
+const CLOCK_SAMPLES: u32 = 8;
+/// Prints the clock in nanoseconds since the epoch for every line read.
+const CLOCK_SCRIPT: &str = "while read line; do date +%s%N; done";
+
+/// Where `sync` captures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// This machine.
+    Local,
+    /// An ssh host, with a copy of the profiler on its PATH or at
+    /// `--ssh-binary`.
+    Ssh(String),
+    /// An Android device over adb, the one adb picks without a serial.
+    Adb(Option<String>),
+}
+
+impl Target {
+    /// Name of the target's log in `--dir`, without `.xml`.
+    fn name(&self) -> String {
+        match self {
+            Target::Local => "local".to_string(),
+            Target::Ssh(host) => host.replace('/', "-"),
+            Target::Adb(Some(serial)) => serial.replace(['/', ':'], "-"),
+            Target::Adb(None) => "adb".to_string(),
+        }
+    }
+}
+
+fn parse_target(text: &str) -> Result<Target, String> {
+    match text.split_once(':') {
+        None if text == "local" => Ok(Target::Local),
+        None if text == "adb" => Ok(Target::Adb(None)),
+        Some(("ssh", host)) if !host.is_empty() => Ok(Target::Ssh(host.to_string())),
+        Some(("adb", serial)) if !serial.is_empty() => Ok(Target::Adb(Some(serial.to_string()))),
+        _ => Err(format!(
+            "\"{}\" is no target, expected local, ssh:HOST or adb[:SERIAL]",
+            text
+        )),
+    }
+}
+
+/// Starts captures on several machines at the same instant, e.g. two phones
+/// and the server they talk to, and gathers their logs into one directory.
+///
+/// Measures how far each target's clock is off this one's, asks each
+/// logger to start at the same moment on its own clock with `--start-at`
+/// and records the offset in its log, for `report --compare --align` to
+/// line the captures up by.
+#[derive(Parser)]
+#[command(name = "sync")]
+pub struct SyncArgs {
+    /// Where to capture: `local`, `ssh:HOST` or `adb[:SERIAL]`, once per
+    /// target
+    #[arg(short, long = "target", value_name = "TARGET", value_parser = parse_target, required = true)]
+    targets: Vec<Target>,
+
+    /// Directory to write each target's log to, as `local.xml`, `HOST.xml`
+    /// or `SERIAL.xml`
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    dir: PathBuf,
+
+    /// Overwrite the logs if they exist
+    #[arg(long)]
+    force: bool,
+
+    /// How long to capture for, as with `profile`
+    #[arg(short, long, value_parser = parse_duration)]
+    duration: Duration,
+
+    /// Time between samples, as with `profile`
+    #[arg(short, long, value_parser = parse_duration)]
+    interval: Duration,
+
+    /// How far ahead to start, after the targets are ready, for the
+    /// instruction to reach every one of them in time
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "5s")]
+    lead: Duration,
+
+    /// The android profiler to push to adb targets; `profiler-android` next
+    /// to this binary when omitted
+    #[arg(long, value_name = "PATH")]
+    binary: Option<PathBuf>,
+
+    /// The profiler on ssh targets
+    #[arg(long, value_name = "PATH", default_value = "profiler")]
+    ssh_binary: String,
+
+    /// Further options of `profile` for every target, after `--`
+    #[arg(last = true, value_name = "OPTIONS")]
+    options: Vec<String>,
+}
+
+/// A target made ready to capture.
+#[derive(Clone)]
+enum Prepared {
+    Local,
+    Ssh(Host),
+    Adb(Device),
+}
+
+impl Prepared {
+    /// A command printing a line of the target's clock in nanoseconds for
+    /// every line it reads; none for this machine's, which the others are
+    /// measured against.
+    fn clock(&self) -> Option<Command> {
+        match self {
+            Prepared::Local => None,
+            Prepared::Ssh(host) => Some(host.shell(CLOCK_SCRIPT)),
+            Prepared::Adb(device) => Some(device.shell(CLOCK_SCRIPT)),
+        }
+    }
+
+    fn profile(&self, options: &[String], output: &str) -> Command {
+        match self {
+            Prepared::Local => {
+                let mut profile = Command::new(std::env::current_exe().unwrap());
+                profile
+                    .args(["profile", "--force", "--output", output])
+                    .args(options)
+                    .stdin(Stdio::null())
+                    .process_group(0);
+                profile
+            }
+            Prepared::Ssh(host) => host.profile(options),
+            Prepared::Adb(device) => device.profile(options),
+        }
+    }
+
+    fn collect(self, output: &str) -> bool {
+        match self {
+            Prepared::Local => true,
+            Prepared::Ssh(host) => host.collect(output),
+            Prepared::Adb(device) => device.collect(output),
+        }
+    }
+}
+
+/// Milliseconds `clock` is ahead of this machine's, from the round trip
+/// that took least time, so that it is off by half of that at most.
+fn measure_offset(mut clock: Command) -> Result<i64, String> {
+    let mut child = clock
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|error| error.to_string())?;
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut quickest: Option<(Duration, i64)> = None;
+    for _ in 0..CLOCK_SAMPLES {
+        let (sent, timer) = (SystemTime::now(), Instant::now());
+        let mut line = String::new();
+        writeln!(stdin)
+            .and_then(|_| stdin.flush())
+            .and_then(|_| stdout.read_line(&mut line))
+            .map_err(|error| error.to_string())?;
+        let trip = timer.elapsed();
+        let there: i128 = line
+            .trim()
+            .parse()
+            .map_err(|_| format!("cannot read the clock from \"{}\"", line.trim()))?;
+        let here = (sent + trip / 2)
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as i128;
+        let offset = ((there - here) / 1_000_000) as i64;
+        if quickest.is_none_or(|(quickest, _)| trip < quickest) {
+            quickest = Some((trip, offset));
+        }
+    }
+    drop(stdin);
+    let _ = child.wait();
+    Ok(quickest.unwrap().1)
+}
+
+/// Passes a target's stderr on, its lines under its name and its progress
+/// folded into one line of every target's.
+fn relay(name: String, stderr: ChildStderr, index: usize, progress: Arc<Mutex<Vec<String>>>) {
+    let mut segment = Vec::new();
+    for byte in BufReader::new(stderr).bytes() {
+        let Ok(byte) = byte else {
+            break;
+        };
+        if byte != b'\r' && byte != b'\n' {
+            segment.push(byte);
+            continue;
+        }
+        let line = String::from_utf8_lossy(&segment).into_owned();
+        segment.clear();
+        if line.starts_with("Logged ") || line.starts_with("Elapsed ") {
+            let mut progress = progress.lock().unwrap();
+            progress[index] = format!("{}: {}", name, line);
+            let shown: Vec<_> = progress
+                .iter()
+                .filter(|line| !line.is_empty())
+                .cloned()
+                .collect();
+            eprint!("\r{}", shown.join(", "));
+        } else if !line.is_empty() {
+            eprintln!("\r{}: {}", name, line);
+        }
+    }
+}
+
+pub fn run(args: SyncArgs) {
+    let mut command = SyncArgs::command();
+    let names: Vec<_> = args.targets.iter().map(Target::name).collect();
+    if let Some(index) = (1..names.len()).find(|&index| names[..index].contains(&names[index])) {
+        command
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!("{} is a target more than once", names[index]),
+            )
+            .exit();
+    }
+    let outputs: Vec<_> = names
+        .iter()
+        .map(|name| {
+            args.dir
+                .join(format!("{}.xml", name))
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    if let Some(output) = outputs
+        .iter()
+        .find(|output| !args.force && metadata(output).is_ok())
+    {
+        command
+            .error(
+                ErrorKind::ValueValidation,
+                format!("{} exists, pass --force to overwrite it", output),
+            )
+            .exit();
+    }
+    let binary = args.binary.clone().unwrap_or_else(android::default_binary);
+    if args
+        .targets
+        .iter()
+        .any(|target| matches!(target, Target::Adb(_)))
+        && !binary.is_file()
+    {
+        command
+            .error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "no android profiler at {}, pass one with --binary",
+                    binary.display()
+                ),
+            )
+            .exit();
+    }
+    if let Err(error) = create_dir_all(&args.dir) {
+        eprintln!("cannot create {}: {}", args.dir.display(), error);
+        exit(1);
+    }
+
+    let mut targets = Vec::new();
+    for (target, name) in args.targets.iter().zip(names.iter()) {
+        let prepared = match target {
+            Target::Local => Prepared::Local,
+            Target::Ssh(host) => Prepared::Ssh(Host::new(host, &args.ssh_binary)),
+            Target::Adb(serial) => Prepared::Adb(
+                Device::push(serial.clone(), &binary).unwrap_or_else(|error| {
+                    eprintln!("{}: {}", name, error);
+                    exit(1);
+                }),
+            ),
+        };
+        let offset = match prepared.clock() {
+            Some(clock) => measure_offset(clock).unwrap_or_else(|error| {
+                eprintln!("{}: cannot measure the clock: {}", name, error);
+                exit(1);
+            }),
+            None => 0,
+        };
+        eprintln!("{}: clock {:+}ms off this one", name, offset);
+        targets.push((prepared, offset));
+    }
+
+    let start = SystemTime::now() + args.lead;
+    let start_ms = start.duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
+    eprintln!("Starting at {}", crate::timestamp::format(start));
+
+    // The first SIGINT or SIGTERM has every target close its log, the next
+    // kills them.
+    let running: Arc<Mutex<Vec<(Prepared, u32)>>> = Arc::default();
+    let mut signals = Signals::new([SIGINT, SIGTERM]).unwrap();
+    {
+        let running = running.clone();
+        thread::spawn(move || {
+            for (count, _) in signals.forever().enumerate() {
+                let signal = if count == 0 { "INT" } else { "KILL" };
+                for (target, pid) in running.lock().unwrap().iter() {
+                    match target {
+                        Prepared::Local => unsafe {
+                            libc::kill(
+                                *pid as libc::pid_t,
+                                if count == 0 {
+                                    libc::SIGINT
+                                } else {
+                                    libc::SIGKILL
+                                },
+                            );
+                        },
+                        Prepared::Ssh(host) => host.kill(signal),
+                        Prepared::Adb(device) => device.kill(signal),
+                    }
+                }
+            }
+        });
+    }
+
+    let progress = Arc::new(Mutex::new(vec![String::new(); targets.len()]));
+    let mut captures = Vec::new();
+    for (index, ((prepared, offset), output)) in targets.into_iter().zip(outputs).enumerate() {
+        let mut options = vec![
+            "--duration".to_string(),
+            format!("{}ms", args.duration.as_millis()),
+            "--interval".to_string(),
+            format!("{}ms", args.interval.as_millis()),
+            "--start-at".to_string(),
+            (start_ms + offset).to_string(),
+            "--clock-offset-ms".to_string(),
+            offset.to_string(),
+        ];
+        options.extend(args.options.iter().cloned());
+        let mut child = prepared
+            .profile(&options, &output)
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap_or_else(|error| {
+                eprintln!("{}: cannot start the capture: {}", names[index], error);
+                exit(1);
+            });
+        running.lock().unwrap().push((prepared.clone(), child.id()));
+        let (name, progress) = (names[index].clone(), progress.clone());
+        let stderr = child.stderr.take().unwrap();
+        let relay = thread::spawn(move || relay(name, stderr, index, progress));
+        captures.push(thread::spawn(move || {
+            let status = child.wait();
+            relay.join().unwrap();
+            let code = status.map_or(1, |status| status.code().unwrap_or(1));
+            let collected = prepared.collect(&output);
+            (code, collected, output)
+        }));
+    }
+
+    let results: Vec<_> = captures
+        .into_iter()
+        .map(|capture| capture.join().unwrap())
+        .collect();
+    eprintln!();
+    let mut failed = false;
+    for ((code, collected, output), name) in results.into_iter().zip(names) {
+        if code == 0 && collected {
+            eprintln!("{}: {}", name, output);
+        } else {
+            eprintln!("{}: failed with exit code {}", name, code);
+            failed = true;
+        }
+    }
+    if failed {
+        exit(1);
+    }
+}
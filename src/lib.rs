@@ -0,0 +1,7 @@
+pub mod cli;
+pub mod log;
+pub mod platform;
+pub mod prometheus;
+pub mod report;
+pub mod schema;
+pub mod timestamp;
@@ -0,0 +1,1918 @@
+use duct::cmd;
+use flate2::{write::GzEncoder, Compression};
+use regex::Regex;
+use signal_hook::{consts::SIGUSR1, iterator::Signals};
+use std::{
+    fs::{read_to_string, File},
+    io::{
+        self, stderr, stdout, BufRead, BufReader, BufWriter, Error, ErrorKind, Read, Seek,
+        SeekFrom, Stdout, Write,
+    },
+    net::{Shutdown, TcpStream, ToSocketAddrs},
+    os::unix::process::ExitStatusExt,
+    path::Path,
+    process::ExitStatus,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread::{self, sleep},
+    time::{Duration, Instant, SystemTime},
+};
+use uuid::Uuid;
+
+use crate::{
+    platform::{PerfGranularity, Platform},
+    prometheus::{render, Exporter},
+    schema::{self, Element},
+    timestamp,
+};
+
+/// Both perf and simpleperf refuse interval prints shorter than this.
+pub const MIN_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Counts system-wide split by `granularity`, on the `cpus` mask if any, or
+/// only the given processes when `pids` is not empty. perf can't split a
+/// process's counts per core, so on ubuntu the per-process rows carry no CPU
+/// label.
+fn spawn_perf(
+    platform: Platform,
+    interval: Duration,
+    events: &str,
+    granularity: PerfGranularity,
+    cpus: Option<&str>,
+    pids: &[u32],
+) -> duct::ReaderHandle {
+    let target = if !pids.is_empty() {
+        let pids: Vec<_> = pids.iter().map(|pid| pid.to_string()).collect();
+        format!("-p {}", pids.join(","))
+    } else if let Some(cpus) = cpus {
+        match platform {
+            Platform::Ubuntu => format!("-a -C {}", cpus),
+            Platform::Android => format!("-a --cpu {}", cpus),
+        }
+    } else {
+        "-a".to_string()
+    };
+    let command = match platform {
+        Platform::Ubuntu => format!(
+            "stdbuf -o0 -e0 perf stat {} --csv --interval-print {} -e {} {}",
+            target,
+            interval.as_millis(),
+            events,
+            if pids.is_empty() {
+                granularity.flag(platform)
+            } else {
+                ""
+            }
+        ),
+        Platform::Android => format!(
+            "stdbuf -o0 -e0 simpleperf stat --use-devfreq-counters {} --csv --interval {} -e {} {}",
+            target,
+            interval.as_millis(),
+            events,
+            granularity.flag(platform)
+        ),
+    };
+    // sudo is our direct child so that stop_perf can signal it; through a
+    // shell, the signal would stop at the shell and leave perf running.
+    cmd("sudo", command.split_whitespace())
+        .stderr_to_stdout()
+        .unchecked()
+        .reader()
+        .unwrap()
+}
+
+/// The CPUs the kernel has online, from ranges such as `0-3,6`.
+pub fn online_cpus() -> Vec<u32> {
+    let Ok(online) = read_to_string("/sys/devices/system/cpu/online") else {
+        return (0..num_cpus::get() as u32).collect();
+    };
+    let mut cpus = Vec::new();
+    for range in online.trim().split(',') {
+        let (first, last) = range.split_once('-').unwrap_or((range, range));
+        if let (Ok(first), Ok(last)) = (first.parse::<u32>(), last.parse::<u32>()) {
+            cpus.extend(first..=last);
+        }
+    }
+    cpus
+}
+
+/// Pins the calling thread, and with it the threads and processes it starts
+/// from then on, to `cpu`.
+pub fn pin_to_cpu(cpu: u32) -> std::io::Result<()> {
+    set_cpus(0, &[cpu])
+}
+
+/// Lets process `pid`, or the calling thread for 0, run on `cpus` only.
+pub fn set_cpus(pid: u32, cpus: &[u32]) -> std::io::Result<()> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus {
+            libc::CPU_SET(cpu as usize, &mut set);
+        }
+        let size = std::mem::size_of::<libc::cpu_set_t>();
+        if libc::sched_setaffinity(pid as libc::pid_t, size, &set) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Asks perf to exit and reaps it. sudo relays SIGTERM to perf, whereas
+/// killing sudo outright would orphan perf, so that is only done if perf
+/// hasn't exited within a second.
+fn stop_perf(perf: &duct::ReaderHandle) {
+    for pid in perf.pids() {
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGTERM);
+        }
+    }
+    let deadline = Instant::now() + Duration::from_secs(1);
+    while Instant::now() < deadline {
+        if !matches!(perf.try_wait(), Ok(None)) {
+            return;
+        }
+        sleep(Duration::from_millis(10));
+    }
+    let _ = perf.kill();
+}
+
+/// The running perf, shared with the signal handler. It is replaced when perf
+/// is respawned and emptied once perf has been stopped or given up on.
+pub type PerfSlot = Arc<Mutex<Option<Arc<duct::ReaderHandle>>>>;
+
+pub fn stop_perf_slot(slot: &PerfSlot) {
+    if let Some(perf) = slot.lock().unwrap().take() {
+        stop_perf(&perf);
+    }
+}
+
+/// Stops perf when dropped, so it doesn't outlive a panicking capture loop.
+pub struct PerfGuard(pub PerfSlot);
+
+impl Drop for PerfGuard {
+    fn drop(&mut self) {
+        stop_perf_slot(&self.0);
+    }
+}
+
+/// Consecutive perf stalls after which the logger carries on without perf.
+const MAX_PERF_FAILURES: u32 = 3;
+
+/// Splits perf's output into one entry per interval. ubuntu perf starts every
+/// row with the interval's timestamp, and simpleperf ends every interval with
+/// its total test time.
+struct PerfGrouper {
+    platform: Platform,
+    entry: String,
+    /// Timestamp of the interval in `entry`.
+    time: Option<String>,
+    /// Timestamp of the last entry returned.
+    sent_time: Option<String>,
+    rows: usize,
+    /// Rows in an interval, learnt from the first one so that later entries
+    /// are complete without waiting for the next interval to start.
+    interval_rows: Option<usize>,
+}
+
+impl PerfGrouper {
+    fn new(platform: Platform) -> Self {
+        PerfGrouper {
+            platform,
+            entry: String::new(),
+            time: None,
+            sent_time: None,
+            rows: 0,
+            interval_rows: None,
+        }
+    }
+
+    /// Adds a line of perf's output, returning the previous interval's entry
+    /// once it is complete.
+    fn push(&mut self, line: &str) -> Option<String> {
+        match self.platform {
+            Platform::Ubuntu => self.push_timestamped(line),
+            Platform::Android => {
+                self.entry.push_str(line);
+                line.starts_with("Total test time")
+                    .then(|| std::mem::take(&mut self.entry))
+            }
+        }
+    }
+
+    fn push_timestamped(&mut self, line: &str) -> Option<String> {
+        if line.starts_with('#') {
+            return None;
+        }
+        let Some(time) = line
+            .split_whitespace()
+            .next()
+            .filter(|time| time.parse::<f64>().is_ok())
+        else {
+            self.entry.push_str(line);
+            return None;
+        };
+
+        if self.sent_time.as_deref() == Some(time) {
+            // The interval has more rows than the last one, e.g. as a core
+            // came online, and was sent without them. They go ahead of the
+            // next interval's rows, which replace them for the CPUs in both,
+            // and intervals are told apart by timestamp again.
+            self.interval_rows = None;
+            self.entry.push_str(line);
+            return None;
+        }
+        let mut complete = None;
+        if self.time.as_deref().is_some_and(|current| current != time) {
+            self.interval_rows.get_or_insert(self.rows);
+            complete = self.take();
+        }
+        self.time = Some(time.to_string());
+        self.entry.push_str(line);
+        self.rows += 1;
+        if complete.is_none() && self.interval_rows == Some(self.rows) {
+            complete = self.take();
+        }
+        complete
+    }
+
+    fn take(&mut self) -> Option<String> {
+        self.sent_time = self.time.take();
+        self.rows = 0;
+        Some(std::mem::take(&mut self.entry))
+    }
+}
+
+/// Spawns perf into `slot` along with a thread that reads its output, and
+/// returns the channel on which that thread sends one entry per interval.
+pub fn start_perf(config: &Config, slot: &PerfSlot) -> Receiver<String> {
+    let platform = config.platform;
+    let perf = Arc::new(spawn_perf(
+        platform,
+        config.interval,
+        &config.events,
+        config.perf_granularity,
+        config.cpu_list.as_deref(),
+        &config.pids,
+    ));
+    *slot.lock().unwrap() = Some(perf.clone());
+
+    let (perf_tx, perf_rx) = channel::<String>();
+    thread::spawn(move || {
+        let mut reader = BufReader::new(&*perf);
+        let mut grouper = PerfGrouper::new(platform);
+        let mut line = String::new();
+        while let Ok(1..) = reader.read_line(&mut line) {
+            if let Some(entry) = grouper.push(&line) {
+                if perf_tx.send(entry).is_err() {
+                    break;
+                }
+            }
+            line.clear();
+        }
+    });
+    perf_rx
+}
+
+/// `<element name="value"...>`, or the whole element when `empty`.
+fn start_tag(element: Element, attributes: &[(&str, String)], empty: bool) -> String {
+    let mut tag = format!("<{}", element.name());
+    for (name, value) in attributes {
+        tag.push_str(&format!(" {}=\"{}\"", name, value));
+    }
+    tag.push_str(if empty { "/>\n" } else { ">\n" });
+    tag
+}
+
+/// `text` is a file's content and so already ends in a newline.
+fn write_element(output: &mut impl Write, element: Element, text: &str) {
+    output
+        .write_all(
+            format!(
+                "<{0}>{1}</{0}>\n",
+                element.name(),
+                cdata(&format!("\n{}", text))
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+}
+
+/// `text` as a CDATA section, split where it holds the section's end, so
+/// that payloads such as perf's with `<not counted>` are logged as read.
+pub fn cdata(text: &str) -> String {
+    format!("<![CDATA[{}]]>", text.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+/// One `cpuN kHz` line per core that exposes cpufreq.
+fn read_cpu_freq() -> String {
+    let mut cores: Vec<(u32, u64)> = std::fs::read_dir("/sys/devices/system/cpu")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let core = name.to_str()?.strip_prefix("cpu")?.parse().ok()?;
+            let freq = read_to_string(entry.path().join("cpufreq/scaling_cur_freq")).ok()?;
+            Some((core, freq.trim().parse().ok()?))
+        })
+        .collect();
+    cores.sort();
+
+    cores
+        .iter()
+        .map(|(core, freq)| format!("cpu{} {}\n", core, freq))
+        .collect()
+}
+
+/// The version and per CPU lines of /proc/schedstat, leaving out the many
+/// lines of its scheduling domains.
+pub fn read_schedstat() -> Option<String> {
+    let schedstat = read_to_string("/proc/schedstat").ok()?;
+    Some(
+        schedstat
+            .lines()
+            .filter(|line| line.starts_with("version ") || line.starts_with("cpu"))
+            .map(|line| format!("{}\n", line))
+            .collect(),
+    )
+}
+
+/// One `cpuN khz ticks` line per frequency of each CPU's cpufreq stats, the
+/// time spent at it since boot in USER_HZ ticks.
+pub fn read_time_in_state() -> String {
+    let mut cores: Vec<(u32, String)> = std::fs::read_dir("/sys/devices/system/cpu")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let core = name.to_str()?.strip_prefix("cpu")?.parse().ok()?;
+            let table = read_to_string(entry.path().join("cpufreq/stats/time_in_state")).ok()?;
+            Some((core, table))
+        })
+        .collect();
+    cores.sort();
+
+    cores
+        .iter()
+        .flat_map(|(core, table)| {
+            table
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(move |line| format!("cpu{} {}\n", core, line.trim()))
+        })
+        .collect()
+}
+
+/// One `cpuN stateK usage time name` line per cpuidle state of each CPU,
+/// with the time in microseconds and the state's name, e.g. "WFI" or "C6",
+/// last as the driver picks it freely.
+pub fn read_cpuidle() -> String {
+    let mut states: Vec<(u32, u32, String)> = Vec::new();
+    for cpu in std::fs::read_dir("/sys/devices/system/cpu")
+        .into_iter()
+        .flatten()
+        .flatten()
+    {
+        let Some(core) = cpu
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_prefix("cpu")?.parse().ok())
+        else {
+            continue;
+        };
+        for state in std::fs::read_dir(cpu.path().join("cpuidle"))
+            .into_iter()
+            .flatten()
+            .flatten()
+        {
+            let Some(index) = state
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix("state")?.parse().ok())
+            else {
+                continue;
+            };
+            let read = |file| read_to_string(state.path().join(file)).ok();
+            if let (Some(usage), Some(time), Some(name)) =
+                (read("usage"), read("time"), read("name"))
+            {
+                let line = format!("{} {} {}", usage.trim(), time.trim(), name.trim());
+                states.push((core, index, line));
+            }
+        }
+    }
+    states.sort();
+
+    states
+        .iter()
+        .map(|(core, index, line)| format!("cpu{} state{} {}\n", core, index, line))
+        .collect()
+}
+
+/// One `thermal_zoneN type millidegrees` line per zone, with `-` in place of
+/// the temperature when the zone fails to read (some SoCs return EAGAIN).
+fn read_thermal() -> String {
+    let mut zones: Vec<(u32, String)> = std::fs::read_dir("/sys/class/thermal")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let zone = name.to_str()?.strip_prefix("thermal_zone")?.parse().ok()?;
+            let kind = read_to_string(entry.path().join("type")).unwrap_or_default();
+            let kind = match kind.trim() {
+                "" => "unknown".to_string(),
+                kind => kind.replace(char::is_whitespace, "_"),
+            };
+            let temp = read_to_string(entry.path().join("temp"))
+                .ok()
+                .and_then(|temp| temp.trim().parse::<i64>().ok())
+                .map_or("-".to_string(), |temp| temp.to_string());
+            Some((zone, format!("thermal_zone{} {} {}\n", zone, kind, temp)))
+        })
+        .collect();
+    zones.sort();
+    zones.into_iter().map(|(_, line)| line).collect()
+}
+
+pub const BATTERY: &str = "/sys/class/power_supply/battery";
+
+/// One `name value` line per battery attribute the kernel exposes, as read
+/// at the end of an interval.
+pub fn read_battery() -> String {
+    let mut battery = String::new();
+    for name in [
+        "current_now",
+        "voltage_now",
+        "capacity",
+        "charge_counter",
+        "status",
+    ] {
+        if let Ok(value) = read_to_string(format!("{}/{}", BATTERY, name)) {
+            battery.push_str(&format!("{} {}\n", name, value.trim()));
+        }
+    }
+    battery
+}
+
+/// Where GPUs show how busy they are: Adreno's busy and total time of its
+/// last window, then the devfreq `load` of a device named like a GPU (Mali
+/// and most others) as a percentage, optionally `@` its frequency.
+pub fn find_gpu() -> Option<String> {
+    let kgsl = "/sys/class/kgsl/kgsl-3d0/gpubusy";
+    if read_to_string(kgsl).is_ok() {
+        return Some(kgsl.to_string());
+    }
+    let mut devices: Vec<_> = std::fs::read_dir("/sys/class/devfreq")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            ["gpu", "mali", "kgsl"].iter().any(|gpu| name.contains(gpu))
+        })
+        .map(|entry| entry.path().join("load"))
+        .filter(|load| load.is_file())
+        .collect();
+    devices.sort();
+    Some(devices.first()?.to_string_lossy().into_owned())
+}
+
+/// Lines of `dumpsys telephony.registry` that the report reads the network
+/// type and cell signal from.
+const RADIO_LINES: [&str; 3] = [
+    "mServiceState=",
+    "mSignalStrength=",
+    "mTelephonyDisplayInfo=",
+];
+
+/// The `RADIO_LINES` of the first phone in `dumpsys telephony.registry`,
+/// then the interface rows of /proc/net/wireless; either is missing where
+/// there's no such thing.
+pub fn read_radio() -> String {
+    let registry = cmd("dumpsys", ["telephony.registry"])
+        .stderr_null()
+        .unchecked()
+        .read()
+        .unwrap_or_default();
+    let mut radio = String::new();
+    for prefix in RADIO_LINES {
+        if let Some(line) = registry
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with(prefix))
+        {
+            radio.push_str(line);
+            radio.push('\n');
+        }
+    }
+    // Past its two header lines.
+    for line in read_to_string("/proc/net/wireless")
+        .unwrap_or_default()
+        .lines()
+        .skip(2)
+    {
+        radio.push_str(line.trim());
+        radio.push('\n');
+    }
+    radio
+}
+
+pub fn read_pid_stat(pid: u32) -> Option<String> {
+    read_to_string(format!("/proc/{}/stat", pid)).ok()
+}
+
+/// For text in attributes and `<metadata>`, which may contain anything; the
+/// payloads of an entry are logged as CDATA instead.
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Keeps the two header lines of /proc/net/dev and the rows of the selected
+/// interfaces, or everything when no interface was selected.
+fn filter_net_dev(text: String, ifaces: &[String]) -> String {
+    if ifaces.is_empty() {
+        return text;
+    }
+
+    let mut filtered = String::new();
+    for (i, line) in text.lines().enumerate() {
+        let iface = line.split(':').next().unwrap_or_default().trim();
+        if i < 2 || ifaces.iter().any(|selected| selected == iface) {
+            filtered.push_str(line);
+            filtered.push('\n');
+        }
+    }
+    filtered
+}
+
+/// Keeps the rows of /proc/diskstats of the selected devices, or everything
+/// when no device was selected.
+fn filter_diskstats(text: String, disks: &[String]) -> String {
+    if disks.is_empty() {
+        return text;
+    }
+
+    let mut filtered = String::new();
+    for line in text.lines() {
+        let disk = line.split_whitespace().nth(2).unwrap_or_default();
+        if disks.iter().any(|selected| selected == disk) {
+            filtered.push_str(line);
+            filtered.push('\n');
+        }
+    }
+    filtered
+}
+
+/// The cpu.stat of the cgroup v2 directory `path`, `None` once it is gone.
+pub fn read_cgroup_stat(path: &str) -> Option<String> {
+    read_to_string(format!("{}/cpu.stat", path)).ok()
+}
+
+/// Labels sent during the capture, each with when it arrived: a line written
+/// to `fifo`, or `mark-N` for the Nth SIGUSR1.
+fn listen_for_markers(fifo: Option<&str>) -> Receiver<(Instant, String)> {
+    let (marker_tx, marker_rx) = channel();
+    let signal_tx = marker_tx.clone();
+    let mut signals = Signals::new([SIGUSR1]).unwrap();
+    thread::spawn(move || {
+        for (count, _) in signals.forever().enumerate() {
+            let label = format!("mark-{}", count + 1);
+            if signal_tx.send((Instant::now(), label)).is_err() {
+                break;
+            }
+        }
+    });
+    if let Some(fifo) = fifo.map(str::to_string) {
+        thread::spawn(move || {
+            // Reading ends whenever the last writer closes the FIFO, e.g.
+            // after every `echo`, so it is reopened for the next one.
+            while let Ok(file) = File::open(&fifo) {
+                for line in BufReader::new(file).lines().map_while(Result::ok) {
+                    let label = line.trim().to_string();
+                    if !label.is_empty() && marker_tx.send((Instant::now(), label)).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+    marker_rx
+}
+
+/// A `<marker>` for every label received so far, timed from `start` like
+/// the entries.
+fn write_markers(output: &mut impl Write, markers: &Receiver<(Instant, String)>, start: Instant) {
+    for (received, label) in markers.try_iter() {
+        let attributes = [
+            (
+                schema::TIME_MS,
+                received
+                    .saturating_duration_since(start)
+                    .as_millis()
+                    .to_string(),
+            ),
+            (schema::LABEL, escape_xml(&label).replace('"', "&quot;")),
+        ];
+        output
+            .write_all(start_tag(Element::Marker, &attributes, true).as_bytes())
+            .unwrap();
+    }
+}
+
+/// The `Tcp:` rows of /proc/net/snmp and the `TcpExt:` rows of
+/// /proc/net/netstat, each a row of counter names followed by one of their
+/// values, or `None` where the kernel shows neither.
+fn read_tcp() -> Option<String> {
+    let rows = |path: &str, protocol: &str| -> String {
+        read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| line.split_whitespace().next() == Some(protocol))
+            .map(|line| format!("{}\n", line))
+            .collect()
+    };
+    let rows = rows("/proc/net/snmp", "Tcp:") + &rows("/proc/net/netstat", "TcpExt:");
+    (!rows.is_empty()).then_some(rows)
+}
+
+/// The CPU header of /proc/interrupts and its rows, only those matching
+/// `filter` when given, as the whole table is long on phones.
+fn read_interrupts(filter: Option<&Regex>) -> Option<String> {
+    let interrupts = read_to_string("/proc/interrupts").ok()?;
+    let mut lines = interrupts.lines();
+    let mut rows = format!("{}\n", lines.next()?);
+    for line in lines.filter(|line| filter.is_none_or(|filter| filter.is_match(line))) {
+        rows.push_str(line);
+        rows.push('\n');
+    }
+    Some(rows)
+}
+
+/// The lines of /proc/pressure/cpu, memory and io, each prefixed with its
+/// resource, or `None` on kernels without PSI.
+fn read_pressure() -> Option<String> {
+    let mut pressure = String::new();
+    for resource in ["cpu", "memory", "io"] {
+        let Ok(text) = read_to_string(format!("/proc/pressure/{}", resource)) else {
+            continue;
+        };
+        for line in text.lines() {
+            pressure.push_str(&format!("{} {}\n", resource, line));
+        }
+    }
+    (!pressure.is_empty()).then_some(pressure)
+}
+
+/// Big enough for an entry of a many-core machine, so that pushing one out
+/// takes a single write.
+const OUTPUT_BUFFER: usize = 256 * 1024;
+
+/// The log file, gzip compressed when its name ends in `.gz`, or stdout for
+/// `-`, or a stream to `profiler collect`. Writes are buffered until
+/// `process` flushes them as `Config::flush` says.
+pub enum Output {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Stdout(BufWriter<Stdout>),
+    Remote(Remote),
+}
+
+impl Output {
+    pub fn create(path: &str) -> io::Result<Self> {
+        if path == "-" {
+            return Ok(Output::Stdout(BufWriter::with_capacity(
+                OUTPUT_BUFFER,
+                stdout(),
+            )));
+        }
+        let file = BufWriter::with_capacity(OUTPUT_BUFFER, File::create(path)?);
+        Ok(if path.ends_with(".gz") {
+            Output::Gzip(GzEncoder::new(file, Compression::default()))
+        } else {
+            Output::Plain(file)
+        })
+    }
+
+    /// Bytes written to the file so far, buffered ones included; for gzip,
+    /// bar those the encoder still holds back.
+    pub fn size(&self) -> u64 {
+        let file = match self {
+            Output::Plain(file) => file,
+            Output::Gzip(encoder) => encoder.get_ref(),
+            Output::Stdout(_) => return 0,
+            Output::Remote(remote) => &remote.spool,
+        };
+        file.get_ref()
+            .metadata()
+            .map_or(0, |metadata| metadata.len())
+            + file.buffer().len() as u64
+    }
+
+    /// Flushes and, for a file, waits until the kernel has written it to
+    /// the disk.
+    pub fn sync(&mut self) {
+        self.flush().unwrap();
+        match self {
+            Output::Plain(file) => file.get_ref().sync_data().unwrap(),
+            Output::Gzip(encoder) => encoder.get_ref().get_ref().sync_data().unwrap(),
+            Output::Stdout(_) => {}
+            Output::Remote(remote) => remote.spool.get_ref().sync_data().unwrap(),
+        }
+    }
+
+    /// Writes the gzip trailer; a log whose trailer is missing reads as
+    /// truncated.
+    pub fn finish(self, sync: bool) {
+        let mut file = match self {
+            Output::Plain(file) => file,
+            Output::Stdout(mut stdout) => {
+                stdout.flush().unwrap();
+                return;
+            }
+            Output::Gzip(encoder) => encoder.finish().unwrap(),
+            Output::Remote(remote) => {
+                remote.finish(sync);
+                return;
+            }
+        };
+        file.flush().unwrap();
+        if sync {
+            file.get_ref().sync_data().unwrap();
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Output::Plain(file) => file.write(buf),
+            Output::Gzip(encoder) => encoder.write(buf),
+            Output::Stdout(stdout) => stdout.write(buf),
+            Output::Remote(remote) => remote.spool.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Output::Plain(file) => file.flush(),
+            Output::Gzip(encoder) => encoder.flush(),
+            Output::Stdout(stdout) => stdout.flush(),
+            Output::Remote(remote) => remote.spool.flush(),
+        }
+    }
+}
+
+/// First line a logger sends `profiler collect` on connecting, followed by
+/// the log's id; the collector answers with how many bytes of the log it
+/// already has, as a line of its own, and the logger goes on from there.
+pub const REMOTE_HELLO: &str = "PROFILER";
+/// How long the logger keeps trying to hand the rest of the log to the
+/// collector after the capture ends, before leaving it in the spool.
+const REMOTE_FINISH_TIMEOUT: Duration = Duration::from_secs(30);
+const REMOTE_RETRY: Duration = Duration::from_secs(1);
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A log streamed to `profiler collect`. Entries are spooled to a local file,
+/// which a thread sends on from wherever the collector's copy ends, so that
+/// the link dropping loses nothing: the thread reconnects and catches up.
+pub struct Remote {
+    spool: BufWriter<File>,
+    path: String,
+    done: Arc<AtomicBool>,
+    sender: thread::JoinHandle<bool>,
+}
+
+impl Output {
+    pub fn remote(address: &str, id: Uuid, spool: &str) -> io::Result<Self> {
+        let file = BufWriter::with_capacity(OUTPUT_BUFFER, File::create(spool)?);
+        let done = Arc::new(AtomicBool::new(false));
+        let sender = {
+            let (address, path, done) = (address.to_string(), spool.to_string(), done.clone());
+            thread::spawn(move || send_spool(&address, id, &path, &done))
+        };
+        Ok(Output::Remote(Remote {
+            spool: file,
+            path: spool.to_string(),
+            done,
+            sender,
+        }))
+    }
+}
+
+impl Remote {
+    /// Waits for the collector to have the whole log, then drops the spool;
+    /// it is kept if the collector can't be reached in time.
+    fn finish(mut self, sync: bool) {
+        self.spool.flush().unwrap();
+        if sync {
+            self.spool.get_ref().sync_data().unwrap();
+        }
+        self.done.store(true, Ordering::SeqCst);
+        if self.sender.join().unwrap() {
+            let _ = std::fs::remove_file(&self.path);
+        } else {
+            eprintln!(
+                "The collector didn't get the whole log, it is left in {}",
+                self.path
+            );
+        }
+    }
+}
+
+/// Sends the spool at `path` to the collector at `address` as it grows,
+/// reconnecting whenever the link drops, until `done` and all of it is sent.
+/// False if the collector is still out of reach `REMOTE_FINISH_TIMEOUT` after
+/// `done`.
+fn send_spool(address: &str, id: Uuid, path: &str, done: &AtomicBool) -> bool {
+    let mut connected = true;
+    let mut deadline = None;
+    loop {
+        match stream_spool(address, id, path, done, &mut connected) {
+            Ok(()) => return true,
+            Err(error) => {
+                if connected {
+                    eprintln!(
+                        "\nLost the collector at {}: {}, spooling to {}",
+                        address, error, path
+                    );
+                    connected = false;
+                }
+                if done.load(Ordering::SeqCst)
+                    && Instant::now()
+                        > *deadline.get_or_insert(Instant::now() + REMOTE_FINISH_TIMEOUT)
+                {
+                    return false;
+                }
+                sleep(REMOTE_RETRY);
+            }
+        }
+    }
+}
+
+fn stream_spool(
+    address: &str,
+    id: Uuid,
+    path: &str,
+    done: &AtomicBool,
+    connected: &mut bool,
+) -> std::io::Result<()> {
+    let socket = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "no such address"))?;
+    let mut stream = TcpStream::connect_timeout(&socket, REMOTE_TIMEOUT)?;
+    stream.set_read_timeout(Some(REMOTE_TIMEOUT))?;
+    stream.set_write_timeout(Some(REMOTE_TIMEOUT))?;
+    writeln!(stream, "{} {}", REMOTE_HELLO, id)?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let offset: u64 = line
+        .trim()
+        .parse()
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "not a profiler collector"))?;
+    if !*connected {
+        eprintln!("\nReconnected to the collector at {}", address);
+        *connected = true;
+    }
+
+    let mut spool = File::open(path)?;
+    spool.seek(SeekFrom::Start(offset))?;
+    let mut buffer = vec![0; OUTPUT_BUFFER];
+    loop {
+        // Checked before reading, so that the read after the capture ended
+        // takes in all of the spool.
+        let finished = done.load(Ordering::SeqCst);
+        let read = spool.read(&mut buffer)?;
+        if read > 0 {
+            stream.write_all(&buffer[..read])?;
+        } else if finished {
+            // The collector closes its end once it has written everything.
+            stream.shutdown(Shutdown::Write)?;
+            stream.set_read_timeout(None)?;
+            stream.read_to_end(&mut Vec::new())?;
+            return Ok(());
+        } else {
+            sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+/// Runs the capture loop until `duration` elapses (or until interrupted when
+/// it is `None`) and returns how long the capture, or its last part, actually
+/// ran. Each entry logged also goes to `exporter`, if any.
+pub fn process(
+    output: &mut Output,
+    config: &Config,
+    perf: &PerfSlot,
+    stop: &AtomicBool,
+    exporter: Option<&Exporter>,
+) -> Duration {
+    let Config {
+        duration,
+        interval,
+        warmup,
+        ..
+    } = *config;
+    let warmup_ticks = (warmup.as_nanos() / interval.as_nanos()) as u32;
+    let markers = listen_for_markers(config.markers.as_deref());
+    let mut perf_rx = config.perf.then(|| start_perf(config, perf));
+    let mut perf_failures = 0;
+    // perf needs a moment to start, which very short intervals don't give it.
+    let perf_timeout = (interval * 3).max(Duration::from_secs(1));
+
+    let mut i = 0;
+    // With `--rotate-*`, entries go to a new part once this one is full, each
+    // timed from its own start as consecutive logs are.
+    let mut part = 1;
+    let mut part_entries = 0;
+    let mut part_start = Duration::ZERO;
+    let mut unflushed = 0;
+    let mut flushed_at = Instant::now();
+    // The interval since the start that the entry being logged covers, so
+    // that reading and writing don't push every later entry back.
+    let mut tick: u32 = 0;
+    let start_time = Instant::now();
+
+    loop {
+        let elapsed = start_time.elapsed();
+        // Over an interval behind, e.g. after a stall or a suspend: skip to
+        // the current interval rather than logging a burst of short ones, and
+        // drop the samples perf queued for the skipped intervals.
+        let current = (elapsed.as_nanos() / interval.as_nanos()) as u32;
+        let skipped = current.saturating_sub(tick);
+        if skipped > 0 {
+            tick = current;
+            if let Some(rx) = perf_rx.as_ref() {
+                while rx.try_recv().is_ok() {}
+            }
+        }
+        let wall_time = SystemTime::now();
+        let proc_start = read_to_string("/proc/stat").unwrap();
+        let net_start = filter_net_dev(read_to_string("/proc/net/dev").unwrap(), &config.ifaces);
+        let tcp_start = read_tcp();
+        let pressure_start = read_pressure();
+        let disk_start = read_to_string("/proc/diskstats")
+            .ok()
+            .map(|text| filter_diskstats(text, &config.disks));
+        let softnet_start = read_to_string("/proc/net/softnet_stat").ok();
+        let irq_start = read_interrupts(config.irq_filter.as_ref());
+        let softirqs_start = config
+            .softirqs
+            .then(|| read_to_string("/proc/softirqs").ok())
+            .flatten();
+        let idle_start = config.cpuidle.then(read_cpuidle);
+        let schedstat_start = config.schedstat.then(read_schedstat).flatten();
+        let time_in_state_start = config.time_in_state.then(read_time_in_state);
+        let pid_start: Vec<_> = config.pids.iter().map(|&pid| read_pid_stat(pid)).collect();
+        let cgroup_start: Vec<_> = config
+            .cgroups
+            .iter()
+            .map(|path| read_cgroup_stat(path))
+            .collect();
+        let self_start = read_to_string("/proc/self/stat").unwrap();
+        sleep((start_time + interval * (tick + 1)).saturating_duration_since(Instant::now()));
+        let perf_entry = match perf_rx.as_ref().map(|rx| rx.recv_timeout(perf_timeout)) {
+            Some(Ok(perf_entry)) => {
+                perf_failures = 0;
+                Some(perf_entry)
+            }
+            // perf shares our process group, so on Ctrl-C it may exit before
+            // delivering the last sample; drop that entry.
+            Some(Err(RecvTimeoutError::Disconnected)) if stop.load(Ordering::SeqCst) => break,
+            Some(Err(error)) => {
+                perf_failures += 1;
+                stop_perf_slot(perf);
+                if perf_failures < MAX_PERF_FAILURES {
+                    eprintln!(
+                        "\nperf {}, restarting it",
+                        if error == RecvTimeoutError::Timeout {
+                            "stalled"
+                        } else {
+                            "exited"
+                        }
+                    );
+                    perf_rx = Some(start_perf(config, perf));
+                } else {
+                    eprintln!("\nperf failed {} times, logging without it", perf_failures);
+                    perf_rx = None;
+                }
+                None
+            }
+            None => None,
+        };
+        let proc_end = read_to_string("/proc/stat").unwrap();
+        let net_end = filter_net_dev(read_to_string("/proc/net/dev").unwrap(), &config.ifaces);
+        let tcp_end = read_tcp();
+        let pressure_end = read_pressure();
+        let disk_end = read_to_string("/proc/diskstats")
+            .ok()
+            .map(|text| filter_diskstats(text, &config.disks));
+        let softnet_end = read_to_string("/proc/net/softnet_stat").ok();
+        let irq_end = read_interrupts(config.irq_filter.as_ref());
+        let softirqs_end = config
+            .softirqs
+            .then(|| read_to_string("/proc/softirqs").ok())
+            .flatten();
+        let idle_end = config.cpuidle.then(read_cpuidle);
+        let schedstat_end = config.schedstat.then(read_schedstat).flatten();
+        let time_in_state_end = config.time_in_state.then(read_time_in_state);
+        let pid_end: Vec<_> = config.pids.iter().map(|&pid| read_pid_stat(pid)).collect();
+        let cgroup_end: Vec<_> = config
+            .cgroups
+            .iter()
+            .map(|path| read_cgroup_stat(path))
+            .collect();
+        let self_end = read_to_string("/proc/self/stat").unwrap();
+        let meminfo = read_to_string("/proc/meminfo").unwrap();
+        let loadavg = read_to_string("/proc/loadavg").ok();
+        let sockets = config.sockets.as_deref().and_then(|filter| {
+            cmd("ss", ["-tin", filter])
+                .stderr_null()
+                .unchecked()
+                .read()
+                .ok()
+        });
+        let radio = config.radio.then(read_radio);
+        let freq = read_cpu_freq();
+        let thermal = config.thermal.then(read_thermal);
+        let battery = config.battery.then(read_battery);
+        let gpu = config.gpu.as_ref().and_then(|gpu| read_to_string(gpu).ok());
+
+        // Sampled like the others so that perf and the caches settle, but
+        // not logged.
+        if tick < warmup_ticks {
+            tick += 1;
+            if stop.load(Ordering::SeqCst) {
+                break;
+            }
+            let progress = if tick < warmup_ticks {
+                format!(
+                    "\rWarming up, {} left",
+                    format_elapsed(warmup - interval * tick)
+                )
+            } else {
+                format!("\rWarmed up for {}\n", format_elapsed(warmup))
+            };
+            stderr().write_all(progress.as_bytes()).unwrap();
+            continue;
+        }
+
+        let elapsed = elapsed.saturating_sub(warmup);
+        write_markers(output, &markers, start_time + warmup + part_start);
+        let mut time = vec![(
+            schema::TIME_MS,
+            elapsed.saturating_sub(part_start).as_millis().to_string(),
+        )];
+        if config.timestamps {
+            time.push((schema::WALL_TIME, timestamp::format(wall_time)));
+        }
+        if skipped > 0 {
+            time.push((schema::SKIPPED, skipped.to_string()));
+        }
+        output
+            .write_all(start_tag(Element::LogEntry, &time, false).as_bytes())
+            .unwrap();
+        write_element(output, Element::ProcStart, &proc_start);
+        write_element(output, Element::ProcEnd, &proc_end);
+        // Both snapshots of a process, or an empty element once it has exited.
+        // Older reports lose track of a repeated element that ends its
+        // parent, so these stay ahead of the other payloads.
+        for (pid, (start, end)) in config.pids.iter().zip(pid_start.iter().zip(pid_end.iter())) {
+            let pid = [(schema::PID, pid.to_string())];
+            let element = match (start, end) {
+                (Some(start), Some(end)) => format!(
+                    "{}{}{}</{}>\n",
+                    start_tag(Element::ProcPid, &pid, false),
+                    cdata(start),
+                    cdata(end),
+                    Element::ProcPid.name()
+                ),
+                _ => start_tag(Element::ProcPid, &pid, true),
+            };
+            output.write_all(element.as_bytes()).unwrap();
+        }
+        for (path, (start, end)) in config
+            .cgroups
+            .iter()
+            .zip(cgroup_start.iter().zip(cgroup_end.iter()))
+        {
+            let path = [(schema::PATH, escape_xml(path).replace('"', "&quot;"))];
+            let element = match (start, end) {
+                (Some(start), Some(end)) => format!(
+                    "{}{}{}</{}>\n",
+                    start_tag(Element::Cgroup, &path, false),
+                    cdata(start),
+                    cdata(end),
+                    Element::Cgroup.name()
+                ),
+                _ => start_tag(Element::Cgroup, &path, true),
+            };
+            output.write_all(element.as_bytes()).unwrap();
+        }
+        // The logger's own time, perf reader thread included but not perf.
+        write_element(output, Element::SelfStat, &(self_start + &self_end));
+        if let Some(exporter) = exporter {
+            exporter.update(render(
+                &proc_start,
+                &proc_end,
+                perf_entry.as_deref(),
+                config.platform,
+            ));
+        }
+        if let Some(perf_entry) = perf_entry {
+            write_element(output, Element::Perf, &perf_entry);
+        }
+        write_element(output, Element::Meminfo, &meminfo);
+        if let Some(loadavg) = loadavg {
+            write_element(output, Element::Loadavg, &loadavg);
+        }
+        write_element(output, Element::Freq, &freq);
+        if let (Some(start), Some(end)) = (time_in_state_start, time_in_state_end) {
+            write_element(output, Element::TimeInStateStart, &start);
+            write_element(output, Element::TimeInStateEnd, &end);
+        }
+        if let Some(thermal) = thermal {
+            write_element(output, Element::Thermal, &thermal);
+        }
+        if let Some(battery) = battery {
+            write_element(output, Element::Battery, &battery);
+        }
+        if let Some(gpu) = gpu {
+            write_element(output, Element::Gpu, &gpu);
+        }
+        if let Some(radio) = radio {
+            write_element(output, Element::Radio, &radio);
+        }
+        write_element(output, Element::NetStart, &net_start);
+        write_element(output, Element::NetEnd, &net_end);
+        if let (Some(start), Some(end)) = (tcp_start, tcp_end) {
+            write_element(output, Element::TcpStart, &start);
+            write_element(output, Element::TcpEnd, &end);
+        }
+        if let (Some(start), Some(end)) = (softnet_start, softnet_end) {
+            write_element(output, Element::SoftnetStart, &start);
+            write_element(output, Element::SoftnetEnd, &end);
+        }
+        if let (Some(start), Some(end)) = (irq_start, irq_end) {
+            write_element(output, Element::IrqStart, &start);
+            write_element(output, Element::IrqEnd, &end);
+        }
+        if let (Some(start), Some(end)) = (softirqs_start, softirqs_end) {
+            write_element(output, Element::SoftirqsStart, &start);
+            write_element(output, Element::SoftirqsEnd, &end);
+        }
+        if let (Some(start), Some(end)) = (idle_start, idle_end) {
+            write_element(output, Element::IdleStart, &start);
+            write_element(output, Element::IdleEnd, &end);
+        }
+        if let (Some(start), Some(end)) = (schedstat_start, schedstat_end) {
+            write_element(output, Element::SchedstatStart, &start);
+            write_element(output, Element::SchedstatEnd, &end);
+        }
+        if let Some(sockets) = sockets {
+            write_element(output, Element::Sockets, &sockets);
+        }
+        if let (Some(start), Some(end)) = (disk_start, disk_end) {
+            write_element(output, Element::DiskStart, &start);
+            write_element(output, Element::DiskEnd, &end);
+        }
+        if let (Some(start), Some(end)) = (pressure_start, pressure_end) {
+            write_element(output, Element::PressureStart, &start);
+            write_element(output, Element::PressureEnd, &end);
+        }
+        output
+            .write_all(format!("</{}>\n", Element::LogEntry.name()).as_bytes())
+            .unwrap();
+        // Push entries out for readers streaming the log and in case the
+        // logger dies, but not so often that writing disturbs the machine.
+        unflushed += 1;
+        let due = match config.flush {
+            Flush::Entries(entries) => unflushed >= entries,
+            Flush::Every(every) => flushed_at.elapsed() >= every,
+        };
+        if due {
+            if config.sync {
+                output.sync();
+            } else {
+                output.flush().unwrap();
+            }
+            unflushed = 0;
+            flushed_at = Instant::now();
+        }
+
+        tick += 1;
+        if duration.is_some_and(|duration| interval * (tick - warmup_ticks) >= duration)
+            || stop.load(Ordering::SeqCst)
+        {
+            break;
+        }
+
+        part_entries += 1;
+        let full = config
+            .rotate_entries
+            .is_some_and(|entries| part_entries >= entries)
+            || config.rotate_size.is_some_and(|size| output.size() >= size);
+        let next = full.then(|| {
+            let path = part_path(&config.output, part + 1);
+            (Output::create(&path), path)
+        });
+        match next {
+            // The entries go on in this part, and the next try is a part later.
+            Some((Err(error), path)) => {
+                eprintln!(
+                    "\nCannot create {}: {}, continuing in this part",
+                    path, error
+                );
+                part_entries = 0;
+            }
+            Some((Ok(next), path)) => {
+                write_markers(output, &markers, start_time + warmup + part_start);
+                // In whole milliseconds, as logged, so that the parts add up.
+                let ended = start_time.elapsed().saturating_sub(warmup).as_millis();
+                let ended = Duration::from_millis(ended as u64);
+                part += 1;
+                part_entries = 0;
+                let name = Path::new(&path).file_name().unwrap().to_string_lossy();
+                write_log_end(output, ended - part_start, None, Some(&name));
+                part_start = ended;
+                std::mem::replace(output, next).finish(config.sync);
+                write_log_start(output, config, Some(part));
+                eprintln!("\nContinuing in {}", path);
+            }
+            None => {}
+        }
+
+        i += 1;
+        // Formatted up front, as stderr would write each piece separately.
+        let progress = if duration.is_some() {
+            format!("\rLogged {} times", i)
+        } else {
+            format!("\rElapsed {} ({} entries)", format_elapsed(elapsed), i)
+        };
+        stderr().write_all(progress.as_bytes()).unwrap();
+    }
+    write_markers(output, &markers, start_time + warmup + part_start);
+    eprintln!();
+
+    start_time.elapsed().saturating_sub(warmup + part_start)
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+pub struct Config {
+    /// The `id` of the log, also the `{id}` of its output name.
+    pub id: Uuid,
+    /// The log file or, with `remote`, its spool.
+    pub output: String,
+    /// `host:port` of the `profiler collect` to stream the log to.
+    pub remote: Option<String>,
+    /// Address to serve the latest interval's metrics to Prometheus on.
+    pub prometheus: Option<String>,
+    pub platform: Platform,
+    /// `None` captures until interrupted.
+    pub duration: Option<Duration>,
+    pub interval: Duration,
+    pub ifaces: Vec<String>,
+    /// Block devices to keep of /proc/diskstats, all when empty.
+    pub disks: Vec<String>,
+    pub pids: Vec<u32>,
+    /// cgroup v2 directories whose cpu.stat to sample.
+    pub cgroups: Vec<String>,
+    pub thermal: bool,
+    /// Sample the current, voltage and charge of the battery.
+    pub battery: bool,
+    /// sysfs file to sample the GPU's load from, as `find_gpu` reads it.
+    pub gpu: Option<String>,
+    /// Run dumpsys and read /proc/net/wireless for the network type and
+    /// signal at the end of each interval.
+    pub radio: bool,
+    /// Sample /proc/softirqs, for its split of softirq time by type.
+    pub softirqs: bool,
+    /// Sample the usage and time of each CPU's cpuidle states.
+    pub cpuidle: bool,
+    /// Sample /proc/schedstat, for how long tasks waited to run.
+    pub schedstat: bool,
+    /// Sample the time each CPU spent at each frequency.
+    pub time_in_state: bool,
+    pub events: String,
+    pub perf: bool,
+    /// How perf splits system-wide counts, recorded unless per core.
+    pub perf_granularity: PerfGranularity,
+    /// The only CPUs perf counts on, as `0-3,6`.
+    pub cpu_list: Option<String>,
+    /// Milliseconds this clock is ahead of the `profiler sync` coordinator's.
+    pub clock_offset: Option<i64>,
+    /// Roll over to a new part once the file is this many bytes long.
+    pub rotate_size: Option<u64>,
+    /// Roll over to a new part after this many entries.
+    pub rotate_entries: Option<u32>,
+    /// The options the capture runs with, defaults and `--config` included,
+    /// as TOML that `--config` reads back.
+    pub options: Option<String>,
+    /// Record the wall-clock time of every entry, not only of the start.
+    pub timestamps: bool,
+    pub flush: Flush,
+    /// fsync the log at every flush, for devices that may lose power.
+    pub sync: bool,
+    /// CPU the logger and perf were pinned to, recorded in the metadata.
+    pub pin_cpu: Option<u32>,
+    /// Sampled but not logged before the duration starts counting, a whole
+    /// number of intervals.
+    pub warmup: Duration,
+    /// Command line of the command the capture lasts for, with `profiler run`.
+    pub command: Option<String>,
+    /// FIFO to read markers from, a label a line.
+    pub markers: Option<String>,
+    /// Keeps only the /proc/interrupts rows it matches.
+    pub irq_filter: Option<Regex>,
+    /// ss filter of the TCP connections to snapshot at the end of every
+    /// interval.
+    pub sockets: Option<String>,
+}
+
+impl Config {
+    /// A capture of `platform` every `interval` to stdout, with nothing
+    /// optional sampled and without perf.
+    pub fn bare(platform: Platform, interval: Duration) -> Self {
+        Config {
+            id: Uuid::nil(),
+            output: "-".to_string(),
+            remote: None,
+            prometheus: None,
+            platform,
+            duration: None,
+            interval,
+            ifaces: Vec::new(),
+            disks: Vec::new(),
+            pids: Vec::new(),
+            cgroups: Vec::new(),
+            thermal: false,
+            battery: false,
+            gpu: None,
+            radio: false,
+            softirqs: false,
+            cpuidle: false,
+            schedstat: false,
+            time_in_state: false,
+            events: platform.default_events().to_string(),
+            perf: false,
+            perf_granularity: PerfGranularity::Core,
+            options: None,
+            cpu_list: None,
+            clock_offset: None,
+            rotate_size: None,
+            rotate_entries: None,
+            timestamps: false,
+            flush: Flush::Entries(1),
+            sync: false,
+            pin_cpu: None,
+            warmup: Duration::ZERO,
+            command: None,
+            markers: None,
+            irq_filter: None,
+            sockets: None,
+        }
+    }
+}
+
+/// When the capture loop pushes buffered entries out to the log. Entries
+/// are only lost if the logger is killed; the end of a capture, including
+/// one stopped by the first SIGINT, always flushes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flush {
+    /// After this many entries.
+    Entries(u32),
+    /// After the first entry at least this long since the last flush.
+    Every(Duration),
+}
+
+/// One `cpuN package core cluster capacity` line per CPU, `-` for what the
+/// kernel doesn't show: cluster_id before 5.16 and cpu_capacity off arm.
+fn read_topology() -> String {
+    let mut cpus: Vec<(u32, String)> = std::fs::read_dir("/sys/devices/system/cpu")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let cpu = name.to_str()?.strip_prefix("cpu")?.parse().ok()?;
+            let read = |file: &str| {
+                read_to_string(entry.path().join(file))
+                    .map_or("-".to_string(), |value| value.trim().to_string())
+            };
+            let line = [
+                "topology/physical_package_id",
+                "topology/core_id",
+                "topology/cluster_id",
+                "cpu_capacity",
+            ]
+            .map(read)
+            .join(" ");
+            Some((cpu, line))
+        })
+        .collect();
+    cpus.sort();
+
+    cpus.iter()
+        .map(|(cpu, line)| format!("cpu{} {}\n", cpu, line))
+        .collect()
+}
+
+/// The machine the log is captured on, leaving out what can't be read.
+fn read_metadata(platform: Platform) -> Vec<(Element, String)> {
+    let mut metadata = Vec::new();
+    if let Ok(hostname) = read_to_string("/proc/sys/kernel/hostname") {
+        metadata.push((Element::Hostname, hostname.trim().to_string()));
+    }
+    if let Ok(uname) = cmd("uname", ["-a"]).read() {
+        metadata.push((Element::Uname, uname));
+    }
+    // x86 names the model of every CPU, arm devices at most the SoC.
+    let cpuinfo = read_to_string("/proc/cpuinfo").unwrap_or_default();
+    let mut models: Vec<&str> = Vec::new();
+    for (key, value) in cpuinfo.lines().filter_map(|line| line.split_once(':')) {
+        if matches!(key.trim(), "model name" | "Hardware") && !models.contains(&value.trim()) {
+            models.push(value.trim());
+        }
+    }
+    if !models.is_empty() {
+        metadata.push((Element::CpuModel, models.join("; ")));
+    }
+    metadata.push((Element::LogicalCpus, num_cpus::get().to_string()));
+    metadata.push((Element::PhysicalCpus, num_cpus::get_physical().to_string()));
+    let topology = read_topology();
+    if !topology.is_empty() {
+        metadata.push((Element::Topology, topology));
+    }
+    if platform == Platform::Android {
+        let model = cmd("getprop", ["ro.product.model"]).stderr_null().read();
+        if let Some(model) = model.ok().filter(|model| !model.trim().is_empty()) {
+            metadata.push((Element::DeviceModel, model.trim().to_string()));
+        }
+    }
+    metadata
+}
+
+/// The name of the machine for output names: the serial number adb knows an
+/// Android device by, whose hostname is usually `localhost`, or else the
+/// hostname.
+pub fn device_id(platform: Platform) -> String {
+    let serial = match platform {
+        Platform::Android => cmd("getprop", ["ro.serialno"]).stderr_null().read().ok(),
+        Platform::Ubuntu => None,
+    };
+    serial
+        .filter(|serial| !serial.trim().is_empty())
+        .or_else(|| read_to_string("/proc/sys/kernel/hostname").ok())
+        .unwrap_or_default()
+        .trim()
+        .replace('/', "-")
+}
+
+/// Fills in the `{date}`, `{time}`, `{host}` and `{id}` of an output name,
+/// the date and time of `start` in UTC as `2024-03-01` and `123000`.
+pub fn expand_output(
+    template: &str,
+    id: Uuid,
+    start: SystemTime,
+    host: &str,
+) -> Result<String, String> {
+    let start = timestamp::format(start);
+    let fields = [
+        ("date", start[..10].to_string()),
+        ("time", start[11..19].replace(':', "")),
+        ("host", host.to_string()),
+        ("id", id.to_string()),
+    ];
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        output.push_str(&rest[..open]);
+        let close = rest[open..]
+            .find('}')
+            .ok_or_else(|| format!("\"{}\" has a {{ without a }}", template))?;
+        let name = &rest[open + 1..open + close];
+        let (_, value) = fields
+            .iter()
+            .find(|(field, _)| *field == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown placeholder {{{}}}, expected {{date}}, {{time}}, {{host}} or {{id}}",
+                    name
+                )
+            })?;
+        output.push_str(value);
+        rest = &rest[open + close + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// `output` with `.partN` ahead of its `.xml` or `.xml.gz`, e.g.
+/// `run.part2.xml`, for the parts after the first.
+pub fn part_path(output: &str, part: u32) -> String {
+    let extension = [".xml.gz", ".xml", ".gz"]
+        .into_iter()
+        .find(|extension| output.ends_with(extension))
+        .unwrap_or_default();
+    format!(
+        "{}.part{}{}",
+        &output[..output.len() - extension.len()],
+        part,
+        extension
+    )
+}
+
+/// Opens the `<log>` element, whose attributes describe the capture, and
+/// writes the `<metadata>` describing the machine. `part` is the number of
+/// the file with `--rotate-*`; the parts after the first start as the one
+/// before ends, past any warmup.
+pub fn write_log_start(output: &mut impl Write, config: &Config, part: Option<u32>) {
+    write_log_header(output, config, part, read_metadata(config.platform));
+}
+
+/// `write_log_start` describing another machine by `metadata`, for logs
+/// assembled from what it sends.
+pub fn write_log_header(
+    output: &mut impl Write,
+    config: &Config,
+    part: Option<u32>,
+    mut metadata: Vec<(Element, String)>,
+) {
+    let warmup = if part.is_some_and(|part| part > 1) {
+        Duration::ZERO
+    } else {
+        config.warmup
+    };
+    let mut attributes = vec![
+        (schema::ID, config.id.to_string()),
+        (schema::SCHEMA, schema::VERSION.to_string()),
+        (schema::PLATFORM, config.platform.name().to_string()),
+        (
+            schema::DURATION,
+            config.duration.unwrap_or_default().as_secs().to_string(),
+        ),
+        (schema::INTERVAL_MS, config.interval.as_millis().to_string()),
+        (schema::START, timestamp::format(SystemTime::now() + warmup)),
+    ];
+    if !warmup.is_zero() {
+        attributes.push((schema::WARMUP_MS, warmup.as_millis().to_string()));
+    }
+    if let Some(part) = part {
+        attributes.push((schema::PART, part.to_string()));
+    }
+    if let Some(offset) = config.clock_offset {
+        attributes.push((schema::CLOCK_OFFSET_MS, offset.to_string()));
+    }
+    if config.perf {
+        attributes.push((schema::EVENTS, config.events.clone()));
+        if config.perf_granularity != PerfGranularity::Core {
+            attributes.push((
+                schema::PERF_GRANULARITY,
+                config.perf_granularity.name().to_string(),
+            ));
+        }
+    } else {
+        attributes.push((schema::PERF, schema::PERF_OFF.to_string()));
+    }
+    if !config.pids.is_empty() {
+        let pids: Vec<_> = config.pids.iter().map(|pid| pid.to_string()).collect();
+        attributes.push((schema::PIDS, pids.join(",")));
+    }
+    if let Some(cpus) = &config.cpu_list {
+        attributes.push((schema::CPU_LIST, cpus.clone()));
+    }
+    let mut start = start_tag(Element::Log, &attributes, false);
+    start.push_str(&start_tag(Element::Metadata, &[], false));
+    if let Some(cpu) = config.pin_cpu {
+        metadata.push((Element::PinnedCpu, cpu.to_string()));
+    }
+    if let Some(command) = &config.command {
+        metadata.push((Element::Command, command.clone()));
+    }
+    if let Some(options) = &config.options {
+        metadata.push((Element::Options, options.clone()));
+    }
+    for (element, value) in metadata {
+        start.push_str(&format!(
+            "<{0}>{1}</{0}>\n",
+            element.name(),
+            escape_xml(&value)
+        ));
+    }
+    start.push_str(&format!("</{}>\n", Element::Metadata.name()));
+    output.write_all(start.as_bytes()).unwrap();
+}
+
+/// An entry of no more than two /proc/stat snapshots, /proc/meminfo and
+/// /proc/loadavg, for logs assembled from what another machine sends.
+pub fn write_proc_entry(
+    output: &mut impl Write,
+    time: Duration,
+    proc_start: &str,
+    proc_end: &str,
+    meminfo: &str,
+    loadavg: &str,
+) {
+    let time = [(schema::TIME_MS, time.as_millis().to_string())];
+    output
+        .write_all(start_tag(Element::LogEntry, &time, false).as_bytes())
+        .unwrap();
+    write_element(output, Element::ProcStart, proc_start);
+    write_element(output, Element::ProcEnd, proc_end);
+    write_element(output, Element::Meminfo, meminfo);
+    write_element(output, Element::Loadavg, loadavg);
+    output
+        .write_all(format!("</{}>\n", Element::LogEntry.name()).as_bytes())
+        .unwrap();
+}
+
+/// Records how long the capture actually ran, how the command it ran for
+/// ended, if any, and the `next` part it goes on in, and closes the `<log>`
+/// element.
+pub fn write_log_end(
+    output: &mut impl Write,
+    elapsed: Duration,
+    status: Option<ExitStatus>,
+    next: Option<&str>,
+) {
+    let mut attributes = vec![(schema::DURATION_ACTUAL, elapsed.as_millis().to_string())];
+    if let Some(next) = next {
+        attributes.push((schema::NEXT, escape_xml(next).replace('"', "&quot;")));
+    }
+    match status.map(|status| (status.code(), status.signal())) {
+        Some((Some(code), _)) => attributes.push((schema::EXIT_CODE, code.to_string())),
+        Some((None, Some(signal))) => attributes.push((schema::EXIT_SIGNAL, signal.to_string())),
+        _ => {}
+    }
+    output
+        .write_all(
+            format!(
+                "{}</{}>\n",
+                start_tag(Element::LogEnd, &attributes, true),
+                Element::Log.name()
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report;
+
+    const EVENTS: [&str; 2] = ["cycles", "context-switches"];
+
+    /// perf's `--per-core` output, `threads` being the hyperthreads summed into
+    /// every core's row.
+    fn ubuntu_interval(time: u32, cores: usize, threads: usize) -> Vec<String> {
+        let mut lines = Vec::new();
+        for core in 0..cores {
+            for event in EVENTS {
+                lines.push(format!(
+                    "     {}.000 S0-D0-C{}           {}            1000 {}\n",
+                    time, core, threads, event
+                ));
+            }
+        }
+        lines
+    }
+
+    /// simpleperf's `--per-core` output, with a row per logical CPU.
+    fn android_interval(cpus: usize) -> Vec<String> {
+        let mut lines = vec!["Performance counter statistics,\n".to_string()];
+        for cpu in 0..cpus {
+            for event in EVENTS {
+                lines.push(format!("{},1000,{},1.000 M/sec,(100%),\n", cpu, event));
+            }
+        }
+        lines.push("Total test time,1.000100,seconds,\n".to_string());
+        lines
+    }
+
+    fn group(platform: Platform, lines: &[String]) -> Vec<String> {
+        let mut grouper = PerfGrouper::new(platform);
+        lines.iter().filter_map(|line| grouper.push(line)).collect()
+    }
+
+    #[test]
+    fn groups_ubuntu_output_per_interval() {
+        for (cores, threads) in [(4, 1), (4, 2), (6, 2)] {
+            let mut lines = vec!["# started on Mon Jan  1 00:00:00 2024\n".to_string()];
+            for time in 1..=3 {
+                lines.extend(ubuntu_interval(time, cores, threads));
+            }
+
+            let entries = group(Platform::Ubuntu, &lines);
+            assert_eq!(entries.len(), 3);
+            for (time, entry) in (1..=3).zip(entries.iter()) {
+                assert_eq!(*entry, ubuntu_interval(time, cores, threads).concat());
+            }
+        }
+    }
+
+    #[test]
+    fn groups_ubuntu_intervals_missing_a_core() {
+        let mut lines = ubuntu_interval(1, 4, 1);
+        lines.extend(ubuntu_interval(2, 3, 1));
+        lines.extend(ubuntu_interval(3, 4, 1));
+        lines.extend(ubuntu_interval(4, 4, 1));
+
+        let entries = group(Platform::Ubuntu, &lines);
+        assert_eq!(
+            entries,
+            [
+                ubuntu_interval(1, 4, 1).concat(),
+                ubuntu_interval(2, 3, 1).concat(),
+                ubuntu_interval(3, 4, 1).concat(),
+                ubuntu_interval(4, 4, 1).concat(),
+            ]
+        );
+    }
+
+    #[test]
+    fn carries_rows_of_an_interval_sent_without_them() {
+        // Three cores the first interval, a fourth online from the second.
+        let mut lines = ubuntu_interval(1, 3, 1);
+        for time in 2..=4 {
+            lines.extend(ubuntu_interval(time, 4, 1));
+        }
+
+        let entries = group(Platform::Ubuntu, &lines);
+        let (sent, late) = lines[6..14].split_at(6);
+        assert_eq!(
+            entries,
+            [
+                ubuntu_interval(1, 3, 1).concat(),
+                sent.concat(),
+                late.concat() + &ubuntu_interval(3, 4, 1).concat(),
+                ubuntu_interval(4, 4, 1).concat(),
+            ]
+        );
+        assert_eq!(entries.concat(), lines.concat());
+        // The fourth core's count for the last interval is the one it keeps.
+        let carried = entries[2].replacen(" 1000 ", " 7 ", 1);
+        let perf = report::parse_and_analyze_perf(&carried, Platform::Ubuntu).unwrap();
+        assert_eq!(perf["S0-D0-C3"].get(report::CYCLES), Some(1000));
+    }
+
+    #[test]
+    fn groups_android_output_per_interval() {
+        for cpus in [4, 8] {
+            let lines: Vec<_> = (0..3).flat_map(|_| android_interval(cpus)).collect();
+
+            let entries = group(Platform::Android, &lines);
+            assert_eq!(entries.len(), 3);
+            for entry in entries {
+                assert_eq!(entry, android_interval(cpus).concat());
+            }
+        }
+    }
+
+    #[test]
+    fn expands_output_placeholders() {
+        let start = std::time::UNIX_EPOCH + Duration::from_millis(1_709_296_200_250);
+        let expand = |template| expand_output(template, Uuid::nil(), start, "pixel-8");
+        assert_eq!(
+            expand("run-{date}-{time}-{host}-{id}.xml").unwrap(),
+            "run-2024-03-01-123000-pixel-8-00000000-0000-0000-0000-000000000000.xml"
+        );
+        assert_eq!(expand("plain.xml.gz").unwrap(), "plain.xml.gz");
+        assert!(expand("run-{hostname}.xml").is_err());
+        assert!(expand("run-{date.xml").is_err());
+    }
+
+    /// A log of two entries written as the logger writes them.
+    fn written_log() -> String {
+        let mut config = Config::bare(Platform::Ubuntu, Duration::from_secs(1));
+        config.duration = Some(Duration::from_secs(2));
+        let mut output = Vec::new();
+        let metadata = vec![(Element::Hostname, "build & test".to_string())];
+        write_log_header(&mut output, &config, None, metadata);
+        for time in [0, 1000] {
+            write_proc_entry(
+                &mut output,
+                Duration::from_millis(time),
+                include_str!("../tests/fixtures/ubuntu/proc-stat-start.txt"),
+                include_str!("../tests/fixtures/ubuntu/proc-stat-end.txt"),
+                "MemTotal: 2048 kB\nMemAvailable: 1024 kB\n",
+                "0.50 0.40 0.30 1/100 4242\n",
+            );
+        }
+        write_log_end(&mut output, Duration::from_millis(2001), None, None);
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn reads_back_the_logs_it_writes() {
+        let report = report::get_report(written_log().as_bytes(), false, None).unwrap();
+        assert_eq!(report.schema, Some(schema::VERSION));
+        assert_eq!(report.entries.len(), 2);
+        assert_eq!(report.entries[1].time, Duration::from_secs(1));
+        assert_eq!(report.interval, Duration::from_secs(1));
+        assert_eq!(report.duration_actual, Some(Duration::from_millis(2001)));
+        assert_eq!(report.metadata.hostname.as_deref(), Some("build & test"));
+        assert!(report.perf_events.is_empty());
+        assert!(!report.truncated);
+    }
+
+    #[test]
+    fn logs_perf_output_with_angle_brackets_as_read() {
+        let perf = include_str!("../tests/fixtures/ubuntu/perf-not-counted.txt");
+        let mut config = Config::bare(Platform::Ubuntu, Duration::from_secs(1));
+        config.perf = true;
+        config.events = EVENTS.join(",");
+        let mut output = Vec::new();
+        write_log_header(&mut output, &config, None, Vec::new());
+        write_proc_entry(
+            &mut output,
+            Duration::ZERO,
+            include_str!("../tests/fixtures/ubuntu/proc-stat-start.txt"),
+            include_str!("../tests/fixtures/ubuntu/proc-stat-end.txt"),
+            "MemTotal: 2048 kB\nMemAvailable: 1024 kB\n",
+            "0.50 0.40 0.30 1/100 4242\n",
+        );
+        // Into the entry ahead of its end, as the logger orders them.
+        let end = format!("</{}>\n", Element::LogEntry.name());
+        output.truncate(output.len() - end.len());
+        write_element(&mut output, Element::Perf, perf);
+        output.extend(end.as_bytes());
+        write_log_end(&mut output, Duration::from_secs(1), None, None);
+
+        let log = String::from_utf8(output).unwrap();
+        assert!(log.contains("<![CDATA[\n# started on"));
+        assert!(!log.contains("&lt;"));
+        let report = report::get_report(log.as_bytes(), false, None).unwrap();
+        let perf = &report.entries[0].perf;
+        assert_eq!(perf["S0-D0-C0"].get(report::CYCLES), Some(1_000_000));
+        assert!(perf["S0-D0-C0"].missing.contains(report::CONTEXT_SWITCHES));
+        assert!(perf["S0-D0-C1"].missing.contains(report::CONTEXT_SWITCHES));
+        assert_eq!(perf["all"].get(report::CYCLES), Some(3_000_000));
+        assert_eq!(cdata("a]]>b"), "<![CDATA[a]]]]><![CDATA[>b]]>");
+    }
+
+    #[test]
+    fn refuses_logs_of_a_newer_schema_and_reads_unversioned_ones() {
+        let log = written_log();
+        let attribute = format!("{}=\"{}\"", schema::SCHEMA, schema::VERSION);
+        assert!(log.contains(&attribute));
+
+        let newer = log.replace(
+            &attribute,
+            &format!("{}=\"{}\"", schema::SCHEMA, schema::VERSION + 1),
+        );
+        match report::get_report(newer.as_bytes(), false, None) {
+            Err(report::ParseError::NewerSchema(version)) => {
+                assert_eq!(version, schema::VERSION + 1)
+            }
+            other => panic!("{:?}", other.map(|report| report.schema)),
+        }
+
+        // Logs predating the version wrote entry times as `time`.
+        let unversioned = log.replace(&attribute, "").replace("time-ms=", "time=");
+        let report = report::get_report(unversioned.as_bytes(), false, None).unwrap();
+        assert_eq!(report.schema, None);
+        assert_eq!(report.entries[1].time, Duration::from_secs(1));
+        let mixed = log.replace("time-ms=", "time=");
+        assert!(report::get_report(mixed.as_bytes(), false, None).is_err());
+    }
+
+    #[test]
+    fn streams_the_spool_from_the_collectors_offset() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let id = Uuid::new_v4();
+        // A collector that already has the first 5 bytes.
+        let collector = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut hello = String::new();
+            reader.read_line(&mut hello).unwrap();
+            writeln!(&stream, "5").unwrap();
+            let mut rest = String::new();
+            reader.read_to_string(&mut rest).unwrap();
+            (hello, rest)
+        });
+        let spool = std::env::temp_dir().join(format!("profiler-test-{}.xml", id));
+        let spool = spool.to_str().unwrap();
+        let mut output = Output::remote(&address, id, spool).unwrap();
+        output.write_all(b"<log>entries</log>").unwrap();
+        output.finish(false);
+
+        let (hello, rest) = collector.join().unwrap();
+        assert_eq!(hello, format!("{} {}\n", REMOTE_HELLO, id));
+        assert_eq!(rest, "entries</log>");
+        assert!(std::fs::metadata(spool).is_err());
+    }
+}
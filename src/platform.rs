@@ -0,0 +1,126 @@
+use clap::ValueEnum;
+use lazy_regex::regex_captures;
+use serde::{Deserialize, Serialize};
+
+/// Which perf tool the logger drives, and so which output format the report
+/// parses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Ubuntu,
+    Android,
+}
+
+impl Platform {
+    /// The cargo features pick the default; without one, a `simpleperf` on
+    /// PATH means we are running on Android.
+    pub fn detect() -> Self {
+        if cfg!(feature = "android") {
+            Platform::Android
+        } else if cfg!(feature = "ubuntu") || !on_path("simpleperf") {
+            Platform::Ubuntu
+        } else {
+            Platform::Android
+        }
+    }
+
+    /// For logs that don't record their platform: the cargo features pick the
+    /// default, otherwise simpleperf's comma separated rows in the perf output
+    /// of the first entry give it away.
+    pub fn guess(perf: &str) -> Self {
+        if cfg!(feature = "android") {
+            return Platform::Android;
+        } else if cfg!(feature = "ubuntu") {
+            return Platform::Ubuntu;
+        }
+
+        let android = perf
+            .lines()
+            .any(|line| regex_captures!(r#"^\d+,\d+,[\w-]+,"#, line).is_some());
+        if android {
+            Platform::Android
+        } else {
+            Platform::Ubuntu
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ubuntu" => Some(Platform::Ubuntu),
+            "android" => Some(Platform::Android),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::Ubuntu => "ubuntu",
+            Platform::Android => "android",
+        }
+    }
+
+    /// Rows perf prints per event and interval. perf's `--per-core` sums the
+    /// hyperthreads of a core whereas simpleperf's has a row per logical CPU,
+    /// and perf attached to processes on ubuntu reports a single row.
+    pub fn perf_rows(self, per_process: bool) -> usize {
+        match (self, per_process) {
+            (Platform::Ubuntu, true) => 1,
+            (Platform::Ubuntu, false) => num_cpus::get_physical(),
+            (Platform::Android, _) => num_cpus::get(),
+        }
+    }
+
+    pub fn default_events(self) -> &'static str {
+        match self {
+            Platform::Ubuntu => "cycles,context-switches",
+            Platform::Android => "cpu-cycles,context-switches",
+        }
+    }
+}
+
+/// What perf's counts are split by on ubuntu; simpleperf splits per CPU or
+/// not at all. Per-process counts are never split.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PerfGranularity {
+    #[default]
+    Core,
+    Socket,
+    System,
+}
+
+impl PerfGranularity {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "core" => Some(PerfGranularity::Core),
+            "socket" => Some(PerfGranularity::Socket),
+            "system" => Some(PerfGranularity::System),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PerfGranularity::Core => "core",
+            PerfGranularity::Socket => "socket",
+            PerfGranularity::System => "system",
+        }
+    }
+
+    /// The flag asking `platform`'s perf for these counts, empty for the
+    /// system-wide total. simpleperf has no per-socket counts, which
+    /// `profile` rejects up front.
+    pub fn flag(self, platform: Platform) -> &'static str {
+        match (self, platform) {
+            (PerfGranularity::Core, _) => "--per-core",
+            (PerfGranularity::Socket, Platform::Ubuntu) => "--per-socket",
+            (PerfGranularity::Socket, Platform::Android) => unreachable!(),
+            (PerfGranularity::System, _) => "",
+        }
+    }
+}
+
+pub fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
@@ -0,0 +1,165 @@
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    platform::Platform,
+    report::{
+        analyze_proc, compare_cpus, parse_and_analyze_perf, parse_proc, CONTEXT_SWITCHES, CYCLES,
+    },
+};
+
+/// How long a scraper gets to send its request and take the reply, so that
+/// a stuck one only holds up its own connection.
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves the metrics of the latest interval to Prometheus at `/metrics`.
+/// The capture loop only swaps the text under the lock, so scrapes, or the
+/// lack of them, never hold it up.
+pub struct Exporter {
+    metrics: Arc<Mutex<String>>,
+}
+
+impl Exporter {
+    /// Listens on `address`, e.g. `:9184` for every interface.
+    pub fn serve(address: &str) -> io::Result<Self> {
+        let address = if address.starts_with(':') {
+            format!("0.0.0.0{}", address)
+        } else {
+            address.to_string()
+        };
+        let listener = TcpListener::bind(address)?;
+        let metrics = Arc::new(Mutex::new(String::new()));
+        let served = metrics.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let served = served.clone();
+                thread::spawn(move || {
+                    let _ = reply(stream, &served);
+                });
+            }
+        });
+        Ok(Exporter { metrics })
+    }
+
+    pub fn update(&self, metrics: String) {
+        *self.metrics.lock().unwrap() = metrics;
+    }
+}
+
+fn reply(stream: TcpStream, metrics: &Mutex<String>) -> io::Result<()> {
+    stream.set_read_timeout(Some(SCRAPE_TIMEOUT))?;
+    stream.set_write_timeout(Some(SCRAPE_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // The headers, which say nothing the reply depends on.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+    let (status, body) = if path == "/metrics" || path.starts_with("/metrics?") {
+        ("200 OK", metrics.lock().unwrap().clone())
+    } else {
+        ("404 Not Found", "Metrics are at /metrics\n".to_string())
+    };
+    write!(
+        &stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+fn gauge(text: &mut String, name: &str, help: &str, values: &[(String, f64)]) {
+    if values.is_empty() {
+        return;
+    }
+    writeln!(text, "# HELP {} {}", name, help).unwrap();
+    writeln!(text, "# TYPE {} gauge", name).unwrap();
+    for (cpu, value) in values {
+        writeln!(text, "{}{{cpu=\"{}\"}} {}", name, cpu, value).unwrap();
+    }
+}
+
+/// The exposition of an interval's /proc/stat snapshots and perf counts:
+/// `profiler_cpu_load`, `profiler_cpu_cycles` and
+/// `profiler_cpu_context_switches`, labelled by `cpu`, `all` for the total.
+pub fn render(proc_start: &str, proc_end: &str, perf: Option<&str>, platform: Platform) -> String {
+    let mut loads = Vec::new();
+    if let (Ok(start), Ok(end)) = (parse_proc(proc_start), parse_proc(proc_end)) {
+        for (cpu, end) in end.iter() {
+            if let Some(Ok(report)) = start.get(cpu).map(|start| analyze_proc(start, end)) {
+                loads.push((cpu.clone(), report.load));
+            }
+        }
+    }
+    let perf = perf
+        .and_then(|perf| parse_and_analyze_perf(perf, platform).ok())
+        .unwrap_or_default();
+    let counts = |event: &str| {
+        let mut counts: Vec<_> = perf
+            .iter()
+            .filter_map(|(cpu, report)| Some((cpu.clone(), report.get(event)? as f64)))
+            .collect();
+        counts.sort_by(|(a, _), (b, _)| compare_cpus(a, b));
+        counts
+    };
+    loads.sort_by(|(a, _), (b, _)| compare_cpus(a, b));
+
+    let mut text = String::new();
+    gauge(
+        &mut text,
+        "profiler_cpu_load",
+        "Percentage of the latest interval the CPU was busy.",
+        &loads,
+    );
+    gauge(
+        &mut text,
+        "profiler_cpu_cycles",
+        "CPU cycles perf counted over the latest interval.",
+        &counts(CYCLES),
+    );
+    gauge(
+        &mut text,
+        "profiler_cpu_context_switches",
+        "Context switches perf counted over the latest interval.",
+        &counts(CONTEXT_SWITCHES),
+    );
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_gauges_by_cpu() {
+        let start = "cpu  100 0 100 800 0 0 0 0 0 0\ncpu0 50 0 50 400 0 0 0 0 0 0\n";
+        let end = "cpu  150 0 150 900 0 0 0 0 0 0\ncpu0 100 0 100 400 0 0 0 0 0 0\n";
+        let perf = "0,1000,cpu-cycles,1,1,\n0,7,context-switches,1,1,\n";
+        assert_eq!(
+            render(start, end, Some(perf), Platform::Android),
+            "# HELP profiler_cpu_load Percentage of the latest interval the CPU was busy.
+# TYPE profiler_cpu_load gauge
+profiler_cpu_load{cpu=\"0\"} 100
+profiler_cpu_load{cpu=\"all\"} 50
+# HELP profiler_cpu_cycles CPU cycles perf counted over the latest interval.
+# TYPE profiler_cpu_cycles gauge
+profiler_cpu_cycles{cpu=\"0\"} 1000
+profiler_cpu_cycles{cpu=\"all\"} 1000
+# HELP profiler_cpu_context_switches Context switches perf counted over the latest interval.
+# TYPE profiler_cpu_context_switches gauge
+profiler_cpu_context_switches{cpu=\"0\"} 7
+profiler_cpu_context_switches{cpu=\"all\"} 7
+"
+        );
+    }
+}
@@ -1,100 +1,1213 @@
 use std::{
-    collections::{HashMap, HashSet},
-    process::exit,
-    time::Duration,
+    collections::{BTreeSet, HashMap, HashSet},
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, SystemTime},
 };
 
-use colored::Colorize;
+use clap::ValueEnum;
+use flate2::read::GzDecoder;
 use lazy_regex::regex_captures;
-use quick_xml::Reader;
-use regex::Regex;
-use serde::{Deserialize, Serialize};
+use memmap2::{Mmap, UncheckedAdvice};
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct LogEntry {
-    time: String,
-    #[serde(rename = "proc-start")]
-    proc_start: String,
-    #[serde(rename = "proc-end")]
-    proc_end: String,
-    perf: String,
-}
+use crate::{
+    platform::{PerfGranularity, Platform},
+    report::cache::{load_cached, Cache},
+    schema::{self, Element},
+    timestamp,
+};
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Log {
-    id: String,
-    duration: String,
-    interval: String,
-    #[serde(rename = "$value")]
-    entries: Vec<LogEntry>,
+pub mod cache;
+pub mod stats;
+
+/// The text of one `<log-entry>`, kept only until the entry is analyzed.
+#[derive(Debug, Default)]
+pub struct LogEntry {
+    pub time: String,
+    /// `time` came from the unitless attribute of logs predating `time-ms`.
+    pub legacy_time: bool,
+    /// Only in logs captured with `--timestamps`.
+    pub wall_time: Option<String>,
+    /// Only when the logger skipped intervals before this entry.
+    pub skipped: Option<String>,
+    pub proc_start: Option<String>,
+    pub proc_end: Option<String>,
+    pub perf: Option<String>,
+    /// Absent in logs captured before memory was sampled.
+    pub meminfo: Option<String>,
+    pub loadavg: Option<String>,
+    /// `cpuN kHz` lines, absent in logs captured before frequency sampling.
+    pub freq: Option<String>,
+    /// `thermal_zoneN type millidegrees` lines, only with `--thermal`.
+    pub thermal: Option<String>,
+    pub battery: Option<String>,
+    pub gpu: Option<String>,
+    pub radio: Option<String>,
+    pub net_start: Option<String>,
+    pub net_end: Option<String>,
+    /// Absent in logs captured before TCP was sampled.
+    pub tcp_start: Option<String>,
+    pub tcp_end: Option<String>,
+    /// Absent in logs captured before softnet_stat was sampled.
+    pub softnet_start: Option<String>,
+    pub softnet_end: Option<String>,
+    /// Absent in logs captured before /proc/interrupts was sampled.
+    pub irq_start: Option<String>,
+    pub irq_end: Option<String>,
+    /// Only in logs captured with `--softirqs`.
+    pub softirqs_start: Option<String>,
+    pub softirqs_end: Option<String>,
+    /// Only in logs captured with `--time-in-state`.
+    pub time_in_state_start: Option<String>,
+    pub time_in_state_end: Option<String>,
+    /// Only in logs captured with `--cpuidle`.
+    pub idle_start: Option<String>,
+    pub idle_end: Option<String>,
+    /// Only in logs captured with `--schedstat`.
+    pub schedstat_start: Option<String>,
+    pub schedstat_end: Option<String>,
+    /// Only in logs captured with `--sockets`.
+    pub sockets: Option<String>,
+    /// Absent in logs captured before /proc/diskstats was sampled.
+    pub disk_start: Option<String>,
+    pub disk_end: Option<String>,
+    /// Absent on kernels without PSI.
+    pub pressure_start: Option<String>,
+    pub pressure_end: Option<String>,
+    pub proc_pids: Vec<LogProcPid>,
+    pub cgroups: Vec<LogCgroup>,
+    /// Start and end /proc/self/stat lines of the logger, absent in logs
+    /// captured before it measured itself.
+    pub self_stat: Option<String>,
 }
 
+/// Start and end /proc/<pid>/stat lines, empty once the process has exited.
 #[derive(Debug)]
-struct ProcReport {
-    user: u64,
-    nice: u64,
-    system: u64,
-    idle: u64,
-    iowait: u64,
-    irq: u64,
-    softirq: u64,
-    total: u64,
-    load: f64,
-}
-
-struct ProcReportPoint {
-    user: u64,
-    nice: u64,
-    system: u64,
-    idle: u64,
-    iowait: u64,
-    irq: u64,
-    softirq: u64,
-    total: u64,
+pub struct LogProcPid {
+    pub pid: String,
+    pub stat: Option<String>,
 }
 
+/// Start and end cpu.stat of a cgroup, empty once the cgroup is gone.
 #[derive(Debug)]
-struct PerfReport {
-    cycles: u64,
-    context_switches: u64,
+pub struct LogCgroup {
+    pub path: String,
+    pub stat: Option<String>,
 }
 
+/// Attributes of the `<log>` element.
 #[derive(Debug)]
-struct ReportEntry {
-    time: Duration,
-    proc: HashMap<String, ProcReport>,
-    perf: HashMap<String, PerfReport>,
+pub struct Log {
+    pub id: String,
+    /// Absent in logs written before the schema was versioned.
+    pub schema: Option<String>,
+    /// Absent in logs written before runtime platform detection.
+    pub platform: Option<String>,
+    /// Comma separated, perf counted only these processes when present.
+    pub pids: Option<String>,
+    /// perf counted only on these CPUs when present.
+    pub cpu_list: Option<String>,
+    /// Comma separated perf events, cycles and context switches when absent.
+    pub events: Option<String>,
+    /// `off` when the logger ran without perf.
+    pub perf: Option<String>,
+    /// Absent for per core counts.
+    pub perf_granularity: Option<String>,
+    pub duration: String,
+    /// Whole seconds, only written by loggers predating `interval-ms`.
+    pub interval: Option<String>,
+    pub interval_ms: Option<String>,
+    /// Absent in logs written before the start time was recorded.
+    pub start: Option<String>,
+    /// Only in logs captured with `--warmup`.
+    pub warmup_ms: Option<String>,
+    /// Only in the parts of a rotated capture.
+    pub part: Option<String>,
+    /// Only in captures started by `profiler sync`.
+    pub clock_offset_ms: Option<String>,
 }
 
-#[derive(Debug)]
-struct Report {
-    id: Uuid,
-    duration: Duration,
-    interval: Duration,
-    entries: Vec<ReportEntry>,
-    proc_cpus: Vec<String>,
-    perf_cpus: Vec<String>,
+impl Log {
+    pub fn from_element(element: &BytesStart) -> Result<Self, quick_xml::Error> {
+        let attribute = |name| -> Result<Option<String>, quick_xml::Error> {
+            match element.try_get_attribute(name)? {
+                Some(attribute) => Ok(Some(attribute.unescape_value()?.into_owned())),
+                None => Ok(None),
+            }
+        };
+        Ok(Log {
+            id: attribute(schema::ID)?.unwrap_or_default(),
+            schema: attribute(schema::SCHEMA)?,
+            platform: attribute(schema::PLATFORM)?,
+            pids: attribute(schema::PIDS)?,
+            cpu_list: attribute(schema::CPU_LIST)?,
+            events: attribute(schema::EVENTS)?,
+            perf: attribute(schema::PERF)?,
+            perf_granularity: attribute(schema::PERF_GRANULARITY)?,
+            duration: attribute(schema::DURATION)?.unwrap_or_default(),
+            interval: attribute(schema::INTERVAL)?,
+            interval_ms: attribute(schema::INTERVAL_MS)?,
+            start: attribute(schema::START)?,
+            warmup_ms: attribute(schema::WARMUP_MS)?,
+            part: attribute(schema::PART)?,
+            clock_offset_ms: attribute(schema::CLOCK_OFFSET_MS)?,
+        })
+    }
+}
+
+/// The unescaped value of `name`, empty when the element lacks it.
+fn get_attribute(element: &BytesStart, name: &str) -> Result<String, quick_xml::Error> {
+    match element.try_get_attribute(name)? {
+        Some(attribute) => Ok(attribute.unescape_value()?.into_owned()),
+        None => Ok(String::new()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TimeUnit {
+    Ms,
+    S,
+}
+
+/// Jiffies spent in each state during one interval, as serialized by `--json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcReport {
+    pub user: u64,
+    pub nice: u64,
+    pub system: u64,
+    pub idle: u64,
+    pub iowait: u64,
+    pub irq: u64,
+    pub softirq: u64,
+    pub total: u64,
+    /// Percentage of the interval the CPU was not idle.
+    pub load: f64,
+}
+
+pub struct ProcReportPoint {
+    pub user: u64,
+    pub nice: u64,
+    pub system: u64,
+    pub idle: u64,
+    pub iowait: u64,
+    pub irq: u64,
+    pub softirq: u64,
+    pub total: u64,
+}
+
+/// Counter deltas over one interval, keyed by event name with simpleperf's
+/// `cpu-cycles` folded into `cycles` and dashes turned into underscores.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PerfReport {
+    pub events: HashMap<String, u64>,
+    /// Events perf printed as `<not counted>` or `<not supported>`, sorted so
+    /// that serializing a report twice gives the same output.
+    pub missing: BTreeSet<String>,
+}
+
+impl PerfReport {
+    pub fn get(&self, event: &str) -> Option<u64> {
+        self.events.get(event).copied()
+    }
+
+    /// Counters multiplexed out of an interval are missing or zero, so those
+    /// intervals have no IPC instead of an IPC of zero.
+    pub fn ipc(&self) -> Option<(u64, u64)> {
+        match (self.get(INSTRUCTIONS)?, self.get(CYCLES)?) {
+            (_, 0) => None,
+            (instructions, cycles) => Some((instructions, cycles)),
+        }
+    }
+}
+
+pub const CYCLES: &str = "cycles";
+pub const CONTEXT_SWITCHES: &str = "context_switches";
+pub const INSTRUCTIONS: &str = "instructions";
+
+pub fn event_key(event: &str) -> String {
+    match event {
+        "cpu-cycles" => CYCLES.to_string(),
+        event => event.replace('-', "_"),
+    }
+}
+
+pub fn event_title(event: &str) -> String {
+    match event {
+        CYCLES => "CPU cycles".to_string(),
+        event => event.replace('_', " "),
+    }
+}
+
+/// /proc/meminfo at the end of an interval, in kB.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemReport {
+    pub total: u64,
+    pub available: u64,
+    pub buffers: u64,
+    pub cached: u64,
+    pub swap_free: u64,
+}
+
+/// /proc/loadavg at the end of an interval.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoadavgReport {
+    /// Tasks running, runnable or in uninterruptible sleep, averaged over
+    /// the last 1, 5 and 15 minutes.
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+    /// Tasks runnable at the time, including the reader itself.
+    pub runnable: u32,
+    pub tasks: u32,
+}
+
+/// /proc/net/dev counter deltas of one interface over one interval.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NetReport {
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+}
+
+pub struct DiskReportPoint {
+    pub reads: u64,
+    pub read_sectors: u64,
+    pub writes: u64,
+    pub written_sectors: u64,
+    pub io_ms: u64,
+}
+
+/// One block device during one interval.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct DiskReport {
+    pub reads: u64,
+    pub read_bytes: u64,
+    pub writes: u64,
+    pub written_bytes: u64,
+    /// Milliseconds the device had requests in flight.
+    pub io_ms: u64,
+}
+
+/// One line of PSI during one interval.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PressureReport {
+    /// The kernel's percentage of the last 10 seconds stalled, at the end of
+    /// the interval.
+    pub avg10: f64,
+    /// Microseconds stalled during the interval.
+    pub stall_us: u64,
+}
+
+/// The resources PSI covers, each with a `some` line and, but for `cpu` on
+/// kernels before 5.13, a `full` one.
+pub const PRESSURE_RESOURCES: [&str; 3] = ["cpu", "memory", "io"];
+
+/// TCP segments and retransmissions during one interval, machine-wide; a
+/// counter the kernel doesn't show counts as zero.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TcpReport {
+    pub in_segs: u64,
+    pub out_segs: u64,
+    pub retrans_segs: u64,
+    pub in_errs: u64,
+    pub out_rsts: u64,
+    pub fast_retrans: u64,
+    pub lost_retransmit: u64,
+    pub timeouts: u64,
+    pub listen_drops: u64,
+}
+
+/// The receive path of one CPU from /proc/net/softnet_stat, as counted so far
+/// or, once analyzed, during one interval.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SoftnetReport {
+    /// Packets taken off the CPU's backlog and device queues.
+    pub processed: u64,
+    /// Packets dropped as the backlog was full.
+    pub dropped: u64,
+    /// Times NAPI ran out of budget or time with packets left.
+    pub time_squeeze: u64,
+}
+
+/// One row of /proc/interrupts, with its counts so far or, once analyzed,
+/// during one interval.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IrqReport {
+    /// What the kernel shows after the counts, e.g. "GICv3 45 Level wlan0".
+    pub name: String,
+    pub counts: CpuCounts,
+}
+
+/// One TCP connection at the end of an interval, as `ss -tin` shows it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SocketReport {
+    pub state: String,
+    /// Smoothed round trip time in milliseconds.
+    pub rtt: Option<f64>,
+    /// Congestion window in segments.
+    pub cwnd: Option<u64>,
+    /// Segments retransmitted since the connection opened.
+    pub retrans: u64,
+    /// Bits per second, absent until the kernel has measured it.
+    pub delivery_rate: Option<f64>,
+}
+
+pub struct NetReportPoint {
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+}
+
+/// Jiffies a process spent on CPU during one interval.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcessReport {
+    pub utime: u64,
+    pub stime: u64,
+    /// Percentage of the time of all CPUs combined.
+    pub share: f64,
+}
+
+/// The CPU time of a cgroup during one interval, from its cpu.stat.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CgroupReport {
+    pub usage_us: u64,
+    pub user_us: u64,
+    pub system_us: u64,
+    /// Periods the cgroup hit its `cpu.max` quota in, and for how long.
+    pub nr_throttled: u64,
+    pub throttled_us: u64,
+    /// Percentage of the time of all CPUs combined.
+    pub share: f64,
+}
+
+/// The clock /proc/stat counts jiffies in, fixed at 100 Hz for userspace on
+/// every architecture Linux runs phones and servers on.
+const USER_HZ: u64 = 100;
+
+/// How one CPU's tasks fared over an interval, from /proc/schedstat.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SchedstatReport {
+    /// Nanoseconds tasks ran on the CPU.
+    pub run_ns: u64,
+    /// Nanoseconds tasks waited on its run queue, runnable but not running.
+    pub run_delay_ns: u64,
+    /// Timeslices run.
+    pub timeslices: u64,
+}
+
+/// A cpuidle state of one CPU over an interval.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IdleStateReport {
+    /// As the driver names it, e.g. "POLL", "WFI" or "C6".
+    pub name: String,
+    /// Times the CPU entered the state.
+    pub usage: u64,
+    /// Microseconds the CPU spent in the state.
+    pub time_us: u64,
+}
+
+/// The battery at the end of an interval as the kernel shows it, in the
+/// units of the device; see `battery_power`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BatteryReport {
+    pub current_now: Option<i64>,
+    pub voltage_now: Option<i64>,
+    /// Percent charged.
+    pub capacity: Option<u32>,
+    /// Charge left in microamp hours.
+    pub charge_counter: Option<i64>,
+    /// "Charging", "Discharging", "Full" or "Not charging".
+    pub status: Option<String>,
+}
+
+/// The network the device was on at the end of an interval, from what
+/// `--radio` keeps of dumpsys and /proc/net/wireless; signal the modem didn't
+/// report is null.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RadioReport {
+    /// Radio access technology of mobile data as Android names it, e.g.
+    /// "LTE" or "NR_NSA", "WIFI" with only Wi-Fi to go by, else "unknown".
+    pub net_type: String,
+    /// dBm, of NR on an NR network and of LTE otherwise.
+    pub rsrp: Option<i64>,
+    /// dB, likewise.
+    pub rsrq: Option<i64>,
+    /// dBm, of LTE or else GSM.
+    pub rssi: Option<i64>,
+    /// dBm, of the first wireless interface.
+    pub wifi_rssi: Option<i64>,
+}
+
+/// How a device's battery strays from the power_supply ABI, which has
+/// current in microamps and negative while discharging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatteryQuirk {
+    pub milliamps: bool,
+    pub discharging_positive: bool,
+}
+
+/// Quirks by prefix of the device model, as measured on the devices.
+const BATTERY_QUIRKS: [(&str, BatteryQuirk); 1] = [(
+    // Samsung Galaxy phones.
+    "SM-",
+    BatteryQuirk {
+        milliamps: true,
+        discharging_positive: false,
+    },
+)];
+
+pub fn battery_quirk(device_model: Option<&str>) -> BatteryQuirk {
+    BATTERY_QUIRKS
+        .iter()
+        .find(|(prefix, _)| device_model.is_some_and(|model| model.starts_with(prefix)))
+        .map_or(
+            BatteryQuirk {
+                milliamps: false,
+                discharging_positive: false,
+            },
+            |(_, quirk)| *quirk,
+        )
+}
+
+/// Milliwatts drawn from the battery, none while it charges or when the
+/// kernel left out current or voltage. A reported status tells discharging
+/// apart whatever the sign of the current; without one, `quirk` does.
+pub fn battery_power(battery: &BatteryReport, quirk: BatteryQuirk) -> Option<f64> {
+    let (current, voltage) = (battery.current_now?, battery.voltage_now?);
+    let current_ma = if quirk.milliamps {
+        current as f64
+    } else {
+        current as f64 / 1000.0
+    };
+    let discharge_ma = match battery.status.as_deref() {
+        Some("Discharging") => current_ma.abs(),
+        Some(_) => return None,
+        None if quirk.discharging_positive => current_ma.max(0.0),
+        None => (-current_ma).max(0.0),
+    };
+    Some(discharge_ma * voltage as f64 / 1e6)
+}
+
+/// One thermal zone at the end of an interval.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ThermalReport {
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Millidegrees Celsius, null when the zone failed to read.
+    pub temp: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReportEntry {
+    /// Offset from the start of the run, in milliseconds.
+    #[serde(
+        rename = "time_ms",
+        serialize_with = "serialize_millis",
+        deserialize_with = "deserialize_millis"
+    )]
+    pub time: Duration,
+    /// Wall-clock time the interval started at, only in logs captured with
+    /// `--timestamps`.
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_timestamp",
+        deserialize_with = "deserialize_optional_timestamp"
+    )]
+    pub wall_time: Option<SystemTime>,
+    /// Intervals the logger fell too far behind to sample just before this
+    /// one.
+    #[serde(default)]
+    pub skipped: u32,
+    /// Keyed by /proc/stat CPU number, plus "all".
+    pub proc: HashMap<String, ProcReport>,
+    /// CPUs left out of `proc` for this interval as their counters don't
+    /// describe it.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub proc_anomalies: HashMap<String, ProcAnomaly>,
+    /// Keyed by perf's CPU label, plus "all".
+    pub perf: HashMap<String, PerfReport>,
+    pub mem: Option<MemReport>,
+    /// Absent in logs captured before /proc/loadavg was sampled.
+    #[serde(default)]
+    pub loadavg: Option<LoadavgReport>,
+    /// Keyed by interface name.
+    pub net: HashMap<String, NetReport>,
+    /// Absent in logs captured before TCP was sampled.
+    #[serde(default)]
+    pub tcp: Option<TcpReport>,
+    /// Keyed by /proc/stat CPU number, empty in logs captured before
+    /// softnet_stat was sampled.
+    #[serde(default)]
+    pub softnet: HashMap<String, SoftnetReport>,
+    /// Keyed by the IRQ, e.g. "45" or "LOC", absent in logs captured before
+    /// /proc/interrupts was sampled. Only the rows `--irq-filter` kept that
+    /// fired during the interval, with the CPUs that serviced them: most of
+    /// the table stays at zero, and would make up most of a report held in
+    /// memory.
+    #[serde(default)]
+    pub irqs: Option<HashMap<String, IrqReport>>,
+    /// Softirqs raised keyed by type, e.g. "NET_RX", then by /proc/stat CPU
+    /// number, only in logs captured with `--softirqs`.
+    #[serde(default)]
+    pub softirqs: HashMap<String, CpuCounts>,
+    /// The cpuidle states of each CPU, shallowest first, keyed by /proc/stat
+    /// CPU number; only in logs captured with `--cpuidle`.
+    #[serde(default)]
+    pub cpuidle: HashMap<String, Vec<IdleStateReport>>,
+    /// Keyed by /proc/stat CPU number, only in logs captured with
+    /// `--schedstat`.
+    #[serde(default)]
+    pub schedstat: HashMap<String, SchedstatReport>,
+    /// Keyed by "local -> peer" address and port, only in logs captured with
+    /// `--sockets` and only the connections open at the end of the interval.
+    #[serde(default)]
+    pub sockets: HashMap<String, SocketReport>,
+    /// Keyed by device name, empty in logs captured before /proc/diskstats
+    /// was sampled.
+    #[serde(default)]
+    pub disk: HashMap<String, DiskReport>,
+    /// Keyed by resource and line, e.g. "memory full", empty on kernels
+    /// without PSI.
+    #[serde(default)]
+    pub pressure: HashMap<String, PressureReport>,
+    /// Current frequency in kHz at the end of the interval, keyed by
+    /// /proc/stat CPU number; cores without cpufreq are missing.
+    pub freq: HashMap<String, u64>,
+    /// Milliseconds spent at each frequency in kHz over the interval, by
+    /// ascending frequency, keyed by /proc/stat CPU number; only in logs
+    /// captured with `--time-in-state`.
+    #[serde(default)]
+    pub time_in_state: HashMap<String, Vec<(u64, u64)>>,
+    /// Keyed by zone name, e.g. "thermal_zone0".
+    pub thermal: HashMap<String, ThermalReport>,
+    /// Only in logs captured with `--battery`.
+    #[serde(default)]
+    pub battery: Option<BatteryReport>,
+    /// Percentage of the interval the GPU was busy, only in logs captured
+    /// with `--gpu`.
+    #[serde(default)]
+    pub gpu: Option<f64>,
+    /// Only in logs captured with `--radio`.
+    #[serde(default)]
+    pub radio: Option<RadioReport>,
+    /// Keyed by pid, null while the process wasn't running.
+    pub processes: HashMap<String, Option<ProcessReport>>,
+    /// Keyed by cgroup directory, null once the cgroup was gone; only in
+    /// logs captured with `--cgroup`.
+    #[serde(default)]
+    pub cgroups: HashMap<String, Option<CgroupReport>>,
+    /// The logger itself, absent in logs captured before it measured itself.
+    #[serde(default)]
+    pub profiler: Option<ProcessReport>,
+}
+
+/// The machine a log was captured on. Fields are absent when the logger
+/// couldn't read them or predates `<metadata>`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// `uname -a`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uname: Option<String>,
+    /// The distinct models in /proc/cpuinfo, separated by "; ".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logical_cpus: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub physical_cpus: Option<u32>,
+    /// Where each CPU sits, by CPU number.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub topology: Vec<CpuTopology>,
+    /// Android's `ro.product.model`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_model: Option<String>,
+    /// CPU the profiler and perf ran on, captured with `--pin-cpu`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned_cpu: Option<u32>,
+    /// Command line of the command captured with `profiler run`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// The options the capture ran with, as a `--config` TOML file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<String>,
+}
+
+/// A CPU's place in the machine as sysfs shows it, each field absent when the
+/// kernel doesn't; x86 has no capacity and reports a cluster per core or -1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CpuTopology {
+    pub cpu: u64,
+    pub package: Option<i64>,
+    pub core: Option<i64>,
+    pub cluster: Option<i64>,
+    /// Relative performance, 1024 for the fastest CPUs.
+    pub capacity: Option<u64>,
+}
+
+/// A label sent to the logger during the capture, e.g. as the network
+/// conditions changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Marker {
+    /// Offset from the start of the run it was received at.
+    #[serde(
+        rename = "time_ms",
+        serialize_with = "serialize_millis",
+        deserialize_with = "deserialize_millis"
+    )]
+    pub time: Duration,
+    pub label: String,
+}
+
+/// How the command captured with `profiler run` ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Exit {
+    Code(i32),
+    /// Killed by this signal.
+    Signal(i32),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Report {
+    pub id: Uuid,
+    /// Version of the format the log was written in, absent in logs written
+    /// before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<u32>,
+    pub platform: Platform,
+    /// What the perf rows are split by: cores, sockets or only "all".
+    #[serde(default)]
+    pub perf_granularity: PerfGranularity,
+    #[serde(default)]
+    pub metadata: Metadata,
+    /// Wall-clock time the capture started at, absent in logs written before
+    /// it was recorded.
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_timestamp",
+        deserialize_with = "deserialize_optional_timestamp"
+    )]
+    pub start: Option<SystemTime>,
+    /// Requested duration, zero for runs captured until interrupted.
+    #[serde(
+        rename = "duration_ms",
+        serialize_with = "serialize_millis",
+        deserialize_with = "deserialize_millis"
+    )]
+    pub duration: Duration,
+    /// Measured duration, absent in logs written before it was recorded.
+    #[serde(
+        rename = "duration_actual_ms",
+        serialize_with = "serialize_optional_millis",
+        deserialize_with = "deserialize_optional_millis"
+    )]
+    pub duration_actual: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit: Option<Exit>,
+    /// Number of the file of a capture rotated with `--rotate-*`, from 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part: Option<u32>,
+    /// File name of the part this one continues in, on all but the last.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_part: Option<String>,
+    /// Milliseconds the logger's clock was ahead of the `profiler sync`
+    /// coordinator's, which `align_by_time` takes off its wall-clock times.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_offset_ms: Option<i64>,
+    /// Sampled before the start of the log but left out of it.
+    #[serde(
+        default,
+        rename = "warmup_ms",
+        serialize_with = "serialize_optional_millis",
+        deserialize_with = "deserialize_optional_millis"
+    )]
+    pub warmup: Option<Duration>,
+    #[serde(
+        rename = "interval_ms",
+        serialize_with = "serialize_millis",
+        deserialize_with = "deserialize_millis"
+    )]
+    pub interval: Duration,
+    pub entries: Vec<ReportEntry>,
+    pub proc_cpus: Vec<String>,
+    pub perf_cpus: Vec<String>,
+    /// Event keys in the order they were requested, as in `PerfReport`.
+    pub perf_events: Vec<String>,
+    pub interfaces: Vec<String>,
+    #[serde(default)]
+    pub disks: Vec<String>,
+    pub freq_cpus: Vec<String>,
+    pub thermal_zones: Vec<String>,
+    /// Processes followed with `--pid`; perf counted only these when set.
+    pub pids: Vec<String>,
+    /// CPUs of `--cpu-list`, the only ones kept and summed into "all" when
+    /// set.
+    #[serde(default)]
+    pub cpu_list: Vec<u64>,
+    /// cgroups followed with `--cgroup`.
+    #[serde(default)]
+    pub cgroups: Vec<String>,
+    /// Groups added by `add_cpu_groups`, whose rows sum their members.
+    #[serde(default)]
+    pub groups: Vec<CpuGroup>,
+    /// In the order they were received.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub markers: Vec<Marker>,
+    /// Logs `merge_reports` concatenated, in order; empty for a single log.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    /// Entries left out by `--lenient`.
+    #[serde(skip)]
+    pub skipped: Vec<ParseError>,
+    /// The log was cut off and only its complete entries were read.
+    #[serde(skip)]
+    pub truncated: bool,
+}
+
+impl Report {
+    /// Whether `cpu` labels a row summing others: "all" or a CPU group.
+    pub fn is_aggregate(&self, cpu: &str) -> bool {
+        cpu == "all" || self.groups.iter().any(|group| group.name == cpu)
+    }
+}
+
+/// How much of a mapped log is read before its pages are handed back.
+const RELEASED: usize = 16 * 1024 * 1024;
+
+/// A log file read straight from its pages mapped into memory rather than
+/// copied through a buffer. The pages the reader is done with are handed
+/// back as it goes, so that a log of gigabytes doesn't stay resident while
+/// the rest of it is parsed.
+pub struct MappedLog {
+    map: Mmap,
+    position: usize,
+    released: usize,
+}
+
+impl MappedLog {
+    pub fn open(file: &File) -> io::Result<Self> {
+        // SAFETY: logs are only ever appended to, which leaves the length
+        // mapped here as it is; one cut short while read would fault.
+        let map = unsafe { Mmap::map(file)? };
+        map.advise(memmap2::Advice::Sequential)?;
+        Ok(MappedLog {
+            map,
+            position: 0,
+            released: 0,
+        })
+    }
+}
+
+impl Read for MappedLog {
+    fn read(&mut self, output: &mut [u8]) -> io::Result<usize> {
+        let read = (&self.map[self.position..]).read(output)?;
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl BufRead for MappedLog {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(&self.map[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.map.len());
+        if self.position - self.released >= RELEASED {
+            let end = self.position / RELEASED * RELEASED;
+            // SAFETY: the mapping is private and read only, so dropped pages
+            // are read back from the file, unchanged, if touched again.
+            unsafe {
+                self.map
+                    .unchecked_advise_range(
+                        UncheckedAdvice::DontNeed,
+                        self.released,
+                        end - self.released,
+                    )
+                    .ok();
+            }
+            self.released = end;
+        }
+    }
+}
+
+/// Reads `filename` from a mapping of it, or decompressing it when it ends
+/// in `.gz`, or stdin for `-`.
+pub fn load_report(
+    filename: &str,
+    lenient: bool,
+    time_unit: Option<TimeUnit>,
+) -> Result<Report, ReportError> {
+    let input: Result<Box<dyn BufRead>, _> = match filename {
+        "-" => Ok(Box::new(std::io::stdin().lock())),
+        _ => File::open(filename).and_then(|file| -> io::Result<Box<dyn BufRead>> {
+            if filename.ends_with(".gz") {
+                Ok(Box::new(BufReader::new(GzDecoder::new(file))))
+            } else {
+                Ok(Box::new(MappedLog::open(&file)?))
+            }
+        }),
+    };
+    input
+        .map_err(ParseError::from)
+        .and_then(|input| get_report(input, lenient, time_unit))
+        .map_err(|source| ReportError {
+            file: filename.to_string(),
+            source,
+        })
+}
+
+#[derive(Default)]
+struct LoaderState {
+    /// Index of the next file to load.
+    next: usize,
+    /// How many reports have been taken off the loader.
+    taken: usize,
+    loaded: HashMap<usize, Result<Report, ReportError>>,
+    dropped: bool,
+}
+
+#[derive(Default)]
+struct LoaderShared {
+    state: Mutex<LoaderState>,
+    changed: Condvar,
+}
+
+/// The reports of `load_reports`, in the order of their files.
+pub struct Loader {
+    shared: Arc<LoaderShared>,
+    next: usize,
+    count: usize,
+}
+
+impl Iterator for Loader {
+    type Item = Result<Report, ReportError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next == self.count {
+            return None;
+        }
+        let mut state = self.shared.state.lock().unwrap();
+        let report = loop {
+            match state.loaded.remove(&self.next) {
+                Some(report) => break report,
+                None => state = self.shared.changed.wait(state).unwrap(),
+            }
+        };
+        self.next += 1;
+        state.taken = self.next;
+        self.shared.changed.notify_all();
+        Some(report)
+    }
+}
+
+impl Drop for Loader {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().dropped = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+/// Loads `filenames` on up to `jobs` threads, each file on its own so that
+/// one failing to parse doesn't affect the others. Loading stays at most
+/// `jobs` reports ahead of the ones taken, which bounds the memory held.
+pub fn load_reports(
+    filenames: &[String],
+    jobs: usize,
+    lenient: bool,
+    time_unit: Option<TimeUnit>,
+    cache: Cache,
+) -> Loader {
+    let filenames = Arc::new(filenames.to_vec());
+    let shared = Arc::new(LoaderShared::default());
+    let jobs = jobs.max(1);
+    for _ in 0..jobs.min(filenames.len()) {
+        let (filenames, shared) = (filenames.clone(), shared.clone());
+        thread::spawn(move || loop {
+            let index = {
+                let mut state = shared.state.lock().unwrap();
+                while !state.dropped
+                    && state.next < filenames.len()
+                    && state.next >= state.taken + jobs
+                {
+                    state = shared.changed.wait(state).unwrap();
+                }
+                if state.dropped || state.next == filenames.len() {
+                    return;
+                }
+                state.next += 1;
+                state.next - 1
+            };
+            let filename = &filenames[index];
+            let report = catch_unwind(AssertUnwindSafe(|| {
+                load_cached(filename, lenient, time_unit, cache)
+            }))
+            .unwrap_or_else(|_| {
+                Err(ReportError {
+                    file: filename.clone(),
+                    source: ParseError::Panicked,
+                })
+            });
+            shared.state.lock().unwrap().loaded.insert(index, report);
+            shared.changed.notify_all();
+        });
+    }
+    Loader {
+        shared,
+        next: 0,
+        count: filenames.len(),
+    }
+}
+
+fn serialize_millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis() as u64)
+}
+
+fn serialize_optional_millis<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serialize_millis(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    u64::deserialize(deserializer).map(Duration::from_millis)
+}
+
+fn deserialize_optional_millis<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    Ok(Option::<u64>::deserialize(deserializer)?.map(Duration::from_millis))
+}
+
+fn serialize_optional_timestamp<S: Serializer>(
+    time: &Option<SystemTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => serializer.serialize_str(&timestamp::format(*time)),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_optional_timestamp<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<SystemTime>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|text| {
+            timestamp::parse(&text).ok_or_else(|| {
+                serde::de::Error::custom(format!("\"{}\" is not an ISO 8601 time", text))
+            })
+        })
+        .transpose()
+}
+
+/// Run-level averages of one CPU; a metric is null when its source never
+/// reported the CPU.
+#[derive(Serialize, Deserialize)]
+pub struct CpuAverages {
+    pub cpu: String,
+    pub load: Option<f64>,
+    /// Percentage of intervals in which /proc/stat listed the CPU.
+    pub online: Option<f64>,
+    pub cycles: Option<f64>,
+    pub context_switches: Option<f64>,
+}
+
+/// The entries from one split up to the next, named after the split they
+/// follow or "start" before the first.
+pub struct Phase {
+    pub label: String,
+    /// Start of the first entry.
+    pub from: Duration,
+    /// End of the last entry.
+    pub to: Duration,
+    pub entries: usize,
+    pub averages: Vec<CpuAverages>,
+}
+
+/// Spread across runs of one CPU's run averages; a metric is null when its
+/// source never reported the CPU.
+#[derive(Serialize)]
+pub struct CpuAggregate {
+    pub cpu: String,
+    pub load: Option<stats::RunSpread>,
+    pub cycles: Option<stats::RunSpread>,
+}
+
+/// Repeated runs of one experiment, from `aggregate_runs`.
+#[derive(Serialize)]
+pub struct Aggregate {
+    pub files: Vec<String>,
+    #[serde(rename = "interval_ms", serialize_with = "serialize_millis")]
+    pub interval: Duration,
+    /// Entries every run was cut down to.
+    pub entries: usize,
+    pub cpus: Vec<CpuAggregate>,
+}
+
+/// Why a log file could not be turned into a report.
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("cannot read log: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("malformed XML at byte {position}: {source}")]
+    Xml {
+        position: usize,
+        source: quick_xml::Error,
+    },
+    #[error("invalid UTF-8 at byte {0}")]
+    Utf8(usize),
+    #[error(
+        "log schema {0} is newer than {latest}, the latest this profiler reads; please upgrade it",
+        latest = schema::VERSION
+    )]
+    NewerSchema(u32),
+    #[error("missing <log> element")]
+    NoLog,
+    #[error("invalid {0} attribute \"{1}\"")]
+    Attribute(&'static str, String),
+    #[error("unknown platform \"{0}\"")]
+    UnknownPlatform(String),
+    #[error("log has no interval")]
+    NoInterval,
+    #[error("log has no complete entries")]
+    NoEntries,
+    #[error("log has no start time to compare wall-clock times with")]
+    NoStartTime,
+    #[error("no entries between the given times")]
+    NoEntriesInRange,
+    #[error("the parser panicked")]
+    Panicked,
+    #[error("entry {index} (time {time}): {source}")]
+    Entry {
+        index: usize,
+        time: String,
+        source: EntryError,
+    },
+}
+
+/// Why a single `<log-entry>` could not be analyzed.
+#[derive(Debug, Error)]
+pub enum EntryError {
+    #[error("malformed {section} line \"{line}\"")]
+    Line { section: &'static str, line: String },
+    #[error("missing <{0}> element")]
+    Missing(&'static str),
+    #[error("invalid time")]
+    Time,
+    #[error("invalid count of skipped intervals")]
+    Skipped,
+    #[error("unsupported {section} version {version}")]
+    Version { section: &'static str, version: u32 },
+}
+
+/// A `ParseError` together with the log file it came from.
+#[derive(Debug, Error)]
+#[error("{file}: {source}")]
+pub struct ReportError {
+    pub file: String,
+    pub source: ParseError,
+}
+
+/// Why runs could not be aggregated, naming the first run that differs from
+/// the first file.
+#[derive(Debug, Error)]
+pub enum AggregateError {
+    #[error("{file}: interval of {interval:?} instead of {expected:?}")]
+    Interval {
+        file: String,
+        interval: Duration,
+        expected: Duration,
+    },
+    #[error("{file}: CPUs differ from the first run's")]
+    Cpus { file: String },
+    #[error("{file}: {entries} entries instead of {expected}")]
+    Entries {
+        file: String,
+        entries: usize,
+        expected: usize,
+    },
+}
+
+/// Why logs could not be merged into one report.
+#[derive(Debug, Error)]
+pub enum MergeError {
+    #[error("{file}: interval of {interval:?} instead of {expected:?}")]
+    Interval {
+        file: String,
+        interval: Duration,
+        expected: Duration,
+    },
+    #[error("{file}: {platform} log among {expected} logs")]
+    Platform {
+        file: String,
+        platform: &'static str,
+        expected: &'static str,
+    },
+    #[error("{file}: part {part} of {id}, whose part {expected} is missing")]
+    MissingPart {
+        file: String,
+        id: Uuid,
+        part: u32,
+        expected: u32,
+    },
+    #[error("{file}: part {part} of {id} is given twice")]
+    DuplicatePart { file: String, id: Uuid, part: u32 },
+    #[error("{file}: continues in {next}, which is missing")]
+    MissingNextPart { file: String, next: String },
+}
+
+fn parse_attribute<T: std::str::FromStr>(name: &'static str, value: &str) -> Result<T, ParseError> {
+    value
+        .parse()
+        .map_err(|_| ParseError::Attribute(name, value.to_string()))
 }
 
-fn parse_proc(text: &str) -> HashMap<String, ProcReportPoint> {
+pub fn parse_proc(text: &str) -> Result<HashMap<String, ProcReportPoint>, EntryError> {
     let mut proc: HashMap<String, ProcReportPoint> = HashMap::new();
     let lines = text.lines();
 
     for line in lines {
+        let malformed = || EntryError::Line {
+            section: "proc",
+            line: line.to_string(),
+        };
         let matches = regex_captures!(
             r#"cpu(\d+| ) (\d+) (\d+) (\d+) (\d+) (\d+) (\d+) (\d+) \d+ \d+ \d+"#,
             line
         );
-        if let Some((_, mut cpu, user, system, nice, idle, iowait, irq, softirq)) = matches {
+        if matches.is_none() && line.starts_with("cpu") {
+            return Err(malformed());
+        }
+        if let Some((_, mut cpu, user, nice, system, idle, iowait, irq, softirq)) = matches {
+            let parse = |value: &str| value.parse().map_err(|_| malformed());
             let (user, system, nice, idle, iowait, irq, softirq) = (
-                user.parse().unwrap(),
-                system.parse().unwrap(),
-                nice.parse().unwrap(),
-                idle.parse().unwrap(),
-                iowait.parse().unwrap(),
-                irq.parse().unwrap(),
-                softirq.parse().unwrap(),
+                parse(user)?,
+                parse(system)?,
+                parse(nice)?,
+                parse(idle)?,
+                parse(iowait)?,
+                parse(irq)?,
+                parse(softirq)?,
             );
             let total = user + system + nice + idle + iowait + irq + softirq;
             let proc_report = ProcReportPoint {
@@ -116,252 +1229,5050 @@ fn parse_proc(text: &str) -> HashMap<String, ProcReportPoint> {
         }
     }
 
-    proc
+    Ok(proc)
+}
+
+/// Why an interval of a CPU has no load: its /proc/stat counters don't
+/// describe it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcAnomaly {
+    /// A counter went down, e.g. across a suspend and resume or on a buggy
+    /// kernel.
+    Backwards,
+    /// No jiffy passed, as when both snapshots fell in the same one.
+    Stalled,
 }
 
-fn analyze_proc(start: &ProcReportPoint, end: &ProcReportPoint) -> ProcReport {
-    ProcReport {
-        user: end.user - start.user,
-        nice: end.nice - start.nice,
-        system: end.system - start.system,
-        idle: end.idle - start.idle,
-        iowait: end.iowait - start.iowait,
-        irq: end.irq - start.irq,
-        softirq: end.softirq - start.softirq,
-        total: end.total - start.total,
-        load: 100.0 * (1.0 - (end.idle - start.idle) as f64 / (end.total - start.total) as f64),
+pub fn analyze_proc(
+    start: &ProcReportPoint,
+    end: &ProcReportPoint,
+) -> Result<ProcReport, ProcAnomaly> {
+    let delta = |start: u64, end: u64| end.checked_sub(start).ok_or(ProcAnomaly::Backwards);
+    let (idle, total) = (delta(start.idle, end.idle)?, delta(start.total, end.total)?);
+    if total == 0 {
+        return Err(ProcAnomaly::Stalled);
     }
+    Ok(ProcReport {
+        user: delta(start.user, end.user)?,
+        nice: delta(start.nice, end.nice)?,
+        system: delta(start.system, end.system)?,
+        idle,
+        iowait: delta(start.iowait, end.iowait)?,
+        irq: delta(start.irq, end.irq)?,
+        softirq: delta(start.softirq, end.softirq)?,
+        total,
+        load: 100.0 * (1.0 - idle as f64 / total as f64),
+    })
 }
 
-fn parse_and_analyze_perf(text: &str) -> HashMap<String, PerfReport> {
+pub fn parse_and_analyze_perf(
+    text: &str,
+    platform: Platform,
+) -> Result<HashMap<String, PerfReport>, EntryError> {
     let mut perf: HashMap<String, PerfReport> = HashMap::new();
     let lines = text.lines();
     for line in lines {
-        let matches = if cfg!(feature = "android") {
-            regex_captures!(r#"(\d)+,(\d+),(.+?),.+?,.+?,.*"#, line)
-        } else if cfg!(feature = "ubuntu") {
-            regex_captures!(r#"\d+\.\d+\s+(.+?\-.+?\-.+?)\s+\d+\s+(\d+)\s+(.+)"#, line)
-        } else {
-            panic!("Unknown System");
+        let matches = match platform {
+            // Rows without a CPU come from simpleperf counting system-wide.
+            Platform::Android => regex_captures!(
+                r#"^(\d+),(\d+|<not counted>|<not supported>),(.+?),.+?,.+?,.*"#,
+                line
+            )
+            .or_else(|| {
+                regex_captures!(
+                    r#"^(\d+|<not counted>|<not supported>),([\w-]+),.+?,.+?,.*"#,
+                    line
+                )
+                .map(|(line, value, event)| (line, "all", value, event))
+            }),
+            // Cores are labelled e.g. `S0-D0-C1` and sockets `S0`. Rows
+            // without a label come from perf counting system-wide or attached
+            // to processes.
+            Platform::Ubuntu => regex_captures!(
+                r#"\d+\.\d+\s+(S\d+(?:-D\d+)?(?:-C\d+)?)\s+\d+\s+(\d+|<not counted>|<not supported>)\s+(.+)"#,
+                line
+            )
+            .or_else(|| {
+                regex_captures!(
+                    r#"^\s*\d+\.\d+\s+(\d+|<not counted>|<not supported>)\s+(\S+)"#,
+                    line
+                )
+                .map(|(line, value, event)| (line, "all", value, event))
+            }),
         };
 
         if let Some((_, cpu, value, event)) = matches {
-            let value = value.parse().unwrap();
-            let event = event.trim();
-
-            let perf = perf.entry(cpu.to_string()).or_insert(PerfReport {
-                cycles: 0,
-                context_switches: 0,
-            });
-
-            if event
-                == if cfg!(feature = "android") {
-                    "cpu-cycles"
-                } else if cfg!(feature = "ubuntu") {
-                    "cycles"
-                } else {
-                    panic!("Unknown system");
+            let event = event_key(event.trim());
+            let value = match value.starts_with('<') {
+                true => None,
+                false => Some(value.parse().map_err(|_| EntryError::Line {
+                    section: "perf",
+                    line: line.to_string(),
+                })?),
+            };
+            let record = |report: &mut PerfReport| match value {
+                Some(value) => {
+                    report.events.insert(event, value);
                 }
-            {
-                perf.cycles = value;
-            } else if event == "context-switches" {
-                perf.context_switches = value;
+                None => {
+                    report.missing.insert(event);
+                }
+            };
+            if let Some(report) = perf.get_mut(cpu) {
+                record(report);
             } else {
-                panic!("Unknown event: \"{}\"", event);
+                let mut report = PerfReport::default();
+                record(&mut report);
+                perf.insert(cpu.to_owned(), report);
             }
         }
     }
 
-    for cpu in perf.keys().cloned().collect::<Vec<_>>() {
-        let cycles = perf[&cpu].cycles;
-        let context_switches = perf[&cpu].context_switches;
-
-        let all = perf.entry("all".to_string()).or_insert(PerfReport {
-            cycles: 0,
-            context_switches: 0,
-        });
-
-        all.cycles += cycles;
-        all.context_switches += context_switches;
+    let mut all = PerfReport::default();
+    for (cpu, report) in perf.iter() {
+        if cpu == "all" {
+            continue;
+        }
+        for (event, value) in report.events.iter() {
+            *all.events.entry(event.clone()).or_insert(0) += value;
+        }
+    }
+    // Counted system-wide, perf's own "all" row is the only one.
+    if !perf.contains_key("all")
+        && (!all.events.is_empty() || perf.values().any(|report| !report.missing.is_empty()))
+    {
+        perf.insert("all".to_string(), all);
     }
 
-    perf
+    Ok(perf)
 }
 
-fn get_report(xml: String) -> Report {
-    let log = serde_xml_rs::from_str::<Log>(&xml).unwrap();
-
-    let mut report_entries: Vec<ReportEntry> = Vec::new();
-    let mut proc_cpus = HashSet::new();
-    let mut perf_cpus = HashSet::new();
-
-    for log_entry in log.entries {
-        let proc_start = parse_proc(&log_entry.proc_start);
-        let proc_end = parse_proc(&log_entry.proc_end);
-        let proc = HashMap::from_iter(proc_start.keys().map(|cpu| {
-            if !proc_end.contains_key(cpu) {
-                panic!("CPU {} not found in proc_end", cpu);
-            }
-
-            (cpu.clone(), analyze_proc(&proc_start[cpu], &proc_end[cpu]))
-        }));
-
-        let perf = parse_and_analyze_perf(&log_entry.perf);
-
-        for cpu in proc.keys() {
-            proc_cpus.insert(cpu.clone());
-        }
-
-        for cpu in perf.keys() {
-            perf_cpus.insert(cpu.clone());
+pub fn parse_meminfo(text: &str) -> MemReport {
+    let mut fields = HashMap::new();
+    for line in text.lines() {
+        if let Some((_, name, value)) = regex_captures!(r#"^(\w+):\s+(\d+)"#, line) {
+            fields.insert(name, value.parse::<u64>().unwrap());
         }
-
-        report_entries.push(ReportEntry {
-            time: Duration::from_secs(log_entry.time.parse().unwrap()),
-            proc,
-            perf,
-        });
     }
 
-    let mut perf_cpus: Vec<_> = perf_cpus.into_iter().collect();
-    let mut proc_cpus: Vec<_> = proc_cpus.into_iter().collect();
-    let compare = |a: &String, b: &String| {
-        if a == "all" {
-            std::cmp::Ordering::Greater
-        } else if b == "all" {
-            std::cmp::Ordering::Less
-        } else if a.len() < b.len() {
-            std::cmp::Ordering::Less
-        } else if a.len() > b.len() {
-            std::cmp::Ordering::Greater
-        } else {
-            a.cmp(b)
-        }
-    };
-    perf_cpus.sort_by(compare);
-    proc_cpus.sort_by(compare);
-
-    Report {
-        id: log.id.parse().unwrap(),
-        duration: Duration::from_secs(log.duration.parse().unwrap()),
-        interval: Duration::from_secs(log.interval.parse().unwrap()),
-        entries: report_entries,
-        perf_cpus,
-        proc_cpus,
+    let field = |name| fields.get(name).copied().unwrap_or(0);
+    MemReport {
+        total: field("MemTotal"),
+        available: field("MemAvailable"),
+        buffers: field("Buffers"),
+        cached: field("Cached"),
+        swap_free: field("SwapFree"),
     }
 }
 
-fn get_average_proc_load(report: &Report, cpu: &str) -> f64 {
-    let mut total = 0.0;
-    let mut count = 0;
-    for entry in report.entries.iter() {
-        if entry.proc.contains_key(cpu) {
-            total += entry.proc[cpu].load;
-            count += 1;
-        }
-    }
-
-    total / count as f64
+/// `0.52 0.58 0.59 3/412 12345`, the last being the newest pid.
+pub fn parse_loadavg(text: &str) -> Result<LoadavgReport, EntryError> {
+    let (_, one, five, fifteen, runnable, tasks) = regex_captures!(
+        r#"^(\d+\.\d+) (\d+\.\d+) (\d+\.\d+) (\d+)/(\d+)"#,
+        text.trim()
+    )
+    .ok_or_else(|| EntryError::Line {
+        section: "loadavg",
+        line: text.trim().to_string(),
+    })?;
+    Ok(LoadavgReport {
+        one: one.parse().unwrap(),
+        five: five.parse().unwrap(),
+        fifteen: fifteen.parse().unwrap(),
+        runnable: runnable.parse().unwrap(),
+        tasks: tasks.parse().unwrap(),
+    })
 }
 
-fn get_average_cpu_cycles(report: &Report, cpu: &str) -> f64 {
-    let mut total = 0.0;
-    let mut count = 0;
-    for entry in report.entries.iter() {
-        if entry.perf.contains_key(cpu) {
-            total += entry.perf[cpu].cycles as f64;
-            count += 1;
+/// The interfaces of /proc/net/dev, whose two header lines have no colon.
+pub fn parse_net_dev(text: &str) -> Result<HashMap<String, NetReportPoint>, EntryError> {
+    let mut net = HashMap::new();
+    for line in text.lines() {
+        let Some((iface, counters)) = line.split_once(':') else {
+            continue;
+        };
+        let malformed = || EntryError::Line {
+            section: "net",
+            line: line.to_string(),
+        };
+        let counters = counters
+            .split_whitespace()
+            .map(|counter| counter.parse::<u64>().map_err(|_| malformed()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if counters.len() < 10 {
+            return Err(malformed());
         }
+
+        net.insert(
+            iface.trim().to_string(),
+            NetReportPoint {
+                rx_bytes: counters[0],
+                rx_packets: counters[1],
+                tx_bytes: counters[8],
+                tx_packets: counters[9],
+            },
+        );
     }
-    total / count as f64
+    Ok(net)
 }
 
-const UNIT_NAMES: &[&str] = &[" ", ",000", "M", "B", "T"];
-fn format_number(number: f64) -> String {
-    let mut number = number;
-    let mut unit = 0;
-    while number >= 1000.0 {
-        number /= 1000.0;
-        unit += 1;
-    }
-    format!("{:.2}{}", number, UNIT_NAMES[unit])
-}
-
-fn print_string_collection(arr: &[String]) {
-    let (width, _) = termion::terminal_size().unwrap_or((u16::MAX, u16::MAX));
-    let width = width as i32;
-    let mut remaining_width = width as i32;
-    let mut first = true;
-    let max_len = arr.iter().map(|x| x.len()).max().unwrap();
-    for item in arr {
-        let space = " | ";
-        remaining_width -= (max_len + space.len()) as i32;
-        if first || remaining_width < 0 {
-            if !first {
-                println!();
-            }
-            print!("{:max_len$}", item);
-            remaining_width = width - max_len as i32;
-            first = false;
-        } else {
-            print!("{}{:max_len$}", space, item);
+/// The rows of /proc/diskstats keyed by device. Rows with only the four
+/// counters kernels before 2.6.25 showed for partitions are left out.
+pub fn parse_diskstats(text: &str) -> Result<HashMap<String, DiskReportPoint>, EntryError> {
+    let mut disks = HashMap::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let malformed = || EntryError::Line {
+            section: "diskstats",
+            line: line.to_string(),
+        };
+        let columns: Vec<_> = line.split_whitespace().collect();
+        if columns.len() < 3 {
+            return Err(malformed());
+        }
+        let counters = columns[3..]
+            .iter()
+            .map(|counter| counter.parse::<u64>().map_err(|_| malformed()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if counters.len() < 10 {
+            continue;
         }
+        disks.insert(
+            columns[2].to_string(),
+            DiskReportPoint {
+                reads: counters[0],
+                read_sectors: counters[2],
+                writes: counters[4],
+                written_sectors: counters[6],
+                io_ms: counters[9],
+            },
+        );
     }
-    println!();
+    Ok(disks)
 }
 
-fn main() {
-    let mut args = std::env::args();
-    let cmd = args.next().unwrap();
-    if args.len() == 0 {
-        println!("Usage: {} <file1> <file2> ...", cmd);
-        exit(1);
+/// The lines of /proc/pressure/* as the logger prefixes them with their
+/// resource, e.g. `io some avg10=0.14 avg60=0.29 avg300=0.22 total=16711647`,
+/// keyed like "io some" with avg10 and the total microseconds stalled.
+pub fn parse_pressure(text: &str) -> Result<HashMap<String, (f64, u64)>, EntryError> {
+    let mut pressure = HashMap::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let (_, resource, kind, avg10, total) = regex_captures!(
+            r#"^(\w+) (some|full) avg10=(\d+\.\d+) avg60=\d+\.\d+ avg300=\d+\.\d+ total=(\d+)$"#,
+            line.trim()
+        )
+        .ok_or_else(|| EntryError::Line {
+            section: "pressure",
+            line: line.to_string(),
+        })?;
+        pressure.insert(
+            format!("{} {}", resource, kind),
+            (avg10.parse().unwrap(), total.parse().unwrap()),
+        );
     }
+    Ok(pressure)
+}
 
-    for filename in args {
-        let report = get_report(std::fs::read_to_string(&filename).unwrap());
+/// The counters of /proc/net/snmp or /proc/net/netstat, keyed like
+/// `Tcp:RetransSegs`. Both list each protocol as a row of counter names
+/// followed by a row of values, each starting with the protocol.
+pub fn parse_tcp(text: &str) -> Result<HashMap<String, i64>, EntryError> {
+    let malformed = |line: &str| EntryError::Line {
+        section: "tcp",
+        line: line.to_string(),
+    };
+    let mut counters = HashMap::new();
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    while let Some(header) = lines.next() {
+        let mut names = header.split_whitespace();
+        let protocol = names
+            .next()
+            .filter(|protocol| protocol.ends_with(':'))
+            .ok_or_else(|| malformed(header))?;
+        let row = lines.next().ok_or_else(|| malformed(header))?;
+        let mut values = row.split_whitespace();
+        if values.next() != Some(protocol) {
+            return Err(malformed(row));
+        }
+        let (names, values): (Vec<_>, Vec<_>) = (names.collect(), values.collect());
+        if names.len() != values.len() {
+            return Err(malformed(row));
+        }
+        for (name, value) in names.into_iter().zip(values) {
+            let value = value.parse().map_err(|_| malformed(row))?;
+            counters.insert(format!("{}{}", protocol, name), value);
+        }
+    }
+    Ok(counters)
+}
+
+/// The hex rows of /proc/net/softnet_stat keyed by CPU. Since 5.10 the 13th
+/// column is the row's CPU; before, there is a row per online CPU in order,
+/// the order of `online`, with later columns varying between kernels.
+pub fn parse_softnet(
+    text: &str,
+    online: &[&String],
+) -> Result<HashMap<String, SoftnetReport>, EntryError> {
+    let mut softnet = HashMap::new();
+    for (row, line) in text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+    {
+        let malformed = || EntryError::Line {
+            section: "softnet",
+            line: line.to_string(),
+        };
+        let columns = line
+            .split_whitespace()
+            .map(|column| u64::from_str_radix(column, 16).map_err(|_| malformed()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if columns.len() < 3 {
+            return Err(malformed());
+        }
+        let cpu = match (columns.get(12), online.get(row)) {
+            (Some(cpu), _) => cpu.to_string(),
+            (None, Some(cpu)) => cpu.to_string(),
+            (None, None) => row.to_string(),
+        };
+        softnet.insert(
+            cpu,
+            SoftnetReport {
+                processed: columns[0],
+                dropped: columns[1],
+                time_squeeze: columns[2],
+            },
+        );
+    }
+    Ok(softnet)
+}
+
+/// Counts keyed by /proc/stat CPU number.
+pub type CpuCounts = HashMap<String, u64>;
+
+/// The rows of a table with a column per online CPU under a header naming
+/// them, as in /proc/interrupts and /proc/softirqs: each row's key, its
+/// counts keyed by /proc/stat CPU number and the text after them. Rows such
+/// as `ERR:` counting for the whole machine have a single column and are
+/// left out.
+fn parse_cpu_table(
+    text: &str,
+    section: &'static str,
+) -> Result<Vec<(String, CpuCounts, String)>, EntryError> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next().unwrap_or_default();
+    let cpus = header
+        .split_whitespace()
+        .map(|cpu| cpu.strip_prefix("CPU").map(str::to_string))
+        .collect::<Option<Vec<_>>>()
+        .filter(|cpus| !cpus.is_empty())
+        .ok_or_else(|| EntryError::Line {
+            section,
+            line: header.to_string(),
+        })?;
+    let mut rows = Vec::new();
+    for line in lines {
+        let (key, rest) = line.split_once(':').ok_or_else(|| EntryError::Line {
+            section,
+            line: line.to_string(),
+        })?;
+        let mut columns = rest.split_whitespace().peekable();
+        let mut counts = HashMap::new();
+        for cpu in cpus.iter() {
+            let Some(count) = columns.peek().and_then(|count| count.parse::<u64>().ok()) else {
+                break;
+            };
+            counts.insert(cpu.clone(), count);
+            columns.next();
+        }
+        if counts.len() < cpus.len() {
+            continue;
+        }
+        rows.push((
+            key.trim().to_string(),
+            counts,
+            columns.collect::<Vec<_>>().join(" "),
+        ));
+    }
+    Ok(rows)
+}
+
+/// Per CPU counts of the CPUs in both, wrapping like the kernel's 32 bit
+/// counters.
+fn analyze_cpu_counts(start: &CpuCounts, end: &CpuCounts) -> CpuCounts {
+    start
+        .iter()
+        .filter_map(|(cpu, start)| {
+            let end = end.get(cpu)?;
+            Some((
+                cpu.clone(),
+                (*end as u32).wrapping_sub(*start as u32) as u64,
+            ))
+        })
+        .collect()
+}
+
+/// The rows of /proc/interrupts keyed by IRQ.
+pub fn parse_interrupts(text: &str) -> Result<HashMap<String, IrqReport>, EntryError> {
+    Ok(parse_cpu_table(text, "interrupts")?
+        .into_iter()
+        .map(|(irq, counts, name)| (irq, IrqReport { name, counts }))
+        .collect())
+}
+
+/// The rows that fired between `start` and `end`, each with only the CPUs
+/// that serviced it.
+pub fn analyze_interrupts(
+    start: &HashMap<String, IrqReport>,
+    end: &HashMap<String, IrqReport>,
+) -> HashMap<String, IrqReport> {
+    start
+        .iter()
+        .filter_map(|(irq, start)| {
+            let end = end.get(irq)?;
+            let mut counts = analyze_cpu_counts(&start.counts, &end.counts);
+            counts.retain(|_, count| *count > 0);
+            (!counts.is_empty()).then(|| {
+                (
+                    irq.clone(),
+                    IrqReport {
+                        name: end.name.clone(),
+                        counts,
+                    },
+                )
+            })
+        })
+        .collect()
+}
+
+/// The counts of /proc/softirqs keyed by type, e.g. "NET_RX", then by CPU.
+pub fn parse_softirqs(text: &str) -> Result<HashMap<String, CpuCounts>, EntryError> {
+    Ok(parse_cpu_table(text, "softirqs")?
+        .into_iter()
+        .map(|(kind, counts, _)| (kind, counts))
+        .collect())
+}
+
+pub fn analyze_softirqs(
+    start: &HashMap<String, CpuCounts>,
+    end: &HashMap<String, CpuCounts>,
+) -> HashMap<String, CpuCounts> {
+    start
+        .iter()
+        .filter_map(|(kind, start)| Some((kind.clone(), analyze_cpu_counts(start, end.get(kind)?))))
+        .collect()
+}
+
+/// The per CPU counters of /proc/schedstat, keyed by CPU number. Versions 15
+/// and 16 both have a `cpuN` line of the deprecated yield and expired
+/// counts, schedule() calls and goes idle, wakeups and local wakeups, then
+/// the time run, the run delay and the timeslices; 16 differs only in the
+/// domain lines, which are skipped.
+pub fn parse_schedstat(text: &str) -> Result<HashMap<String, SchedstatReport>, EntryError> {
+    let malformed = |line: &str| EntryError::Line {
+        section: "schedstat",
+        line: line.to_string(),
+    };
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let version_line = lines.next().unwrap_or_default();
+    let version: u32 = version_line
+        .strip_prefix("version ")
+        .and_then(|version| version.trim().parse().ok())
+        .ok_or_else(|| malformed(version_line))?;
+    let (run, delay, timeslices) = match version {
+        15 | 16 => (6, 7, 8),
+        _ => {
+            return Err(EntryError::Version {
+                section: "schedstat",
+                version,
+            })
+        }
+    };
+    // Raw /proc/schedstat also has a timestamp and the domain lines.
+    lines
+        .filter(|line| line.starts_with("cpu"))
+        .map(|line| {
+            let (cpu, counters) = line
+                .strip_prefix("cpu")
+                .and_then(|line| line.split_once(' '))
+                .ok_or_else(|| malformed(line))?;
+            let counters: Vec<u64> = counters
+                .split_whitespace()
+                .map(|counter| counter.parse().map_err(|_| malformed(line)))
+                .collect::<Result<_, _>>()?;
+            let counter =
+                |index: usize| counters.get(index).copied().ok_or_else(|| malformed(line));
+            Ok((
+                cpu.to_string(),
+                SchedstatReport {
+                    run_ns: counter(run)?,
+                    run_delay_ns: counter(delay)?,
+                    timeslices: counter(timeslices)?,
+                },
+            ))
+        })
+        .collect()
+}
+
+pub fn analyze_schedstat(
+    start: &HashMap<String, SchedstatReport>,
+    end: &HashMap<String, SchedstatReport>,
+) -> HashMap<String, SchedstatReport> {
+    start
+        .iter()
+        .filter_map(|(cpu, start)| {
+            let end = end.get(cpu)?;
+            Some((
+                cpu.clone(),
+                SchedstatReport {
+                    run_ns: end.run_ns.saturating_sub(start.run_ns),
+                    run_delay_ns: end.run_delay_ns.saturating_sub(start.run_delay_ns),
+                    timeslices: end.timeslices.saturating_sub(start.timeslices),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// The `cpuN stateK usage time name` lines of the logger's `read_cpuidle`,
+/// as CPU number, state number and the state's counters.
+pub fn parse_cpuidle(text: &str) -> Result<Vec<(String, u32, IdleStateReport)>, EntryError> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (_, cpu, state, usage, time, name) =
+                regex_captures!(r#"^cpu(\d+) state(\d+) (\d+) (\d+) (.*)$"#, line.trim())
+                    .ok_or_else(|| EntryError::Line {
+                        section: "cpuidle",
+                        line: line.to_string(),
+                    })?;
+            Ok((
+                cpu.to_string(),
+                state.parse().unwrap(),
+                IdleStateReport {
+                    name: name.to_string(),
+                    usage: usage.parse().unwrap(),
+                    time_us: time.parse().unwrap(),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Each CPU's states over the interval; states missing at either end, as
+/// of a CPU hotplugged meanwhile, are left out.
+pub fn analyze_cpuidle(
+    start: &[(String, u32, IdleStateReport)],
+    end: &[(String, u32, IdleStateReport)],
+) -> HashMap<String, Vec<IdleStateReport>> {
+    let mut cpus: HashMap<String, Vec<IdleStateReport>> = HashMap::new();
+    for (cpu, state, start) in start.iter() {
+        let Some((_, _, end)) = end
+            .iter()
+            .find(|(end_cpu, end_state, _)| end_cpu == cpu && end_state == state)
+        else {
+            continue;
+        };
+        cpus.entry(cpu.clone()).or_default().push(IdleStateReport {
+            name: end.name.clone(),
+            usage: end.usage.saturating_sub(start.usage),
+            time_us: end.time_us.saturating_sub(start.time_us),
+        });
+    }
+    cpus
+}
+
+/// A rate as ss prints it, bits per second scaled by a `K`, `M` or `G`
+/// prefix or, in newer versions, not at all, e.g. `23.1Mbps`.
+fn parse_bandwidth(text: &str) -> Option<f64> {
+    let (_, value, prefix) = regex_captures!(r#"^(\d+(?:\.\d+)?)([KMG]?)bps$"#, text)?;
+    let scale = match prefix {
+        "K" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        _ => 1.0,
+    };
+    Some(value.parse::<f64>().ok()? * scale)
+}
+
+/// The connections of `ss -tin`, a row per connection followed by an
+/// indented row of its TCP info, keyed by their addresses.
+pub fn parse_sockets(text: &str) -> Result<HashMap<String, SocketReport>, EntryError> {
+    let mut sockets = HashMap::new();
+    let mut connection: Option<String> = None;
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let malformed = || EntryError::Line {
+            section: "sockets",
+            line: line.to_string(),
+        };
+        if line.starts_with("State") {
+            continue;
+        }
+        if !line.starts_with(char::is_whitespace) {
+            let columns: Vec<_> = line.split_whitespace().collect();
+            let [state, _, _, local, peer, ..] = columns[..] else {
+                return Err(malformed());
+            };
+            let key = format!("{} -> {}", local, peer);
+            sockets.insert(
+                key.clone(),
+                SocketReport {
+                    state: state.to_string(),
+                    rtt: None,
+                    cwnd: None,
+                    retrans: 0,
+                    delivery_rate: None,
+                },
+            );
+            connection = Some(key);
+            continue;
+        }
+        let socket = connection
+            .take()
+            .and_then(|key| sockets.get_mut(&key))
+            .ok_or_else(malformed)?;
+        let mut words = line.split_whitespace();
+        while let Some(word) = words.next() {
+            match word.split_once(':') {
+                Some(("rtt", rtt)) => {
+                    let (rtt, _) = rtt.split_once('/').ok_or_else(malformed)?;
+                    socket.rtt = Some(rtt.parse().map_err(|_| malformed())?);
+                }
+                Some(("cwnd", cwnd)) => socket.cwnd = Some(cwnd.parse().map_err(|_| malformed())?),
+                // The segments unacknowledged now, then all so far.
+                Some(("retrans", retrans)) => {
+                    let (_, total) = retrans.split_once('/').ok_or_else(malformed)?;
+                    socket.retrans = total.parse().map_err(|_| malformed())?;
+                }
+                None if word == "delivery_rate" => {
+                    let rate = words.next().ok_or_else(malformed)?;
+                    socket.delivery_rate = Some(parse_bandwidth(rate).ok_or_else(malformed)?);
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(sockets)
+}
+
+/// The kernel keeps these counters in 32 bits, so they wrap.
+pub fn analyze_softnet(start: &SoftnetReport, end: &SoftnetReport) -> SoftnetReport {
+    let delta = |start: u64, end: u64| (end as u32).wrapping_sub(start as u32) as u64;
+    SoftnetReport {
+        processed: delta(start.processed, end.processed),
+        dropped: delta(start.dropped, end.dropped),
+        time_squeeze: delta(start.time_squeeze, end.time_squeeze),
+    }
+}
+
+pub fn analyze_tcp(start: &HashMap<String, i64>, end: &HashMap<String, i64>) -> TcpReport {
+    let delta = |key: &str| match (start.get(key), end.get(key)) {
+        (Some(start), Some(end)) => end.saturating_sub(*start).max(0) as u64,
+        _ => 0,
+    };
+    TcpReport {
+        in_segs: delta("Tcp:InSegs"),
+        out_segs: delta("Tcp:OutSegs"),
+        retrans_segs: delta("Tcp:RetransSegs"),
+        in_errs: delta("Tcp:InErrs"),
+        out_rsts: delta("Tcp:OutRsts"),
+        fast_retrans: delta("TcpExt:TCPFastRetrans"),
+        lost_retransmit: delta("TcpExt:TCPLostRetransmit"),
+        timeouts: delta("TcpExt:TCPTimeouts"),
+        listen_drops: delta("TcpExt:ListenDrops"),
+    }
+}
+
+pub fn analyze_net(start: &NetReportPoint, end: &NetReportPoint) -> NetReport {
+    NetReport {
+        rx_bytes: end.rx_bytes.saturating_sub(start.rx_bytes),
+        rx_packets: end.rx_packets.saturating_sub(start.rx_packets),
+        tx_bytes: end.tx_bytes.saturating_sub(start.tx_bytes),
+        tx_packets: end.tx_packets.saturating_sub(start.tx_packets),
+    }
+}
+
+/// /proc/diskstats counts in 512 byte sectors whatever the device's own.
+pub fn analyze_disk(start: &DiskReportPoint, end: &DiskReportPoint) -> DiskReport {
+    DiskReport {
+        reads: end.reads.saturating_sub(start.reads),
+        read_bytes: end.read_sectors.saturating_sub(start.read_sectors) * 512,
+        writes: end.writes.saturating_sub(start.writes),
+        written_bytes: end.written_sectors.saturating_sub(start.written_sectors) * 512,
+        io_ms: end.io_ms.saturating_sub(start.io_ms),
+    }
+}
+
+/// utime and stime from a /proc/<pid>/stat line. The command name can contain
+/// spaces and parentheses, so fields are counted from its closing parenthesis.
+pub fn parse_pid_stat(line: &str) -> Option<(u64, u64)> {
+    let (_, fields) = line.rsplit_once(')')?;
+    let mut fields = fields.split_whitespace().skip(11);
+    let utime = fields.next()?.parse().ok()?;
+    let stime = fields.next()?.parse().ok()?;
+    Some((utime, stime))
+}
+
+/// A cgroup's cpu.stat at the start of an interval followed by the one at
+/// its end, each a `key value` line per counter, into the counters during
+/// the interval. `total` is the jiffies of all CPUs, as for processes.
+pub fn analyze_cgroup(stat: &str, total: u64) -> Result<CgroupReport, EntryError> {
+    let mut start = HashMap::new();
+    let mut end = HashMap::new();
+    for line in stat.lines().filter(|line| !line.trim().is_empty()) {
+        let malformed = || EntryError::Line {
+            section: "cgroup",
+            line: line.to_string(),
+        };
+        let (key, value) = line.trim().split_once(' ').ok_or_else(malformed)?;
+        let value: u64 = value.parse().map_err(|_| malformed())?;
+        if start.contains_key(key) {
+            end.insert(key, value);
+        } else {
+            start.insert(key, value);
+        }
+    }
+    let delta = |key: &str| -> Result<u64, EntryError> {
+        match (start.get(key), end.get(key)) {
+            (Some(start), Some(end)) => Ok(end.saturating_sub(*start)),
+            // Only cgroups with the cpu controller enabled count throttling.
+            _ if key != "usage_usec" => Ok(0),
+            _ => Err(EntryError::Line {
+                section: "cgroup",
+                line: stat.lines().next().unwrap_or_default().to_string(),
+            }),
+        }
+    };
+    let usage_us = delta("usage_usec")?;
+    Ok(CgroupReport {
+        usage_us,
+        user_us: delta("user_usec")?,
+        system_us: delta("system_usec")?,
+        nr_throttled: delta("nr_throttled")?,
+        throttled_us: delta("throttled_usec")?,
+        share: 100.0 * (usage_us * USER_HZ) as f64 / 1e6 / total.max(1) as f64,
+    })
+}
+
+pub fn analyze_process(stat: &str, total: u64) -> Option<ProcessReport> {
+    let mut lines = stat.lines().filter(|line| !line.trim().is_empty());
+    let (start_utime, start_stime) = parse_pid_stat(lines.next()?)?;
+    let (end_utime, end_stime) = parse_pid_stat(lines.next()?)?;
+    let utime = end_utime.saturating_sub(start_utime);
+    let stime = end_stime.saturating_sub(start_stime);
+    Some(ProcessReport {
+        utime,
+        stime,
+        share: 100.0 * (utime + stime) as f64 / total.max(1) as f64,
+    })
+}
+
+pub fn parse_freq(text: &str) -> HashMap<String, u64> {
+    text.lines()
+        .filter_map(|line| {
+            let (_, cpu, freq) = regex_captures!(r#"^cpu(\d+) (\d+)"#, line)?;
+            Some((cpu.to_string(), freq.parse().unwrap()))
+        })
+        .collect()
+}
+
+/// The `cpuN khz ticks` lines of the logger's `read_time_in_state`, keyed by
+/// CPU number.
+pub fn parse_time_in_state(text: &str) -> Result<HashMap<String, Vec<(u64, u64)>>, EntryError> {
+    let mut cpus: HashMap<String, Vec<(u64, u64)>> = HashMap::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let (_, cpu, khz, ticks) = regex_captures!(r#"^cpu(\d+) (\d+) (\d+)$"#, line.trim())
+            .ok_or_else(|| EntryError::Line {
+                section: "time_in_state",
+                line: line.to_string(),
+            })?;
+        cpus.entry(cpu.to_string())
+            .or_default()
+            .push((khz.parse().unwrap(), ticks.parse().unwrap()));
+    }
+    Ok(cpus)
+}
+
+/// Milliseconds at each frequency over the interval, by ascending
+/// frequency; frequencies missing at either end are left out.
+pub fn analyze_time_in_state(
+    start: &HashMap<String, Vec<(u64, u64)>>,
+    end: &HashMap<String, Vec<(u64, u64)>>,
+) -> HashMap<String, Vec<(u64, u64)>> {
+    start
+        .iter()
+        .filter_map(|(cpu, start)| {
+            let mut states: Vec<_> = end
+                .get(cpu)?
+                .iter()
+                .filter_map(|(khz, end)| {
+                    let (_, start) = start.iter().find(|(start_khz, _)| start_khz == khz)?;
+                    Some((*khz, end.saturating_sub(*start) * 1000 / USER_HZ))
+                })
+                .collect();
+            states.sort();
+            Some((cpu.clone(), states))
+        })
+        .collect()
+}
+
+/// The frequency in kHz weighted by the time at each, none when no time
+/// passed at any, e.g. on an offline CPU.
+pub fn effective_khz(states: &[(u64, u64)]) -> Option<f64> {
+    let time: u64 = states.iter().map(|(_, ms)| ms).sum();
+    (time > 0).then(|| states.iter().map(|(khz, ms)| khz * ms).sum::<u64>() as f64 / time as f64)
+}
+
+/// The `name value` lines the logger reads from the battery's power_supply
+/// directory, each optional.
+pub fn parse_battery(text: &str) -> Result<BatteryReport, EntryError> {
+    let mut battery = BatteryReport::default();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let malformed = || EntryError::Line {
+            section: "battery",
+            line: line.to_string(),
+        };
+        let (name, value) = line.trim().split_once(' ').ok_or_else(malformed)?;
+        let number = || value.parse::<i64>().map_err(|_| malformed());
+        match name {
+            "current_now" => battery.current_now = Some(number()?),
+            "voltage_now" => battery.voltage_now = Some(number()?),
+            "capacity" => battery.capacity = Some(value.parse().map_err(|_| malformed())?),
+            "charge_counter" => battery.charge_counter = Some(number()?),
+            "status" => battery.status = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    Ok(battery)
+}
+
+/// GPU load from Adreno's `busy total` times, from a devfreq `load` such as
+/// `45@600000000Hz` or from a plain percentage such as `45 %`. An idle Adreno
+/// has an empty window, `0 0`.
+pub fn parse_gpu(text: &str) -> Result<f64, EntryError> {
+    let malformed = || EntryError::Line {
+        section: "gpu",
+        line: text.trim().to_string(),
+    };
+    if let Some((_, busy, total)) = regex_captures!(r#"^\s*(\d+)\s+(\d+)\s*$"#, text) {
+        let (busy, total): (u64, u64) = (busy.parse().unwrap(), total.parse().unwrap());
+        return Ok(if total == 0 {
+            0.0
+        } else {
+            (100.0 * busy as f64 / total as f64).min(100.0)
+        });
+    }
+    let (_, load) = regex_captures!(r#"^\s*(\d+(?:\.\d+)?)\s*(?:%|@\d+Hz)?\s*$"#, text)
+        .ok_or_else(malformed)?;
+    load.parse().map_err(|_| malformed())
+}
+
+/// A signal value of dumpsys, where Integer.MAX_VALUE stands for
+/// unavailable.
+fn signal_number(value: &str) -> Option<i64> {
+    let value: i64 = value.parse().ok()?;
+    (value.abs() < i32::MAX as i64).then_some(value)
+}
+
+/// The `name=value` of a `CellSignalStrength*` part, which NR prints as
+/// `name = value`.
+fn signal_value(part: &str, name: &str) -> Option<i64> {
+    signal_number(
+        part.replace(" = ", "=")
+            .split_whitespace()
+            .find_map(|token| token.strip_prefix(name)?.strip_prefix('='))?,
+    )
+}
+
+/// Reads the network type and signal out of `dumpsys telephony.registry` as
+/// Android 10 onwards prints it, each technology's signal in a part of its
+/// own, or out of the bare list of older versions. What doesn't parse is
+/// unknown rather than an error, as the format changes between versions and
+/// vendors.
+pub fn parse_radio(text: &str) -> RadioReport {
+    let line = |prefix: &str| {
+        text.lines()
+            .map(str::trim)
+            .find(|line| line.starts_with(prefix))
+            .unwrap_or_default()
+    };
+    let (service, signal, display) = (
+        line("mServiceState="),
+        line("mSignalStrength="),
+        line("mTelephonyDisplayInfo="),
+    );
+
+    // The 5G icon's override first, then the technology of mobile data as
+    // the registration of Android 11 onwards or the service state names it.
+    let known =
+        |net_type: &&str| !["", "UNKNOWN", "NONE"].contains(&net_type.to_uppercase().as_str());
+    let mut net_type = [
+        regex_captures!(r#"overrideNetwork=(\w+)"#, display),
+        regex_captures!(r#"\bnetwork=(\w+)"#, display),
+        regex_captures!(
+            r#"domain=PS transportType=WWAN.*?accessNetworkTechnology=(\w+)"#,
+            service
+        ),
+        regex_captures!(r#"RilDataRadioTechnology=\d+\((\w+)\)"#, service),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|(_, net_type)| net_type)
+    .find(known)
+    .unwrap_or("unknown")
+    .to_string();
+    // LTE anchoring an NR carrier, on Androids without TelephonyDisplayInfo.
+    if net_type == "LTE" && display.is_empty() && service.contains("mNrState=CONNECTED") {
+        net_type = "NR_NSA".to_string();
+    }
+
+    let part = |name: &str| {
+        signal
+            .split_once(&format!("m{}=CellSignalStrength", name))
+            .map_or("", |(_, part)| part.split([',', '}']).next().unwrap())
+    };
+    let (lte, nr, gsm) = (part("Lte"), part("Nr"), part("Gsm"));
+    let (mut rsrp, mut rsrq) = (signal_value(lte, "rsrp"), signal_value(lte, "rsrq"));
+    if net_type.starts_with("NR") && signal_value(nr, "ssRsrp").is_some() {
+        (rsrp, rsrq) = (signal_value(nr, "ssRsrp"), signal_value(nr, "ssRsrq"));
+    }
+    let mut rssi = signal_value(lte, "rssi").or_else(|| signal_value(gsm, "rssi"));
+    // Before Android 10: GSM signal, bit error rate, CDMA and EVDO dBm and
+    // Ec/Io, EVDO SNR, then LTE signal, RSRP and RSRQ.
+    if let Some((_, list)) = regex_captures!(
+        r#"^mSignalStrength=SignalStrength: (-?\d+(?: -?\d+)*)"#,
+        signal
+    ) {
+        let list: Vec<_> = list.split(' ').collect();
+        let value = |index: usize| signal_number(list.get(index)?);
+        (rsrp, rsrq) = (value(8), value(9));
+        // In ASU, 99 for unknown.
+        rssi = value(0).filter(|asu| *asu != 99).map(|asu| 2 * asu - 113);
+    }
+
+    // `wlan0: 0000   54.  -56.  -256 ...`; some drivers print the level as
+    // an unsigned byte.
+    let wifi_rssi = text.lines().find_map(|line| {
+        let (_, level) =
+            regex_captures!(r#"^[\w.-]+:\s+[0-9a-fA-F]+\s+-?\d+\.?\s+(-?\d+)\.?"#, line)?;
+        let level: i64 = level.parse().ok()?;
+        Some(if level > 63 { level - 256 } else { level })
+    });
+    if net_type == "unknown" && wifi_rssi.is_some() {
+        net_type = "WIFI".to_string();
+    }
+
+    RadioReport {
+        net_type,
+        rsrp,
+        rsrq,
+        rssi,
+        wifi_rssi,
+    }
+}
+
+pub fn parse_thermal(text: &str) -> HashMap<String, ThermalReport> {
+    text.lines()
+        .filter_map(|line| {
+            let (_, zone, kind, temp) = regex_captures!(r#"^(thermal_zone\d+) (\S+) (\S+)"#, line)?;
+            let report = ThermalReport {
+                kind: kind.to_string(),
+                temp: temp.parse().ok(),
+            };
+            Some((zone.to_string(), report))
+        })
+        .collect()
+}
+
+/// The `cpuN package core cluster capacity` lines of the logger's
+/// `read_topology`, skipping lines it doesn't follow.
+pub fn parse_topology(text: &str) -> Vec<CpuTopology> {
+    text.lines()
+        .filter_map(|line| {
+            let (_, cpu, package, core, cluster, capacity) =
+                regex_captures!(r#"^cpu(\d+) (\S+) (\S+) (\S+) (\S+)$"#, line.trim())?;
+            Some(CpuTopology {
+                cpu: cpu.parse().ok()?,
+                package: package.parse().ok(),
+                core: core.parse().ok(),
+                cluster: cluster.parse().ok(),
+                capacity: capacity.parse().ok(),
+            })
+        })
+        .collect()
+}
+
+/// Groups of CPUs a topology suggests: by capacity on big.LITTLE SoCs, as
+/// "little", "mid" for every tier in between and "big"; else by cluster when
+/// there are several; else by package on multi-socket machines, one group
+/// each. None for a machine of alike CPUs.
+pub fn detect_clusters(topology: &[CpuTopology]) -> Vec<CpuGroup> {
+    // Group names may not hold digits, so the n-th is lettered.
+    let letter = |index: usize| (b'a' + (index % 26) as u8) as char;
+    let by = |key: fn(&CpuTopology) -> Option<i64>| -> Vec<(i64, Vec<u64>)> {
+        let mut groups: Vec<(i64, Vec<u64>)> = Vec::new();
+        for cpu in topology.iter() {
+            let Some(key) = key(cpu) else {
+                continue;
+            };
+            match groups.iter_mut().find(|(group, _)| *group == key) {
+                Some((_, cpus)) => cpus.push(cpu.cpu),
+                None => groups.push((key, vec![cpu.cpu])),
+            }
+        }
+        groups.sort();
+        groups
+    };
+    let named = |groups: Vec<(i64, Vec<u64>)>, name: &dyn Fn(usize, usize) -> String| {
+        let count = groups.len();
+        let mut named: Vec<CpuGroup> = Vec::new();
+        for (index, (_, cpus)) in groups.into_iter().enumerate() {
+            let name = name(index, count);
+            match named.iter_mut().find(|group| group.name == name) {
+                Some(group) => group.cpus.extend(cpus),
+                None => named.push(CpuGroup { name, cpus }),
+            }
+        }
+        named
+    };
+
+    let capacities = by(|cpu| cpu.capacity.map(|capacity| capacity as i64));
+    if capacities.len() > 1 {
+        return named(capacities, &|index, count| {
+            match index {
+                0 => "little",
+                _ if index == count - 1 => "big",
+                _ => "mid",
+            }
+            .to_string()
+        });
+    }
+    // x86 has a cluster per core, or -1 without any.
+    let clusters = by(|cpu| cpu.cluster.filter(|cluster| *cluster >= 0));
+    let cores = topology
+        .iter()
+        .filter_map(|cpu| Some((cpu.package?, cpu.core?)))
+        .collect::<HashSet<_>>()
+        .len();
+    if clusters.len() > 1 && clusters.len() < cores {
+        return named(clusters, &|index, _| format!("cluster-{}", letter(index)));
+    }
+    let packages = by(|cpu| cpu.package);
+    if packages.len() > 1 {
+        return named(packages, &|index, _| format!("socket-{}", letter(index)));
+    }
+    Vec::new()
+}
+
+/// The numbers in a CPU label, e.g. `[0, 0, 2]` for the perf core `S0-D0-C2`
+/// and `[2]` for `2`, `cpu2` or `thermal_zone2`.
+fn cpu_indices(cpu: &str) -> Vec<u64> {
+    cpu.split(|c: char| !c.is_ascii_digit())
+        .filter_map(|number| number.parse().ok())
+        .collect()
+}
+
+/// Orders CPU labels by their numbers, then labels without any such as CPU
+/// groups, with "all" last.
+pub fn compare_cpus(a: &str, b: &str) -> std::cmp::Ordering {
+    let key = |cpu: &str| {
+        let indices = cpu_indices(cpu);
+        (cpu == "all", indices.is_empty(), indices)
+    };
+    (key(a), a).cmp(&(key(b), b))
+}
+
+pub fn get_report_entry(
+    log_entry: &LogEntry,
+    platform: Platform,
+) -> Result<ReportEntry, EntryError> {
+    let proc_start = parse_proc(
+        log_entry
+            .proc_start
+            .as_deref()
+            .ok_or(EntryError::Missing("proc-start"))?,
+    )?;
+    let proc_end = parse_proc(
+        log_entry
+            .proc_end
+            .as_deref()
+            .ok_or(EntryError::Missing("proc-end"))?,
+    )?;
+    // A core taken offline during the interval is missing from one of the
+    // snapshots and has no load for it, nor has one whose counters make no
+    // sense, which is kept apart to be told about.
+    let mut proc = HashMap::new();
+    let mut proc_anomalies = HashMap::new();
+    for (cpu, start) in proc_start.iter() {
+        match proc_end.get(cpu).map(|end| analyze_proc(start, end)) {
+            Some(Ok(report)) => {
+                proc.insert(cpu.clone(), report);
+            }
+            Some(Err(anomaly)) => {
+                proc_anomalies.insert(cpu.clone(), anomaly);
+            }
+            None => {}
+        }
+    }
+
+    // Entries for which perf stalled or that were logged without perf have
+    // no counts.
+    let perf = parse_and_analyze_perf(log_entry.perf.as_deref().unwrap_or_default(), platform)?;
+    let mem = log_entry.meminfo.as_deref().map(parse_meminfo);
+    let loadavg = log_entry
+        .loadavg
+        .as_deref()
+        .map(parse_loadavg)
+        .transpose()?;
+    let freq = log_entry
+        .freq
+        .as_deref()
+        .map(parse_freq)
+        .unwrap_or_default();
+    let thermal = log_entry
+        .thermal
+        .as_deref()
+        .map(parse_thermal)
+        .unwrap_or_default();
+    let battery = log_entry
+        .battery
+        .as_deref()
+        .map(parse_battery)
+        .transpose()?;
+    let gpu = log_entry.gpu.as_deref().map(parse_gpu).transpose()?;
+    let radio = log_entry.radio.as_deref().map(parse_radio);
+    let net = match (&log_entry.net_start, &log_entry.net_end) {
+        (Some(start), Some(end)) => {
+            let (start, end) = (parse_net_dev(start)?, parse_net_dev(end)?);
+            start
+                .iter()
+                .filter_map(|(iface, start)| {
+                    Some((iface.clone(), analyze_net(start, end.get(iface)?)))
+                })
+                .collect()
+        }
+        _ => HashMap::new(),
+    };
+    // Devices attached during the interval, such as loop mounts, are only in
+    // the end snapshot and left out.
+    let disk = match (&log_entry.disk_start, &log_entry.disk_end) {
+        (Some(start), Some(end)) => {
+            let (start, end) = (parse_diskstats(start)?, parse_diskstats(end)?);
+            start
+                .iter()
+                .filter_map(|(disk, start)| {
+                    Some((disk.clone(), analyze_disk(start, end.get(disk)?)))
+                })
+                .collect()
+        }
+        _ => HashMap::new(),
+    };
+    let pressure = match (&log_entry.pressure_start, &log_entry.pressure_end) {
+        (Some(start), Some(end)) => {
+            let (start, end) = (parse_pressure(start)?, parse_pressure(end)?);
+            start
+                .iter()
+                .filter_map(|(line, (_, start))| {
+                    let (avg10, end) = end.get(line)?;
+                    Some((
+                        line.clone(),
+                        PressureReport {
+                            avg10: *avg10,
+                            stall_us: end.saturating_sub(*start),
+                        },
+                    ))
+                })
+                .collect()
+        }
+        _ => HashMap::new(),
+    };
+    let mut online: Vec<_> = proc_start.keys().filter(|cpu| *cpu != "all").collect();
+    online.sort_by(|a, b| compare_cpus(a, b));
+    let softnet = match (&log_entry.softnet_start, &log_entry.softnet_end) {
+        (Some(start), Some(end)) => {
+            // An end snapshot taken after a core went offline or came back
+            // lists the rows of the others under the wrong CPUs.
+            let online_end: Vec<_> = {
+                let mut cpus: Vec<_> = proc_end.keys().filter(|cpu| *cpu != "all").collect();
+                cpus.sort_by(|a, b| compare_cpus(a, b));
+                cpus
+            };
+            let (start, end) = (
+                parse_softnet(start, &online)?,
+                parse_softnet(end, &online_end)?,
+            );
+            start
+                .iter()
+                .filter_map(|(cpu, start)| {
+                    Some((cpu.clone(), analyze_softnet(start, end.get(cpu)?)))
+                })
+                .collect()
+        }
+        _ => HashMap::new(),
+    };
+    let tcp = match (&log_entry.tcp_start, &log_entry.tcp_end) {
+        (Some(start), Some(end)) => Some(analyze_tcp(&parse_tcp(start)?, &parse_tcp(end)?)),
+        _ => None,
+    };
+    let irqs = match (&log_entry.irq_start, &log_entry.irq_end) {
+        (Some(start), Some(end)) => Some(analyze_interrupts(
+            &parse_interrupts(start)?,
+            &parse_interrupts(end)?,
+        )),
+        _ => None,
+    };
+    let softirqs = match (&log_entry.softirqs_start, &log_entry.softirqs_end) {
+        (Some(start), Some(end)) => {
+            analyze_softirqs(&parse_softirqs(start)?, &parse_softirqs(end)?)
+        }
+        _ => HashMap::new(),
+    };
+    let time_in_state = match (&log_entry.time_in_state_start, &log_entry.time_in_state_end) {
+        (Some(start), Some(end)) => {
+            analyze_time_in_state(&parse_time_in_state(start)?, &parse_time_in_state(end)?)
+        }
+        _ => HashMap::new(),
+    };
+    let schedstat = match (&log_entry.schedstat_start, &log_entry.schedstat_end) {
+        (Some(start), Some(end)) => {
+            analyze_schedstat(&parse_schedstat(start)?, &parse_schedstat(end)?)
+        }
+        _ => HashMap::new(),
+    };
+    let cpuidle = match (&log_entry.idle_start, &log_entry.idle_end) {
+        (Some(start), Some(end)) => analyze_cpuidle(&parse_cpuidle(start)?, &parse_cpuidle(end)?),
+        _ => HashMap::new(),
+    };
+
+    let sockets = match &log_entry.sockets {
+        Some(sockets) => parse_sockets(sockets)?,
+        None => HashMap::new(),
+    };
+
+    let total = proc.get("all").map(|proc| proc.total).unwrap_or(0);
+    let processes = log_entry
+        .proc_pids
+        .iter()
+        .map(|proc_pid| {
+            let report = proc_pid
+                .stat
+                .as_deref()
+                .and_then(|stat| analyze_process(stat, total));
+            (proc_pid.pid.clone(), report)
+        })
+        .collect();
+    let cgroups = log_entry
+        .cgroups
+        .iter()
+        .map(|cgroup| {
+            let report = match cgroup.stat.as_deref() {
+                Some(stat) => Some(analyze_cgroup(stat, total)?),
+                None => None,
+            };
+            Ok((cgroup.path.clone(), report))
+        })
+        .collect::<Result<_, EntryError>>()?;
+
+    Ok(ReportEntry {
+        time: Duration::from_millis(log_entry.time.parse().map_err(|_| EntryError::Time)?),
+        wall_time: match &log_entry.wall_time {
+            Some(wall_time) => Some(timestamp::parse(wall_time).ok_or(EntryError::Time)?),
+            None => None,
+        },
+        skipped: match &log_entry.skipped {
+            Some(skipped) => skipped.parse().map_err(|_| EntryError::Skipped)?,
+            None => 0,
+        },
+        proc,
+        proc_anomalies,
+        perf,
+        mem,
+        loadavg,
+        net,
+        tcp,
+        softnet,
+        irqs,
+        softirqs,
+        cpuidle,
+        schedstat,
+        sockets,
+        disk,
+        pressure,
+        freq,
+        time_in_state,
+        thermal,
+        battery,
+        gpu,
+        radio,
+        processes,
+        cgroups,
+        profiler: log_entry
+            .self_stat
+            .as_deref()
+            .and_then(|stat| analyze_process(stat, total)),
+    })
+}
+
+/// Analyzes a log as it is read, so only the text of the entry being read is
+/// held in memory. With `lenient`, entries that fail to parse are left out
+/// and kept in `Report::skipped` instead of failing the log. A log cut off by
+/// a killed logger is read up to its last complete entry.
+pub fn get_report(
+    input: impl BufRead,
+    lenient: bool,
+    time_unit: Option<TimeUnit>,
+) -> Result<Report, ParseError> {
+    read_report(input, lenient, time_unit, |_| {})
+}
+
+/// `get_report`, handing `on_entry` each entry as it is analyzed, e.g. to
+/// follow a log still being written. Reading stops at `</log>`.
+pub fn read_report(
+    input: impl BufRead,
+    lenient: bool,
+    time_unit: Option<TimeUnit>,
+    mut on_entry: impl FnMut(&ReportEntry),
+) -> Result<Report, ParseError> {
+    let mut reader = Reader::from_reader(input);
+    // Older logs may close a payload holding perf's raw `<not counted>`.
+    reader.check_end_names(false);
+    let mut buf = Vec::new();
+    let mut log: Option<Log> = None;
+    let mut platform = None;
+    let mut log_entry: Option<LogEntry> = None;
+    let mut metadata = Metadata::default();
+    let mut pid = String::new();
+    let mut cgroup = String::new();
+    let mut text = String::new();
+    let mut index = 0;
+    let mut truncated = true;
+    let mut legacy_time = false;
+    let mut version = None;
+
+    let mut report_entries: Vec<ReportEntry> = Vec::new();
+    let mut proc_cpus = HashSet::new();
+    let mut perf_cpus = HashSet::new();
+    let mut perf_events: Vec<String> = Vec::new();
+    let mut interfaces = HashSet::new();
+    let mut disks = HashSet::new();
+    let mut cgroups = HashSet::new();
+    let mut freq_cpus = HashSet::new();
+    let mut thermal_zones = HashSet::new();
+    let mut duration_actual = None;
+    let mut next_part = None;
+    let mut exit = None;
+    let mut markers = Vec::new();
+    let mut skipped = Vec::new();
+
+    loop {
+        let event = match reader.read_event_into(&mut buf) {
+            Ok(event) => event,
+            Err(source) => {
+                let position = reader.buffer_position();
+                // A logger killed mid-write leaves a half-written tag behind,
+                // which is only an error when more of the log follows it.
+                buf.clear();
+                if let Ok(Event::Eof) = reader.read_event_into(&mut buf) {
+                    break;
+                }
+                return Err(ParseError::Xml { position, source });
+            }
+        };
+        let position = reader.buffer_position();
+        let xml_error = |source| ParseError::Xml { position, source };
+
+        let log_entry = match event {
+            Event::Start(element) => {
+                let known = Element::from_name(element.name().as_ref());
+                // Taken for the text it was in a payload of an older log.
+                if known.is_none()
+                    && log_entry.is_some()
+                    && version.is_none_or(|version| version < schema::CDATA_SINCE)
+                {
+                    text.push('<');
+                    text.push_str(&String::from_utf8_lossy(&element));
+                    text.push('>');
+                    buf.clear();
+                    continue;
+                }
+                text.clear();
+                match known {
+                    Some(Element::Log) => {
+                        let header = Log::from_element(&element).map_err(xml_error)?;
+                        // Checked first, as a newer log may fail anywhere
+                        // after.
+                        version = match &header.schema {
+                            Some(version) => Some(parse_attribute(schema::SCHEMA, version)?),
+                            None => None,
+                        };
+                        if let Some(version) = version.filter(|&version| version > schema::VERSION)
+                        {
+                            return Err(ParseError::NewerSchema(version));
+                        }
+                        if let Some(name) = &header.platform {
+                            platform = Some(
+                                Platform::from_name(name)
+                                    .ok_or_else(|| ParseError::UnknownPlatform(name.clone()))?,
+                            );
+                        }
+                        perf_events = match (&header.events, header.perf.as_deref()) {
+                            (Some(events), _) => events.split(',').map(event_key).collect(),
+                            (None, Some(schema::PERF_OFF)) => Vec::new(),
+                            (None, _) => vec![CYCLES.to_string(), CONTEXT_SWITCHES.to_string()],
+                        };
+                        log = Some(header);
+                    }
+                    Some(Element::LogEntry) => {
+                        if log.is_none() {
+                            return Err(ParseError::NoLog);
+                        }
+                        let time_ms =
+                            get_attribute(&element, schema::TIME_MS).map_err(xml_error)?;
+                        let wall_time =
+                            get_attribute(&element, schema::WALL_TIME).map_err(xml_error)?;
+                        let wall_time = (!wall_time.is_empty()).then_some(wall_time);
+                        let skipped =
+                            get_attribute(&element, schema::SKIPPED).map_err(xml_error)?;
+                        let skipped = (!skipped.is_empty()).then_some(skipped);
+                        // Only loggers predating the schema version wrote
+                        // `time`, whose unit is guessed below.
+                        if time_ms.is_empty() && version.is_some() {
+                            return Err(ParseError::Attribute(schema::TIME_MS, time_ms));
+                        }
+                        log_entry = Some(if time_ms.is_empty() {
+                            LogEntry {
+                                time: get_attribute(&element, schema::TIME).map_err(xml_error)?,
+                                legacy_time: true,
+                                wall_time,
+                                skipped,
+                                ..Default::default()
+                            }
+                        } else {
+                            LogEntry {
+                                time: time_ms,
+                                wall_time,
+                                skipped,
+                                ..Default::default()
+                            }
+                        });
+                    }
+                    Some(Element::ProcPid) => {
+                        pid = get_attribute(&element, schema::PID).map_err(xml_error)?
+                    }
+                    Some(Element::Cgroup) => {
+                        cgroup = get_attribute(&element, schema::PATH).map_err(xml_error)?
+                    }
+                    _ => {}
+                }
+                buf.clear();
+                continue;
+            }
+            Event::Empty(element) => {
+                match Element::from_name(element.name().as_ref()) {
+                    Some(Element::LogEnd) => {
+                        let duration =
+                            get_attribute(&element, schema::DURATION_ACTUAL).map_err(xml_error)?;
+                        duration_actual = Some(Duration::from_millis(parse_attribute(
+                            schema::DURATION_ACTUAL,
+                            &duration,
+                        )?));
+                        let next = get_attribute(&element, schema::NEXT).map_err(xml_error)?;
+                        next_part = (!next.is_empty()).then_some(next);
+                        let code = get_attribute(&element, schema::EXIT_CODE).map_err(xml_error)?;
+                        let signal =
+                            get_attribute(&element, schema::EXIT_SIGNAL).map_err(xml_error)?;
+                        if !code.is_empty() {
+                            exit = Some(Exit::Code(parse_attribute(schema::EXIT_CODE, &code)?));
+                        } else if !signal.is_empty() {
+                            exit =
+                                Some(Exit::Signal(parse_attribute(schema::EXIT_SIGNAL, &signal)?));
+                        }
+                    }
+                    Some(Element::Marker) => {
+                        let time = get_attribute(&element, schema::TIME_MS).map_err(xml_error)?;
+                        markers.push(Marker {
+                            time: Duration::from_millis(parse_attribute(schema::TIME_MS, &time)?),
+                            label: get_attribute(&element, schema::LABEL).map_err(xml_error)?,
+                        });
+                    }
+                    Some(Element::ProcPid) => {
+                        let pid = get_attribute(&element, schema::PID).map_err(xml_error)?;
+                        if let Some(log_entry) = log_entry.as_mut() {
+                            log_entry.proc_pids.push(LogProcPid { pid, stat: None });
+                        }
+                    }
+                    Some(Element::Cgroup) => {
+                        let path = get_attribute(&element, schema::PATH).map_err(xml_error)?;
+                        if let Some(log_entry) = log_entry.as_mut() {
+                            log_entry.cgroups.push(LogCgroup { path, stat: None });
+                        }
+                    }
+                    _ => {}
+                }
+                buf.clear();
+                continue;
+            }
+            Event::Text(content) => {
+                // The text ends where the reader is, so the offset of a bad
+                // byte in it is an offset in the log.
+                let raw = std::str::from_utf8(&content).map_err(|error| {
+                    ParseError::Utf8(position - content.len() + error.valid_up_to())
+                })?;
+                text.push_str(
+                    &quick_xml::escape::unescape(raw).map_err(|error| xml_error(error.into()))?,
+                );
+                buf.clear();
+                continue;
+            }
+            Event::CData(content) => {
+                let raw = std::str::from_utf8(&content).map_err(|error| {
+                    ParseError::Utf8(position - "]]>".len() - content.len() + error.valid_up_to())
+                })?;
+                text.push_str(raw);
+                buf.clear();
+                continue;
+            }
+            Event::End(element) => {
+                let name = Element::from_name(element.name().as_ref());
+                let field = log_entry.as_mut().and_then(|log_entry| match name? {
+                    Element::ProcStart => Some(&mut log_entry.proc_start),
+                    Element::ProcEnd => Some(&mut log_entry.proc_end),
+                    Element::Perf => Some(&mut log_entry.perf),
+                    Element::Meminfo => Some(&mut log_entry.meminfo),
+                    Element::Loadavg => Some(&mut log_entry.loadavg),
+                    Element::Freq => Some(&mut log_entry.freq),
+                    Element::Thermal => Some(&mut log_entry.thermal),
+                    Element::Battery => Some(&mut log_entry.battery),
+                    Element::Gpu => Some(&mut log_entry.gpu),
+                    Element::Radio => Some(&mut log_entry.radio),
+                    Element::NetStart => Some(&mut log_entry.net_start),
+                    Element::NetEnd => Some(&mut log_entry.net_end),
+                    Element::TcpStart => Some(&mut log_entry.tcp_start),
+                    Element::TcpEnd => Some(&mut log_entry.tcp_end),
+                    Element::SoftnetStart => Some(&mut log_entry.softnet_start),
+                    Element::SoftnetEnd => Some(&mut log_entry.softnet_end),
+                    Element::IrqStart => Some(&mut log_entry.irq_start),
+                    Element::IrqEnd => Some(&mut log_entry.irq_end),
+                    Element::SoftirqsStart => Some(&mut log_entry.softirqs_start),
+                    Element::SoftirqsEnd => Some(&mut log_entry.softirqs_end),
+                    Element::TimeInStateStart => Some(&mut log_entry.time_in_state_start),
+                    Element::TimeInStateEnd => Some(&mut log_entry.time_in_state_end),
+                    Element::IdleStart => Some(&mut log_entry.idle_start),
+                    Element::IdleEnd => Some(&mut log_entry.idle_end),
+                    Element::SchedstatStart => Some(&mut log_entry.schedstat_start),
+                    Element::SchedstatEnd => Some(&mut log_entry.schedstat_end),
+                    Element::Sockets => Some(&mut log_entry.sockets),
+                    Element::DiskStart => Some(&mut log_entry.disk_start),
+                    Element::DiskEnd => Some(&mut log_entry.disk_end),
+                    Element::PressureStart => Some(&mut log_entry.pressure_start),
+                    Element::PressureEnd => Some(&mut log_entry.pressure_end),
+                    Element::SelfStat => Some(&mut log_entry.self_stat),
+                    _ => None,
+                });
+                if let Some(field) = field {
+                    *field = Some(std::mem::take(&mut text));
+                }
+                let value = || Some(text.trim().to_string());
+                match name {
+                    Some(Element::Hostname) => metadata.hostname = value(),
+                    Some(Element::Uname) => metadata.uname = value(),
+                    Some(Element::CpuModel) => metadata.cpu_model = value(),
+                    Some(Element::LogicalCpus) => metadata.logical_cpus = text.trim().parse().ok(),
+                    Some(Element::PhysicalCpus) => {
+                        metadata.physical_cpus = text.trim().parse().ok()
+                    }
+                    Some(Element::Topology) => metadata.topology = parse_topology(&text),
+                    Some(Element::DeviceModel) => metadata.device_model = value(),
+                    Some(Element::PinnedCpu) => metadata.pinned_cpu = text.trim().parse().ok(),
+                    Some(Element::Command) => metadata.command = value(),
+                    Some(Element::Options) => metadata.options = value(),
+                    Some(Element::ProcPid) => {
+                        if let Some(log_entry) = log_entry.as_mut() {
+                            log_entry.proc_pids.push(LogProcPid {
+                                pid: std::mem::take(&mut pid),
+                                stat: Some(std::mem::take(&mut text)),
+                            });
+                        }
+                    }
+                    Some(Element::Cgroup) => {
+                        if let Some(log_entry) = log_entry.as_mut() {
+                            log_entry.cgroups.push(LogCgroup {
+                                path: std::mem::take(&mut cgroup),
+                                stat: Some(std::mem::take(&mut text)),
+                            });
+                        }
+                    }
+                    Some(Element::Log) => {
+                        truncated = false;
+                        break;
+                    }
+                    _ => {}
+                }
+                if name != Some(Element::LogEntry) {
+                    buf.clear();
+                    continue;
+                }
+                match log_entry.take() {
+                    Some(log_entry) => log_entry,
+                    None => {
+                        buf.clear();
+                        continue;
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {
+                buf.clear();
+                continue;
+            }
+        };
+        buf.clear();
+
+        legacy_time |= log_entry.legacy_time;
+        let platform = *platform
+            .get_or_insert_with(|| Platform::guess(log_entry.perf.as_deref().unwrap_or_default()));
+        index += 1;
+        let report_entry = match get_report_entry(&log_entry, platform) {
+            Ok(report_entry) => report_entry,
+            Err(source) => {
+                let error = ParseError::Entry {
+                    index: index - 1,
+                    time: log_entry.time.clone(),
+                    source,
+                };
+                if !lenient {
+                    return Err(error);
+                }
+                skipped.push(error);
+                continue;
+            }
+        };
+
+        for cpu in report_entry.freq.keys() {
+            freq_cpus.insert(cpu.clone());
+        }
+        for zone in report_entry.thermal.keys() {
+            thermal_zones.insert(zone.clone());
+        }
+        for iface in report_entry.net.keys() {
+            interfaces.insert(iface.clone());
+        }
+        for disk in report_entry.disk.keys() {
+            disks.insert(disk.clone());
+        }
+        for cgroup in report_entry.cgroups.keys() {
+            cgroups.insert(cgroup.clone());
+        }
+        for cpu in report_entry.proc.keys() {
+            proc_cpus.insert(cpu.clone());
+        }
+        for (cpu, report) in report_entry.perf.iter() {
+            perf_cpus.insert(cpu.clone());
+            for event in report.events.keys().chain(report.missing.iter()) {
+                if !perf_events.contains(event) {
+                    perf_events.push(event.clone());
+                }
+            }
+        }
+
+        on_entry(&report_entry);
+        report_entries.push(report_entry);
+    }
+
+    if report_entries.is_empty() {
+        return Err(ParseError::NoEntries);
+    }
+
+    perf_events.retain(|event| {
+        report_entries.iter().any(|entry| {
+            entry
+                .perf
+                .values()
+                .any(|perf| perf.get(event).is_some() || perf.missing.contains(event))
+        })
+    });
+
+    let mut perf_cpus: Vec<_> = perf_cpus.into_iter().collect();
+    let mut proc_cpus: Vec<_> = proc_cpus.into_iter().collect();
+    perf_cpus.sort_by(|a, b| compare_cpus(a, b));
+    let mut freq_cpus: Vec<_> = freq_cpus.into_iter().collect();
+    freq_cpus.sort_by(|a, b| compare_cpus(a, b));
+    let mut thermal_zones: Vec<_> = thermal_zones.into_iter().collect();
+    thermal_zones.sort_by(|a, b| compare_cpus(a, b));
+    let mut interfaces: Vec<_> = interfaces.into_iter().collect();
+    interfaces.sort();
+    let mut disks: Vec<_> = disks.into_iter().collect();
+    disks.sort();
+    let mut cgroups: Vec<_> = cgroups.into_iter().collect();
+    cgroups.sort();
+    proc_cpus.sort_by(|a, b| compare_cpus(a, b));
+
+    let log = log.ok_or(ParseError::NoLog)?;
+    let duration = Duration::from_secs(parse_attribute(schema::DURATION, &log.duration)?);
+
+    // The logger has always written milliseconds, but the reporter used to
+    // read `time` as seconds, so a log whose times never pass its duration in
+    // seconds is taken to be in seconds.
+    let legacy_unit = match time_unit {
+        Some(time_unit) => time_unit,
+        None if report_entries.len() > 1
+            && report_entries.last().unwrap().time.as_millis() <= duration.as_secs() as u128 =>
+        {
+            TimeUnit::S
+        }
+        None => TimeUnit::Ms,
+    };
+    if legacy_time && legacy_unit == TimeUnit::S {
+        for entry in report_entries.iter_mut() {
+            entry.time = Duration::from_secs(entry.time.as_millis() as u64);
+        }
+    }
+
+    let mut report = Report {
+        id: parse_attribute(schema::ID, &log.id)?,
+        schema: version,
+        platform: platform.unwrap_or(Platform::Ubuntu),
+        perf_granularity: match &log.perf_granularity {
+            Some(name) => PerfGranularity::from_name(name)
+                .ok_or_else(|| ParseError::Attribute(schema::PERF_GRANULARITY, name.clone()))?,
+            None => PerfGranularity::Core,
+        },
+        metadata,
+        start: match &log.start {
+            Some(start) => Some(
+                timestamp::parse(start)
+                    .ok_or_else(|| ParseError::Attribute(schema::START, start.clone()))?,
+            ),
+            None => None,
+        },
+        duration,
+        duration_actual,
+        exit,
+        part: match &log.part {
+            Some(part) => Some(parse_attribute(schema::PART, part)?),
+            None => None,
+        },
+        next_part,
+        clock_offset_ms: match &log.clock_offset_ms {
+            Some(offset) => Some(parse_attribute(schema::CLOCK_OFFSET_MS, offset)?),
+            None => None,
+        },
+        warmup: match &log.warmup_ms {
+            Some(warmup) => Some(Duration::from_millis(parse_attribute(
+                schema::WARMUP_MS,
+                warmup,
+            )?)),
+            None => None,
+        },
+        interval: match (log.interval_ms, log.interval) {
+            (Some(interval_ms), _) => {
+                Duration::from_millis(parse_attribute(schema::INTERVAL_MS, &interval_ms)?)
+            }
+            (None, Some(interval)) => {
+                Duration::from_secs(parse_attribute(schema::INTERVAL, &interval)?)
+            }
+            (None, None) => return Err(ParseError::NoInterval),
+        },
+        entries: report_entries,
+        markers,
+        sources: Vec::new(),
+        perf_cpus,
+        proc_cpus,
+        perf_events,
+        interfaces,
+        disks,
+        freq_cpus,
+        thermal_zones,
+        pids: log
+            .pids
+            .map(|pids| pids.split(',').map(|pid| pid.to_string()).collect())
+            .unwrap_or_default(),
+        cpu_list: match &log.cpu_list {
+            Some(cpus) => parse_cpu_list(cpus)
+                .ok_or_else(|| ParseError::Attribute(schema::CPU_LIST, cpus.clone()))?,
+            None => Vec::new(),
+        },
+        cgroups,
+        groups: Vec::new(),
+        skipped,
+        truncated,
+    };
+    if !report.cpu_list.is_empty() {
+        let cpus = report.cpu_list.clone();
+        filter_cpus(&mut report, &cpus);
+    }
+    Ok(report)
+}
+
+pub fn get_average_proc_load(report: &Report, cpu: &str) -> f64 {
+    let mut total = 0.0;
+    let mut count = 0;
+    for entry in report.entries.iter() {
+        if entry.proc.contains_key(cpu) {
+            total += entry.proc[cpu].load;
+            count += 1;
+        }
+    }
+
+    total / count as f64
+}
+
+pub const PROC_CATEGORIES: [&str; 7] =
+    ["user", "nice", "system", "idle", "iowait", "irq", "softirq"];
+
+/// Average percentage of time `cpu` spent in each of `PROC_CATEGORIES`.
+pub fn get_average_breakdown(report: &Report, cpu: &str) -> [f64; 7] {
+    let mut total = [0.0; 7];
+    let mut count = 0;
+    for proc in report
+        .entries
+        .iter()
+        .filter_map(|entry| entry.proc.get(cpu))
+    {
+        if proc.total == 0 {
+            continue;
+        }
+        let categories = [
+            proc.user,
+            proc.nice,
+            proc.system,
+            proc.idle,
+            proc.iowait,
+            proc.irq,
+            proc.softirq,
+        ];
+        for (total, jiffies) in total.iter_mut().zip(categories) {
+            *total += 100.0 * jiffies as f64 / proc.total as f64;
+        }
+        count += 1;
+    }
+    total.map(|total| total / count as f64)
+}
+
+/// Percentage of the intervals in which `cpu` was online for its whole length.
+pub fn get_online_percent(report: &Report, cpu: &str) -> f64 {
+    let online = report
+        .entries
+        .iter()
+        .filter(|entry| entry.proc.contains_key(cpu) || entry.proc_anomalies.contains_key(cpu))
+        .count();
+    100.0 * online as f64 / report.entries.len() as f64
+}
+
+pub fn get_average_perf(report: &Report, cpu: &str, event: &str) -> f64 {
+    if cpu == "all" && report.perf_cpus.len() > 1 {
+        // Summing the per-core averages keeps the "all" row equal to the sum
+        // of the rows above it even when a core is missing from some entries.
+        return report
+            .perf_cpus
+            .iter()
+            .filter(|cpu| !report.is_aggregate(cpu))
+            .map(|cpu| get_average_perf(report, cpu, event))
+            .filter(|average| !average.is_nan())
+            .sum();
+    }
+
+    let mut total = 0.0;
+    let mut count = 0;
+    for entry in report.entries.iter() {
+        if let Some(value) = entry.perf.get(cpu).and_then(|perf| perf.get(event)) {
+            total += value as f64;
+            count += 1;
+        }
+    }
+    total / count as f64
+}
+
+pub fn get_average_cpu_cycles(report: &Report, cpu: &str) -> f64 {
+    get_average_perf(report, cpu, CYCLES)
+}
+
+pub fn get_average_context_switches(report: &Report, cpu: &str) -> f64 {
+    get_average_perf(report, cpu, CONTEXT_SWITCHES)
+}
+
+/// Number of per-core samples perf could not count for `event`.
+pub fn get_dropped_samples(report: &Report, event: &str) -> usize {
+    report
+        .entries
+        .iter()
+        .flat_map(|entry| entry.perf.iter())
+        .filter(|(cpu, perf)| {
+            (!report.is_aggregate(cpu) || report.perf_cpus.len() == 1)
+                && perf.missing.contains(event)
+        })
+        .count()
+}
+
+/// Average instructions per cycle over the intervals where both counters were
+/// running. The "all" row only counts cores that had both counters.
+pub fn get_average_ipc(report: &Report, cpu: &str) -> f64 {
+    let mut total = 0.0;
+    let mut count = 0;
+    for entry in report.entries.iter() {
+        let ipc = if cpu == "all" && report.perf_cpus.len() > 1 {
+            let (instructions, cycles) = entry
+                .perf
+                .iter()
+                .filter(|(cpu, _)| !report.is_aggregate(cpu))
+                .filter_map(|(_, perf)| perf.ipc())
+                .fold((0, 0), |(i, c), (instructions, cycles)| {
+                    (i + instructions, c + cycles)
+                });
+            (cycles > 0).then(|| instructions as f64 / cycles as f64)
+        } else {
+            entry
+                .perf
+                .get(cpu)
+                .and_then(|perf| perf.ipc())
+                .map(|(instructions, cycles)| instructions as f64 / cycles as f64)
+        };
+        if let Some(ipc) = ipc {
+            total += ipc;
+            count += 1;
+        }
+    }
+    total / count as f64
+}
+
+/// Average and minimum MemAvailable in kB, if the log sampled memory.
+pub fn get_available_memory(report: &Report) -> Option<(f64, u64)> {
+    let available: Vec<_> = report
+        .entries
+        .iter()
+        .filter_map(|entry| entry.mem.as_ref())
+        .map(|mem| mem.available)
+        .collect();
+    let min = *available.iter().min()?;
+    let average = available.iter().sum::<u64>() as f64 / available.len() as f64;
+    Some((average, min))
+}
+
+/// The `count` entries with the highest `value`, earliest first among equal
+/// values; entries without a value are left out.
+pub fn get_busiest_entries(
+    report: &Report,
+    count: usize,
+    value: impl Fn(&ReportEntry) -> Option<f64>,
+) -> Vec<(&ReportEntry, f64)> {
+    let mut entries: Vec<_> = report
+        .entries
+        .iter()
+        .filter_map(|entry| Some((entry, value(entry)?)))
+        .collect();
+    entries
+        .sort_by(|(a, a_value), (b, b_value)| b_value.total_cmp(a_value).then(a.time.cmp(&b.time)));
+    entries.truncate(count);
+    entries
+}
+
+/// The load of `cpu` averaged over at most `buckets` runs of consecutive
+/// entries, `None` for runs in which the CPU was never online.
+pub fn get_load_buckets(report: &Report, cpu: &str, buckets: usize) -> Vec<Option<f64>> {
+    let entries = &report.entries;
+    let buckets = buckets.clamp(1, entries.len().max(1));
+    (0..buckets)
+        .map(|bucket| {
+            let range = bucket * entries.len() / buckets..(bucket + 1) * entries.len() / buckets;
+            let loads: Vec<_> = entries[range]
+                .iter()
+                .filter_map(|entry| Some(entry.proc.get(cpu)?.load))
+                .collect();
+            (!loads.is_empty()).then(|| loads.iter().sum::<f64>() / loads.len() as f64)
+        })
+        .collect()
+}
+
+/// Wall-clock time an entry started at, when the log recorded its start.
+pub fn get_wall_time(report: &Report, entry: &ReportEntry) -> Option<SystemTime> {
+    entry
+        .wall_time
+        .or_else(|| report.start.map(|start| start + entry.time))
+}
+
+/// A `--from` or `--to` bound: an offset into the run or a wall-clock time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeBound {
+    Offset(Duration),
+    At(SystemTime),
+}
+
+/// Keeps the entries whose interval starts within `from..=to`, failing rather
+/// than leaving nothing to average. Wall-clock
+/// bounds are compared with an entry's `wall_time`, or with the start of the
+/// run plus its offset in logs captured without `--timestamps`.
+pub fn filter_time(
+    report: &mut Report,
+    from: Option<TimeBound>,
+    to: Option<TimeBound>,
+) -> Result<(), ParseError> {
+    if from.is_none() && to.is_none() {
+        return Ok(());
+    }
+    let start = report.start;
+    let wall_time = |entry: &ReportEntry| entry.wall_time.or(start.map(|start| start + entry.time));
+    let absolute = [from, to]
+        .iter()
+        .any(|bound| matches!(bound, Some(TimeBound::At(_))));
+    if absolute
+        && report
+            .entries
+            .iter()
+            .any(|entry| wall_time(entry).is_none())
+    {
+        return Err(ParseError::NoStartTime);
+    }
+    let compare = |entry: &ReportEntry, bound: TimeBound| match bound {
+        TimeBound::Offset(offset) => entry.time.cmp(&offset),
+        TimeBound::At(time) => wall_time(entry).unwrap().cmp(&time),
+    };
+    report.entries.retain(|entry| {
+        from.is_none_or(|from| compare(entry, from).is_ge())
+            && to.is_none_or(|to| compare(entry, to).is_le())
+    });
+    if report.entries.is_empty() {
+        return Err(ParseError::NoEntriesInRange);
+    }
+
+    // A core or interface only seen outside the window would average to NaN.
+    retain_seen(report);
+    Ok(())
+}
+
+/// Trims `reports` to the wall-clock window all of them cover, their clocks
+/// first put right by their `clock_offset_ms`, so that entries captured on
+/// different machines line up; returns the window on the coordinator's clock.
+pub fn align_by_time(reports: &mut [&mut Report]) -> Result<(SystemTime, SystemTime), ParseError> {
+    // The coordinator's time from a report's clock and back.
+    let shift = |time: SystemTime, offset: i64, back: bool| {
+        let by = Duration::from_millis(offset.unsigned_abs());
+        if (offset >= 0) != back {
+            time - by
+        } else {
+            time + by
+        }
+    };
+    let mut window: Option<(SystemTime, SystemTime)> = None;
+    for report in reports.iter() {
+        let offset = report.clock_offset_ms.unwrap_or(0);
+        let wall_time = |entry: &ReportEntry| {
+            entry
+                .wall_time
+                .or(report.start.map(|start| start + entry.time))
+                .map(|time| shift(time, offset, false))
+        };
+        let (Some(first), Some(last)) = (
+            report.entries.first().and_then(wall_time),
+            report.entries.last().and_then(wall_time),
+        ) else {
+            return Err(ParseError::NoStartTime);
+        };
+        window = Some(match window {
+            Some((from, to)) => (from.max(first), to.min(last)),
+            None => (first, last),
+        });
+    }
+    let Some((from, to)) = window.filter(|(from, to)| from <= to) else {
+        return Err(ParseError::NoEntriesInRange);
+    };
+    for report in reports.iter_mut() {
+        let offset = report.clock_offset_ms.unwrap_or(0);
+        filter_time(
+            report,
+            Some(TimeBound::At(shift(from, offset, true))),
+            Some(TimeBound::At(shift(to, offset, true))),
+        )?;
+    }
+    Ok((from, to))
+}
+
+/// Drops the CPUs, zones, interfaces and disks no entry reports any more.
+fn retain_seen(report: &mut Report) {
+    let entries = &report.entries;
+    report
+        .proc_cpus
+        .retain(|cpu| entries.iter().any(|entry| entry.proc.contains_key(cpu)));
+    report
+        .perf_cpus
+        .retain(|cpu| entries.iter().any(|entry| entry.perf.contains_key(cpu)));
+    report
+        .freq_cpus
+        .retain(|cpu| entries.iter().any(|entry| entry.freq.contains_key(cpu)));
+    report
+        .thermal_zones
+        .retain(|zone| entries.iter().any(|entry| entry.thermal.contains_key(zone)));
+    report
+        .interfaces
+        .retain(|iface| entries.iter().any(|entry| entry.net.contains_key(iface)));
+    report
+        .disks
+        .retain(|disk| entries.iter().any(|entry| entry.disk.contains_key(disk)));
+}
+
+/// Time a log covers: as measured, or up to the end of its last entry for
+/// logs that were cut off or predate `duration-actual`.
+fn covered(report: &Report) -> Duration {
+    report.duration_actual.unwrap_or_else(|| {
+        report
+            .entries
+            .last()
+            .map_or(Duration::ZERO, |entry| entry.time + report.interval)
+    })
+}
+
+/// Puts the parts of each rotated capture in order, where its first given
+/// part was, and checks that none is missing.
+fn order_parts(logs: Vec<(String, Report)>) -> Result<Vec<(String, Report)>, MergeError> {
+    let keys: Vec<_> = logs
+        .iter()
+        .enumerate()
+        .map(|(index, (_, report))| match report.part {
+            Some(part) => {
+                let first = logs
+                    .iter()
+                    .position(|(_, other)| other.part.is_some() && other.id == report.id)
+                    .unwrap();
+                (first, part)
+            }
+            None => (index, 0),
+        })
+        .collect();
+    let mut logs: Vec<_> = keys.into_iter().zip(logs).collect();
+    logs.sort_by_key(|(key, _)| *key);
+    let logs: Vec<_> = logs.into_iter().map(|(_, log)| log).collect();
+
+    for (index, (file, report)) in logs.iter().enumerate() {
+        let Some(part) = report.part else {
+            continue;
+        };
+        let previous = index
+            .checked_sub(1)
+            .map(|index| &logs[index].1)
+            .filter(|previous| previous.part.is_some() && previous.id == report.id);
+        let expected = previous.map_or(1, |previous| previous.part.unwrap() + 1);
+        if previous.is_some_and(|previous| previous.part == Some(part)) {
+            return Err(MergeError::DuplicatePart {
+                file: file.clone(),
+                id: report.id,
+                part,
+            });
+        }
+        if part != expected {
+            return Err(MergeError::MissingPart {
+                file: file.clone(),
+                id: report.id,
+                part,
+                expected,
+            });
+        }
+        let next = logs.get(index + 1).map(|(_, next)| next);
+        if let Some(next_part) = &report.next_part {
+            if !next.is_some_and(|next| next.part.is_some() && next.id == report.id) {
+                return Err(MergeError::MissingNextPart {
+                    file: file.clone(),
+                    next: next_part.clone(),
+                });
+            }
+        }
+    }
+    Ok(logs)
+}
+
+/// Concatenates consecutive logs of one experiment, e.g. of a restarted
+/// logger or the parts of a rotated capture, into one report in which each
+/// log's entries start where the previous log ended. Logs whose start times
+/// say they began before the previous one ended are still merged, but with a
+/// warning.
+pub fn merge_reports(logs: Vec<(String, Report)>) -> Result<(Report, Vec<String>), MergeError> {
+    let mut logs = order_parts(logs)?.into_iter();
+    let (file, mut merged) = logs.next().unwrap();
+    let mut warnings = Vec::new();
+    let mut offset = covered(&merged);
+    let mut previous = (file.clone(), merged.start, offset);
+    merged.sources.push(file);
+    for (file, mut report) in logs {
+        if report.interval != merged.interval {
+            return Err(MergeError::Interval {
+                file,
+                interval: report.interval,
+                expected: merged.interval,
+            });
+        }
+        if report.platform != merged.platform {
+            return Err(MergeError::Platform {
+                file,
+                platform: report.platform.name(),
+                expected: merged.platform.name(),
+            });
+        }
+        let (previous_file, previous_start, previous_length) = &previous;
+        // `order_parts` put every later part of a capture right after the
+        // one before, which it follows on from by construction.
+        let next_part = report.part.is_some_and(|part| part > 1);
+        if let (false, Some(previous_start), Some(start)) =
+            (next_part, previous_start, report.start)
+        {
+            if start < *previous_start {
+                warnings.push(format!(
+                    "{}: starts before {}, so the logs are out of order",
+                    file, previous_file
+                ));
+            } else if let Ok(overlap) = (*previous_start + *previous_length).duration_since(start) {
+                if !overlap.is_zero() {
+                    warnings.push(format!(
+                        "{}: overlaps the end of {} by {:.1}s",
+                        file,
+                        previous_file,
+                        overlap.as_secs_f64()
+                    ));
+                }
+            }
+        }
+
+        let length = covered(&report);
+        for entry in report.entries.iter_mut() {
+            entry.time += offset;
+        }
+        merged.entries.append(&mut report.entries);
+        for marker in report.markers.iter_mut() {
+            marker.time += offset;
+        }
+        merged.markers.append(&mut report.markers);
+        for (into, from) in [
+            (&mut merged.proc_cpus, &report.proc_cpus),
+            (&mut merged.perf_cpus, &report.perf_cpus),
+            (&mut merged.perf_events, &report.perf_events),
+            (&mut merged.interfaces, &report.interfaces),
+            (&mut merged.disks, &report.disks),
+            (&mut merged.freq_cpus, &report.freq_cpus),
+            (&mut merged.thermal_zones, &report.thermal_zones),
+            (&mut merged.pids, &report.pids),
+            (&mut merged.cgroups, &report.cgroups),
+        ] {
+            for item in from {
+                if !into.contains(item) {
+                    into.push(item.clone());
+                }
+            }
+        }
+        merged.duration = if next_part {
+            merged.duration
+        } else if merged.duration.is_zero() || report.duration.is_zero() {
+            Duration::ZERO
+        } else {
+            merged.duration + report.duration
+        };
+        merged.truncated |= report.truncated;
+        merged.skipped.append(&mut report.skipped);
+        offset += length;
+        previous = (file.clone(), report.start, length);
+        merged.sources.push(file);
+    }
+    merged.duration_actual = Some(offset);
+    merged.part = None;
+    merged.next_part = None;
+    merged.proc_cpus.sort_by(|a, b| compare_cpus(a, b));
+    merged.perf_cpus.sort_by(|a, b| compare_cpus(a, b));
+    merged.freq_cpus.sort_by(|a, b| compare_cpus(a, b));
+    merged.thermal_zones.sort_by(|a, b| compare_cpus(a, b));
+    merged.interfaces.sort();
+    merged.disks.sort();
+    Ok((merged, warnings))
+}
+
+/// A named set of CPUs reported as a row of its own, like "all".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CpuGroup {
+    pub name: String,
+    pub cpus: Vec<u64>,
+}
+
+/// CPUs from a list such as `0-3,6`, `None` unless it is one.
+pub fn parse_cpu_list(text: &str) -> Option<Vec<u64>> {
+    let mut cpus = Vec::new();
+    for part in text.split(',') {
+        let (first, last) = part.split_once('-').unwrap_or((part, part));
+        let (first, last): (u64, u64) = (first.trim().parse().ok()?, last.trim().parse().ok()?);
+        if first > last {
+            return None;
+        }
+        cpus.extend(first..=last);
+    }
+    Some(cpus)
+}
+
+/// The reverse of `parse_cpu_list`, with runs as ranges, e.g. `0-3,6`.
+pub fn format_cpu_list(cpus: &[u64]) -> String {
+    let mut cpus = cpus.to_vec();
+    cpus.sort();
+    let mut runs: Vec<(u64, u64)> = Vec::new();
+    for cpu in cpus {
+        match runs.last_mut() {
+            Some((_, last)) if *last + 1 == cpu => *last = cpu,
+            _ => runs.push((cpu, cpu)),
+        }
+    }
+    runs.iter()
+        .map(|(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{}-{}", first, last)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The CPU number of a /proc/stat, cpufreq or simpleperf label, or the core
+/// number of a perf `--per-core` label such as `S0-D0-C4`.
+fn cpu_number(cpu: &str) -> Option<u64> {
+    cpu.parse().ok().or_else(|| {
+        regex_captures!(r#"^S\d+-D\d+-C(\d+)$"#, cpu).and_then(|(_, core)| core.parse().ok())
+    })
+}
+
+fn sum_proc<'a>(reports: impl Iterator<Item = &'a ProcReport>) -> Option<ProcReport> {
+    let mut sum: Option<ProcReport> = None;
+    for report in reports {
+        let sum = sum.get_or_insert(ProcReport {
+            user: 0,
+            nice: 0,
+            system: 0,
+            idle: 0,
+            iowait: 0,
+            irq: 0,
+            softirq: 0,
+            total: 0,
+            load: 0.0,
+        });
+        sum.user += report.user;
+        sum.nice += report.nice;
+        sum.system += report.system;
+        sum.idle += report.idle;
+        sum.iowait += report.iowait;
+        sum.irq += report.irq;
+        sum.softirq += report.softirq;
+        sum.total += report.total;
+    }
+    // Weighting by the jiffies of each CPU, as /proc/stat's own "all" does.
+    sum.map(|sum| ProcReport {
+        load: 100.0 * (1.0 - sum.idle as f64 / sum.total as f64),
+        ..sum
+    })
+}
+
+fn sum_perf<'a>(reports: impl Iterator<Item = &'a PerfReport>) -> Option<PerfReport> {
+    let mut sum: Option<PerfReport> = None;
+    for report in reports {
+        let sum = sum.get_or_insert_with(PerfReport::default);
+        for (event, value) in report.events.iter() {
+            *sum.events.entry(event.clone()).or_insert(0) += value;
+        }
+        sum.missing.extend(report.missing.iter().cloned());
+    }
+    sum
+}
+
+/// Leaves only the given CPUs in a report, with "all" summing just them.
+/// perf attached to processes has no per-CPU rows and is left as it is.
+pub fn filter_cpus(report: &mut Report, cpus: &[u64]) {
+    if cpus.is_empty() {
+        return;
+    }
+    let selected = |cpu: &String| cpu_number(cpu).is_some_and(|cpu| cpus.contains(&cpu));
+    for entry in report.entries.iter_mut() {
+        entry.proc.retain(|cpu, _| selected(cpu));
+        entry.proc_anomalies.retain(|cpu, _| selected(cpu));
+        // "all" can only sum them when they all make sense.
+        if let Some(&anomaly) = entry.proc_anomalies.values().next() {
+            entry.proc_anomalies.insert("all".to_string(), anomaly);
+        } else if let Some(all) = sum_proc(entry.proc.values()) {
+            entry.proc.insert("all".to_string(), all);
+        }
+        // Socket and system-wide rows can't be narrowed down to CPUs.
+        if entry.perf.keys().any(|cpu| cpu_number(cpu).is_some()) {
+            entry.perf.retain(|cpu, _| selected(cpu));
+            if let Some(all) = sum_perf(entry.perf.values()) {
+                entry.perf.insert("all".to_string(), all);
+            }
+        }
+        entry.freq.retain(|cpu, _| selected(cpu));
+    }
+    retain_seen(report);
+}
+
+/// The profiler's average and peak share of the time of all CPUs, `None`
+/// for logs captured before it measured itself.
+pub fn get_profiler_share(report: &Report) -> Option<(f64, f64)> {
+    let shares: Vec<_> = report
+        .entries
+        .iter()
+        .filter_map(|entry| Some(entry.profiler.as_ref()?.share))
+        .collect();
+    if shares.is_empty() {
+        return None;
+    }
+    Some((
+        shares.iter().sum::<f64>() / shares.len() as f64,
+        shares.iter().copied().fold(0.0, f64::max),
+    ))
+}
+
+/// Takes the profiler's own time out of the load of "all", as if it hadn't
+/// run. Goes by the jiffies it used, so it also holds once "all" sums only
+/// some CPUs.
+pub fn subtract_profiler(report: &mut Report) {
+    for entry in report.entries.iter_mut() {
+        let (Some(profiler), Some(all)) = (entry.profiler.as_ref(), entry.proc.get_mut("all"))
+        else {
+            continue;
+        };
+        let jiffies = profiler.utime + profiler.stime;
+        all.load = (all.load - 100.0 * jiffies as f64 / all.total.max(1) as f64).max(0.0);
+    }
+}
+
+/// Leaves out one CPU, e.g. the one the profiler was pinned to, with "all"
+/// summing the others.
+pub fn exclude_cpu(report: &mut Report, excluded: u64) {
+    let mut cpus: Vec<_> = report
+        .proc_cpus
+        .iter()
+        .chain(report.perf_cpus.iter())
+        .filter_map(|cpu| cpu_number(cpu))
+        .filter(|&cpu| cpu != excluded)
+        .collect();
+    cpus.sort();
+    cpus.dedup();
+    filter_cpus(report, &cpus);
+}
+
+/// The `detect_clusters` of a report's topology, of the CPUs it has data for.
+pub fn topology_groups(report: &Report) -> Vec<CpuGroup> {
+    let known: Vec<_> = report
+        .proc_cpus
+        .iter()
+        .chain(report.perf_cpus.iter())
+        .filter_map(|cpu| cpu_number(cpu))
+        .collect();
+    detect_clusters(&report.metadata.topology)
+        .into_iter()
+        .filter_map(|mut group| {
+            group.cpus.retain(|cpu| known.contains(cpu));
+            (!group.cpus.is_empty()).then_some(group)
+        })
+        .collect()
+}
+
+/// Adds a row per group to every entry that has any of its CPUs, returning a
+/// warning for each CPU the log has no data for.
+pub fn add_cpu_groups(report: &mut Report, groups: &[CpuGroup]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for group in groups {
+        let member = |cpu: &String| cpu_number(cpu).is_some_and(|cpu| group.cpus.contains(&cpu));
+        for cpu in group.cpus.iter() {
+            let known = report
+                .proc_cpus
+                .iter()
+                .chain(report.perf_cpus.iter())
+                .any(|label| cpu_number(label) == Some(*cpu));
+            if !known {
+                warnings.push(format!("group {}: the log has no CPU {}", group.name, cpu));
+            }
+        }
+        for entry in report.entries.iter_mut() {
+            let proc = sum_proc(
+                entry
+                    .proc
+                    .iter()
+                    .filter(|(cpu, _)| member(cpu))
+                    .map(|(_, proc)| proc),
+            );
+            if let Some(proc) = proc {
+                entry.proc.insert(group.name.clone(), proc);
+            }
+            let perf = sum_perf(
+                entry
+                    .perf
+                    .iter()
+                    .filter(|(cpu, _)| member(cpu))
+                    .map(|(_, perf)| perf),
+            );
+            if let Some(perf) = perf {
+                entry.perf.insert(group.name.clone(), perf);
+            }
+        }
+        let seen = |cpus: &[String]| cpus.iter().any(member);
+        if seen(&report.proc_cpus) {
+            report.proc_cpus.push(group.name.clone());
+            report.proc_cpus.sort_by(|a, b| compare_cpus(a, b));
+        }
+        if seen(&report.perf_cpus) {
+            report.perf_cpus.push(group.name.clone());
+            report.perf_cpus.sort_by(|a, b| compare_cpus(a, b));
+        }
+        report.groups.push(group.clone());
+    }
+    warnings
+}
+
+/// Restricts the network data of a report to the given interfaces.
+pub fn filter_interfaces(report: &mut Report, ifaces: &[String]) {
+    if ifaces.is_empty() {
+        return;
+    }
+    report.interfaces.retain(|iface| ifaces.contains(iface));
+    for entry in report.entries.iter_mut() {
+        entry.net.retain(|iface, _| ifaces.contains(iface));
+    }
+}
+
+/// Average per-second rate of a network counter of one interface.
+pub fn get_average_net_rate(report: &Report, iface: &str, metric: fn(&NetReport) -> u64) -> f64 {
+    let mut total = 0.0;
+    let mut count = 0;
+    for entry in report.entries.iter() {
+        if let Some(net) = entry.net.get(iface) {
+            total += metric(net) as f64;
+            count += 1;
+        }
+    }
+    total / count as f64 / report.interval.as_secs_f64()
+}
+
+/// A block device over the intervals of a run, bytes per second and
+/// percentages of the time it was busy.
+pub struct DiskStats {
+    pub read_rate: f64,
+    pub peak_read_rate: f64,
+    pub write_rate: f64,
+    pub peak_write_rate: f64,
+    pub busy: f64,
+    pub peak_busy: f64,
+}
+
+pub fn get_disk_stats(report: &Report, disk: &str) -> DiskStats {
+    let seconds = report.interval.as_secs_f64();
+    let samples: Vec<_> = report
+        .entries
+        .iter()
+        .filter_map(|entry| entry.disk.get(disk))
+        .map(|disk| {
+            (
+                disk.read_bytes as f64 / seconds,
+                disk.written_bytes as f64 / seconds,
+                (disk.io_ms as f64 / 1000.0 / seconds * 100.0).min(100.0),
+            )
+        })
+        .collect();
+    let average = |value: fn(&(f64, f64, f64)) -> f64| {
+        samples.iter().map(value).sum::<f64>() / samples.len().max(1) as f64
+    };
+    let peak = |value: fn(&(f64, f64, f64)) -> f64| samples.iter().map(value).fold(0.0, f64::max);
+    DiskStats {
+        read_rate: average(|sample| sample.0),
+        peak_read_rate: peak(|sample| sample.0),
+        write_rate: average(|sample| sample.1),
+        peak_write_rate: peak(|sample| sample.1),
+        busy: average(|sample| sample.2),
+        peak_busy: peak(|sample| sample.2),
+    }
+}
+
+/// A line of PSI over the intervals of a run that sampled it: the average
+/// avg10 and the time stalled in all.
+pub fn get_pressure_stats(report: &Report, line: &str) -> Option<(f64, Duration)> {
+    let samples: Vec<_> = report
+        .entries
+        .iter()
+        .filter_map(|entry| entry.pressure.get(line))
+        .collect();
+    if samples.is_empty() {
+        return None;
+    }
+    let avg10 = samples.iter().map(|sample| sample.avg10).sum::<f64>() / samples.len() as f64;
+    let stall_us = samples.iter().map(|sample| sample.stall_us).sum();
+    Some((avg10, Duration::from_micros(stall_us)))
+}
+
+/// Average and peak GPU load of a run captured with `--gpu`.
+pub fn get_gpu_load(report: &Report) -> Option<(f64, f64)> {
+    let loads: Vec<_> = report
+        .entries
+        .iter()
+        .filter_map(|entry| entry.gpu)
+        .collect();
+    if loads.is_empty() {
+        return None;
+    }
+    Some((
+        loads.iter().sum::<f64>() / loads.len() as f64,
+        loads.iter().copied().fold(0.0, f64::max),
+    ))
+}
+
+/// Averages of the signal over the intervals that reported it, in dBm and,
+/// for RSRQ, dB.
+#[derive(Debug, Default)]
+pub struct SignalAverages {
+    pub rsrp: Option<f64>,
+    pub rsrq: Option<f64>,
+    pub rssi: Option<f64>,
+    pub wifi_rssi: Option<f64>,
+}
+
+fn average_signal(radios: &[&RadioReport]) -> SignalAverages {
+    let average = |value: fn(&RadioReport) -> Option<i64>| {
+        let values: Vec<_> = radios.iter().filter_map(|radio| value(radio)).collect();
+        (!values.is_empty()).then(|| values.iter().sum::<i64>() as f64 / values.len() as f64)
+    };
+    SignalAverages {
+        rsrp: average(|radio| radio.rsrp),
+        rsrq: average(|radio| radio.rsrq),
+        rssi: average(|radio| radio.rssi),
+        wifi_rssi: average(|radio| radio.wifi_rssi),
+    }
+}
+
+/// How many intervals of a run captured with `--radio` ended on each
+/// network type, the most frequent first.
+pub fn get_net_types(report: &Report) -> Vec<(String, usize)> {
+    count_net_types(&report.entries)
+}
+
+fn count_net_types(entries: &[ReportEntry]) -> Vec<(String, usize)> {
+    let mut net_types: Vec<(String, usize)> = Vec::new();
+    for radio in entries.iter().filter_map(|entry| entry.radio.as_ref()) {
+        match net_types
+            .iter_mut()
+            .find(|(net_type, _)| *net_type == radio.net_type)
+        {
+            Some((_, count)) => *count += 1,
+            None => net_types.push((radio.net_type.clone(), 1)),
+        }
+    }
+    net_types.sort_by(|(_, a), (_, b)| b.cmp(a));
+    net_types
+}
+
+pub fn get_signal_averages(report: &Report) -> SignalAverages {
+    let radios: Vec<_> = report
+        .entries
+        .iter()
+        .filter_map(|entry| entry.radio.as_ref())
+        .collect();
+    average_signal(&radios)
+}
+
+/// The entries that ended on one network type, from `group_by_net_type`.
+pub struct NetTypeGroup {
+    pub net_type: String,
+    pub entries: usize,
+    pub averages: Vec<CpuAverages>,
+    pub signal: SignalAverages,
+}
+
+/// Averages the entries of each network type separately, the most frequent
+/// first, as `split_phases` does each phase; none without `--radio`.
+pub fn group_by_net_type(report: &mut Report) -> Vec<NetTypeGroup> {
+    let mut entries = std::mem::take(&mut report.entries);
+    let mut done = Vec::new();
+    let mut groups = Vec::new();
+    for (net_type, count) in count_net_types(&entries) {
+        // The averages read the report's entries, so it holds only this
+        // type's for a moment.
+        (report.entries, entries) = entries.into_iter().partition(|entry| {
+            entry
+                .radio
+                .as_ref()
+                .is_some_and(|radio| radio.net_type == net_type)
+        });
+        let radios: Vec<_> = report
+            .entries
+            .iter()
+            .filter_map(|entry| entry.radio.as_ref())
+            .collect();
+        groups.push(NetTypeGroup {
+            signal: average_signal(&radios),
+            net_type,
+            entries: count,
+            averages: get_averages(report),
+        });
+        done.append(&mut report.entries);
+    }
+    // Entries without a radio, if any, and back in order.
+    done.append(&mut entries);
+    done.sort_by_key(|entry| entry.time);
+    report.entries = done;
+    groups
+}
+
+/// The power a run drew from the battery, over the intervals it was
+/// discharging in.
+pub struct BatteryStats {
+    pub power: f64,
+    pub peak_power: f64,
+    pub energy_mwh: f64,
+    /// Millijoules spent per billion cycles, with perf counting cycles.
+    pub energy_per_gigacycle: Option<f64>,
+    /// Percent charged in the first and last entries.
+    pub capacity: Option<(u32, u32)>,
+    /// Intervals left out as the battery was charging or full.
+    pub charging: usize,
+}
+
+pub fn get_battery_stats(report: &Report) -> Option<BatteryStats> {
+    let quirk = battery_quirk(report.metadata.device_model.as_deref());
+    let batteries: Vec<_> = report
+        .entries
+        .iter()
+        .filter_map(|entry| Some((entry, entry.battery.as_ref()?)))
+        .collect();
+    if batteries.is_empty() {
+        return None;
+    }
+    let discharging: Vec<_> = batteries
+        .iter()
+        .filter_map(|(entry, battery)| Some((*entry, battery_power(battery, quirk)?)))
+        .collect();
+    let seconds = report.interval.as_secs_f64();
+    let energy_mj: f64 = discharging.iter().map(|(_, power)| power * seconds).sum();
+    let cycles: Vec<_> = discharging
+        .iter()
+        .filter_map(|(entry, power)| Some((entry.perf.get("all")?.get(CYCLES)?, power)))
+        .collect();
+    let cycled_mj: f64 = cycles.iter().map(|(_, power)| *power * seconds).sum();
+    let gigacycles = cycles.iter().map(|(cycles, _)| *cycles).sum::<u64>() as f64 / 1e9;
+    let capacities: Vec<_> = batteries
+        .iter()
+        .filter_map(|(_, battery)| battery.capacity)
+        .collect();
+    Some(BatteryStats {
+        power: energy_mj / (discharging.len().max(1) as f64 * seconds),
+        peak_power: discharging
+            .iter()
+            .map(|(_, power)| *power)
+            .fold(0.0, f64::max),
+        energy_mwh: energy_mj / 3600.0,
+        energy_per_gigacycle: (gigacycles > 0.0).then(|| cycled_mj / gigacycles),
+        capacity: capacities.first().copied().zip(capacities.last().copied()),
+        charging: batteries.len() - discharging.len(),
+    })
+}
+
+/// TCP over the intervals of a run that sampled it, counts per second.
+pub struct TcpRates {
+    pub in_segs: f64,
+    pub out_segs: f64,
+    pub retrans_segs: f64,
+    pub fast_retrans: f64,
+    pub lost_retransmit: f64,
+    pub timeouts: f64,
+    pub in_errs: u64,
+    pub out_rsts: u64,
+    pub listen_drops: u64,
+    /// Percentage of the segments sent that were retransmissions, none when
+    /// nothing was sent.
+    pub retrans_ratio: Option<f64>,
+    /// The interval with the highest such percentage, and the percentage.
+    pub peak_ratio: Option<(Duration, f64)>,
+}
+
+/// The percentage of the segments sent during `tcp` that were
+/// retransmissions.
+pub fn retrans_ratio(tcp: &TcpReport) -> Option<f64> {
+    (tcp.out_segs > 0).then(|| tcp.retrans_segs as f64 / tcp.out_segs as f64 * 100.0)
+}
+
+pub fn get_tcp_rates(report: &Report) -> Option<TcpRates> {
+    let samples: Vec<_> = report
+        .entries
+        .iter()
+        .filter_map(|entry| Some((entry.time, entry.tcp.as_ref()?)))
+        .collect();
+    if samples.is_empty() {
+        return None;
+    }
+    let seconds = samples.len() as f64 * report.interval.as_secs_f64();
+    let total =
+        |counter: fn(&TcpReport) -> u64| samples.iter().map(|(_, tcp)| counter(tcp)).sum::<u64>();
+    let rate = |counter: fn(&TcpReport) -> u64| total(counter) as f64 / seconds;
+    let (out_segs, retrans_segs) = (total(|tcp| tcp.out_segs), total(|tcp| tcp.retrans_segs));
+    Some(TcpRates {
+        in_segs: rate(|tcp| tcp.in_segs),
+        out_segs: rate(|tcp| tcp.out_segs),
+        retrans_segs: rate(|tcp| tcp.retrans_segs),
+        fast_retrans: rate(|tcp| tcp.fast_retrans),
+        lost_retransmit: rate(|tcp| tcp.lost_retransmit),
+        timeouts: rate(|tcp| tcp.timeouts),
+        in_errs: total(|tcp| tcp.in_errs),
+        out_rsts: total(|tcp| tcp.out_rsts),
+        listen_drops: total(|tcp| tcp.listen_drops),
+        retrans_ratio: (out_segs > 0).then(|| retrans_segs as f64 / out_segs as f64 * 100.0),
+        peak_ratio: samples
+            .iter()
+            .filter_map(|(time, tcp)| Some((*time, retrans_ratio(tcp)?)))
+            .max_by(|a, b| a.1.total_cmp(&b.1)),
+    })
+}
+
+/// The receive path of a CPU over the intervals of a run that sampled
+/// softnet_stat.
+pub struct SoftnetStats {
+    pub cpu: String,
+    pub processed_rate: f64,
+    pub time_squeeze: u64,
+    pub dropped: u64,
+    /// Average percentage of the CPU's time in softirqs, where NET_RX runs.
+    pub softirq: f64,
+}
+
+/// An interval in which some CPU ran out of NAPI budget or dropped packets.
+pub struct SoftnetPressure {
+    pub time: Duration,
+    pub cpu: String,
+    pub time_squeeze: u64,
+    pub dropped: u64,
+    /// The CPU's softirq percentage in that interval, if /proc/stat had it.
+    pub softirq: Option<f64>,
+}
+
+/// Per CPU, sorted like `compare_cpus`, then the intervals under pressure in
+/// order; both empty in runs that did not sample softnet_stat.
+pub fn get_softnet_stats(report: &Report) -> (Vec<SoftnetStats>, Vec<SoftnetPressure>) {
+    let mut cpus: Vec<_> = report
+        .entries
+        .iter()
+        .flat_map(|entry| entry.softnet.keys())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    cpus.sort_by(|a, b| compare_cpus(a, b));
+    let stats = cpus
+        .iter()
+        .map(|cpu| {
+            let samples: Vec<_> = report
+                .entries
+                .iter()
+                .filter_map(|entry| entry.softnet.get(*cpu))
+                .collect();
+            let seconds = samples.len() as f64 * report.interval.as_secs_f64();
+            SoftnetStats {
+                cpu: cpu.to_string(),
+                processed_rate: samples.iter().map(|softnet| softnet.processed).sum::<u64>() as f64
+                    / seconds,
+                time_squeeze: samples.iter().map(|softnet| softnet.time_squeeze).sum(),
+                dropped: samples.iter().map(|softnet| softnet.dropped).sum(),
+                softirq: get_average_breakdown(report, cpu)[6],
+            }
+        })
+        .collect();
+    let mut pressure = Vec::new();
+    for entry in report.entries.iter() {
+        for cpu in cpus.iter() {
+            let Some(softnet) = entry.softnet.get(*cpu) else {
+                continue;
+            };
+            if softnet.time_squeeze == 0 && softnet.dropped == 0 {
+                continue;
+            }
+            pressure.push(SoftnetPressure {
+                time: entry.time,
+                cpu: cpu.to_string(),
+                time_squeeze: softnet.time_squeeze,
+                dropped: softnet.dropped,
+                softirq: entry
+                    .proc
+                    .get(*cpu)
+                    .filter(|proc| proc.total > 0)
+                    .map(|proc| 100.0 * proc.softirq as f64 / proc.total as f64),
+            });
+        }
+    }
+    (stats, pressure)
+}
+
+/// The softirq types the report breaks down, of the ten the kernel counts.
+pub const SOFTIRQ_TYPES: [&str; 4] = ["NET_RX", "NET_TX", "TASKLET", "TIMER"];
+
+/// The softirqs a CPU ran over a run captured with `--softirqs`.
+pub struct SoftirqRates {
+    pub cpu: String,
+    /// Per second, as in `SOFTIRQ_TYPES`.
+    pub rates: [f64; 4],
+    /// Percentage of all CPUs' NET_RX softirqs this one ran, none when there
+    /// were none.
+    pub net_rx_share: Option<f64>,
+}
+
+/// Per CPU, sorted like `compare_cpus`; empty in runs captured without
+/// `--softirqs`.
+pub fn get_softirq_rates(report: &Report) -> Vec<SoftirqRates> {
+    let mut cpus: Vec<_> = report
+        .entries
+        .iter()
+        .flat_map(|entry| entry.softirqs.values().flat_map(|counts| counts.keys()))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    cpus.sort_by(|a, b| compare_cpus(a, b));
+    let total = |kind: &str, cpu: &str| -> (u64, usize) {
+        let counts: Vec<_> = report
+            .entries
+            .iter()
+            .filter_map(|entry| entry.softirqs.get(kind)?.get(cpu))
+            .collect();
+        (counts.iter().copied().sum(), counts.len())
+    };
+    let net_rx: u64 = cpus.iter().map(|cpu| total("NET_RX", cpu).0).sum();
+    cpus.iter()
+        .map(|cpu| SoftirqRates {
+            cpu: cpu.to_string(),
+            rates: SOFTIRQ_TYPES.map(|kind| {
+                let (count, samples) = total(kind, cpu);
+                count as f64 / (samples.max(1) as f64 * report.interval.as_secs_f64())
+            }),
+            net_rx_share: (net_rx > 0)
+                .then(|| total("NET_RX", cpu).0 as f64 / net_rx as f64 * 100.0),
+        })
+        .collect()
+}
+
+/// The 1 minute loadavg of a run next to how many CPUs /proc/stat had busy,
+/// to check one against the other, bearing in mind that loadavg decays over
+/// a minute and also counts tasks in uninterruptible sleep.
+pub struct LoadavgStats {
+    pub average: f64,
+    pub peak: f64,
+    /// The load of "all" as a number of CPUs.
+    pub busy_cpus: Option<f64>,
+    pub peak_runnable: u32,
+    pub tasks: u32,
+}
+
+/// None for logs captured before /proc/loadavg was sampled.
+pub fn get_loadavg_stats(report: &Report) -> Option<LoadavgStats> {
+    let loadavgs: Vec<_> = report
+        .entries
+        .iter()
+        .filter_map(|entry| entry.loadavg.as_ref())
+        .collect();
+    let peak_runnable = loadavgs.iter().max_by_key(|loadavg| loadavg.runnable)?;
+    let busy: Vec<_> = report
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            let cpus = entry
+                .proc
+                .keys()
+                .filter(|cpu| !report.is_aggregate(cpu) && cpu_number(cpu).is_some())
+                .count();
+            Some(entry.proc.get("all")?.load / 100.0 * cpus as f64)
+        })
+        .collect();
+    Some(LoadavgStats {
+        average: loadavgs.iter().map(|loadavg| loadavg.one).sum::<f64>() / loadavgs.len() as f64,
+        peak: loadavgs
+            .iter()
+            .map(|loadavg| loadavg.one)
+            .fold(0.0, f64::max),
+        busy_cpus: (!busy.is_empty()).then(|| busy.iter().sum::<f64>() / busy.len() as f64),
+        peak_runnable: peak_runnable.runnable,
+        tasks: peak_runnable.tasks,
+    })
+}
+
+/// How long tasks waited for a CPU over a run captured with `--schedstat`.
+pub struct RunDelayStats {
+    pub cpu: String,
+    /// Milliseconds of waiting per second, on average and in the worst
+    /// interval; a thousand means a task was always waiting.
+    pub average: f64,
+    pub peak: f64,
+    /// Average wait per timeslice in microseconds, none without any.
+    pub per_timeslice_us: Option<f64>,
+}
+
+/// Per CPU sorted like `compare_cpus`, then for "all" summing every CPU's
+/// waits; empty in runs captured without `--schedstat`.
+pub fn get_run_delay_stats(report: &Report) -> Vec<RunDelayStats> {
+    let mut cpus: Vec<_> = report
+        .entries
+        .iter()
+        .flat_map(|entry| entry.schedstat.keys())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    if cpus.is_empty() {
+        return Vec::new();
+    }
+    cpus.sort_by(|a, b| compare_cpus(a, b));
+    let seconds = report.interval.as_secs_f64();
+    let summarize = |samples: Vec<&SchedstatReport>, cpu: &str| {
+        let delays: Vec<_> = samples
+            .iter()
+            .map(|sample| sample.run_delay_ns as f64 / 1e6 / seconds)
+            .collect();
+        let timeslices: u64 = samples.iter().map(|sample| sample.timeslices).sum();
+        let delay_ns: u64 = samples.iter().map(|sample| sample.run_delay_ns).sum();
+        RunDelayStats {
+            cpu: cpu.to_string(),
+            average: delays.iter().sum::<f64>() / delays.len().max(1) as f64,
+            peak: delays.iter().copied().fold(0.0, f64::max),
+            per_timeslice_us: (timeslices > 0).then(|| delay_ns as f64 / 1e3 / timeslices as f64),
+        }
+    };
+    let mut stats: Vec<_> = cpus
+        .iter()
+        .map(|cpu| {
+            let samples = report
+                .entries
+                .iter()
+                .filter_map(|entry| entry.schedstat.get(*cpu))
+                .collect();
+            summarize(samples, cpu)
+        })
+        .collect();
+    let sums: Vec<_> = report
+        .entries
+        .iter()
+        .filter(|entry| !entry.schedstat.is_empty())
+        .map(|entry| {
+            entry
+                .schedstat
+                .values()
+                .fold(SchedstatReport::default(), |sum, sample| SchedstatReport {
+                    run_ns: sum.run_ns + sample.run_ns,
+                    run_delay_ns: sum.run_delay_ns + sample.run_delay_ns,
+                    timeslices: sum.timeslices + sample.timeslices,
+                })
+        })
+        .collect();
+    stats.push(summarize(sums.iter().collect(), "all"));
+    stats
+}
+
+/// How a CPU idled over a run captured with `--cpuidle`.
+pub struct CpuIdleStats {
+    pub cpu: String,
+    /// Average percentage of the interval spent in each state, shallowest
+    /// first.
+    pub states: Vec<(String, f64)>,
+    /// Average percentage of the interval /proc/stat counted as idle or
+    /// iowait, through both of which the CPU sleeps, to hold the states
+    /// against; none when /proc/stat didn't list the CPU.
+    pub proc_idle: Option<f64>,
+}
+
+/// Per CPU, sorted like `compare_cpus`; empty in runs captured without
+/// `--cpuidle`.
+pub fn get_cpuidle_stats(report: &Report) -> Vec<CpuIdleStats> {
+    let mut cpus: Vec<_> = report
+        .entries
+        .iter()
+        .flat_map(|entry| entry.cpuidle.keys())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    cpus.sort_by(|a, b| compare_cpus(a, b));
+    let interval_us = report.interval.as_micros() as f64;
+    cpus.into_iter()
+        .map(|cpu| {
+            let (mut states, mut samples): (Vec<(String, u64)>, u64) = (Vec::new(), 0);
+            let mut proc_idle: Vec<f64> = Vec::new();
+            for entry in report.entries.iter() {
+                let Some(cpuidle) = entry.cpuidle.get(cpu) else {
+                    continue;
+                };
+                samples += 1;
+                for state in cpuidle {
+                    match states.iter_mut().find(|(name, _)| *name == state.name) {
+                        Some((_, time_us)) => *time_us += state.time_us,
+                        None => states.push((state.name.clone(), state.time_us)),
+                    }
+                }
+                if let Some(proc) = entry.proc.get(cpu).filter(|proc| proc.total > 0) {
+                    proc_idle.push((proc.idle + proc.iowait) as f64 / proc.total as f64 * 100.0);
+                }
+            }
+            CpuIdleStats {
+                cpu: cpu.clone(),
+                states: states
+                    .into_iter()
+                    .map(|(name, time_us)| {
+                        (
+                            name,
+                            time_us as f64 / (samples as f64 * interval_us) * 100.0,
+                        )
+                    })
+                    .collect(),
+                proc_idle: (!proc_idle.is_empty())
+                    .then(|| proc_idle.iter().sum::<f64>() / proc_idle.len() as f64),
+            }
+        })
+        .collect()
+}
+
+/// A TCP connection over the intervals it was open in, of a run captured
+/// with `--sockets`.
+pub struct SocketStats {
+    pub connection: String,
+    /// The first and last entries that saw it.
+    pub first: Duration,
+    pub last: Duration,
+    pub rtt: Option<MinAvgMax>,
+    pub cwnd: Option<MinAvgMax>,
+    /// Segments retransmitted while it was sampled; for connections already
+    /// open at the start, since the first entry.
+    pub retrans: u64,
+    /// Average bits per second.
+    pub delivery_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinAvgMax {
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+impl MinAvgMax {
+    fn of(values: impl Iterator<Item = f64>) -> Option<Self> {
+        let values: Vec<_> = values.collect();
+        (!values.is_empty()).then(|| MinAvgMax {
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            avg: values.iter().sum::<f64>() / values.len() as f64,
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        })
+    }
+}
+
+/// The connections of a run in the order they were first seen.
+pub fn get_socket_stats(report: &Report) -> Vec<SocketStats> {
+    let mut connections: Vec<_> = report
+        .entries
+        .iter()
+        .flat_map(|entry| entry.sockets.keys())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    connections.sort();
+    let mut stats: Vec<_> = connections
+        .into_iter()
+        .map(|connection| {
+            let samples: Vec<_> = report
+                .entries
+                .iter()
+                .filter_map(|entry| Some((entry.time, entry.sockets.get(connection)?)))
+                .collect();
+            let (first, last) = (samples[0], samples[samples.len() - 1]);
+            let already_open = report.entries.first().map(|entry| entry.time) == Some(first.0);
+            SocketStats {
+                connection: connection.clone(),
+                first: first.0,
+                last: last.0,
+                rtt: MinAvgMax::of(samples.iter().filter_map(|(_, socket)| socket.rtt)),
+                cwnd: MinAvgMax::of(
+                    samples
+                        .iter()
+                        .filter_map(|(_, socket)| Some(socket.cwnd? as f64)),
+                ),
+                retrans: if already_open {
+                    last.1.retrans.saturating_sub(first.1.retrans)
+                } else {
+                    last.1.retrans
+                },
+                delivery_rate: MinAvgMax::of(
+                    samples
+                        .iter()
+                        .filter_map(|(_, socket)| socket.delivery_rate),
+                )
+                .map(|rate| rate.avg),
+            }
+        })
+        .collect();
+    stats.sort_by_key(|stats| stats.first);
+    stats
+}
+
+/// An interrupt source over the intervals of a run that sampled it.
+pub struct IrqStats {
+    pub irq: String,
+    pub name: String,
+    /// Interrupts per second over all CPUs.
+    pub rate: f64,
+    /// The percentage of them each CPU serviced, the busiest first, leaving
+    /// out those that serviced none.
+    pub cpus: Vec<(String, f64)>,
+}
+
+/// The interrupt sources of a run, the most frequent first, over the
+/// intervals that sampled /proc/interrupts.
+pub fn get_irq_stats(report: &Report) -> Vec<IrqStats> {
+    let mut totals: HashMap<&str, (&str, HashMap<&str, u64>)> = HashMap::new();
+    let mut samples = 0;
+    for irqs in report
+        .entries
+        .iter()
+        .filter_map(|entry| entry.irqs.as_ref())
+    {
+        samples += 1;
+        for (irq, counts) in irqs.iter() {
+            let (name, cpus) = totals
+                .entry(irq)
+                .or_insert_with(|| (counts.name.as_str(), HashMap::new()));
+            *name = counts.name.as_str();
+            for (cpu, count) in counts.counts.iter() {
+                *cpus.entry(cpu).or_default() += count;
+            }
+        }
+    }
+    let mut stats: Vec<_> = totals
+        .into_iter()
+        .map(|(irq, (name, cpus))| {
+            let total: u64 = cpus.values().sum();
+            let mut cpus: Vec<_> = cpus
+                .into_iter()
+                .filter(|(_, count)| *count > 0)
+                .map(|(cpu, count)| (cpu.to_string(), count as f64 / total as f64 * 100.0))
+                .collect();
+            cpus.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| compare_cpus(&a.0, &b.0)));
+            IrqStats {
+                irq: irq.to_string(),
+                name: name.to_string(),
+                rate: total as f64 / (samples as f64 * report.interval.as_secs_f64()),
+                cpus,
+            }
+        })
+        .collect();
+    stats.sort_by(|a, b| b.rate.total_cmp(&a.rate).then_with(|| a.irq.cmp(&b.irq)));
+    stats
+}
+
+/// Average and minimum frequency of a core in kHz.
+pub fn get_freq_stats(report: &Report, cpu: &str) -> (f64, u64) {
+    let freqs: Vec<_> = report
+        .entries
+        .iter()
+        .filter_map(|entry| entry.freq.get(cpu).copied())
+        .collect();
+    let average = freqs.iter().sum::<u64>() as f64 / freqs.len() as f64;
+    (average, freqs.iter().copied().min().unwrap_or(0))
+}
+
+/// Frequencies from `min_khz` to `max_khz` that a CPU spent `share` percent
+/// of its time at.
+pub struct FreqBucket {
+    pub min_khz: u64,
+    pub max_khz: u64,
+    pub share: f64,
+}
+
+/// Where a CPU's frequency spent a run captured with `--time-in-state`.
+pub struct FreqResidency {
+    pub cpu: String,
+    pub buckets: Vec<FreqBucket>,
+    pub effective_khz: Option<f64>,
+    /// Average load scaled by the effective frequency over the highest, of
+    /// the intervals with both.
+    pub invariant_load: Option<f64>,
+}
+
+/// Per CPU, sorted like `compare_cpus`, the frequencies grouped into at most
+/// `buckets` runs of neighbouring ones so that SoCs with dozens stay
+/// readable; empty in runs captured without `--time-in-state`.
+pub fn get_freq_residency(report: &Report, buckets: usize) -> Vec<FreqResidency> {
+    let mut cpus: Vec<_> = report
+        .entries
+        .iter()
+        .flat_map(|entry| entry.time_in_state.keys())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    cpus.sort_by(|a, b| compare_cpus(a, b));
+    cpus.into_iter()
+        .map(|cpu| {
+            let mut states: Vec<(u64, u64)> = Vec::new();
+            for (khz, ms) in report
+                .entries
+                .iter()
+                .filter_map(|entry| entry.time_in_state.get(cpu))
+                .flatten()
+            {
+                match states.iter_mut().find(|(state, _)| state == khz) {
+                    Some((_, total)) => *total += ms,
+                    None => states.push((*khz, *ms)),
+                }
+            }
+            states.sort();
+            let time: u64 = states.iter().map(|(_, ms)| ms).sum();
+            let count = buckets.clamp(1, states.len().max(1));
+            let buckets = (0..count)
+                .filter_map(|bucket| {
+                    let bucket =
+                        &states[bucket * states.len() / count..(bucket + 1) * states.len() / count];
+                    Some(FreqBucket {
+                        min_khz: bucket.first()?.0,
+                        max_khz: bucket.last()?.0,
+                        share: bucket.iter().map(|(_, ms)| ms).sum::<u64>() as f64
+                            / time.max(1) as f64
+                            * 100.0,
+                    })
+                })
+                .collect();
+            let max_khz = states.last().map_or(0, |(khz, _)| *khz);
+            let invariant: Vec<_> = report
+                .entries
+                .iter()
+                .filter_map(|entry| {
+                    let load = entry.proc.get(cpu)?.load;
+                    Some(load * effective_khz(entry.time_in_state.get(cpu)?)? / max_khz as f64)
+                })
+                .collect();
+            FreqResidency {
+                cpu: cpu.clone(),
+                buckets,
+                effective_khz: effective_khz(&states),
+                invariant_load: (!invariant.is_empty())
+                    .then(|| invariant.iter().sum::<f64>() / invariant.len() as f64),
+            }
+        })
+        .collect()
+}
+
+/// How far apart the logger actually took consecutive samples, as it sleeps
+/// for the interval after sampling rather than keeping to a fixed rate.
+pub struct Jitter {
+    /// Milliseconds each gap was over (or under) the interval.
+    pub stats: stats::Stats,
+    /// Entries that came more than 1.5 intervals after the previous one,
+    /// e.g. as perf stalled, with the gap before them.
+    pub late: Vec<(Duration, Duration)>,
+}
+
+/// Each entry but the first with the time since the previous one.
+fn get_gaps(report: &Report) -> Vec<(Duration, Duration)> {
+    report
+        .entries
+        .windows(2)
+        .map(|pair| (pair[1].time, pair[1].time.saturating_sub(pair[0].time)))
+        .collect()
+}
+
+fn is_late(report: &Report, gap: Duration) -> bool {
+    gap.as_secs_f64() > 1.5 * report.interval.as_secs_f64()
+}
+
+/// `None` with fewer than two entries, which have no gap between them.
+pub fn get_jitter(report: &Report) -> Option<Jitter> {
+    let gaps = get_gaps(report);
+    let interval = report.interval.as_secs_f64() * 1000.0;
+    let samples: Vec<_> = gaps
+        .iter()
+        .map(|(_, gap)| gap.as_secs_f64() * 1000.0 - interval)
+        .collect();
+    Some(Jitter {
+        stats: stats::Stats::from_samples(&samples)?,
+        late: gaps
+            .into_iter()
+            .filter(|(_, gap)| is_late(report, *gap))
+            .collect(),
+    })
+}
+
+/// Whether a log holds the whole capture: as many entries as its duration
+/// and interval call for, none of them late.
+pub struct Completeness {
+    /// `None` for captures until interrupted or until their command exited,
+    /// and for the parts of a rotated capture, which have no set count.
+    pub expected: Option<usize>,
+    /// Entries read, including those `--lenient` left out.
+    pub actual: usize,
+    /// Late entries as in `Jitter::late`, with the gap before them.
+    pub gaps: Vec<(Duration, Duration)>,
+}
+
+impl Completeness {
+    pub fn is_complete(&self) -> bool {
+        self.expected.is_none_or(|expected| self.actual >= expected) && self.gaps.is_empty()
+    }
+}
+
+/// The logger samples until the duration is covered, so a capture of 10s
+/// every 3s has 4 entries.
+pub fn get_completeness(report: &Report) -> Completeness {
+    let open_ended = report.duration.is_zero() || report.exit.is_some() || report.part.is_some();
+    Completeness {
+        expected: (!open_ended && !report.interval.is_zero()).then(|| {
+            report
+                .duration
+                .as_millis()
+                .div_ceil(report.interval.as_millis()) as usize
+        }),
+        actual: report.entries.len() + report.skipped.len(),
+        gaps: get_gaps(report)
+            .into_iter()
+            .filter(|(_, gap)| is_late(report, *gap))
+            .collect(),
+    }
+}
+
+/// A core's frequency fell by more than the threshold from one interval to
+/// the next, which on phones usually means thermal throttling.
+pub struct FreqDrop {
+    pub time: Duration,
+    pub cpu: String,
+    pub from: u64,
+    pub to: u64,
+}
+
+pub fn get_freq_drops(report: &Report, threshold: f64) -> Vec<FreqDrop> {
+    let mut drops = Vec::new();
+    for pair in report.entries.windows(2) {
+        let (previous, entry) = (&pair[0], &pair[1]);
+        for cpu in report.freq_cpus.iter() {
+            let (Some(&from), Some(&to)) = (previous.freq.get(cpu), entry.freq.get(cpu)) else {
+                continue;
+            };
+            if from > 0 && (from as f64 - to as f64) / from as f64 * 100.0 > threshold {
+                drops.push(FreqDrop {
+                    time: entry.time,
+                    cpu: cpu.clone(),
+                    from,
+                    to,
+                });
+            }
+        }
+    }
+    drops
+}
+
+/// Average and peak temperature of a zone in millidegrees, over the
+/// intervals where it could be read.
+pub fn get_thermal_stats(report: &Report, zone: &str) -> Option<(f64, i64)> {
+    let temps: Vec<_> = report
+        .entries
+        .iter()
+        .filter_map(|entry| entry.thermal.get(zone)?.temp)
+        .collect();
+    let peak = *temps.iter().max()?;
+    Some((temps.iter().sum::<i64>() as f64 / temps.len() as f64, peak))
+}
+
+/// How far load or cycles must fall between intervals to count as a drop
+/// when looking for thermal throttling.
+pub const THROTTLE_DROP_PERCENT: f64 = 10.0;
+
+/// An interval whose "all" load or cycles fell while some zone was above the
+/// temperature threshold.
+pub struct ThrottleSuspect {
+    pub time: Duration,
+    pub load: Option<(f64, f64)>,
+    pub cycles: Option<(u64, u64)>,
+    pub zone: String,
+    pub temp: i64,
+}
+
+pub fn get_throttle_suspects(report: &Report, threshold: f64) -> Vec<ThrottleSuspect> {
+    let dropped =
+        |from: f64, to: f64| from > 0.0 && (from - to) / from * 100.0 > THROTTLE_DROP_PERCENT;
+    let mut suspects = Vec::new();
+    for pair in report.entries.windows(2) {
+        let (previous, entry) = (&pair[0], &pair[1]);
+        let Some((zone, temp)) = entry
+            .thermal
+            .iter()
+            .filter_map(|(zone, thermal)| Some((zone, thermal.temp?)))
+            .max_by_key(|(_, temp)| *temp)
+        else {
+            continue;
+        };
+        if (temp as f64) < threshold * 1000.0 {
+            continue;
+        }
+
+        let load = match (previous.proc.get("all"), entry.proc.get("all")) {
+            (Some(from), Some(to)) if dropped(from.load, to.load) => Some((from.load, to.load)),
+            _ => None,
+        };
+        let cycles = match (
+            previous.perf.get("all").and_then(|perf| perf.get(CYCLES)),
+            entry.perf.get("all").and_then(|perf| perf.get(CYCLES)),
+        ) {
+            (Some(from), Some(to)) if dropped(from as f64, to as f64) => Some((from, to)),
+            _ => None,
+        };
+        if load.is_some() || cycles.is_some() {
+            suspects.push(ThrottleSuspect {
+                time: entry.time,
+                load,
+                cycles,
+                zone: format!("{} ({})", zone, entry.thermal[zone].kind),
+                temp,
+            });
+        }
+    }
+    suspects
+}
+
+pub fn get_averages(report: &Report) -> Vec<CpuAverages> {
+    let perf_only = report
+        .perf_cpus
+        .iter()
+        .filter(|cpu| !report.proc_cpus.contains(cpu));
+    let mut cpus: Vec<_> = report.proc_cpus.iter().chain(perf_only).collect();
+    cpus.sort_by(|a, b| compare_cpus(a, b));
+
+    cpus.into_iter()
+        .map(|cpu| {
+            let perf = report.perf_cpus.contains(cpu);
+            CpuAverages {
+                cpu: cpu.clone(),
+                load: report
+                    .proc_cpus
+                    .contains(cpu)
+                    .then(|| get_average_proc_load(report, cpu)),
+                online: report
+                    .proc_cpus
+                    .contains(cpu)
+                    .then(|| get_online_percent(report, cpu)),
+                cycles: perf.then(|| get_average_cpu_cycles(report, cpu)),
+                context_switches: perf.then(|| get_average_context_switches(report, cpu)),
+            }
+        })
+        .collect()
+}
+
+/// Averages each stretch between consecutive `splits`, such as the markers'
+/// times, separately, an entry counting towards the stretch it starts in.
+/// Stretches without entries, as outside a `filter_time` window, are left out.
+pub fn split_phases(report: &mut Report, mut splits: Vec<(String, Duration)>) -> Vec<Phase> {
+    splits.sort_by_key(|(_, time)| *time);
+    let mut bounds = vec![("start".to_string(), Duration::ZERO)];
+    bounds.extend(splits);
+
+    let mut entries = std::mem::take(&mut report.entries);
+    let mut done = Vec::new();
+    let mut phases = Vec::new();
+    for (index, (label, _)) in bounds.iter().enumerate() {
+        let count = match bounds.get(index + 1) {
+            Some((_, next)) => entries
+                .iter()
+                .take_while(|entry| entry.time < *next)
+                .count(),
+            None => entries.len(),
+        };
+        if count == 0 {
+            continue;
+        }
+        // The averages read the report's entries, so it holds only this
+        // stretch's for a moment.
+        report.entries = entries.drain(..count).collect();
+        phases.push(Phase {
+            label: label.clone(),
+            from: report.entries[0].time,
+            to: report.entries[count - 1].time + report.interval,
+            entries: count,
+            averages: get_averages(report),
+        });
+        done.append(&mut report.entries);
+    }
+    report.entries = done;
+    phases
+}
+
+/// Aggregates the per CPU load and cycles of repeated runs, which must share
+/// an interval and CPUs. Longer runs are cut down to the shortest, with a
+/// warning for each, unless `strict`, which makes any difference an error.
+pub fn aggregate_runs(
+    runs: &mut [(&str, Report)],
+    strict: bool,
+) -> Result<(Aggregate, Vec<String>), AggregateError> {
+    let sorted = |cpus: &[String]| {
+        let mut cpus = cpus.to_vec();
+        cpus.sort();
+        cpus
+    };
+    let (_, first) = &runs[0];
+    let (interval, proc_cpus, perf_cpus) = (
+        first.interval,
+        sorted(&first.proc_cpus),
+        sorted(&first.perf_cpus),
+    );
+    let shortest = runs
+        .iter()
+        .map(|(_, report)| report.entries.len())
+        .min()
+        .unwrap();
+    let mut warnings = Vec::new();
+    for (file, report) in runs.iter_mut() {
+        if report.interval != interval {
+            return Err(AggregateError::Interval {
+                file: file.to_string(),
+                interval: report.interval,
+                expected: interval,
+            });
+        }
+        if sorted(&report.proc_cpus) != proc_cpus || sorted(&report.perf_cpus) != perf_cpus {
+            return Err(AggregateError::Cpus {
+                file: file.to_string(),
+            });
+        }
+        if report.entries.len() > shortest {
+            if strict {
+                return Err(AggregateError::Entries {
+                    file: file.to_string(),
+                    entries: report.entries.len(),
+                    expected: shortest,
+                });
+            }
+            warnings.push(format!(
+                "{}: truncated from {} to the shortest run's {} entries",
+                file,
+                report.entries.len(),
+                shortest
+            ));
+            report.entries.truncate(shortest);
+        }
+    }
+
+    // Matching CPUs sort the same, so row `index` is one CPU in every run.
+    let averages: Vec<_> = runs
+        .iter()
+        .map(|(_, report)| get_averages(report))
+        .collect();
+    let cpus = averages[0]
+        .iter()
+        .enumerate()
+        .map(|(index, first)| {
+            let spread = |metric: fn(&CpuAverages) -> Option<f64>| {
+                let values: Option<Vec<_>> =
+                    averages.iter().map(|run| metric(&run[index])).collect();
+                stats::RunSpread::from_runs(&values?)
+            };
+            CpuAggregate {
+                cpu: first.cpu.clone(),
+                load: spread(|averages| averages.load),
+                cycles: spread(|averages| averages.cycles),
+            }
+        })
+        .collect();
+    let aggregate = Aggregate {
+        files: runs.iter().map(|(file, _)| file.to_string()).collect(),
+        interval,
+        entries: shortest,
+        cpus,
+    };
+    Ok((aggregate, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UBUNTU_PROC_START: &str = include_str!("../tests/fixtures/ubuntu/proc-stat-start.txt");
+    const UBUNTU_PROC_END: &str = include_str!("../tests/fixtures/ubuntu/proc-stat-end.txt");
+    const UBUNTU_PERF: &str = include_str!("../tests/fixtures/ubuntu/perf.txt");
+    const UBUNTU_TCP_START: &str = include_str!("../tests/fixtures/ubuntu/tcp-start.txt");
+    const UBUNTU_TCP_END: &str = include_str!("../tests/fixtures/ubuntu/tcp-end.txt");
+    const ANDROID_PROC_START: &str = include_str!("../tests/fixtures/android/proc-stat-start.txt");
+    const ANDROID_PROC_END: &str = include_str!("../tests/fixtures/android/proc-stat-end.txt");
+    const ANDROID_PERF: &str = include_str!("../tests/fixtures/android/simpleperf.txt");
+    const UBUNTU_SOFTIRQS_START: &str = include_str!("../tests/fixtures/ubuntu/softirqs-start.txt");
+    const UBUNTU_SOFTIRQS_END: &str = include_str!("../tests/fixtures/ubuntu/softirqs-end.txt");
+    const ANDROID_SOFTIRQS_START: &str =
+        include_str!("../tests/fixtures/android/softirqs-start.txt");
+    const ANDROID_SOFTIRQS_END: &str = include_str!("../tests/fixtures/android/softirqs-end.txt");
+
+    fn loads(start: &str, end: &str) -> HashMap<String, ProcReport> {
+        let (start, end) = (parse_proc(start).unwrap(), parse_proc(end).unwrap());
+        start
+            .iter()
+            .map(|(cpu, start)| (cpu.clone(), analyze_proc(start, &end[cpu]).unwrap()))
+            .collect()
+    }
 
-        println!("{:20} {}", "Report ID ".bold().yellow(), report.id);
-        println!("{:20} {}", "File ".bold().yellow(), filename);
-        println!(
-            "{:20} {}",
-            "Test Duration ".bold().yellow(),
-            report.duration.as_secs()
+    #[test]
+    fn leaves_out_proc_counters_that_go_backwards_or_stall() {
+        let entry = |time: u32, end: &str| {
+            format!(
+                "<log-entry time-ms=\"{}\">\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n</log-entry>\n",
+                time, UBUNTU_PROC_START, end
+            )
+        };
+        // cpu0's user time went back across the first interval, and nothing
+        // moved in the second.
+        let backwards = UBUNTU_PROC_END.replace("cpu0 1600 ", "cpu0 900 ");
+        let log = format!(
+            "<log id=\"{}\" platform=\"ubuntu\" duration=\"3\" interval-ms=\"1000\" perf=\"off\">\n{}{}{}</log>\n",
+            Uuid::nil(),
+            entry(0, &backwards),
+            entry(1000, UBUNTU_PROC_START),
+            entry(2000, UBUNTU_PROC_END)
         );
-        println!(
-            "{:20} {}",
-            "Test Interval ".bold().yellow(),
-            report.interval.as_secs()
+        let report = get_report(log.as_bytes(), false, None).unwrap();
+        let first = &report.entries[0];
+        assert!(!first.proc.contains_key("0"));
+        assert_eq!(first.proc_anomalies["0"], ProcAnomaly::Backwards);
+        assert!(first.proc.contains_key("1"));
+        assert!(report.entries[1].proc.is_empty());
+        assert_eq!(
+            report.entries[1].proc_anomalies["all"],
+            ProcAnomaly::Stalled
         );
 
-        println!("{}", "Per CPU average load".bold().yellow());
-        let max_cpu_len = report.proc_cpus.iter().map(|s| s.len()).max().unwrap_or(0);
-        let proc_average_load = report
-            .proc_cpus
-            .iter()
-            .map(|cpu| {
+        let last = report.entries[2].proc["0"].load;
+        assert_eq!(get_average_proc_load(&report, "0"), last);
+        assert!(get_average_proc_load(&report, "all").is_finite());
+        assert_eq!(get_online_percent(&report, "0"), 100.0);
+    }
+
+    #[test]
+    fn parses_ubuntu_proc_stat() {
+        let proc = parse_proc(UBUNTU_PROC_START).unwrap();
+        let mut cpus: Vec<_> = proc.keys().cloned().collect();
+        cpus.sort_by(|a, b| compare_cpus(a, b));
+        assert_eq!(cpus, ["0", "1", "2", "3", "all"]);
+        let cpu1 = &proc["1"];
+        assert_eq!(
+            (cpu1.user, cpu1.nice, cpu1.system, cpu1.idle, cpu1.iowait),
+            (1010, 20, 300, 5000, 40)
+        );
+        assert_eq!(cpu1.total, 1010 + 20 + 300 + 5000 + 40 + 10);
+    }
+
+    #[test]
+    fn analyzes_ubuntu_proc_stat() {
+        let loads = loads(UBUNTU_PROC_START, UBUNTU_PROC_END);
+        for cpu in ["0", "1", "2", "3", "all"] {
+            assert_eq!(loads[cpu].load, 75.0, "cpu {}", cpu);
+        }
+        assert_eq!(loads["0"].total, 1000);
+        assert_eq!(loads["all"].user, 2400);
+    }
+
+    #[test]
+    fn analyzes_android_proc_stat() {
+        let loads = loads(ANDROID_PROC_START, ANDROID_PROC_END);
+        assert_eq!(loads.len(), 9);
+        for cpu in 0..8 {
+            let load = loads[&cpu.to_string()].load;
+            assert!((load - 10.0 * (cpu + 1) as f64).abs() < 1e-9, "cpu {}", cpu);
+        }
+        assert!((loads["all"].load - 45.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_malformed_proc_stat() {
+        let stat = UBUNTU_PROC_START.replace("cpu2 1020", "cpu2 x");
+        assert!(matches!(
+            parse_proc(&stat),
+            Err(EntryError::Line {
+                section: "proc",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parses_ubuntu_perf() {
+        let perf = parse_and_analyze_perf(UBUNTU_PERF, Platform::Ubuntu).unwrap();
+        assert_eq!(perf.len(), 5);
+        let core = &perf["S0-D0-C2"];
+        assert_eq!(core.get(CYCLES), Some(3_000_000));
+        assert_eq!(core.get(INSTRUCTIONS), Some(4_500_000));
+        assert_eq!(core.get(CONTEXT_SWITCHES), Some(102));
+        assert_eq!(core.ipc(), Some((4_500_000, 3_000_000)));
+        assert_eq!(perf["all"].get(CYCLES), Some(10_000_000));
+        assert_eq!(perf["all"].get(CONTEXT_SWITCHES), Some(406));
+    }
+
+    #[test]
+    fn parses_android_perf() {
+        let perf = parse_and_analyze_perf(ANDROID_PERF, Platform::Android).unwrap();
+        assert_eq!(perf.len(), 9);
+        assert_eq!(perf["7"].get(CYCLES), Some(16_000_000));
+        assert_eq!(perf["7"].get(CONTEXT_SWITCHES), Some(57));
+        assert_eq!(perf["all"].get(CYCLES), Some(72_000_000));
+        assert_eq!(perf["all"].get(INSTRUCTIONS), None);
+    }
+
+    #[test]
+    fn records_uncounted_perf_events() {
+        let text = UBUNTU_PERF.replace(
+            "           2                101      context-switches",
+            "           2    <not counted>      context-switches",
+        );
+        let perf = parse_and_analyze_perf(&text, Platform::Ubuntu).unwrap();
+        assert_eq!(perf["S0-D0-C1"].get(CONTEXT_SWITCHES), None);
+        assert!(perf["S0-D0-C1"].missing.contains(CONTEXT_SWITCHES));
+        assert_eq!(perf["all"].get(CONTEXT_SWITCHES), Some(305));
+    }
+
+    #[test]
+    fn parses_socket_and_system_perf() {
+        let sockets = "     1.001021660 S0        32            1000000      cycles
+     1.001021660 S0        32                100      context-switches
+     1.001021660 S1        32            3000000      cycles
+     1.001021660 S1        32                300      context-switches
+";
+        let perf = parse_and_analyze_perf(sockets, Platform::Ubuntu).unwrap();
+        assert_eq!(perf.len(), 3);
+        assert_eq!(perf["S1"].get(CYCLES), Some(3_000_000));
+        assert_eq!(perf["all"].get(CONTEXT_SWITCHES), Some(400));
+        assert_eq!(sorted(perf.into_keys().collect()), ["S0", "S1", "all"]);
+
+        let system = "     1.001021660          4000000      cycles
+     1.001021660    <not counted>      context-switches
+";
+        let perf = parse_and_analyze_perf(system, Platform::Ubuntu).unwrap();
+        assert_eq!(perf.len(), 1);
+        assert_eq!(perf["all"].get(CYCLES), Some(4_000_000));
+        assert!(perf["all"].missing.contains(CONTEXT_SWITCHES));
+
+        let system =
+            "2000000,cpu-cycles,0.002000 GHz,(100%),\n57,context-switches,57.000 /s,(100%),\n";
+        let perf = parse_and_analyze_perf(system, Platform::Android).unwrap();
+        assert_eq!(perf.len(), 1);
+        assert_eq!(perf["all"].get(CONTEXT_SWITCHES), Some(57));
+    }
+
+    #[test]
+    fn parses_tcp_counters_by_header_row() {
+        let start = parse_tcp(UBUNTU_TCP_START).unwrap();
+        assert_eq!(start["Tcp:MaxConn"], -1);
+        assert_eq!(start["Tcp:RetransSegs"], 1);
+        assert_eq!(start["TcpExt:TCPFastRetrans"], 40);
+        assert_eq!(start.len(), 23);
+
+        let tcp = analyze_tcp(&start, &parse_tcp(UBUNTU_TCP_END).unwrap());
+        assert_eq!(tcp.in_segs, 1000);
+        assert_eq!(tcp.out_segs, 2000);
+        assert_eq!(tcp.retrans_segs, 50);
+        assert_eq!(retrans_ratio(&tcp), Some(2.5));
+        assert_eq!(tcp.out_rsts, 2);
+        assert_eq!(tcp.fast_retrans, 30);
+        assert_eq!(tcp.lost_retransmit, 2);
+        assert_eq!(tcp.timeouts, 2);
+        assert_eq!(tcp.listen_drops, 1);
+    }
+
+    #[test]
+    fn rejects_malformed_net_dev() {
+        let header = "Inter-|   Receive                            |  Transmit\n face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed\n";
+        let net = parse_net_dev(&format!(
+            "{}  eth0: 1000 10 0 0 0 0 0 0 2000 20 0 0 0 0 0 0\n",
+            header
+        ))
+        .unwrap();
+        assert_eq!(net["eth0"].tx_bytes, 2000);
+        for line in [
+            "  eth0: 1000 10 0 0 0 0 0 0 2000 2O 0 0 0 0 0 0",
+            "  eth0: 1000 10 0 0",
+        ] {
+            assert!(
+                matches!(
+                    parse_net_dev(&format!("{}{}\n", header, line)),
+                    Err(EntryError::Line { section: "net", .. })
+                ),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_tcp_counters() {
+        for text in [
+            "Tcp: InSegs OutSegs\nTcp: 1\n",
+            "Tcp: InSegs OutSegs\n",
+            "Tcp: InSegs\nTcpExt: 1\n",
+            "Tcp: InSegs\nTcp: many\n",
+            "InSegs OutSegs\n1 2\n",
+        ] {
+            assert!(
+                matches!(
+                    parse_tcp(text),
+                    Err(EntryError::Line { section: "tcp", .. })
+                ),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(parse_tcp("").unwrap().len(), 0);
+    }
+
+    #[test]
+    fn parses_interrupts_by_cpu_column() {
+        // CPU 2 is offline, so the header skips it.
+        let start = "\n           CPU0       CPU1       CPU3\n\
+                     \x20 45:        100         20          0     GICv3 177 Level     wlan0\n\
+                     \x20 46:          7          0          0     GICv3 178 Level     wlan1\n\
+                     IPI0:         10         10         10       Rescheduling interrupts\n\
+                     Err:          0\n";
+        let end = "           CPU0       CPU1       CPU3\n\
+                   \x20 45:        150         20          5     GICv3 177 Level     wlan0\n\
+                   \x20 46:          7          0          0     GICv3 178 Level     wlan1\n\
+                   IPI0:         11         12         13       Rescheduling interrupts\n";
+        let start = parse_interrupts(start).unwrap();
+        assert_eq!(start.len(), 3);
+        assert_eq!(start["45"].name, "GICv3 177 Level wlan0");
+        assert_eq!(start["45"].counts["3"], 0);
+        let irqs = analyze_interrupts(&start, &parse_interrupts(end).unwrap());
+        assert_eq!(
+            irqs["45"].counts,
+            HashMap::from([("0".to_string(), 50), ("3".to_string(), 5)])
+        );
+        assert_eq!(irqs["IPI0"].counts["3"], 3);
+        assert!(!irqs.contains_key("46"));
+
+        for text in ["45: 100 GICv3 wlan0\n", "CPU0\n45 100 wlan0\n"] {
+            assert!(matches!(
+                parse_interrupts(text),
+                Err(EntryError::Line {
+                    section: "interrupts",
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn parses_softirqs_at_any_core_count() {
+        let net_rx = |start: &str, end: &str| {
+            let softirqs = analyze_softirqs(
+                &parse_softirqs(start).unwrap(),
+                &parse_softirqs(end).unwrap(),
+            );
+            let mut net_rx: Vec<_> = softirqs["NET_RX"]
+                .iter()
+                .map(|(cpu, count)| (cpu.parse::<u32>().unwrap(), *count))
+                .collect();
+            net_rx.sort();
+            assert_eq!(softirqs.len(), 10);
+            net_rx
+                .into_iter()
+                .map(|(_, count)| count)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            net_rx(UBUNTU_SOFTIRQS_START, UBUNTU_SOFTIRQS_END),
+            [300, 0, 50, 10]
+        );
+        assert_eq!(
+            net_rx(ANDROID_SOFTIRQS_START, ANDROID_SOFTIRQS_END),
+            [0, 0, 0, 0, 0, 0, 900, 100]
+        );
+
+        // Printed as the kernel does, with counts wide enough to fill their
+        // columns.
+        let table = |count: &dyn Fn(usize) -> u64| {
+            let mut text = format!("{:20}", "");
+            for cpu in 0..64 {
+                text.push_str(&format!("CPU{:<8}", cpu));
+            }
+            text.push('\n');
+            for kind in [
+                "HI", "TIMER", "NET_TX", "NET_RX", "BLOCK", "IRQ_POLL", "TASKLET", "SCHED",
+                "HRTIMER", "RCU",
+            ] {
+                text.push_str(&format!("{:>12}:", kind));
+                for cpu in 0..64 {
+                    text.push_str(&format!(" {:>10}", count(cpu)));
+                }
+                text.push('\n');
+            }
+            text
+        };
+        let start = table(&|cpu| 4_000_000_000 + cpu as u64);
+        let end = table(&|cpu| 4_000_000_000 + cpu as u64 * 2);
+        let counts = net_rx(&start, &end);
+        assert_eq!(counts.len(), 64);
+        assert_eq!(counts[63], 63);
+        assert_eq!(counts[0], 0);
+
+        assert!(matches!(
+            parse_softirqs("HI: 0 0\n"),
+            Err(EntryError::Line {
+                section: "softirqs",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parses_ss_connections() {
+        let text = "State Recv-Q Send-Q Local Address:Port  Peer Address:Port Process\n\
+                    ESTAB 0      0          10.0.0.2:40278    10.0.0.9:5201\n\
+                    \t cubic wscale:7,7 rto:204 rtt:12.5/3.1 mss:1448 cwnd:42 bytes_acked:1 retrans:1/17 bbr:(bw:0bps,mrtt:0.046) send 56.9Mbps delivery_rate 23.1Mbps minrtt:9.8\n\
+                    SYN-SENT 0   1   [2001:db8::2]:51234 [2001:db8::9]:5201\n\
+                    \t cubic rto:1000 mss:524 cwnd:1\n";
+        let sockets = parse_sockets(text).unwrap();
+        assert_eq!(
+            sockets["10.0.0.2:40278 -> 10.0.0.9:5201"],
+            SocketReport {
+                state: "ESTAB".to_string(),
+                rtt: Some(12.5),
+                cwnd: Some(42),
+                retrans: 17,
+                delivery_rate: Some(23.1e6),
+            }
+        );
+        let syn = &sockets["[2001:db8::2]:51234 -> [2001:db8::9]:5201"];
+        assert_eq!((syn.rtt, syn.cwnd, syn.retrans), (None, Some(1), 0));
+        assert_eq!(parse_bandwidth("56940869565bps"), Some(56940869565.0));
+
+        for text in [
+            "\t cubic rtt:1/1\n",
+            "ESTAB 0 0\n",
+            "ESTAB 0 0 a:1 b:2\n\t cwnd:many\n",
+        ] {
+            assert!(matches!(
+                parse_sockets(text),
+                Err(EntryError::Line {
+                    section: "sockets",
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn parses_diskstats_of_devices_coming_and_going() {
+        let start = "   7       0 loop0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0\n\
+                     \x20179       0 mmcblk0 4000 10 80000 900 2000 30 160000 5000 0 3000 6000\n\
+                     \x20 8       1 sda1 12 24 36 48\n";
+        let end =
+            " 179       0 mmcblk0 4010 10 80080 905 2200 30 180000 5400 1 3500 6500 0 0 0 0 0 0\n\
+                   \x20  7       1 loop1 5 0 40 1 0 0 0 0 0 1 1 0 0 0 0 0 0\n";
+        let (start, end) = (
+            parse_diskstats(start).unwrap(),
+            parse_diskstats(end).unwrap(),
+        );
+        assert!(!start.contains_key("sda1"));
+        let mmc = analyze_disk(&start["mmcblk0"], &end["mmcblk0"]);
+        assert_eq!((mmc.reads, mmc.read_bytes), (10, 80 * 512));
+        assert_eq!((mmc.writes, mmc.written_bytes), (200, 20000 * 512));
+        assert_eq!(mmc.io_ms, 500);
+        assert!(end.contains_key("loop1") && !end.contains_key("loop0"));
+
+        assert!(matches!(
+            parse_diskstats("179 0 mmcblk0 4000 ten 80000 900 2000 30 160000 5000 0 3000 6000\n"),
+            Err(EntryError::Line {
+                section: "diskstats",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parses_pressure_lines() {
+        let pressure = parse_pressure(
+            "cpu some avg10=3.27 avg60=3.44 avg300=3.35 total=247534396\n\
+             io some avg10=0.14 avg60=0.29 avg300=0.22 total=16711647\n\
+             io full avg10=0.03 avg60=0.21 avg300=0.17 total=13010473\n",
+        )
+        .unwrap();
+        assert_eq!(pressure.len(), 3);
+        assert_eq!(pressure["cpu some"], (3.27, 247534396));
+        assert_eq!(pressure["io full"], (0.03, 13010473));
+        assert!(matches!(
+            parse_pressure("some avg10=3.27 avg60=3.44 avg300=3.35 total=1\n"),
+            Err(EntryError::Line {
+                section: "pressure",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn analyzes_cgroup_cpu_stat() {
+        let stat = "usage_usec 1000000\nuser_usec 800000\nsystem_usec 200000\n\
+                    usage_usec 1500000\nuser_usec 1200000\nsystem_usec 300000\n";
+        // Half a second of the 100 seconds of jiffies of all CPUs.
+        let cgroup = analyze_cgroup(stat, 10_000).unwrap();
+        assert_eq!(
+            (cgroup.usage_us, cgroup.user_us, cgroup.system_us),
+            (500000, 400000, 100000)
+        );
+        assert_eq!(cgroup.nr_throttled, 0);
+        assert!((cgroup.share - 0.5).abs() < 1e-9);
+        for stat in ["usage_usec\n", "user_usec 1\nuser_usec 2\n"] {
+            assert!(matches!(
+                analyze_cgroup(stat, 100),
+                Err(EntryError::Line {
+                    section: "cgroup",
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn parses_gpu_load() {
+        assert_eq!(parse_gpu("\n1500 6000\n").unwrap(), 25.0);
+        assert_eq!(parse_gpu("0 0\n").unwrap(), 0.0);
+        assert_eq!(parse_gpu("45@600000000Hz\n").unwrap(), 45.0);
+        assert_eq!(parse_gpu("12 %\n").unwrap(), 12.0);
+        assert!(matches!(
+            parse_gpu("busy\n"),
+            Err(EntryError::Line { section: "gpu", .. })
+        ));
+    }
+
+    #[test]
+    fn parses_radio_across_android_versions() {
+        let radio = parse_radio(include_str!("../tests/fixtures/android/radio.txt"));
+        assert_eq!(radio.net_type, "NR_NSA");
+        assert_eq!(
+            (radio.rsrp, radio.rsrq, radio.rssi),
+            (Some(-95), Some(-12), Some(-69))
+        );
+        assert_eq!(radio.wifi_rssi, None);
+
+        // Android 9 and a Wi-Fi driver printing the level unsigned.
+        let radio = parse_radio(
+            "mServiceState=0 0 home T-Mobile mRilDataRadioTechnology=14(LTE) mNrState=CONNECTED\n\
+             mSignalStrength=SignalStrength: 99 0 -120 -160 -120 -1 -1 28 -101 -9 130 2147483647\n\
+             wlan0: 0000   54.  200.  -256        0      0      0      0    356        0\n",
+        );
+        assert_eq!(radio.net_type, "NR_NSA");
+        assert_eq!(
+            (radio.rsrp, radio.rsrq, radio.rssi),
+            (Some(-101), Some(-9), None)
+        );
+        assert_eq!(radio.wifi_rssi, Some(-56));
+
+        let radio = parse_radio("mServiceState={mDataRegState=1(OUT_OF_SERVICE)}\nwlan0: 0000 54. -56. -256 0 0 0 0 0 0\n");
+        assert_eq!(radio.net_type, "WIFI");
+        assert_eq!(parse_radio("").net_type, "unknown");
+    }
+
+    #[test]
+    fn analyzes_time_in_state() {
+        let start =
+            parse_time_in_state("\ncpu0 300000 100\ncpu0 1800000 50\ncpu4 300000 7\n").unwrap();
+        let end =
+            parse_time_in_state("cpu0 1800000 125\ncpu0 300000 125\ncpu4 300000 7\n").unwrap();
+        let states = analyze_time_in_state(&start, &end);
+        assert_eq!(states["0"], [(300000, 250), (1800000, 750)]);
+        assert_eq!(effective_khz(&states["0"]), Some(1425000.0));
+        assert_eq!(effective_khz(&states["4"]), None);
+        assert!(matches!(
+            parse_time_in_state("cpu0 300000\n"),
+            Err(EntryError::Line {
+                section: "time_in_state",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parses_loadavg() {
+        assert_eq!(
+            parse_loadavg("\n0.52 0.58 0.59 3/412 12345\n").unwrap(),
+            LoadavgReport {
+                one: 0.52,
+                five: 0.58,
+                fifteen: 0.59,
+                runnable: 3,
+                tasks: 412,
+            }
+        );
+        assert!(matches!(
+            parse_loadavg("0.52 0.58\n"),
+            Err(EntryError::Line {
+                section: "loadavg",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parses_schedstat_versions() {
+        for (start, end, cpus, base) in [
+            (
+                include_str!("../tests/fixtures/ubuntu/schedstat-start.txt"),
+                include_str!("../tests/fixtures/ubuntu/schedstat-end.txt"),
+                4,
+                100,
+            ),
+            (
+                include_str!("../tests/fixtures/android/schedstat-start.txt"),
+                include_str!("../tests/fixtures/android/schedstat-end.txt"),
+                8,
+                40,
+            ),
+        ] {
+            let schedstat = analyze_schedstat(
+                &parse_schedstat(start).unwrap(),
+                &parse_schedstat(end).unwrap(),
+            );
+            assert_eq!(schedstat.len(), cpus);
+            for cpu in 0..cpus {
+                let delta = base * (cpu as u64 + 1);
+                assert_eq!(
+                    schedstat[&cpu.to_string()],
+                    SchedstatReport {
+                        run_ns: delta * 1_000_000,
+                        run_delay_ns: delta * 250_000,
+                        timeslices: delta * 3,
+                    }
+                );
+            }
+        }
+        assert!(matches!(
+            parse_schedstat("version 14\ncpu0 0 0 1 2 3 4 5 6 7\n"),
+            Err(EntryError::Version { version: 14, .. })
+        ));
+        assert!(matches!(
+            parse_schedstat("version 15\ncpu0 0 0 1 2\n"),
+            Err(EntryError::Line {
+                section: "schedstat",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn analyzes_cpuidle_states() {
+        let start = parse_cpuidle(
+            "\ncpu0 state0 10 1000 WFI\ncpu0 state1 5 600000 cpu-sleep-0\ncpu4 state0 1 1 WFI\n",
+        )
+        .unwrap();
+        let end = parse_cpuidle(
+            "cpu0 state0 17 21000 WFI\ncpu0 state1 8 1300000 cpu-sleep-0\ncpu1 state0 2 2 WFI\n",
+        )
+        .unwrap();
+        let cpuidle = analyze_cpuidle(&start, &end);
+        assert_eq!(cpuidle.len(), 1);
+        assert_eq!(
+            cpuidle["0"],
+            [
+                IdleStateReport {
+                    name: "WFI".to_string(),
+                    usage: 7,
+                    time_us: 20000,
+                },
+                IdleStateReport {
+                    name: "cpu-sleep-0".to_string(),
+                    usage: 3,
+                    time_us: 700000,
+                },
+            ]
+        );
+        assert!(matches!(
+            parse_cpuidle("cpu0 state0 WFI\n"),
+            Err(EntryError::Line {
+                section: "cpuidle",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn normalizes_battery_current() {
+        let battery = parse_battery(
+            "current_now -450000\nvoltage_now 4000000\ncapacity 81\nstatus Discharging\n",
+        )
+        .unwrap();
+        assert_eq!(battery.capacity, Some(81));
+        let abi = battery_quirk(Some("Pixel 7"));
+        assert_eq!(battery_power(&battery, abi), Some(1800.0));
+
+        // Samsung reports milliamps, and without a status the sign decides.
+        let samsung = battery_quirk(Some("SM-G991B"));
+        let battery = parse_battery("current_now -450\nvoltage_now 4000000\n").unwrap();
+        assert_eq!(battery_power(&battery, samsung), Some(1800.0));
+        let battery = parse_battery("current_now 450\nvoltage_now 4000000\n").unwrap();
+        assert_eq!(battery_power(&battery, samsung), Some(0.0));
+        let battery =
+            parse_battery("current_now 450000\nvoltage_now 4000000\nstatus Charging\n").unwrap();
+        assert_eq!(battery_power(&battery, abi), None);
+
+        assert!(matches!(
+            parse_battery("current_now lots\n"),
+            Err(EntryError::Line {
+                section: "battery",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parses_softnet_rows_across_kernels() {
+        // 5.10 and later, with the CPU in the 13th column: CPU 2 is offline.
+        let text = "0000000a 00000001 00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000\n\
+                    000000ff 00000000 00000010 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000003 00000000 00000000\n";
+        let softnet = parse_softnet(text, &[]).unwrap();
+        assert_eq!(
+            softnet["0"],
+            SoftnetReport {
+                processed: 10,
+                dropped: 1,
+                time_squeeze: 2
+            }
+        );
+        assert_eq!(softnet["3"].processed, 255);
+        assert_eq!(softnet["3"].time_squeeze, 16);
+
+        // Older kernels list the online CPUs in order.
+        let (cpu0, cpu3) = ("0".to_string(), "3".to_string());
+        let text = "00000001 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000\n\
+                    00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000\n";
+        let softnet = parse_softnet(text, &[&cpu0, &cpu3]).unwrap();
+        assert_eq!(softnet["3"].processed, 2);
+        assert_eq!(parse_softnet(text, &[&cpu0]).unwrap()["1"].processed, 2);
+
+        for text in ["0000000a 00000001\n", "0000000a 00000001 zz\n"] {
+            assert!(matches!(
+                parse_softnet(text, &[]),
+                Err(EntryError::Line {
+                    section: "softnet",
+                    ..
+                })
+            ));
+        }
+        let wrapped = analyze_softnet(
+            &SoftnetReport {
+                processed: 0xffff_fff0,
+                ..Default::default()
+            },
+            &SoftnetReport {
+                processed: 0x10,
+                ..Default::default()
+            },
+        );
+        assert_eq!(wrapped.processed, 0x20);
+    }
+
+    #[test]
+    fn guesses_platform_from_perf() {
+        assert_eq!(Platform::guess(ANDROID_PERF), Platform::Android);
+        assert_eq!(Platform::guess(UBUNTU_PERF), Platform::Ubuntu);
+    }
+
+    #[test]
+    fn reads_a_log_and_round_trips_its_report() {
+        let entry = format!(
+            "<log-entry time-ms=\"0\">\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n<perf>\n{}</perf>\n</log-entry>\n",
+            UBUNTU_PROC_START, UBUNTU_PROC_END, UBUNTU_PERF
+        );
+        let log = format!(
+            "<log id=\"{}\" platform=\"ubuntu\" duration=\"2\" interval-ms=\"1000\" events=\"cycles,instructions,context-switches\">\n<metadata>\n<hostname>build &amp; test</hostname>\n<logical-cpus>8</logical-cpus>\n<command>iperf3 -c server</command>\n</metadata>\n{}{}<log-end duration-actual=\"2004\" exit-signal=\"9\"/>\n</log>\n",
+            Uuid::nil(),
+            entry,
+            entry.replace("time-ms=\"0\"", "time-ms=\"1000\"")
+        );
+
+        let report = get_report(log.as_bytes(), false, None).unwrap();
+        assert_eq!(report.entries.len(), 2);
+        assert_eq!(report.entries[1].time, Duration::from_secs(1));
+        assert_eq!(report.duration_actual, Some(Duration::from_millis(2004)));
+        assert_eq!(report.perf_events, [CYCLES, INSTRUCTIONS, CONTEXT_SWITCHES]);
+        assert_eq!(get_average_proc_load(&report, "all"), 75.0);
+        assert_eq!(report.metadata.hostname.as_deref(), Some("build & test"));
+        assert_eq!(report.metadata.logical_cpus, Some(8));
+        assert_eq!(report.metadata.physical_cpus, None);
+        assert_eq!(report.metadata.command.as_deref(), Some("iperf3 -c server"));
+        assert_eq!(report.exit, Some(Exit::Signal(9)));
+
+        let json = serde_json::to_string(&report).unwrap();
+        let copy: Report = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy.interval, report.interval);
+        assert_eq!(copy.entries[1].time, report.entries[1].time);
+        assert_eq!(copy.entries[0].perf["all"].get(CYCLES), Some(10_000_000));
+        assert_eq!(copy.metadata.logical_cpus, Some(8));
+        assert_eq!(copy.exit, report.exit);
+        assert_eq!(serde_json::to_string(&copy).unwrap().len(), json.len());
+    }
+
+    #[test]
+    fn filters_entries_by_offset_and_wall_clock_time() {
+        let entry = |time: u32, wall_time: &str| {
+            format!(
+                "<log-entry time-ms=\"{}\"{}>\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n</log-entry>\n",
+                time, wall_time, UBUNTU_PROC_START, UBUNTU_PROC_END
+            )
+        };
+        let log = |start: &str, wall_time: &str| {
+            format!(
+                "<log id=\"{}\" platform=\"ubuntu\" duration=\"3\" interval-ms=\"1000\" perf=\"off\"{}>\n{}{}{}</log>\n",
+                Uuid::nil(),
+                start,
+                entry(0, ""),
+                entry(1000, wall_time),
+                entry(2000, "")
+            )
+        };
+        let times = |log: String, from, to| {
+            let mut report = get_report(log.as_bytes(), false, None).unwrap();
+            filter_time(&mut report, from, to).map(|()| {
+                report
+                    .entries
+                    .iter()
+                    .map(|entry| entry.time.as_millis())
+                    .collect::<Vec<_>>()
+            })
+        };
+        let at = |text| Some(TimeBound::At(timestamp::parse(text).unwrap()));
+        let offset = |millis| Some(TimeBound::Offset(Duration::from_millis(millis)));
+        let start = " start=\"2024-03-01T12:00:00.000Z\"";
+
+        assert_eq!(
+            times(log(start, ""), offset(1000), None).unwrap(),
+            [1000, 2000]
+        );
+        assert_eq!(
+            times(log(start, ""), None, offset(1500)).unwrap(),
+            [0, 1000]
+        );
+        assert_eq!(
+            times(
+                log(start, ""),
+                at("2024-03-01T12:00:01Z"),
+                at("2024-03-01T12:00:01Z")
+            )
+            .unwrap(),
+            [1000]
+        );
+        // A recorded wall-clock time wins over the start plus the offset.
+        let wall_time = " wall-time=\"2024-03-01T12:00:05.000Z\"";
+        assert_eq!(
+            times(log(start, wall_time), at("2024-03-01T12:00:03Z"), None).unwrap(),
+            [1000]
+        );
+        assert!(matches!(
+            times(log("", ""), at("2024-03-01T12:00:01Z"), None),
+            Err(ParseError::NoStartTime)
+        ));
+        assert!(matches!(
+            times(log(start, ""), offset(5000), None),
+            Err(ParseError::NoEntriesInRange)
+        ));
+    }
+
+    #[test]
+    fn aligns_captures_by_corrected_wall_clock() {
+        let entries: String = (0..4)
+            .map(|index| {
                 format!(
-                    "{:max_cpu_len$} {:>6.20}%",
-                    format!("{}", cpu).bold().yellow(),
-                    format_number(get_average_proc_load(&report, cpu))
+                    "<log-entry time-ms=\"{}\">\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n</log-entry>\n",
+                    index * 1000,
+                    UBUNTU_PROC_START,
+                    UBUNTU_PROC_END
                 )
             })
-            .collect::<Vec<_>>();
-        print_string_collection(&proc_average_load);
-        println!("{}", "Per CPU average CPU cycles".bold().yellow());
-        let max_cpu_len = report.perf_cpus.iter().map(|s| s.len()).max().unwrap_or(0);
-        let perf_average_cycles = report
-            .perf_cpus
+            .collect();
+        let report = |start: &str, offset: &str| {
+            let log = format!(
+                "<log id=\"{}\" platform=\"ubuntu\" duration=\"4\" interval-ms=\"1000\" perf=\"off\" start=\"{}\"{}>\n{}</log>\n",
+                Uuid::nil(),
+                start,
+                offset,
+                entries
+            );
+            get_report(log.as_bytes(), false, None).unwrap()
+        };
+        // The second clock runs 5s ahead, so its capture started a second
+        // after the first.
+        let mut first = report("2024-03-01T12:00:00.000Z", "");
+        let mut second = report("2024-03-01T12:00:06.000Z", " clock-offset-ms=\"5000\"");
+        let (from, to) = align_by_time(&mut [&mut first, &mut second]).unwrap();
+        assert_eq!(timestamp::format(from), "2024-03-01T12:00:01.000Z");
+        assert_eq!(timestamp::format(to), "2024-03-01T12:00:03.000Z");
+        let times = |report: &Report| {
+            report
+                .entries
+                .iter()
+                .map(|entry| entry.time.as_millis())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(times(&first), [1000, 2000, 3000]);
+        assert_eq!(times(&second), [0, 1000, 2000]);
+
+        let mut late = report("2024-03-01T13:00:00.000Z", "");
+        assert!(matches!(
+            align_by_time(&mut [&mut first, &mut late]),
+            Err(ParseError::NoEntriesInRange)
+        ));
+    }
+
+    fn android_report() -> Report {
+        let log = format!(
+            "<log id=\"{}\" platform=\"android\" duration=\"1\" interval-ms=\"1000\">\n<log-entry time-ms=\"0\">\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n<perf>\n{}</perf>\n</log-entry>\n</log>\n",
+            Uuid::nil(),
+            ANDROID_PROC_START,
+            ANDROID_PROC_END,
+            ANDROID_PERF
+        );
+        get_report(log.as_bytes(), false, None).unwrap()
+    }
+
+    #[test]
+    fn splits_merged_logs_by_markers() {
+        let marker = |millis, label: &str| Marker {
+            time: Duration::from_millis(millis),
+            label: label.to_string(),
+        };
+        let mut second = android_report();
+        second.markers.push(marker(500, "lte"));
+        let (mut report, _) = merge_reports(vec![
+            ("a.xml".to_string(), android_report()),
+            ("b.xml".to_string(), second),
+            ("c.xml".to_string(), android_report()),
+        ])
+        .unwrap();
+        report.markers.push(marker(3000, "after the end"));
+
+        let splits = report
+            .markers
             .iter()
+            .map(|marker| (marker.label.clone(), marker.time))
+            .collect();
+        let phases = split_phases(&mut report, splits);
+        let summary: Vec<_> = phases
+            .iter()
+            .map(|phase| (phase.label.as_str(), phase.from, phase.to, phase.entries))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("start", Duration::ZERO, Duration::from_secs(2), 2),
+                ("lte", Duration::from_secs(2), Duration::from_secs(3), 1),
+            ]
+        );
+        let all = phases[1].averages.iter().find(|cpu| cpu.cpu == "all");
+        assert_eq!(all.unwrap().cycles, Some(72_000_000.0));
+        assert_eq!(report.entries.len(), 3);
+    }
+
+    #[test]
+    fn detects_clusters_from_topology() {
+        let groups = |text: &str| {
+            detect_clusters(&parse_topology(text))
+                .into_iter()
+                .map(|group| (group.name, group.cpus))
+                .collect::<Vec<_>>()
+        };
+        let soc: String = (0..8)
             .map(|cpu| {
-                format!(
-                    "{:max_cpu_len$} {}",
-                    format!("{}", cpu).bold().yellow(),
-                    format_number(get_average_cpu_cycles(&report, cpu))
+                let capacity = [325, 325, 325, 325, 828, 828, 828, 1024][cpu];
+                format!("cpu{} 0 {} {} {}\n", cpu, cpu, cpu / 4, capacity)
+            })
+            .collect();
+        assert_eq!(
+            groups(&soc),
+            [
+                ("little".to_string(), vec![0, 1, 2, 3]),
+                ("mid".to_string(), vec![4, 5, 6]),
+                ("big".to_string(), vec![7]),
+            ]
+        );
+        // Two sockets of two hyperthreaded cores, with a cluster per core.
+        let x86 = "cpu0 0 0 0 -\ncpu1 0 1 1 -\ncpu2 1 0 8 -\ncpu3 1 1 9 -\n\
+                   cpu4 0 0 0 -\ncpu5 0 1 1 -\ncpu6 1 0 8 -\ncpu7 1 1 9 -\n";
+        assert_eq!(
+            groups(x86),
+            [
+                ("socket-a".to_string(), vec![0, 1, 4, 5]),
+                ("socket-b".to_string(), vec![2, 3, 6, 7]),
+            ]
+        );
+        assert!(groups("cpu0 0 0 -1 1024\ncpu1 0 1 -1 1024\n").is_empty());
+    }
+
+    #[test]
+    fn adds_cpu_groups_without_counting_them_in_all() {
+        let mut report = android_report();
+        let group = |name: &str, cpus: &[u64]| CpuGroup {
+            name: name.to_string(),
+            cpus: cpus.to_vec(),
+        };
+        let warnings = add_cpu_groups(
+            &mut report,
+            &[
+                group("big", &[4, 5, 6, 7]),
+                group("little", &[0, 1, 2, 3, 9]),
+            ],
+        );
+        assert_eq!(warnings, ["group little: the log has no CPU 9"]);
+        assert_eq!(report.proc_cpus[8..], ["big", "little", "all"]);
+        assert_eq!(get_average_cpu_cycles(&report, "big"), 52_000_000.0);
+        assert_eq!(get_average_cpu_cycles(&report, "little"), 20_000_000.0);
+        assert_eq!(get_average_cpu_cycles(&report, "all"), 72_000_000.0);
+
+        let proc = &report.entries[0].proc;
+        let (busy, total) = ["4", "5", "6", "7"]
+            .iter()
+            .fold((0, 0), |(busy, total), cpu| {
+                (
+                    busy + proc[*cpu].total - proc[*cpu].idle,
+                    total + proc[*cpu].total,
                 )
+            });
+        assert!((proc["big"].load - 100.0 * busy as f64 / total as f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn aggregates_runs_sharing_an_interval() {
+        let mut second = android_report();
+        second.entries[0].proc.get_mut("0").unwrap().load = 30.0;
+        let mut runs = [("a.xml", android_report()), ("b.xml", second)];
+        let (aggregate, warnings) = aggregate_runs(&mut runs, true).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(aggregate.entries, 1);
+        let load = aggregate.cpus[0].load.unwrap();
+        assert_eq!((aggregate.cpus[0].cpu.as_str(), load.mean), ("0", 20.0));
+        assert_eq!(aggregate.cpus[1].cycles.unwrap().stddev, 0.0);
+
+        runs[1].1.interval = Duration::from_secs(2);
+        let error = aggregate_runs(&mut runs, false).err().unwrap();
+        assert_eq!(error.to_string(), "b.xml: interval of 2s instead of 1s");
+    }
+
+    #[test]
+    fn merges_logs_one_after_another() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut first = android_report();
+        first.start = Some(start);
+        let mut second = android_report();
+        second.start = Some(start + Duration::from_millis(500));
+        let (merged, warnings) = merge_reports(vec![
+            ("a.xml".to_string(), first),
+            ("b.xml".to_string(), second),
+        ])
+        .unwrap();
+        assert_eq!(warnings, ["b.xml: overlaps the end of a.xml by 0.5s"]);
+        assert_eq!(merged.sources, ["a.xml", "b.xml"]);
+        assert_eq!(merged.entries[1].time, Duration::from_secs(1));
+        assert_eq!(merged.duration, Duration::from_secs(2));
+        assert_eq!(merged.duration_actual, Some(Duration::from_secs(2)));
+        assert_eq!(merged.proc_cpus.last().unwrap(), "all");
+        assert_eq!(get_average_cpu_cycles(&merged, "all"), 72_000_000.0);
+    }
+
+    #[test]
+    fn stitches_rotated_parts_in_order() {
+        let part = |part: u32, next: Option<&str>| {
+            let mut report = android_report();
+            report.part = Some(part);
+            report.next_part = next.map(str::to_string);
+            report.duration = Duration::from_secs(3);
+            report
+        };
+        let (merged, warnings) = merge_reports(vec![
+            ("run.xml".to_string(), part(1, Some("run.part2.xml"))),
+            ("run.part3.xml".to_string(), part(3, None)),
+            ("run.part2.xml".to_string(), part(2, Some("run.part3.xml"))),
+        ])
+        .unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(
+            merged.sources,
+            ["run.xml", "run.part2.xml", "run.part3.xml"]
+        );
+        assert_eq!(merged.entries[2].time, Duration::from_secs(2));
+        assert_eq!(merged.duration, Duration::from_secs(3));
+        assert_eq!(merged.part, None);
+
+        let error = merge_reports(vec![
+            ("run.xml".to_string(), part(1, Some("run.part2.xml"))),
+            ("run.part3.xml".to_string(), part(3, None)),
+        ])
+        .err()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "run.part3.xml: part 3 of {}, whose part 2 is missing",
+                Uuid::nil()
+            )
+        );
+        let error = merge_reports(vec![
+            ("run.xml".to_string(), part(1, Some("run.part2.xml"))),
+            ("other.xml".to_string(), android_report()),
+        ])
+        .err()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "run.xml: continues in run.part2.xml, which is missing"
+        );
+    }
+
+    #[test]
+    fn measures_gaps_against_the_interval() {
+        assert!(get_jitter(&android_report()).is_none());
+        let log = format!(
+            "<log id=\"{}\" platform=\"android\" duration=\"4\" interval-ms=\"1000\">\n{}</log>\n",
+            Uuid::nil(),
+            [0, 1010, 2000, 3600].map(|time| format!(
+                "<log-entry time-ms=\"{}\">\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n<perf>\n{}</perf>\n</log-entry>\n",
+                time, ANDROID_PROC_START, ANDROID_PROC_END, ANDROID_PERF
+            )).concat()
+        );
+        let report = get_report(log.as_bytes(), false, None).unwrap();
+        let jitter = get_jitter(&report).unwrap();
+        assert_eq!((jitter.stats.min, jitter.stats.max), (-10.0, 600.0));
+        assert_eq!(
+            jitter.late,
+            [(Duration::from_millis(3600), Duration::from_millis(1600))]
+        );
+    }
+
+    #[test]
+    fn checks_the_entries_against_the_duration() {
+        let log = |duration: u32, times: &[u32]| {
+            format!(
+                "<log id=\"{}\" platform=\"android\" duration=\"{}\" interval-ms=\"1000\">\n{}</log>\n",
+                Uuid::nil(),
+                duration,
+                times.iter().map(|time| format!(
+                    "<log-entry time-ms=\"{}\">\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n<perf>\n{}</perf>\n</log-entry>\n",
+                    time, ANDROID_PROC_START, ANDROID_PROC_END, ANDROID_PERF
+                )).collect::<String>()
+            )
+        };
+        let report = get_report(log(3, &[0, 1000, 2000]).as_bytes(), false, None).unwrap();
+        assert!(get_completeness(&report).is_complete());
+
+        let report = get_report(log(5, &[0, 1000, 2600, 3600]).as_bytes(), false, None).unwrap();
+        let completeness = get_completeness(&report);
+        assert_eq!((completeness.expected, completeness.actual), (Some(5), 4));
+        assert_eq!(
+            completeness.gaps,
+            [(Duration::from_millis(2600), Duration::from_millis(1600))]
+        );
+        assert!(!completeness.is_complete());
+
+        // Until interrupted, only the gaps count.
+        let report = get_report(log(0, &[0, 1000]).as_bytes(), false, None).unwrap();
+        assert_eq!(get_completeness(&report).expected, None);
+        assert!(get_completeness(&report).is_complete());
+    }
+
+    #[test]
+    fn subtracts_the_profiler_from_all() {
+        let stat = |utime: u64| format!("42 (profiler) R 1 1 1 0 -1 0 0 0 0 0 {} 5 0 0\n", utime);
+        let log = format!(
+            "<log id=\"{}\" platform=\"android\" duration=\"1\" interval-ms=\"1000\">\n<log-entry time-ms=\"0\">\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n<self>\n{}{}</self>\n<perf>\n{}</perf>\n</log-entry>\n</log>\n",
+            Uuid::nil(),
+            ANDROID_PROC_START,
+            ANDROID_PROC_END,
+            stat(100),
+            stat(104),
+            ANDROID_PERF
+        );
+        let mut report = get_report(log.as_bytes(), false, None).unwrap();
+        let all = &report.entries[0].proc["all"];
+        let (load, share) = (all.load, 400.0 / all.total as f64);
+        assert_eq!(get_profiler_share(&report), Some((share, share)));
+        subtract_profiler(&mut report);
+        assert!((report.entries[0].proc["all"].load - (load - share)).abs() < 1e-9);
+        assert!(get_profiler_share(&android_report()).is_none());
+    }
+
+    #[test]
+    fn excludes_the_pinned_cpu() {
+        let mut report = android_report();
+        exclude_cpu(&mut report, 7);
+        assert_eq!(report.proc_cpus, ["0", "1", "2", "3", "4", "5", "6", "all"]);
+        assert_eq!(get_average_cpu_cycles(&report, "all"), 56_000_000.0);
+    }
+
+    #[test]
+    fn filters_cpus_and_sums_all_over_them() {
+        let mut report = android_report();
+        filter_cpus(&mut report, &[4, 5, 6, 7]);
+        assert_eq!(report.proc_cpus, ["4", "5", "6", "7", "all"]);
+        assert_eq!(report.perf_cpus, ["4", "5", "6", "7", "all"]);
+        assert_eq!(report.entries[0].perf["all"].get(CYCLES), Some(52_000_000));
+        let proc = &report.entries[0].proc;
+        let user: u64 = ["4", "5", "6", "7"].iter().map(|cpu| proc[*cpu].user).sum();
+        assert_eq!(proc["all"].user, user);
+    }
+
+    #[test]
+    fn keeps_only_the_logged_cpu_list() {
+        let log = format!(
+            "<log id=\"{}\" platform=\"android\" duration=\"1\" interval-ms=\"1000\" cpu-list=\"4-5,7\">\n<log-entry time-ms=\"0\">\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n<perf>\n{}</perf>\n</log-entry>\n</log>\n",
+            Uuid::nil(),
+            ANDROID_PROC_START,
+            ANDROID_PROC_END,
+            ANDROID_PERF
+        );
+        let report = get_report(log.as_bytes(), false, None).unwrap();
+        assert_eq!(report.cpu_list, [4, 5, 7]);
+        assert_eq!(format_cpu_list(&report.cpu_list), "4-5,7");
+        assert_eq!(report.proc_cpus, ["4", "5", "7", "all"]);
+        assert_eq!(report.perf_cpus, ["4", "5", "7", "all"]);
+        assert_eq!(report.entries[0].perf["all"].get(CYCLES), Some(38_000_000));
+    }
+
+    #[test]
+    fn hands_over_entries_as_read_and_stops_at_the_close() {
+        let entry = format!(
+            "<log-entry time-ms=\"0\">\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n</log-entry>\n",
+            UBUNTU_PROC_START, UBUNTU_PROC_END
+        );
+        let log = format!(
+            "<log id=\"{}\" platform=\"ubuntu\" duration=\"2\" interval-ms=\"1000\" perf=\"off\">\n{}</log>\n<log-entry",
+            Uuid::nil(),
+            entry.repeat(2)
+        );
+        let mut handed = 0;
+        let report = read_report(log.as_bytes(), false, None, |entry| {
+            assert!(entry.proc.contains_key("all"));
+            handed += 1;
+        })
+        .unwrap();
+        assert_eq!(handed, 2);
+        assert!(!report.truncated);
+
+        let open = format!(
+            "{}<log-entry time-ms=\"2000\">\n<proc-start>\ncpu",
+            &log[..log.find("</log>").unwrap()]
+        );
+        let report = read_report(open.as_bytes(), false, None, |_| {}).unwrap();
+        assert_eq!(report.entries.len(), 2);
+        assert!(report.truncated);
+    }
+
+    #[test]
+    fn reads_raw_not_counted_in_unversioned_logs() {
+        let log = format!(
+            "<log id=\"{}\" platform=\"ubuntu\" duration=\"1\" interval-ms=\"1000\" events=\"cycles,context-switches\">\n<log-entry time-ms=\"0\">\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n<perf>\n{}</perf>\n</log-entry>\n</log>\n",
+            Uuid::nil(),
+            UBUNTU_PROC_START,
+            UBUNTU_PROC_END,
+            include_str!("../tests/fixtures/ubuntu/perf-not-counted.txt")
+        );
+        let report = get_report(log.as_bytes(), false, None).unwrap();
+        let perf = &report.entries[0].perf;
+        assert_eq!(perf["S0-D0-C1"].get(CYCLES), Some(2_000_000));
+        assert!(perf["S0-D0-C0"].missing.contains(CONTEXT_SWITCHES));
+        assert!(perf["S0-D0-C1"].missing.contains(CONTEXT_SWITCHES));
+    }
+
+    #[test]
+    fn points_invalid_utf8_at_its_byte() {
+        let mut log = format!(
+            "<log id=\"{}\" platform=\"ubuntu\" duration=\"1\" interval-ms=\"1000\" perf=\"off\">\n<log-entry time-ms=\"0\">\n<proc-start>\n{}",
+            Uuid::nil(),
+            UBUNTU_PROC_START
+        )
+        .into_bytes();
+        let offset = log.len();
+        log.extend(b"\xffcpu9 1 2 3\n</proc-start>\n</log-entry>\n</log>\n");
+        match get_report(&log[..], false, None) {
+            Err(ParseError::Utf8(position)) => assert_eq!(position, offset),
+            other => panic!("{:?}", other.map(|report| report.id)),
+        }
+    }
+
+    #[test]
+    fn loads_reports_in_order_on_several_threads() {
+        let entry = format!(
+            "<log-entry time-ms=\"0\">\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n</log-entry>\n",
+            UBUNTU_PROC_START, UBUNTU_PROC_END
+        );
+        let dir = std::env::temp_dir().join(format!("profiler-{}", Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let ids: Vec<_> = (0..5).map(|_| Uuid::new_v4()).collect();
+        let files: Vec<_> = ids
+            .iter()
+            .enumerate()
+            .map(|(index, id)| {
+                let path = dir.join(format!("{}.xml", index));
+                let log = if index == 2 {
+                    "not a log".to_string()
+                } else {
+                    format!(
+                        "<log id=\"{}\" platform=\"ubuntu\" duration=\"1\" interval-ms=\"1000\" perf=\"off\">\n{}</log>\n",
+                        id, entry
+                    )
+                };
+                std::fs::write(&path, log).unwrap();
+                path.to_str().unwrap().to_string()
             })
-            .collect::<Vec<_>>();
-        print_string_collection(&perf_average_cycles);
-        println!();
+            .collect();
+
+        let loaded: Vec<_> = load_reports(&files, 2, false, None, Cache::Off).collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.len(), 5);
+        for (index, report) in loaded.iter().enumerate() {
+            match report {
+                Ok(report) => assert_eq!(report.id, ids[index]),
+                Err(error) => {
+                    assert_eq!(index, 2);
+                    assert_eq!(error.file, files[2]);
+                }
+            }
+        }
+        assert!(loaded[2].is_err());
+    }
+
+    #[test]
+    fn ranks_busiest_entries_breaking_ties_by_time() {
+        let entry = format!(
+            "<log-entry time-ms=\"0\">\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n</log-entry>\n",
+            UBUNTU_PROC_START, UBUNTU_PROC_END
+        );
+        let log = format!(
+            "<log id=\"{}\" platform=\"ubuntu\" duration=\"4\" interval-ms=\"1000\" perf=\"off\">\n{}</log>\n",
+            Uuid::nil(),
+            entry.repeat(4)
+        );
+        let mut report = get_report(log.as_bytes(), false, None).unwrap();
+        for (index, load) in [30.0, 80.0, 30.0, 50.0].into_iter().enumerate() {
+            let entry = &mut report.entries[index];
+            entry.time = Duration::from_secs(index as u64);
+            entry.proc.get_mut("all").unwrap().load = load;
+        }
+
+        let busiest = get_busiest_entries(&report, 3, |entry| Some(entry.proc["all"].load));
+        let ranked: Vec<_> = busiest
+            .iter()
+            .map(|(entry, load)| (entry.time.as_secs(), *load))
+            .collect();
+        assert_eq!(ranked, [(1, 80.0), (3, 50.0), (0, 30.0)]);
+        assert!(get_busiest_entries(&report, 3, |_| None).is_empty());
+    }
+
+    #[test]
+    fn averages_loads_into_buckets() {
+        let entry = format!(
+            "<log-entry time-ms=\"0\">\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n</log-entry>\n",
+            UBUNTU_PROC_START, UBUNTU_PROC_END
+        );
+        let log = format!(
+            "<log id=\"{}\" platform=\"ubuntu\" duration=\"5\" interval-ms=\"1000\" perf=\"off\">\n{}</log>\n",
+            Uuid::nil(),
+            entry.repeat(5)
+        );
+        let mut report = get_report(log.as_bytes(), false, None).unwrap();
+        for (entry, load) in report
+            .entries
+            .iter_mut()
+            .zip([10.0, 20.0, 30.0, 40.0, 50.0])
+        {
+            entry.proc.get_mut("all").unwrap().load = load;
+        }
+        report.entries[4].proc.remove("0");
+
+        assert_eq!(
+            get_load_buckets(&report, "all", 2),
+            [Some(15.0), Some(40.0)]
+        );
+        assert_eq!(get_load_buckets(&report, "all", 10).len(), 5);
+        assert_eq!(get_load_buckets(&report, "0", 5)[4], None);
+    }
+
+    fn sorted(mut cpus: Vec<String>) -> Vec<String> {
+        cpus.sort_by(|a, b| compare_cpus(a, b));
+        cpus
+    }
+
+    fn labels(count: usize, label: impl Fn(usize) -> String) -> Vec<String> {
+        let mut cpus: Vec<_> = (0..count).map(label).collect();
+        cpus.push("all".to_string());
+        cpus
+    }
+
+    #[test]
+    fn compare_cpus_orders_numerically() {
+        for count in [8, 10, 128] {
+            let formats: [fn(usize) -> String; 3] = [
+                |core| core.to_string(),
+                |core| format!("cpu{}", core),
+                |core| format!("S0-D0-C{}", core),
+            ];
+            for format in formats {
+                let expected = labels(count, format);
+                let mut shuffled = expected.clone();
+                shuffled.reverse();
+                shuffled.rotate_left(count / 3);
+                assert_eq!(sorted(shuffled), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn compare_cpus_orders_sockets_before_cores() {
+        let cpus = ["S1-D0-C0", "all", "S0-D0-C10", "S0-D0-C9", "S0-D1-C0"];
+        let cpus = sorted(cpus.iter().map(|cpu| cpu.to_string()).collect());
+        assert_eq!(
+            cpus,
+            ["S0-D0-C9", "S0-D0-C10", "S0-D1-C0", "S1-D0-C0", "all"]
+        );
+    }
+
+    /// A line of this process' /proc/self/status, in bytes.
+    fn status_bytes(field: &str) -> u64 {
+        let status = std::fs::read_to_string("/proc/self/status").unwrap();
+        let line = status
+            .lines()
+            .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))
+            .unwrap();
+        line.trim().trim_end_matches(" kB").parse::<u64>().unwrap() * 1024
+    }
+
+    /// A 1GB log of the entry fixture, a default capture of a single CPU
+    /// VM. Run it on its own, in release, with
+    /// `cargo test --release -- --ignored parses_a_huge_log`.
+    #[test]
+    #[ignore = "writes and parses a 1GB log"]
+    fn parses_a_huge_log_without_keeping_it_resident() {
+        const SIZE: u64 = 1 << 30;
+        const BUDGET: u64 = 100 << 20;
+        let entry = include_str!("../tests/fixtures/ubuntu/log-entry.xml");
+        let (_, entry) = entry.split_once('>').unwrap();
+        let path = std::env::temp_dir().join(format!("profiler-{}.xml", Uuid::new_v4()));
+        let mut output = std::io::BufWriter::new(File::create(&path).unwrap());
+        let mut written = 0;
+        let mut entries = 0;
+        while written < SIZE {
+            let text = format!("<log-entry time-ms=\"{}\">{}", entries * 1000, entry);
+            written += text.len() as u64;
+            entries += 1;
+            if entries == 1 {
+                let header = format!(
+                    "<log id=\"{}\" platform=\"ubuntu\" duration=\"0\" interval-ms=\"1000\" perf=\"off\">\n",
+                    Uuid::new_v4()
+                );
+                std::io::Write::write_all(&mut output, header.as_bytes()).unwrap();
+            }
+            std::io::Write::write_all(&mut output, text.as_bytes()).unwrap();
+        }
+        let end = format!(
+            "<log-end duration-actual=\"{}\"/>\n</log>\n",
+            entries * 1000
+        );
+        std::io::Write::write_all(&mut output, end.as_bytes()).unwrap();
+        drop(output);
+
+        // Resets the peak, which the writing above raised.
+        std::fs::write("/proc/self/clear_refs", "5").unwrap();
+        let before = status_bytes("RssAnon");
+        let report = load_report(path.to_str().unwrap(), false, None).unwrap();
+        let (peak, held) = (status_bytes("VmHWM"), status_bytes("RssAnon") - before);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(report.entries.len(), entries as usize);
+        eprintln!(
+            "{} entries, peak RSS {}MB, of which the report holds {}MB ({} bytes an entry)",
+            entries,
+            peak >> 20,
+            held >> 20,
+            held / entries
+        );
+        // What the parse itself takes on top of the report: the mapped log
+        // and the text of the entry being read.
+        assert!(
+            peak - held < BUDGET,
+            "peak RSS was {}MB on top of the report",
+            (peak - held) >> 20
+        );
     }
 }
@@ -0,0 +1,282 @@
+use std::{
+    env,
+    fs::{self, File},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    process,
+    time::UNIX_EPOCH,
+};
+
+use serde::Serialize;
+
+use super::{load_report, Report, ReportError, TimeUnit};
+
+/// Bump when `Report` or its serialization changes in a way the build
+/// identity in the key wouldn't catch, e.g. a reader built from the same
+/// version.
+const VERSION: u32 = 3;
+const MAGIC: &str = "profiler-report-cache";
+/// How much of each end of a log goes into its key, so that a log rewritten
+/// with its size and time preserved is still noticed.
+const SAMPLED: u64 = 64 * 1024;
+
+/// Whether `load_cached` reads and writes the `.cache` file next to a log.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Cache {
+    #[default]
+    Use,
+    /// Parse the log even if its cache is valid, and write the cache anew.
+    Refresh,
+    Off,
+}
+
+pub fn cache_path(filename: &str) -> String {
+    format!("{}.cache", filename)
+}
+
+/// What a cache must have been written for: the log's size, time and ends,
+/// the options it was parsed with and the profiler that parsed it. A cache
+/// from any other build of the profiler, which may lay `Report` out
+/// differently, doesn't match.
+fn key(filename: &str, lenient: bool, time_unit: Option<TimeUnit>) -> io::Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    (VERSION, env!("CARGO_PKG_VERSION"), lenient).hash(&mut hasher);
+    time_unit.map(|unit| unit as u8).hash(&mut hasher);
+    let exe = fs::metadata(env::current_exe()?)?;
+    let mut file = File::open(filename)?;
+    for metadata in [exe, file.metadata()?] {
+        metadata.len().hash(&mut hasher);
+        metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+    }
+    let len = file.metadata()?.len();
+    let mut ends = Vec::new();
+    (&mut file).take(SAMPLED).read_to_end(&mut ends)?;
+    file.seek(SeekFrom::Start(len.saturating_sub(SAMPLED).max(SAMPLED)))?;
+    file.take(SAMPLED).read_to_end(&mut ends)?;
+    ends.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+fn header(key: u64) -> String {
+    format!("{} {} {:016x}\n", MAGIC, VERSION, key)
+}
+
+/// The report cached for `filename` under `key`, `None` if there is none or
+/// it is stale or unreadable.
+fn read_cache(filename: &str, key: u64) -> Option<Report> {
+    let cache = fs::read(cache_path(filename)).ok()?;
+    let body = cache.strip_prefix(header(key).as_bytes())?;
+    rmp_serde::from_slice(body).ok()
+}
+
+/// Writes the cache through a file of its own, so that a reader never sees
+/// it half written.
+fn write_cache(filename: &str, key: u64, report: &Report) -> io::Result<()> {
+    let path = cache_path(filename);
+    let temporary = format!("{}.{}", path, process::id());
+    let mut output = BufWriter::new(File::create(&temporary)?);
+    output.write_all(header(key).as_bytes())?;
+    // MessagePack with the fields named: `Report`'s serialization leaves
+    // fields out and writes some options bare, which only a format that
+    // describes itself reads back.
+    report
+        .serialize(&mut rmp_serde::Serializer::new(&mut output).with_struct_map())
+        .map_err(io::Error::other)?;
+    output.into_inner().map_err(|error| error.into_error())?;
+    fs::rename(&temporary, &path).inspect_err(|_| {
+        fs::remove_file(&temporary).ok();
+    })
+}
+
+/// Like `load_report`, from the `.cache` file next to `filename` when it was
+/// written for the log as it is now and with the same options. Parsed logs
+/// are cached for the next time unless they were cut off or had entries
+/// left out, whose warnings the cache couldn't repeat. A cache that can't be
+/// written, e.g. in a read-only directory, is skipped.
+pub fn load_cached(
+    filename: &str,
+    lenient: bool,
+    time_unit: Option<TimeUnit>,
+    cache: Cache,
+) -> Result<Report, ReportError> {
+    let key = match (filename, cache) {
+        ("-", _) | (_, Cache::Off) => None,
+        _ => key(filename, lenient, time_unit).ok(),
+    };
+    let Some(key) = key else {
+        return load_report(filename, lenient, time_unit);
+    };
+    if cache == Cache::Use {
+        if let Some(report) = read_cache(filename, key) {
+            return Ok(report);
+        }
+    }
+    let report = load_report(filename, lenient, time_unit)?;
+    if !report.truncated && report.skipped.is_empty() {
+        write_cache(filename, key, &report).ok();
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::report::{get_dropped_samples, CONTEXT_SWITCHES, CYCLES};
+
+    /// With perf rows, a context switch count one of them lacks.
+    fn entry(time: u64) -> String {
+        format!(
+            "<log-entry time-ms=\"{}\">\n<proc-start>\n{}</proc-start>\n<proc-end>\n{}</proc-end>\n<perf>\n{}</perf>\n</log-entry>\n",
+            time,
+            include_str!("../../tests/fixtures/ubuntu/proc-stat-start.txt"),
+            include_str!("../../tests/fixtures/ubuntu/proc-stat-end.txt"),
+            include_str!("../../tests/fixtures/ubuntu/perf-not-counted.txt")
+        )
+    }
+
+    fn log(id: Uuid, entries: &str) -> String {
+        format!(
+            "<log id=\"{}\" platform=\"ubuntu\" duration=\"1\" interval-ms=\"1000\" events=\"cycles,context-switches\">\n{}</log>\n",
+            id, entries
+        )
+    }
+
+    /// A log in a directory of its own, removed with it.
+    fn log_file(text: &str) -> (std::path::PathBuf, String) {
+        let dir = env::temp_dir().join(format!("profiler-{}", Uuid::new_v4()));
+        fs::create_dir(&dir).unwrap();
+        let path = dir.join("run.xml");
+        fs::write(&path, text).unwrap();
+        (dir, path.to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn caches_reports_until_the_log_changes() {
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let (dir, filename) = log_file(&log(first, &entry(0)));
+        let filename = filename.as_str();
+
+        let parsed = load_cached(filename, false, None, Cache::Use).unwrap();
+        let valid = key(filename, false, None).unwrap();
+        let cached = read_cache(filename, valid).unwrap();
+        assert_eq!(
+            serde_json::to_value(&cached).unwrap(),
+            serde_json::to_value(&parsed).unwrap()
+        );
+        assert_eq!(cached.perf_events, [CYCLES, CONTEXT_SWITCHES]);
+        let dropped = get_dropped_samples(&parsed, CONTEXT_SWITCHES);
+        assert_eq!(dropped, 2);
+        assert_eq!(get_dropped_samples(&cached, CONTEXT_SWITCHES), dropped);
+        assert!(read_cache(filename, valid ^ 1).is_none());
+        assert_ne!(key(filename, true, None).unwrap(), valid);
+
+        // The same size, only the id differs.
+        fs::write(filename, log(second, &entry(0))).unwrap();
+        let report = load_cached(filename, false, None, Cache::Use).unwrap();
+        assert_eq!(report.id, second);
+        let report = load_cached(filename, false, None, Cache::Use).unwrap();
+        assert_eq!(report.id, second);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn refreshes_or_skips_the_cache_as_asked() {
+        let (logged, cached) = (Uuid::new_v4(), Uuid::new_v4());
+        let (dir, filename) = log_file(&log(logged, &entry(0)));
+        let filename = filename.as_str();
+        let key = key(filename, false, None).unwrap();
+        // A cache valid for the log that tells the two apart.
+        let plant = || {
+            let mut report = load_report(filename, false, None).unwrap();
+            report.id = cached;
+            write_cache(filename, key, &report).unwrap();
+        };
+
+        plant();
+        let id = |cache| load_cached(filename, false, None, cache).unwrap().id;
+        assert_eq!(id(Cache::Use), cached);
+        assert_eq!(id(Cache::Off), logged);
+        assert_eq!(read_cache(filename, key).unwrap().id, cached);
+        assert_eq!(id(Cache::Refresh), logged);
+        assert_eq!(read_cache(filename, key).unwrap().id, logged);
+
+        fs::remove_file(cache_path(filename)).unwrap();
+        assert_eq!(id(Cache::Off), logged);
+        assert!(fs::metadata(cache_path(filename)).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn leaves_cut_off_and_skipped_reports_uncached() {
+        let id = Uuid::new_v4();
+        let cut_off = log(id, &entry(0)).replace("</log>\n", "");
+        let malformed = entry(1000).replace("cpu0 ", "cpu0 x");
+        for (text, lenient) in [(cut_off, false), (log(id, &(entry(0) + &malformed)), true)] {
+            let (dir, filename) = log_file(&text);
+            let report = load_cached(&filename, lenient, None, Cache::Use).unwrap();
+            assert!(report.truncated || !report.skipped.is_empty());
+            assert!(fs::metadata(cache_path(&filename)).is_err());
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+
+    /// A 400MB log of the entry fixture, a default capture of a single CPU
+    /// VM. Run it on its own, in release, with
+    /// `cargo test --release -- --ignored loads_a_large_log`.
+    #[test]
+    #[ignore = "writes and parses a 400MB log"]
+    fn loads_a_large_log_from_its_cache_in_under_a_second() {
+        const SIZE: usize = 400 << 20;
+        let entry = include_str!("../../tests/fixtures/ubuntu/log-entry.xml");
+        let (_, entry) = entry.split_once('>').unwrap();
+        let mut text = format!(
+            "<log id=\"{}\" platform=\"ubuntu\" duration=\"0\" interval-ms=\"1000\" perf=\"off\">\n",
+            Uuid::new_v4()
+        );
+        let mut entries = 0;
+        while text.len() < SIZE {
+            text.push_str(&format!(
+                "<log-entry time-ms=\"{}\">{}",
+                entries * 1000,
+                entry
+            ));
+            entries += 1;
+        }
+        text.push_str(&format!(
+            "<log-end duration-actual=\"{}\"/>\n</log>\n",
+            entries * 1000
+        ));
+        let (dir, filename) = log_file(&text);
+        drop(text);
+
+        let start = Instant::now();
+        let parsed = load_cached(&filename, false, None, Cache::Use).unwrap();
+        let parsing = start.elapsed();
+        let start = Instant::now();
+        let cached = read_cache(&filename, key(&filename, false, None).unwrap()).unwrap();
+        let loading = start.elapsed();
+        let size = fs::metadata(cache_path(&filename)).unwrap().len();
+        eprintln!(
+            "{} entries parsed in {:.2}s, a {}MB cache loaded in {:.2}s",
+            entries,
+            parsing.as_secs_f64(),
+            size >> 20,
+            loading.as_secs_f64()
+        );
+        assert_eq!(cached.entries.len(), entries);
+        assert_eq!(
+            serde_json::to_value(&cached.entries[0]).unwrap(),
+            serde_json::to_value(&parsed.entries[0]).unwrap()
+        );
+        assert!(loading < Duration::from_secs(1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
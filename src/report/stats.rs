@@ -0,0 +1,100 @@
+use serde::Serialize;
+
+/// Summary of the per-interval samples of one metric.
+#[derive(Debug, Clone, Copy)]
+pub struct Stats {
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    pub stddev: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+impl Stats {
+    /// `None` when there are no samples to summarise.
+    pub fn from_samples(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let count = sorted.len() as f64;
+        let mean = sorted.iter().sum::<f64>() / count;
+        let variance = sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / count;
+        Some(Stats {
+            mean,
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            stddev: variance.sqrt(),
+            p50: percentile(&sorted, 50.0),
+            p95: percentile(&sorted, 95.0),
+            p99: percentile(&sorted, 99.0),
+        })
+    }
+}
+
+/// Nearest-rank percentile of already sorted samples.
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Two-sided 95% critical values of Student's t distribution for 1 to 30
+/// degrees of freedom; past 30 the normal distribution's 1.96 is close enough.
+const T_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+/// Spread of one value measured once per run over repeated runs.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RunSpread {
+    pub mean: f64,
+    /// Sample standard deviation, as the runs stand for all possible runs.
+    pub stddev: f64,
+    /// Bounds of the 95% confidence interval of the mean.
+    pub ci95_low: f64,
+    pub ci95_high: f64,
+}
+
+impl RunSpread {
+    /// `None` for fewer than two runs, which have no spread to speak of.
+    pub fn from_runs(values: &[f64]) -> Option<Self> {
+        if values.len() < 2 {
+            return None;
+        }
+
+        let count = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / count;
+        let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (count - 1.0);
+        let stddev = variance.sqrt();
+        let t = T_95.get(values.len() - 2).copied().unwrap_or(1.96);
+        let margin = t * stddev / count.sqrt();
+        Some(RunSpread {
+            mean,
+            stddev,
+            ci95_low: mean - margin,
+            ci95_high: mean + margin,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spreads_runs_with_student_t() {
+        let spread = RunSpread::from_runs(&[10.0, 12.0, 14.0]).unwrap();
+        assert_eq!(spread.mean, 12.0);
+        assert_eq!(spread.stddev, 2.0);
+        let margin = 4.303 * 2.0 / 3f64.sqrt();
+        assert!((spread.ci95_low - (12.0 - margin)).abs() < 1e-9);
+        assert!((spread.ci95_high - (12.0 + margin)).abs() < 1e-9);
+        assert!(RunSpread::from_runs(&[10.0]).is_none());
+    }
+}
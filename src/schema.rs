@@ -0,0 +1,211 @@
+/// Version of the log format, to be bumped whenever an element or attribute
+/// is added, removed or changes meaning.
+pub const VERSION: u32 = 32;
+/// The first `VERSION` logging the payloads of an entry as CDATA. Loggers
+/// before it may have written perf's `<not counted>` as is.
+pub const CDATA_SINCE: u32 = 32;
+
+/// The elements of a log. `<log>` opens with `<metadata>` about the machine,
+/// holds a `<log-entry>` per interval, with a `<marker>` ahead of the entry
+/// it was received in, and ends with `<log-end>`; the others are the fields of
+/// the metadata and the payloads of an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Element {
+    Log,
+    Metadata,
+    Hostname,
+    Uname,
+    CpuModel,
+    LogicalCpus,
+    PhysicalCpus,
+    Topology,
+    DeviceModel,
+    PinnedCpu,
+    Command,
+    Options,
+    LogEntry,
+    ProcStart,
+    ProcEnd,
+    ProcPid,
+    Cgroup,
+    SelfStat,
+    Perf,
+    Meminfo,
+    Loadavg,
+    Freq,
+    TimeInStateStart,
+    TimeInStateEnd,
+    Thermal,
+    Battery,
+    Gpu,
+    Radio,
+    NetStart,
+    NetEnd,
+    TcpStart,
+    TcpEnd,
+    SoftnetStart,
+    SoftnetEnd,
+    IrqStart,
+    IrqEnd,
+    SoftirqsStart,
+    SoftirqsEnd,
+    IdleStart,
+    IdleEnd,
+    SchedstatStart,
+    SchedstatEnd,
+    Sockets,
+    DiskStart,
+    DiskEnd,
+    PressureStart,
+    PressureEnd,
+    Marker,
+    LogEnd,
+}
+
+const ELEMENTS: [(Element, &str); 49] = [
+    (Element::Log, "log"),
+    (Element::Metadata, "metadata"),
+    (Element::Hostname, "hostname"),
+    (Element::Uname, "uname"),
+    (Element::CpuModel, "cpu-model"),
+    (Element::LogicalCpus, "logical-cpus"),
+    (Element::PhysicalCpus, "physical-cpus"),
+    (Element::Topology, "topology"),
+    (Element::DeviceModel, "device-model"),
+    (Element::PinnedCpu, "pinned-cpu"),
+    (Element::Command, "command"),
+    (Element::Options, "options"),
+    (Element::LogEntry, "log-entry"),
+    (Element::ProcStart, "proc-start"),
+    (Element::ProcEnd, "proc-end"),
+    (Element::ProcPid, "proc-pid"),
+    (Element::Cgroup, "cgroup"),
+    (Element::SelfStat, "self"),
+    (Element::Perf, "perf"),
+    (Element::Meminfo, "meminfo"),
+    (Element::Loadavg, "loadavg"),
+    (Element::Freq, "freq"),
+    (Element::TimeInStateStart, "time-in-state-start"),
+    (Element::TimeInStateEnd, "time-in-state-end"),
+    (Element::Thermal, "thermal"),
+    (Element::Battery, "battery"),
+    (Element::Gpu, "gpu"),
+    (Element::Radio, "radio"),
+    (Element::NetStart, "net-start"),
+    (Element::NetEnd, "net-end"),
+    (Element::TcpStart, "tcp-start"),
+    (Element::TcpEnd, "tcp-end"),
+    (Element::SoftnetStart, "softnet-start"),
+    (Element::SoftnetEnd, "softnet-end"),
+    (Element::IrqStart, "irq-start"),
+    (Element::IrqEnd, "irq-end"),
+    (Element::SoftirqsStart, "softirqs-start"),
+    (Element::SoftirqsEnd, "softirqs-end"),
+    (Element::IdleStart, "idle-start"),
+    (Element::IdleEnd, "idle-end"),
+    (Element::SchedstatStart, "schedstat-start"),
+    (Element::SchedstatEnd, "schedstat-end"),
+    (Element::Sockets, "sockets"),
+    (Element::DiskStart, "disk-start"),
+    (Element::DiskEnd, "disk-end"),
+    (Element::PressureStart, "pressure-start"),
+    (Element::PressureEnd, "pressure-end"),
+    (Element::Marker, "marker"),
+    (Element::LogEnd, "log-end"),
+];
+
+impl Element {
+    pub fn name(self) -> &'static str {
+        ELEMENTS
+            .iter()
+            .find(|(element, _)| *element == self)
+            .unwrap()
+            .1
+    }
+
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        ELEMENTS
+            .iter()
+            .find(|(_, element)| element.as_bytes() == name)
+            .map(|(element, _)| *element)
+    }
+}
+
+// Attributes of `<log>`.
+pub const ID: &str = "id";
+/// The `VERSION` the log was written in. Absent in logs predating it, which
+/// are read by the attributes they have; `get_report` refuses newer ones.
+pub const SCHEMA: &str = "schema";
+pub const PLATFORM: &str = "platform";
+/// Requested seconds, zero when capturing until interrupted.
+pub const DURATION: &str = "duration";
+pub const INTERVAL_MS: &str = "interval-ms";
+/// Whole seconds, written by loggers predating `interval-ms`.
+pub const INTERVAL: &str = "interval";
+pub const EVENTS: &str = "events";
+/// `PERF_OFF` for logs captured without perf.
+pub const PERF: &str = "perf";
+pub const PERF_OFF: &str = "off";
+/// `core`, `socket` or `system`, as with `--perf-granularity`; absent for
+/// per core counts.
+pub const PERF_GRANULARITY: &str = "perf-granularity";
+pub const PIDS: &str = "pids";
+/// CPUs perf counted on and the report keeps, as `0-3,6`, only with
+/// `--cpu-list`.
+pub const CPU_LIST: &str = "cpu-list";
+/// Wall-clock time the capture started at, as ISO 8601 UTC; after the
+/// warmup, if any.
+pub const START: &str = "start";
+/// Milliseconds sampled before the capture started but left out of the log,
+/// only with `--warmup`.
+pub const WARMUP_MS: &str = "warmup-ms";
+/// Number of the file, from 1, of a capture split with `--rotate-size` or
+/// `--rotate-entries`; every part has the capture's `id`.
+pub const PART: &str = "part";
+/// Milliseconds the logger's clock was ahead of the `profiler sync`
+/// coordinator's, negative if behind, only in synchronized captures.
+pub const CLOCK_OFFSET_MS: &str = "clock-offset-ms";
+
+// Attributes of `<log-entry>`.
+pub const TIME_MS: &str = "time-ms";
+/// Written by loggers predating `time-ms`, whose unit `get_report` guesses.
+pub const TIME: &str = "time";
+/// Wall-clock time the interval started at, only with `--timestamps`.
+pub const WALL_TIME: &str = "wall-time";
+/// Intervals the logger fell too far behind to sample just before this
+/// entry, only when there were any.
+pub const SKIPPED: &str = "skipped";
+
+// Attributes of `<marker>`, which also has a `time-ms`.
+pub const LABEL: &str = "label";
+
+// Attributes of `<proc-pid>`.
+pub const PID: &str = "pid";
+
+// Attributes of `<cgroup>`, which holds the cgroup's cpu.stat at the start of
+// the interval and then at the end, or is empty once the cgroup is gone.
+pub const PATH: &str = "path";
+
+// Attributes of `<log-end>`.
+pub const DURATION_ACTUAL: &str = "duration-actual";
+/// How the command captured with `profiler run` ended: the code it exited
+/// with, or else the signal that killed it.
+pub const EXIT_CODE: &str = "exit-code";
+pub const EXIT_SIGNAL: &str = "exit-signal";
+/// File name of the part the capture goes on in, on every part of a rotated
+/// capture but the last.
+pub const NEXT: &str = "next";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn element_names_round_trip() {
+        for (element, name) in ELEMENTS {
+            assert_eq!(element.name(), name);
+            assert_eq!(Element::from_name(name.as_bytes()), Some(element));
+        }
+        assert_eq!(Element::from_name(b"log-entries"), None);
+    }
+}
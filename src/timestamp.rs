@@ -0,0 +1,125 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use lazy_regex::regex_captures;
+
+// Converts between days since 1970-01-01 and proleptic Gregorian dates, after
+// Howard Hinnant's `days_from_civil` and `civil_from_days`.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year as i64;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Formats `time` as ISO 8601 UTC to the millisecond, e.g.
+/// `2024-03-01T12:30:00.250Z`; times before 1970 are clamped to the epoch.
+pub fn format(time: SystemTime) -> String {
+    let millis = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64;
+    let (days, millis) = (millis.div_euclid(86_400_000), millis.rem_euclid(86_400_000));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Parses `YYYY-MM-DDTHH:MM:SS` with optional fractional seconds, a space in
+/// place of the `T` and a `Z` or `±HH:MM` offset; times without an offset are
+/// UTC.
+pub fn parse(text: &str) -> Option<SystemTime> {
+    let (_, year, month, day, hour, minute, second, fraction, offset) = regex_captures!(
+        r#"^(\d{4})-(\d{2})-(\d{2})[T ](\d{2}):(\d{2}):(\d{2})(?:\.(\d{1,9}))?(Z|[+-]\d{2}:\d{2})?$"#,
+        text
+    )?;
+    let (year, month, day): (i64, u32, u32) =
+        (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?);
+    let (hour, minute, second): (i64, i64, i64) = (
+        hour.parse().ok()?,
+        minute.parse().ok()?,
+        second.parse().ok()?,
+    );
+    if !(1..=12).contains(&month)
+        || day == 0
+        || civil_from_days(days_from_civil(year, month, day)) != (year, month, day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+    let offset = match offset {
+        "" | "Z" => 0,
+        offset => {
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let hours: i64 = offset[1..3].parse().ok()?;
+            let minutes: i64 = offset[4..].parse().ok()?;
+            sign * (hours * 3600 + minutes * 60)
+        }
+    };
+    let millis: u64 = format!("{:0<3}", fraction)[..3].parse().ok()?;
+    let seconds =
+        days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second - offset;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds.try_into().ok()?) + Duration::from_millis(millis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_and_parses_utc() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_296_200_250);
+        assert_eq!(format(time), "2024-03-01T12:30:00.250Z");
+        assert_eq!(parse("2024-03-01T12:30:00.250Z"), Some(time));
+        assert_eq!(parse("2024-03-01 12:30:00.25"), Some(time));
+        assert_eq!(parse("2024-03-01T14:30:00.250+02:00"), Some(time));
+        assert_eq!(format(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            parse("2000-02-29T00:00:00Z").map(format).as_deref(),
+            Some("2000-02-29T00:00:00.000Z")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_times() {
+        for text in [
+            "2024-03-01",
+            "2023-02-29T00:00:00Z",
+            "2024-13-01T00:00:00Z",
+            "2024-03-00T00:00:00Z",
+            "2024-03-01T24:00:00Z",
+            "1969-12-31T23:59:59Z",
+        ] {
+            assert_eq!(parse(text), None, "{}", text);
+        }
+    }
+}
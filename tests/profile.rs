@@ -0,0 +1,43 @@
+use std::{
+    env,
+    os::unix::process::ExitStatusExt,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use uuid::Uuid;
+
+fn profile(output: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_profiler"));
+    command
+        .args(["--output", output.to_str().unwrap(), "-d", "1", "-i", "1s"])
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null());
+    command
+}
+
+#[test]
+fn leaves_no_log_when_interrupted_during_the_delay() {
+    let output = env::temp_dir().join(format!("profiler-{}.xml", Uuid::new_v4()));
+    let mut child = profile(&output, &["--delay", "30"]).spawn().unwrap();
+    thread::sleep(Duration::from_millis(500));
+    assert!(!output.exists());
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) };
+    let status = child.wait().unwrap();
+    assert_eq!(status.signal(), Some(libc::SIGINT));
+    // Nor one a rerun would be refused for without --force.
+    assert!(!output.exists());
+}
+
+#[test]
+fn refuses_an_output_in_a_missing_directory() {
+    let output = env::temp_dir()
+        .join(format!("profiler-{}", Uuid::new_v4()))
+        .join("log.xml");
+    let status = profile(&output, &["--delay", "30"]).status().unwrap();
+    assert_eq!(status.code(), Some(2));
+    assert!(!output.parent().unwrap().exists());
+}